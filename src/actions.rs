@@ -0,0 +1,337 @@
+use anyhow::Result;
+use std::io::Write;
+use std::path::Path;
+
+use crate::file_utils::{self, DuplicateSet, ReclaimedSpace};
+use crate::prompt::{self, PromptChoice};
+use crate::Cli;
+
+/// What `apply_actions` did (or would have, in dry run) across all sets
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ActionTotals {
+    pub deleted: usize,
+    pub moved: usize,
+    pub quarantined: usize,
+    pub reflinked: usize,
+    pub hardlinked: usize,
+    pub symlinked: usize,
+    pub reclaimed: ReclaimedSpace,
+}
+
+/// Whether any action flag (--delete, --move-to, --quarantine, --reflink, --hardlink,
+/// --symlink-kept) is set
+pub fn has_action(cli: &Cli) -> bool {
+    cli.delete
+        || cli.move_to.is_some()
+        || cli.quarantine.is_some()
+        || cli.reflink
+        || cli.hardlink
+        || cli.symlink_kept
+}
+
+/// Check byte for byte that `file`, about to be removed on the strength of its set's
+/// hash, matches one of the `kept` files. Used for single-file actions such as TUI jobs,
+/// where `verify_duplicate_sets` can't be run over whole sets.
+pub fn verify_against_kept(set: &DuplicateSet, file: &Path, kept: &[&Path]) -> Result<()> {
+    if file_utils::is_similarity_set(set) {
+        return Ok(());
+    }
+    for kept_path in kept {
+        if file_utils::files_are_identical(kept_path, file)? {
+            return Ok(());
+        }
+    }
+    Err(anyhow::anyhow!(
+        "{} is not byte-identical to a kept file in its set",
+        file.display()
+    ))
+}
+
+/// Apply the configured action to each duplicate set, keeping one file per set. Sets are
+/// byte-verified first when the hash alone can't be trusted, sets with only a partial hash
+/// need --allow-approximate-delete, and protected files are never actioned. With
+/// `prompting`, the user picks the file to keep for each set.
+pub fn apply_actions(
+    cli: &Cli,
+    duplicate_sets: &[DuplicateSet],
+    prompting: bool,
+) -> Result<ActionTotals> {
+    let mut out = cli.message_output();
+    // Each set is verified or not by the algorithm that hashed it, so a weak
+    // hash_overrides entry is checked even under a cryptographic --algorithm
+    let mut verified_sets = Vec::with_capacity(duplicate_sets.len());
+    for set in duplicate_sets {
+        if cli.verify_set_before_action(set) {
+            let (sets, logs) = file_utils::verify_duplicate_sets(std::slice::from_ref(set));
+            for log_msg in logs {
+                writeln!(out, "{}", log_msg)?;
+            }
+            verified_sets.extend(sets);
+        } else {
+            verified_sets.push(set.clone());
+        }
+    }
+    let duplicate_sets = verified_sets.as_slice();
+
+    let strategy = cli.selection_strategy()?;
+    let protected = file_utils::ProtectedPaths::from_cli(cli);
+    // Walk for symlinks once up front rather than once per deleted file
+    let symlinks = if cli.delete && cli.fix_symlinks {
+        file_utils::collect_symlinks(&cli.directories)
+    } else {
+        Vec::new()
+    };
+    let mut totals = ActionTotals::default();
+
+    for set in duplicate_sets {
+        if set.files.len() < 2 {
+            continue;
+        }
+
+        if cli.delete || cli.reflink || cli.hardlink || cli.symlink_kept {
+            if let Err(e) =
+                file_utils::check_approximate_delete_allowed(set, cli.allow_approximate_delete)
+            {
+                log::warn!("{}", e);
+                eprintln!("Skipping set: {}", e);
+                continue;
+            }
+        }
+
+        if protected.protects_all(&set.files) {
+            log::info!(
+                "Skipping duplicate set (hash: {}...): every file is protected",
+                set.hash.chars().take(8).collect::<String>()
+            );
+            writeln!(
+                out,
+                "Skipping set: every file is protected ({})",
+                set.files[0].path.display()
+            )?;
+            continue;
+        }
+
+        let targets = if prompting {
+            match prompt::prompt_for_set(set, &mut std::io::stdin().lock(), &mut out)? {
+                PromptChoice::Skip => {
+                    writeln!(out, "Skipped.")?;
+                    continue;
+                }
+                PromptChoice::Keep(index) => Ok(prompt::keep_chosen_file(set, index, &protected)),
+                PromptChoice::ApplyStrategy => file_utils::determine_protected_action_targets(
+                    set,
+                    strategy.clone(),
+                    &cli.tiebreak,
+                    &protected,
+                ),
+            }
+        } else {
+            file_utils::determine_protected_action_targets(
+                set,
+                strategy.clone(),
+                &cli.tiebreak,
+                &protected,
+            )
+        };
+
+        match targets {
+            Ok((kept_file, files_to_action)) => {
+                log::info!(
+                    "For duplicate set (hash: {}...), keeping file: {:?}",
+                    set.hash.chars().take(8).collect::<String>(),
+                    kept_file.path
+                );
+                writeln!(out, "Keeping: {}", kept_file.path.display())?;
+
+                if cli.delete {
+                    for file_info in &files_to_action {
+                        if symlinks.is_empty() {
+                            break;
+                        }
+                        match file_utils::repoint_symlinks_in(
+                            &symlinks,
+                            &file_info.path,
+                            &kept_file.path,
+                            cli.dry_run,
+                        ) {
+                            Ok(logs) => {
+                                for log_msg in logs {
+                                    log::info!("{}", log_msg);
+                                    writeln!(out, "{}", log_msg)?;
+                                }
+                            }
+                            Err(e) => {
+                                log::error!(
+                                    "Could not check symlinks to {:?}: {}",
+                                    file_info.path,
+                                    e
+                                );
+                                eprintln!("Error: {}", e);
+                            }
+                        }
+                    }
+                    match file_utils::delete_files(
+                        &files_to_action,
+                        cli.dry_run,
+                        cli.trash,
+                        cli.retries,
+                    ) {
+                        Ok((actioned, logs)) => {
+                            totals.deleted += actioned.len();
+                            totals.reclaimed.record(&actioned);
+                            // Print and log all messages
+                            for log_msg in logs {
+                                log::info!("{}", log_msg);
+                                writeln!(out, "{}", log_msg)?;
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Error during deletion batch: {}", e);
+                            eprintln!("Error: {}", e);
+                        }
+                    }
+                } else if let Some(ref target_move_dir) = cli.move_to {
+                    let moved = if cli.preserve_structure {
+                        file_utils::move_files_preserving_structure(
+                            &files_to_action,
+                            target_move_dir,
+                            &cli.directories,
+                            cli.dry_run,
+                            cli.on_conflict,
+                            cli.retries,
+                        )
+                    } else {
+                        file_utils::move_files(
+                            &files_to_action,
+                            target_move_dir,
+                            cli.dry_run,
+                            cli.on_conflict,
+                            cli.retries,
+                        )
+                    };
+                    match moved {
+                        Ok((actioned, logs)) => {
+                            totals.moved += actioned.len();
+                            totals.reclaimed.record(&actioned);
+                            // Print and log all messages
+                            for log_msg in logs {
+                                log::info!("{}", log_msg);
+                                writeln!(out, "{}", log_msg)?;
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Error during move batch: {}", e);
+                            eprintln!("Error: {}", e);
+                        }
+                    }
+                } else if let Some(ref quarantine_dir) = cli.quarantine {
+                    match quarantine_set(cli, &files_to_action, quarantine_dir) {
+                        Ok((actioned, logs)) => {
+                            totals.quarantined += actioned.len();
+                            totals.reclaimed.record(&actioned);
+                            for log_msg in logs {
+                                log::info!("{}", log_msg);
+                                writeln!(out, "{}", log_msg)?;
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Error during quarantine batch: {}", e);
+                            eprintln!("Error: {}", e);
+                        }
+                    }
+                } else if cli.reflink {
+                    match file_utils::reflink_files(&kept_file, &files_to_action, cli.dry_run) {
+                        Ok((actioned, logs)) => {
+                            totals.reflinked += actioned.len();
+                            totals.reclaimed.record(&actioned);
+                            for log_msg in logs {
+                                log::info!("{}", log_msg);
+                                writeln!(out, "{}", log_msg)?;
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Error during reflink batch: {}", e);
+                            eprintln!("Error: {}", e);
+                        }
+                    }
+                } else if cli.hardlink {
+                    match file_utils::hardlink_files(&kept_file, &files_to_action, cli.dry_run) {
+                        Ok((actioned, logs)) => {
+                            totals.hardlinked += actioned.len();
+                            totals.reclaimed.record(&actioned);
+                            for log_msg in logs {
+                                log::info!("{}", log_msg);
+                                writeln!(out, "{}", log_msg)?;
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Error during hardlink batch: {}", e);
+                            eprintln!("Error: {}", e);
+                        }
+                    }
+                } else if cli.symlink_kept {
+                    match file_utils::symlink_to_kept(
+                        &kept_file,
+                        &files_to_action,
+                        cli.relative_symlinks,
+                        cli.dry_run,
+                    ) {
+                        Ok((actioned, logs)) => {
+                            totals.symlinked += actioned.len();
+                            totals.reclaimed.record(&actioned);
+                            for log_msg in logs {
+                                log::info!("{}", log_msg);
+                                writeln!(out, "{}", log_msg)?;
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Error during symlink batch: {}", e);
+                            eprintln!("Error: {}", e);
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("Could not determine action targets for a set: {}", e);
+                eprintln!(
+                    "Error: Could not determine which files to keep/delete: {}",
+                    e
+                );
+            }
+        }
+    }
+
+    Ok(totals)
+}
+
+/// Quarantine `files`, keeping each one's path relative to the scanned directory it came from
+fn quarantine_set(
+    cli: &Cli,
+    files: &[file_utils::FileInfo],
+    quarantine_dir: &Path,
+) -> Result<(Vec<file_utils::FileInfo>, Vec<String>)> {
+    let mut by_root: Vec<(&Path, Vec<file_utils::FileInfo>)> = Vec::new();
+    for file in files {
+        // With --files-from there may be no directories; paths then keep their full structure
+        let root = cli
+            .directories
+            .iter()
+            .find(|dir| file.path.starts_with(dir))
+            .or(cli.directories.first())
+            .map_or(Path::new(""), |dir| dir.as_path());
+        match by_root.iter_mut().find(|(r, _)| *r == root) {
+            Some((_, batch)) => batch.push(file.clone()),
+            None => by_root.push((root, vec![file.clone()])),
+        }
+    }
+
+    let mut all_actioned = Vec::new();
+    let mut all_logs = Vec::new();
+    for (root, batch) in by_root {
+        let (actioned, logs) =
+            file_utils::quarantine_files(&batch, quarantine_dir, root, cli.dry_run)?;
+        all_actioned.extend(actioned);
+        all_logs.extend(logs);
+    }
+    Ok((all_actioned, all_logs))
+}
@@ -138,6 +138,216 @@ pub fn compare_fingerprints(fp1: &[u8], fp2: &[u8]) -> f64 {
     (matches as f64) / ((fp1.len() * 8) as f64)
 }
 
+/// Sample rate audio is decoded at for acoustic fingerprinting
+const ACOUSTIC_SAMPLE_RATE: u32 = 11025;
+/// Only the start of a track is fingerprinted, like chromaprint's default
+const ACOUSTIC_MAX_SECONDS: u32 = 120;
+/// Samples between fingerprint frames (~8 frames per second)
+const FRAME_HOP: usize = 1365;
+/// Each frame's energy covers this many hops (~0.37 s window)
+const HOPS_PER_FRAME: usize = 3;
+/// Log-spaced bands between these frequencies; 33 bands give 32 bits per frame
+const BAND_COUNT: usize = 33;
+const BAND_MIN_HZ: f32 = 300.0;
+const BAND_MAX_HZ: f32 = 2000.0;
+/// How far (in frames) two fingerprints may be shifted against each other when
+/// aligning, to absorb leading silence and encoder delay (~3 s)
+const MAX_ALIGN_OFFSET: usize = 24;
+
+/// Decode a file to mono PCM at `ACOUSTIC_SAMPLE_RATE` using ffmpeg
+fn decode_pcm(path: &Path) -> Result<Vec<f32>> {
+    let output = Command::new("ffmpeg")
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(path)
+        .args([
+            "-t",
+            &ACOUSTIC_MAX_SECONDS.to_string(),
+            "-ac",
+            "1",
+            "-ar",
+            &ACOUSTIC_SAMPLE_RATE.to_string(),
+            "-f",
+            "s16le",
+            "-",
+        ])
+        .output()
+        .context("Failed to execute ffmpeg")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "ffmpeg failed to decode {:?}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+        .collect())
+}
+
+/// Compute a chromaprint-style acoustic fingerprint: one 32-bit sub-fingerprint per
+/// frame, where each bit records whether the energy difference between two adjacent
+/// frequency bands rose or fell since the previous frame (Haitsma-Kalker). Such bits
+/// survive re-encoding and bitrate changes, unlike the bytes of the file.
+pub fn acoustic_fingerprint_from_samples(samples: &[f32]) -> Vec<u32> {
+    let block_count = samples.len() / FRAME_HOP;
+    if block_count < HOPS_PER_FRAME + 1 {
+        return Vec::new();
+    }
+
+    // Energy per band per hop-sized block, via a constant-Q biquad bandpass filterbank
+    let ratio = (BAND_MAX_HZ / BAND_MIN_HZ).powf(1.0 / (BAND_COUNT - 1) as f32);
+    let mut block_energy = vec![[0f32; BAND_COUNT]; block_count];
+    let centers = (0..BAND_COUNT).map(|band| BAND_MIN_HZ * ratio.powi(band as i32));
+    for (band, freq) in centers.enumerate() {
+        let w0 = 2.0 * std::f32::consts::PI * freq / ACOUSTIC_SAMPLE_RATE as f32;
+        let alpha = w0.sin() / (2.0 * 4.0); // Q = 4
+        let a0 = 1.0 + alpha;
+        let (b0, b2) = (alpha / a0, -alpha / a0);
+        let (a1, a2) = (-2.0 * w0.cos() / a0, (1.0 - alpha) / a0);
+
+        let (mut x1, mut x2, mut y1, mut y2) = (0f32, 0f32, 0f32, 0f32);
+        for (i, &x) in samples[..block_count * FRAME_HOP].iter().enumerate() {
+            let y = b0 * x + b2 * x2 - a1 * y1 - a2 * y2;
+            (x2, x1, y2, y1) = (x1, x, y1, y);
+            block_energy[i / FRAME_HOP][band] += y * y;
+        }
+    }
+
+    let frames: Vec<[f32; BAND_COUNT]> = block_energy
+        .windows(HOPS_PER_FRAME)
+        .map(|blocks| {
+            let mut frame = [0f32; BAND_COUNT];
+            for block in blocks {
+                for (total, energy) in frame.iter_mut().zip(block) {
+                    *total += energy;
+                }
+            }
+            frame
+        })
+        .collect();
+
+    frames
+        .windows(2)
+        .map(|pair| {
+            let (prev, cur) = (&pair[0], &pair[1]);
+            (0..BAND_COUNT - 1).fold(0u32, |bits, m| {
+                let delta = (cur[m] - cur[m + 1]) - (prev[m] - prev[m + 1]);
+                if delta > 0.0 {
+                    bits | (1 << m)
+                } else {
+                    bits
+                }
+            })
+        })
+        .collect()
+}
+
+/// Decode `path` with ffmpeg and compute its acoustic fingerprint
+pub fn acoustic_fingerprint(path: &Path) -> Result<Vec<u32>> {
+    if !crate::media_dedup::is_ffmpeg_available() {
+        return Err(anyhow::anyhow!(
+            "ffmpeg is required for acoustic fingerprinting but is not available"
+        ));
+    }
+    let fingerprint = acoustic_fingerprint_from_samples(&decode_pcm(path)?);
+    if fingerprint.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Audio too short to fingerprint: {:?}",
+            path
+        ));
+    }
+    Ok(fingerprint)
+}
+
+/// Similarity (0.0-1.0) of two acoustic fingerprints: the fraction of matching bits
+/// at the best alignment within `MAX_ALIGN_OFFSET` frames
+pub fn compare_acoustic_fingerprints(fp1: &[u32], fp2: &[u32]) -> f64 {
+    let shortest = fp1.len().min(fp2.len());
+    if shortest == 0 {
+        return 0.0;
+    }
+    // Don't let a sliver of overlap at an extreme offset count as a match
+    let min_overlap = (shortest / 2).max(1);
+
+    let mut best = 0.0f64;
+    for offset in -(MAX_ALIGN_OFFSET as isize)..=(MAX_ALIGN_OFFSET as isize) {
+        let (a, b) = if offset >= 0 {
+            (fp1.get(offset as usize..), Some(fp2))
+        } else {
+            (Some(fp1), fp2.get(offset.unsigned_abs()..))
+        };
+        let (Some(a), Some(b)) = (a, b) else {
+            continue;
+        };
+        let overlap = a.len().min(b.len());
+        if overlap < min_overlap {
+            continue;
+        }
+        let differing: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+        let score = 1.0 - differing as f64 / (overlap * 32) as f64;
+        best = best.max(score);
+    }
+    best
+}
+
+/// Conservative similarity (0-100) for when audio can't be decoded: files must share
+/// a file stem (e.g. `track.mp3` and `track.flac`), then score by how close their
+/// durations are, or their sizes when durations are unknown.
+pub fn metadata_similarity(
+    a: &Path,
+    b: &Path,
+    duration_a: Option<f64>,
+    duration_b: Option<f64>,
+) -> u32 {
+    if a == b {
+        return 100;
+    }
+    let stem = |p: &Path| p.file_stem().map(|s| s.to_string_lossy().to_lowercase());
+    if stem(a).is_none() || stem(a) != stem(b) {
+        return 0;
+    }
+
+    let closeness = |x: f64, y: f64| {
+        if x <= 0.0 || y <= 0.0 {
+            0
+        } else {
+            (x.min(y) / x.max(y) * 100.0) as u32
+        }
+    };
+    match (duration_a, duration_b) {
+        (Some(da), Some(db)) => closeness(da, db),
+        _ => match (std::fs::metadata(a), std::fs::metadata(b)) {
+            (Ok(ma), Ok(mb)) => closeness(ma.len() as f64, mb.len() as f64),
+            _ => 0,
+        },
+    }
+}
+
+/// Decide whether two audio files have the same content, even when encoded
+/// differently. Uses acoustic fingerprints when ffmpeg is available and falls back
+/// to `metadata_similarity` otherwise. `threshold` is a 0-100 similarity percentage.
+pub fn compare_audio_fingerprints(a: &Path, b: &Path, threshold: u32) -> Result<bool> {
+    if !crate::media_dedup::is_ffmpeg_available() {
+        log::debug!(
+            "ffmpeg unavailable, comparing {:?} and {:?} by metadata",
+            a,
+            b
+        );
+        return Ok(metadata_similarity(a, b, None, None) >= threshold);
+    }
+
+    let fp_a = acoustic_fingerprint(a)?;
+    let fp_b = acoustic_fingerprint(b)?;
+    let similarity = (compare_acoustic_fingerprints(&fp_a, &fp_b) * 100.0) as u32;
+    Ok(similarity >= threshold)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,4 +367,68 @@ mod tests {
         let empty = vec![];
         assert_eq!(compare_fingerprints(&fp1, &empty), 0.0);
     }
+
+    /// A changing two-note chord, so band energies vary over time
+    fn test_signal(seconds: usize) -> Vec<f32> {
+        let rate = ACOUSTIC_SAMPLE_RATE as f32;
+        (0..(ACOUSTIC_SAMPLE_RATE as usize * seconds))
+            .map(|i| {
+                let t = i as f32 / rate;
+                let note = 400.0 + 300.0 * ((t * 2.0) as u32 % 4) as f32;
+                (2.0 * std::f32::consts::PI * note * t).sin() * 0.3
+                    + (2.0 * std::f32::consts::PI * note * 1.5 * t).sin() * 0.2
+            })
+            .collect()
+    }
+
+    /// Minimal 16-bit mono PCM WAV file
+    fn write_wav(path: &Path, samples: &[f32]) -> Result<()> {
+        let data: Vec<u8> = samples
+            .iter()
+            .flat_map(|s| ((s * 32767.0) as i16).to_le_bytes())
+            .collect();
+        let mut wav = Vec::with_capacity(44 + data.len());
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // Mono
+        wav.extend_from_slice(&ACOUSTIC_SAMPLE_RATE.to_le_bytes());
+        wav.extend_from_slice(&(ACOUSTIC_SAMPLE_RATE * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        std::fs::write(path, wav)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_audio_file_matches_itself_at_any_threshold() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("track.wav");
+        write_wav(&path, &test_signal(3))?;
+
+        // Uses acoustic fingerprints with ffmpeg, the metadata fallback without it
+        for threshold in [0, 50, 90, 100] {
+            assert!(compare_audio_fingerprints(&path, &path, threshold)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_acoustic_fingerprint_survives_offset_and_gain() {
+        let signal = test_signal(6);
+        let fp = acoustic_fingerprint_from_samples(&signal);
+        assert!(!fp.is_empty());
+        assert_eq!(compare_acoustic_fingerprints(&fp, &fp), 1.0);
+
+        // Quieter copy with leading silence, as a re-encode might produce
+        let mut shifted = vec![0.0f32; FRAME_HOP * 4];
+        shifted.extend(signal.iter().map(|s| s * 0.5));
+        let fp_shifted = acoustic_fingerprint_from_samples(&shifted);
+        assert!(compare_acoustic_fingerprints(&fp, &fp_shifted) > 0.9);
+    }
 }
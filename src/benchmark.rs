@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use crate::file_utils::{available_hash_algorithms, calculate_hash, is_cryptographic_hash};
+
+/// Size of the generated test file when `--size` isn't given
+pub const DEFAULT_BENCHMARK_SIZE_MB: u64 = 64;
+
+/// Times each algorithm hashes the file; the fastest run is reported
+pub const BENCHMARK_RUNS: usize = 3;
+
+/// Throughput of one algorithm on the test file
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlgorithmThroughput {
+    pub algorithm: &'static str,
+    pub mb_per_sec: f64,
+    pub cryptographic: bool,
+}
+
+/// Hash a generated `size_mb` MB file with every available algorithm, `runs` times each,
+/// and return the best throughput per algorithm, fastest first. The first run also warms
+/// the page cache, so with several runs the numbers reflect hashing rather than the disk.
+pub fn run_benchmark(size_mb: u64, runs: usize) -> Result<Vec<AlgorithmThroughput>> {
+    if size_mb == 0 {
+        anyhow::bail!("Benchmark size must be at least 1 MB");
+    }
+    let file = tempfile::NamedTempFile::new().context("Failed to create benchmark file")?;
+    write_test_data(file.as_file(), size_mb * 1024 * 1024)?;
+
+    let mut results = Vec::new();
+    for algorithm in available_hash_algorithms() {
+        let mut best = Duration::MAX;
+        for _ in 0..runs.max(1) {
+            let start = Instant::now();
+            calculate_hash(file.path(), algorithm)?;
+            best = best.min(start.elapsed());
+        }
+        log::debug!("{}: best of {} runs took {:?}", algorithm, runs, best);
+        results.push(AlgorithmThroughput {
+            algorithm,
+            mb_per_sec: size_mb as f64 / best.as_secs_f64().max(f64::EPSILON),
+            cryptographic: is_cryptographic_hash(algorithm),
+        });
+    }
+    results.sort_by(|a, b| b.mb_per_sec.total_cmp(&a.mb_per_sec));
+    Ok(results)
+}
+
+/// Fill the file with pseudo-random bytes so no algorithm gets an easy input
+fn write_test_data(file: &File, len: u64) -> Result<()> {
+    let mut writer = BufWriter::new(file);
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut block = vec![0u8; 1024 * 1024];
+    let mut remaining = len;
+    while remaining > 0 {
+        for chunk in block.chunks_exact_mut(8) {
+            // xorshift64
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            chunk.copy_from_slice(&state.to_le_bytes());
+        }
+        let n = remaining.min(block.len() as u64) as usize;
+        writer.write_all(&block[..n])?;
+        remaining -= n as u64;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmark_reports_every_algorithm() -> Result<()> {
+        let results = run_benchmark(1, 1)?;
+
+        let mut measured: Vec<&str> = results.iter().map(|r| r.algorithm).collect();
+        measured.sort_unstable();
+        let mut expected = available_hash_algorithms();
+        expected.sort_unstable();
+        assert_eq!(measured, expected);
+        assert_eq!(
+            measured.contains(&"gxhash"),
+            crate::file_utils::GXHASH_AVAILABLE
+        );
+
+        for result in &results {
+            assert!(
+                result.mb_per_sec > 0.0,
+                "{} reported no throughput",
+                result.algorithm
+            );
+        }
+        assert!(results
+            .windows(2)
+            .all(|w| w[0].mb_per_sec >= w[1].mb_per_sec));
+        assert!(results
+            .iter()
+            .any(|r| r.algorithm == "blake3" && r.cryptographic));
+        assert!(results
+            .iter()
+            .any(|r| r.algorithm == "xxhash" && !r.cryptographic));
+
+        assert!(run_benchmark(0, 1).is_err());
+        Ok(())
+    }
+}
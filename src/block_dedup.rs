@@ -0,0 +1,261 @@
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use crate::file_utils::FileInfo;
+
+/// Files smaller than this are left out of `--block-dedup` unless `--block-min-size` says otherwise
+pub const DEFAULT_BLOCK_MIN_SIZE: u64 = 1024 * 1024;
+
+/// Share of the smaller file that must be in shared chunks for a pair to be reported
+pub const DEFAULT_BLOCK_OVERLAP: f64 = 0.5;
+
+/// No chunk boundary is placed before this many bytes
+const MIN_CHUNK_SIZE: usize = 2 * 1024;
+
+/// A chunk is always cut at this length, even without a content boundary
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A boundary is cut where the low 13 bits of the rolling hash are zero, so chunks
+/// average about 8 KiB past the minimum
+const BOUNDARY_MASK: u64 = (1 << 13) - 1;
+
+/// Per-byte values for the gear rolling hash, filled by splitmix64 so every build
+/// cuts the same boundaries
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Parse `--block-overlap`: a fraction between 0 and 1
+pub fn parse_overlap(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(value) if (0.0..=1.0).contains(&value) => Ok(value),
+        _ => Err(format!(
+            "Invalid overlap '{}': expected a fraction between 0 and 1 (e.g. 0.5)",
+            s
+        )),
+    }
+}
+
+/// Content-defined chunks of one file: each distinct chunk hash with its length, plus
+/// a hash of the whole file so identical files can be told apart from overlapping ones
+#[derive(Debug)]
+struct FileChunks {
+    chunks: HashMap<blake3::Hash, u64>,
+    whole: blake3::Hash,
+}
+
+impl FileChunks {
+    /// Bytes covered by the file's distinct chunks
+    fn unique_bytes(&self) -> u64 {
+        self.chunks.values().sum()
+    }
+}
+
+/// Two files that share a large part of their content, e.g. VM images cloned from one base
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockOverlap {
+    pub first: FileInfo,
+    pub second: FileInfo,
+    /// Bytes of content found in both files
+    pub shared_bytes: u64,
+    /// `shared_bytes` as a share of the smaller file's content, 0.0 to 1.0
+    pub overlap: f64,
+}
+
+/// Split a file into content-defined chunks with a gear rolling hash. Boundaries depend
+/// on the bytes around them rather than on offsets, so content shifted by an insertion
+/// earlier in the file still produces the same chunks.
+fn chunk_file(path: &Path) -> Result<FileChunks> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut chunk = Vec::with_capacity(MAX_CHUNK_SIZE);
+    let mut chunks = HashMap::new();
+    let mut whole = blake3::Hasher::new();
+    let mut rolling: u64 = 0;
+
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        whole.update(&buffer[..n]);
+        for &byte in &buffer[..n] {
+            chunk.push(byte);
+            rolling = (rolling << 1).wrapping_add(GEAR[byte as usize]);
+            let at_boundary = chunk.len() >= MIN_CHUNK_SIZE && rolling & BOUNDARY_MASK == 0;
+            if at_boundary || chunk.len() >= MAX_CHUNK_SIZE {
+                chunks.insert(blake3::hash(&chunk), chunk.len() as u64);
+                chunk.clear();
+                rolling = 0;
+            }
+        }
+    }
+    if !chunk.is_empty() {
+        chunks.insert(blake3::hash(&chunk), chunk.len() as u64);
+    }
+    Ok(FileChunks {
+        chunks,
+        whole: whole.finalize(),
+    })
+}
+
+/// Find pairs of files of at least `min_size` bytes whose shared content chunks cover at
+/// least `min_overlap` of the smaller file. Identical files are left to the duplicate scan.
+/// Pairs are sorted by shared bytes, largest first.
+pub fn find_block_overlaps(
+    files: &[FileInfo],
+    min_size: u64,
+    min_overlap: f64,
+) -> Vec<BlockOverlap> {
+    let chunked: Vec<(&FileInfo, FileChunks)> = files
+        .par_iter()
+        .filter(|file| file.size >= min_size)
+        .filter_map(|file| match chunk_file(&file.path) {
+            Ok(chunks) => Some((file, chunks)),
+            Err(e) => {
+                log::warn!("Failed to read {:?}: {}", file.path, e);
+                None
+            }
+        })
+        .collect();
+
+    // Which files hold each chunk, then the shared bytes of every pair with a chunk in common
+    let mut holders: HashMap<&blake3::Hash, Vec<usize>> = HashMap::new();
+    for (index, (_, file_chunks)) in chunked.iter().enumerate() {
+        for hash in file_chunks.chunks.keys() {
+            holders.entry(hash).or_default().push(index);
+        }
+    }
+    let mut shared: HashMap<(usize, usize), u64> = HashMap::new();
+    for (hash, indexes) in &holders {
+        let len = chunked[indexes[0]].1.chunks[*hash];
+        for (i, &a) in indexes.iter().enumerate() {
+            for &b in &indexes[i + 1..] {
+                *shared.entry((a, b)).or_default() += len;
+            }
+        }
+    }
+
+    let mut overlaps: Vec<BlockOverlap> = shared
+        .into_iter()
+        .filter_map(|((a, b), shared_bytes)| {
+            let (first, first_chunks) = &chunked[a];
+            let (second, second_chunks) = &chunked[b];
+            if first_chunks.whole == second_chunks.whole {
+                return None;
+            }
+            let smaller = first_chunks
+                .unique_bytes()
+                .min(second_chunks.unique_bytes())
+                .max(1);
+            let overlap = shared_bytes as f64 / smaller as f64;
+            (overlap >= min_overlap).then(|| BlockOverlap {
+                first: (*first).clone(),
+                second: (*second).clone(),
+                shared_bytes,
+                overlap,
+            })
+        })
+        .collect();
+    overlaps.sort_by(|a, b| {
+        b.shared_bytes
+            .cmp(&a.shared_bytes)
+            .then_with(|| a.first.path.cmp(&b.first.path))
+            .then_with(|| a.second.path.cmp(&b.second.path))
+    });
+    overlaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Deterministic pseudo-random bytes (xorshift64), different for each seed
+    fn noise(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shared_middle_region_is_detected_and_quantified() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let middle = noise(1, 600_000);
+        let image_a = [noise(2, 200_000), middle.clone(), noise(3, 200_000)].concat();
+        // Different-length head, so the shared region sits at another offset
+        let image_b = [noise(4, 123_457), middle, noise(5, 200_000)].concat();
+        let paths = [
+            dir.path().join("a.img"),
+            dir.path().join("b.img"),
+            dir.path().join("unrelated.img"),
+            dir.path().join("a-copy.img"),
+            dir.path().join("small.img"),
+        ];
+        fs::write(&paths[0], &image_a)?;
+        fs::write(&paths[1], &image_b)?;
+        fs::write(&paths[2], noise(6, 1_000_000))?;
+        fs::write(&paths[3], &image_a)?;
+        fs::write(&paths[4], &image_a[..50_000])?;
+
+        let files: Vec<FileInfo> = paths
+            .iter()
+            .map(|path| FileInfo {
+                path: path.clone(),
+                size: fs::metadata(path).unwrap().len(),
+                hash: None,
+                modified_at: None,
+                created_at: None,
+            })
+            .collect();
+        let found = find_block_overlaps(&files, 100_000, 0.5);
+
+        // a.img and its identical copy each overlap b.img; the copies themselves aren't reported
+        assert_eq!(found.len(), 2, "{:?}", found);
+        for pair in &found {
+            let names = [&pair.first.path, &pair.second.path];
+            assert!(names.contains(&&paths[1]), "{:?}", names);
+            assert!(!names.contains(&&paths[2]) && !names.contains(&&paths[4]));
+            // 600 KB of ~923 KB in b.img; chunks straddling the region's edges don't count
+            assert!(
+                pair.shared_bytes > 500_000 && pair.shared_bytes <= 600_000,
+                "{}",
+                pair.shared_bytes
+            );
+            assert!(
+                pair.overlap > 0.55 && pair.overlap < 0.66,
+                "{}",
+                pair.overlap
+            );
+        }
+
+        assert!(find_block_overlaps(&files, 100_000, 0.7).is_empty());
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 #[allow(unused_imports)]
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
@@ -22,8 +23,45 @@ pub fn set_test_config_path(path: Option<PathBuf>) {
     });
 }
 
+/// How to hash files with one extension (`[hash_overrides.<ext>]`), replacing the
+/// global `--algorithm` and `--hash-limit` for those files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HashOverride {
+    /// Hashing algorithm for these files
+    pub algorithm: String,
+
+    /// Only hash the first N bytes; matches are approximate. Unset hashes the whole file.
+    #[serde(default)]
+    pub hash_limit: Option<u64>,
+}
+
+/// `[theme]` section of the config file: a preset plus optional per-slot colors.
+/// Colors use ratatui names ("blue", "lightgreen"), indexes ("42") or hex ("#ff8800").
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    /// Preset to start from (dark, light or high-contrast)
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Background of the highlighted row in every panel
+    #[serde(default)]
+    pub selected_row: Option<String>,
+
+    /// Parent folder rows in the sets panel
+    #[serde(default)]
+    pub folder: Option<String>,
+
+    /// Duplicate set rows in the sets panel
+    #[serde(default)]
+    pub set: Option<String>,
+
+    /// Entries in the jobs panel
+    #[serde(default)]
+    pub job: Option<String>,
+}
+
 /// Configuration structure for .deduprc file
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct DedupConfig {
     /// Hashing algorithm to use for comparing files
     #[serde(default = "default_algorithm")]
@@ -61,6 +99,11 @@ pub struct DedupConfig {
     #[serde(default)]
     pub exclude: Vec<String>,
 
+    /// File and directory names skipped in every scan (replaces the built-in list;
+    /// empty to skip nothing). `--no-default-excludes` ignores this list.
+    #[serde(default = "default_default_excludes")]
+    pub default_excludes: Vec<String>,
+
     /// Location to store file hash cache
     #[serde(default)]
     pub cache_location: Option<PathBuf>,
@@ -72,6 +115,40 @@ pub struct DedupConfig {
     /// Media deduplication options
     #[serde(default)]
     pub media_dedup: MediaDedupOptions,
+
+    /// Record delete/move/copy actions in an undo journal
+    #[serde(default)]
+    pub journal_enabled: bool,
+
+    /// Copy speed limit, e.g. "2M" for 2 MB/s
+    #[serde(default)]
+    pub bwlimit: Option<String>,
+
+    /// Resume partially copied files instead of copying them again
+    #[serde(default)]
+    pub resume_transfers: bool,
+
+    /// Directories whose files are never deleted, moved or replaced
+    #[serde(default)]
+    pub protected_paths: Vec<PathBuf>,
+
+    /// Tie-breakers applied in order when the selection strategy rates files equally
+    /// (path, name, newest, oldest)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tiebreak: Vec<String>,
+
+    /// TUI color theme: a preset name plus optional color overrides
+    #[serde(default)]
+    pub theme: ThemeConfig,
+
+    /// Other config files merged in before this one (with --follow-config-includes).
+    /// Relative paths are resolved against the directory of the including file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub config_includes: Vec<PathBuf>,
+
+    /// Per-extension hashing strategies, keyed by extension without the dot
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hash_overrides: BTreeMap<String, HashOverride>,
 }
 
 fn default_algorithm() -> String {
@@ -94,6 +171,13 @@ fn default_sort_order() -> String {
     "descending".to_string()
 }
 
+fn default_default_excludes() -> Vec<String> {
+    crate::file_utils::DEFAULT_EXCLUDES
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
 impl Default for DedupConfig {
     fn default() -> Self {
         Self {
@@ -106,9 +190,18 @@ impl Default for DedupConfig {
             sort_order: default_sort_order(),
             include: Vec::new(),
             exclude: Vec::new(),
+            default_excludes: default_default_excludes(),
             cache_location: None,
             fast_mode: false,
             media_dedup: MediaDedupOptions::default(),
+            journal_enabled: false,
+            bwlimit: None,
+            resume_transfers: false,
+            protected_paths: Vec::new(),
+            tiebreak: Vec::new(),
+            theme: ThemeConfig::default(),
+            config_includes: Vec::new(),
+            hash_overrides: BTreeMap::new(),
         }
     }
 }
@@ -202,6 +295,12 @@ impl DedupConfig {
                 // Parse the TOML content
                 let config: DedupConfig = toml::from_str(&contents)
                     .with_context(|| format!("Failed to parse config file: {:?}", path))?;
+                if !config.config_includes.is_empty() {
+                    log::warn!(
+                        "Ignoring config_includes in {:?}; pass --follow-config-includes to load them",
+                        path
+                    );
+                }
                 Ok(config)
             }
             Err(e) if e.kind() == ErrorKind::NotFound => {
@@ -215,15 +314,81 @@ impl DedupConfig {
         }
     }
 
+    /// Load `path` merged on top of the files it lists in `config_includes`, each of which
+    /// may include others. Includes are applied in order, later ones overriding earlier
+    /// ones, and the including file overrides them all. Merging is per key (and per key
+    /// within sections like `[theme]`), so a file only overrides what it sets. A missing
+    /// top-level file gives the defaults; a missing include or an include cycle is an error.
+    pub fn load_with_includes(path: &Path) -> Result<Self> {
+        let table = Self::load_table_with_includes(path, &mut Vec::new())?;
+        toml::Value::Table(table)
+            .try_into()
+            .with_context(|| format!("Failed to parse config file: {:?}", path))
+    }
+
+    /// Read one config file as a TOML table with its includes merged underneath.
+    /// `chain` holds the files currently being loaded, outermost first.
+    fn load_table_with_includes(path: &Path, chain: &mut Vec<PathBuf>) -> Result<toml::Table> {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if chain.contains(&canonical) {
+            let cycle: Vec<String> = chain
+                .iter()
+                .chain(std::iter::once(&canonical))
+                .map(|p| p.display().to_string())
+                .collect();
+            anyhow::bail!("Config include cycle: {}", cycle.join(" -> "));
+        }
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound && chain.is_empty() => {
+                return Ok(toml::Table::new());
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read config file: {:?}", path))
+            }
+        };
+        let mut own: toml::Table = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file: {:?}", path))?;
+
+        let includes: Vec<PathBuf> = match own.remove("config_includes") {
+            Some(value) => value.try_into().with_context(|| {
+                format!("config_includes in {:?} must be a list of paths", path)
+            })?,
+            None => Vec::new(),
+        };
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        chain.push(canonical);
+        let mut merged = toml::Table::new();
+        for include in includes {
+            let include_path = base_dir.join(include);
+            log::debug!("Including config file {:?} from {:?}", include_path, path);
+            merge_tables(
+                &mut merged,
+                Self::load_table_with_includes(&include_path, chain)?,
+            );
+        }
+        chain.pop();
+
+        merge_tables(&mut merged, own);
+        Ok(merged)
+    }
+
     /// Save the current configuration to the .deduprc file
     pub fn save(&self) -> Result<()> {
         let config_path = Self::get_config_path()?;
         self.save_to_path(&config_path)
     }
 
+    /// The configuration as .deduprc TOML, including the `[media_dedup]` and `[theme]` sections
+    pub fn to_toml_string(&self) -> Result<String> {
+        toml::to_string_pretty(self).context("Failed to serialize config to TOML")
+    }
+
     /// Save the configuration to a specific path
     pub fn save_to_path(&self, path: &Path) -> Result<()> {
-        let toml = toml::to_string_pretty(self).context("Failed to serialize config to TOML")?;
+        let toml = self.to_toml_string()?;
 
         // Create parent directories if they don't exist
         if let Some(parent) = path.parent() {
@@ -254,6 +419,20 @@ impl DedupConfig {
     }
 }
 
+/// Merge `overlay` into `base`, replacing values key by key and merging nested tables
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        let value = match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(nested)) => {
+                merge_tables(existing, nested);
+                continue;
+            }
+            (_, value) => value,
+        };
+        base.insert(key, value);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -274,6 +453,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_save_and_load_config() -> Result<()> {
         // Create a temporary directory for testing
         let temp_dir = tempdir()?;
@@ -300,4 +480,141 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_populated_config_round_trips_through_toml_and_json() -> Result<()> {
+        use crate::media_dedup::{FormatPreference, ResolutionPreference};
+
+        let config = DedupConfig {
+            algorithm: "blake3".to_string(),
+            parallel: Some(8),
+            mode: "oldest_modified".to_string(),
+            format: "csv".to_string(),
+            progress: true,
+            sort_by: "size".to_string(),
+            sort_order: "ascending".to_string(),
+            include: vec!["*.jpg".to_string()],
+            exclude: vec!["*.tmp".to_string()],
+            default_excludes: vec![".DS_Store".to_string()],
+            cache_location: Some(PathBuf::from("/var/cache/dedups")),
+            fast_mode: true,
+            media_dedup: MediaDedupOptions {
+                enabled: true,
+                resolution_preference: ResolutionPreference::Lowest,
+                format_preference: FormatPreference {
+                    formats: vec!["png".to_string(), "jpg".to_string()],
+                },
+                similarity_threshold: 75,
+                ..Default::default()
+            },
+            journal_enabled: true,
+            bwlimit: Some("2M".to_string()),
+            resume_transfers: true,
+            protected_paths: vec![PathBuf::from("/photos/originals")],
+            tiebreak: vec!["name".to_string(), "oldest".to_string()],
+            theme: ThemeConfig {
+                name: Some("light".to_string()),
+                ..Default::default()
+            },
+            config_includes: vec![PathBuf::from("shared.toml")],
+            hash_overrides: BTreeMap::from([(
+                "iso".to_string(),
+                HashOverride {
+                    algorithm: "xxhash".to_string(),
+                    hash_limit: Some(1 << 20),
+                },
+            )]),
+        };
+
+        let from_toml: DedupConfig = toml::from_str(&config.to_toml_string()?)?;
+        assert_eq!(from_toml, config);
+        let from_json: DedupConfig = serde_json::from_str(&serde_json::to_string(&config)?)?;
+        assert_eq!(from_json, config);
+
+        let path = tempdir()?.path().join("deduprc");
+        config.save_to_path(&path)?;
+        assert_eq!(
+            toml::from_str::<DedupConfig>(&fs::read_to_string(&path)?)?,
+            config
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unavailable_gxhash_in_config_falls_back_to_xxhash() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("deduprc");
+        DedupConfig {
+            algorithm: "gxhash".to_string(),
+            ..Default::default()
+        }
+        .save_to_path(&path)?;
+
+        let cli =
+            crate::Cli::with_config_from(["dedups", "--config-file", path.to_str().unwrap(), "."])?;
+
+        let expected = if crate::file_utils::GXHASH_AVAILABLE {
+            "gxhash"
+        } else {
+            "xxhash"
+        };
+        assert_eq!(cli.algorithm, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_effective_config_merges_cli_over_config() -> Result<()> {
+        use clap::Parser;
+
+        let mut cli = crate::Cli::parse_from([
+            "dedups",
+            "--algorithm",
+            "blake3",
+            "--tiebreak",
+            "name",
+            "--protect",
+            "/photos/keep",
+            ".",
+        ]);
+        cli.apply_config(DedupConfig {
+            algorithm: "sha256".to_string(),
+            cache_location: Some(PathBuf::from("/var/cache/dedups")),
+            fast_mode: true,
+            bwlimit: Some("2M".to_string()),
+            tiebreak: vec!["oldest".to_string()],
+            protected_paths: vec![PathBuf::from("/photos/originals")],
+            media_dedup: MediaDedupOptions {
+                enabled: true,
+                similarity_threshold: 80,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let effective = cli.effective_config();
+        assert_eq!(effective.algorithm, "blake3", "the command line wins");
+        assert_eq!(effective.tiebreak, ["name"]);
+        assert_eq!(
+            effective.protected_paths,
+            [
+                PathBuf::from("/photos/keep"),
+                PathBuf::from("/photos/originals")
+            ]
+        );
+        assert_eq!(
+            effective.cache_location,
+            Some(PathBuf::from("/var/cache/dedups"))
+        );
+        assert!(effective.fast_mode);
+        assert_eq!(effective.bwlimit.as_deref(), Some("2097152"));
+        assert!(effective.media_dedup.enabled);
+        assert_eq!(effective.media_dedup.similarity_threshold, 80);
+        assert_eq!(effective.sort_by, "modified");
+        assert_eq!(effective.default_excludes.len(), 5);
+
+        // The dump reads back as the same configuration
+        let dumped: DedupConfig = toml::from_str(&effective.to_toml_string()?)?;
+        assert_eq!(dumped, effective);
+        Ok(())
+    }
 }
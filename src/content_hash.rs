@@ -0,0 +1,243 @@
+use anyhow::Result;
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+use crate::file_utils::{calculate_hash, hash_bytes};
+
+/// Prefix for hashes of metadata-stripped content (`--ignore-metadata`). The files in
+/// such a set are not byte-identical, so byte-for-byte verification passes them through.
+pub const CONTENT_HASH_PREFIX: &str = "content_";
+
+/// File formats whose metadata can be stripped before hashing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetadataFormat {
+    Mp3,
+    Png,
+    Jpeg,
+}
+
+impl MetadataFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "mp3" => Some(Self::Mp3),
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `--ignore-metadata` hashes this file by content rather than as a whole
+pub fn has_strippable_metadata(path: &Path) -> bool {
+    MetadataFormat::from_path(path).is_some()
+}
+
+/// Hash the file with its known metadata regions removed: ID3 tags for mp3, text, time
+/// and EXIF chunks for PNG, and EXIF/XMP/IPTC segments and comments for JPEG. Unknown
+/// types, and files whose structure can't be parsed, are hashed whole.
+pub fn normalized_hash(path: &Path, algorithm: &str) -> Result<String> {
+    let Some(format) = MetadataFormat::from_path(path) else {
+        return calculate_hash(path, algorithm);
+    };
+    let data = fs::read(path)?;
+    let content = match format {
+        MetadataFormat::Mp3 => Some(strip_id3(&data)),
+        MetadataFormat::Png => strip_png_metadata(&data).map(Cow::Owned),
+        MetadataFormat::Jpeg => strip_jpeg_metadata(&data).map(Cow::Owned),
+    };
+    match content {
+        Some(content) => hash_bytes(&content, algorithm),
+        None => {
+            log::debug!("Could not parse {:?}; hashing the whole file", path);
+            hash_bytes(&data, algorithm)
+        }
+    }
+}
+
+/// Drop leading ID3v2 tags and a trailing 128-byte ID3v1 tag
+fn strip_id3(data: &[u8]) -> Cow<'_, [u8]> {
+    let mut start = 0;
+    // Tags can be stacked, e.g. after re-tagging with different tools
+    while data.len() >= start + 10 && &data[start..start + 3] == b"ID3" {
+        let size_bytes = &data[start + 6..start + 10];
+        if size_bytes.iter().any(|b| b & 0x80 != 0) {
+            break; // Not a syncsafe size, so not really a tag
+        }
+        let size = size_bytes
+            .iter()
+            .fold(0usize, |acc, b| (acc << 7) | *b as usize);
+        let has_footer = data[start + 5] & 0x10 != 0;
+        start = (start + 10 + size + if has_footer { 10 } else { 0 }).min(data.len());
+    }
+
+    let mut end = data.len();
+    if end >= start + 128 && &data[end - 128..end - 125] == b"TAG" {
+        end -= 128;
+    }
+    Cow::Borrowed(&data[start..end])
+}
+
+/// PNG chunks that carry metadata rather than image data
+const PNG_METADATA_CHUNKS: [&[u8; 4]; 5] = [b"tEXt", b"zTXt", b"iTXt", b"tIME", b"eXIf"];
+
+/// Rebuild the PNG without its metadata chunks, or None if it isn't a well-formed PNG
+fn strip_png_metadata(data: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if !data.starts_with(SIGNATURE) {
+        return None;
+    }
+    let mut out = SIGNATURE.to_vec();
+    let mut pos = SIGNATURE.len();
+    while pos < data.len() {
+        let header = data.get(pos..pos + 8)?;
+        let length = u32::from_be_bytes(header[..4].try_into().ok()?) as usize;
+        // length, type, data, crc
+        let chunk = data.get(pos..pos + 12 + length)?;
+        if !PNG_METADATA_CHUNKS.iter().any(|t| &header[4..8] == *t) {
+            out.extend_from_slice(chunk);
+        }
+        pos += chunk.len();
+    }
+    Some(out)
+}
+
+/// Rebuild the JPEG without APP1 (EXIF/XMP), APP13 (IPTC) and comment segments, or None
+/// if it isn't a well-formed JPEG
+fn strip_jpeg_metadata(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut out = vec![0xFF, 0xD8];
+    let mut pos = 2;
+    loop {
+        let marker = data.get(pos..pos + 2)?;
+        if marker[0] != 0xFF {
+            return None;
+        }
+        match marker[1] {
+            // Start of scan: everything after is image data
+            0xDA => {
+                out.extend_from_slice(&data[pos..]);
+                return Some(out);
+            }
+            // Standalone markers carry no length
+            0xD0..=0xD7 | 0x01 => {
+                out.extend_from_slice(marker);
+                pos += 2;
+            }
+            0xD9 => {
+                out.extend_from_slice(marker);
+                return Some(out);
+            }
+            kind => {
+                let length = u16::from_be_bytes(data.get(pos + 2..pos + 4)?.try_into().ok()?);
+                let segment = data.get(pos..pos + 2 + length as usize)?;
+                if !matches!(kind, 0xE1 | 0xED | 0xFE) {
+                    out.extend_from_slice(segment);
+                }
+                pos += segment.len();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    /// An ID3v2.3 tag holding a single title frame
+    fn id3v2_tag(title: &str) -> Vec<u8> {
+        let mut frame = b"TIT2".to_vec();
+        frame.extend_from_slice(&(title.len() as u32 + 1).to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0]);
+        frame.extend_from_slice(title.as_bytes());
+        let mut tag = b"ID3\x03\x00\x00".to_vec();
+        let size = frame.len();
+        tag.extend([21, 14, 7, 0].map(|shift| ((size >> shift) & 0x7f) as u8));
+        tag.extend(frame);
+        tag
+    }
+
+    #[test]
+    fn test_mp3s_differing_only_in_id3_tags() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("music");
+        fs::create_dir(&root)?;
+        let audio: Vec<u8> = (0..4096u32).map(|i| (i % 253) as u8).collect();
+
+        let mut tagged = id3v2_tag("A much longer song title");
+        tagged.extend(&audio);
+        fs::write(root.join("tagged.mp3"), &tagged)?;
+        let mut retagged = id3v2_tag("Short");
+        retagged.extend(&audio);
+        let mut v1 = b"TAG".to_vec();
+        v1.resize(128, b' ');
+        retagged.extend(v1);
+        fs::write(root.join("retagged.mp3"), &retagged)?;
+
+        assert_eq!(
+            normalized_hash(&root.join("tagged.mp3"), "xxhash")?,
+            normalized_hash(&root.join("retagged.mp3"), "xxhash")?
+        );
+
+        let scan = |args: &[&str]| -> Result<Vec<crate::file_utils::DuplicateSet>> {
+            let mut argv = vec!["dedups"];
+            argv.extend(args);
+            argv.push(root.to_str().unwrap());
+            let (tx, _rx) = std::sync::mpsc::channel();
+            crate::file_utils::find_duplicate_files_with_progress(&crate::Cli::parse_from(argv), tx)
+        };
+        assert!(scan(&[])?.is_empty(), "the files differ byte for byte");
+        let sets = scan(&["--ignore-metadata"])?;
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].files.len(), 2);
+        assert!(sets[0].hash.starts_with(CONTENT_HASH_PREFIX));
+        Ok(())
+    }
+
+    #[test]
+    fn test_png_and_jpeg_metadata_is_stripped() {
+        let png_chunk = |kind: &[u8], body: &[u8]| {
+            let mut chunk = (body.len() as u32).to_be_bytes().to_vec();
+            chunk.extend_from_slice(kind);
+            chunk.extend_from_slice(body);
+            chunk.extend_from_slice(&[0, 0, 0, 0]);
+            chunk
+        };
+        let png = |comment: &[u8]| {
+            let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+            png.extend(png_chunk(b"IHDR", &[1; 13]));
+            png.extend(png_chunk(b"tEXt", comment));
+            png.extend(png_chunk(b"IDAT", &[7; 32]));
+            png.extend(png_chunk(b"IEND", &[]));
+            png
+        };
+        assert_eq!(
+            strip_png_metadata(&png(b"Comment\0first")),
+            strip_png_metadata(&png(b"Comment\0second, longer"))
+        );
+
+        let jpeg = |exif: &[u8]| {
+            let mut jpeg = vec![0xFF, 0xD8];
+            jpeg.extend([0xFF, 0xE1]);
+            jpeg.extend((exif.len() as u16 + 2).to_be_bytes());
+            jpeg.extend_from_slice(exif);
+            jpeg.extend([0xFF, 0xDB, 0x00, 0x04, 0x01, 0x02]);
+            jpeg.extend([0xFF, 0xDA, 0x00, 0x02, 0x11, 0x22, 0xFF, 0xD9]);
+            jpeg
+        };
+        let stripped = strip_jpeg_metadata(&jpeg(b"Exif\0\0taken today"));
+        assert_eq!(
+            stripped,
+            strip_jpeg_metadata(&jpeg(b"Exif\0\0taken yesterday"))
+        );
+        assert_eq!(stripped.unwrap().len(), 2 + 6 + 8);
+
+        // Not the format the extension claims: no stripping
+        assert_eq!(strip_png_metadata(b"plain text"), None);
+        assert_eq!(strip_jpeg_metadata(b"plain text"), None);
+    }
+}
@@ -0,0 +1,235 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::file_utils::DuplicateSet;
+
+/// Directories whose entire contents are identical: the same relative paths holding
+/// files with the same hashes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateDirGroup {
+    pub directories: Vec<PathBuf>,
+    /// Files in each copy of the directory
+    pub file_count: usize,
+    /// Bytes in each copy of the directory
+    pub total_size: u64,
+}
+
+impl DuplicateDirGroup {
+    /// Space freed by keeping one copy and removing the rest
+    pub fn reclaimable_bytes(&self) -> u64 {
+        self.total_size * (self.directories.len().saturating_sub(1) as u64)
+    }
+}
+
+/// (path relative to the directory, hash) for every file below a directory
+type Listing = Vec<(PathBuf, String)>;
+
+/// What a directory holds, as far as the duplicate scan knows
+struct DirContents {
+    files: Listing,
+    size: u64,
+    /// False once a file below it turns out not to be a known duplicate
+    complete: bool,
+}
+
+impl DirContents {
+    fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            size: 0,
+            complete: true,
+        }
+    }
+
+    fn taint(&mut self) {
+        self.complete = false;
+        self.files.clear();
+    }
+}
+
+/// Find directories under `roots` that are complete copies of one another, using the
+/// hashes already computed for `sets`. Every file in such a directory must belong to a
+/// duplicate set, so a folder holding even one unique (or unscanned, e.g. hidden) file
+/// never matches. When whole parent folders match, their matching subfolders are not
+/// reported separately. Groups come back largest reclaimable space first.
+pub fn find_duplicate_directories(
+    sets: &[DuplicateSet],
+    roots: &[PathBuf],
+) -> Vec<DuplicateDirGroup> {
+    let known: HashMap<&Path, (&str, u64)> = sets
+        .iter()
+        .flat_map(|set| {
+            set.files
+                .iter()
+                .chain(&set.hardlinks)
+                .map(move |f| (f.path.as_path(), (set.hash.as_str(), f.size)))
+        })
+        .collect();
+    if known.is_empty() {
+        return Vec::new();
+    }
+
+    let mut dirs: BTreeMap<PathBuf, DirContents> = BTreeMap::new();
+    for (i, root) in roots.iter().enumerate() {
+        // Nested roots would walk the same files twice
+        if roots
+            .iter()
+            .enumerate()
+            .any(|(j, other)| j != i && root.starts_with(other) && (root != other || j < i))
+        {
+            continue;
+        }
+
+        for entry in WalkDir::new(root).follow_links(false) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    // Whatever couldn't be read might not be a duplicate
+                    if let Some(path) = e.path() {
+                        for ancestor in path.ancestors().filter(|a| a.starts_with(root)) {
+                            dirs.entry(ancestor.to_path_buf())
+                                .or_insert_with(DirContents::new)
+                                .taint();
+                        }
+                    }
+                    continue;
+                }
+            };
+            if entry.file_type().is_dir() {
+                dirs.entry(entry.path().to_path_buf())
+                    .or_insert_with(DirContents::new);
+                continue;
+            }
+
+            // Symlinks and other non-regular entries can't be vouched for
+            let known_file = if entry.file_type().is_file() {
+                known.get(entry.path())
+            } else {
+                None
+            };
+            for ancestor in entry.path().ancestors().skip(1) {
+                if !ancestor.starts_with(root) {
+                    break;
+                }
+                let contents = dirs
+                    .entry(ancestor.to_path_buf())
+                    .or_insert_with(DirContents::new);
+                match known_file {
+                    Some((hash, size)) if contents.complete => {
+                        let relative = entry.path().strip_prefix(ancestor).unwrap_or(entry.path());
+                        contents
+                            .files
+                            .push((relative.to_path_buf(), hash.to_string()));
+                        contents.size += size;
+                    }
+                    Some(_) => {}
+                    None => contents.taint(),
+                }
+            }
+        }
+    }
+
+    // Group complete, non-empty directories by their sorted (relative path, hash) listing
+    let mut by_listing: BTreeMap<Listing, (Vec<PathBuf>, u64)> = BTreeMap::new();
+    for (dir, mut contents) in dirs {
+        if !contents.complete || contents.files.is_empty() {
+            continue;
+        }
+        contents.files.sort();
+        let entry = by_listing
+            .entry(contents.files)
+            .or_insert_with(|| (Vec::new(), contents.size));
+        entry.0.push(dir);
+    }
+    let groups: Vec<DuplicateDirGroup> = by_listing
+        .into_iter()
+        .filter(|(_, (dirs, _))| dirs.len() > 1)
+        .map(|(files, (directories, total_size))| DuplicateDirGroup {
+            directories,
+            file_count: files.len(),
+            total_size,
+        })
+        .collect();
+
+    // Drop groups that are just the matching subfolders of a larger matching group
+    let group_of: HashMap<&Path, usize> = groups
+        .iter()
+        .enumerate()
+        .flat_map(|(i, g)| g.directories.iter().map(move |d| (d.as_path(), i)))
+        .collect();
+    let implied: HashSet<usize> = groups
+        .iter()
+        .enumerate()
+        .filter(|(_, group)| {
+            let parent_groups: HashSet<Option<usize>> = group
+                .directories
+                .iter()
+                .map(|d| d.parent().and_then(|p| group_of.get(p).copied()))
+                .collect();
+            parent_groups.len() == 1 && parent_groups.iter().all(Option::is_some)
+        })
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut result: Vec<DuplicateDirGroup> = groups
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !implied.contains(i))
+        .map(|(_, g)| g)
+        .collect();
+    result.sort_by_key(|g| std::cmp::Reverse(g.reclaimable_bytes()));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+
+    #[test]
+    fn test_identical_trees_are_reported_as_duplicate_directories() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        for copy in ["backup_a", "backup_b"] {
+            fs::create_dir_all(root.join(copy).join("sub"))?;
+            fs::write(root.join(copy).join("notes.txt"), "notes")?;
+            fs::write(root.join(copy).join("sub/photo.jpg"), "pixels")?;
+        }
+        // Same two files, plus one that exists nowhere else
+        fs::create_dir_all(root.join("partial/sub"))?;
+        fs::write(root.join("partial/notes.txt"), "notes")?;
+        fs::write(root.join("partial/sub/photo.jpg"), "pixels")?;
+        fs::write(root.join("partial/unique.txt"), "only here")?;
+
+        let cli = crate::Cli::parse_from(["dedups", root.to_str().unwrap()]);
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = crate::file_utils::find_duplicate_files_with_progress(&cli, tx)?;
+
+        let groups = find_duplicate_directories(&sets, &cli.directories);
+        let pair = groups
+            .iter()
+            .find(|g| g.directories.contains(&root.join("backup_a")))
+            .expect("backup_a and backup_b should match");
+        assert_eq!(
+            pair.directories,
+            vec![root.join("backup_a"), root.join("backup_b")]
+        );
+        assert_eq!(pair.file_count, 2);
+        assert_eq!(pair.total_size, 11);
+
+        // partial/ has an extra file, but its sub/ folder is a full copy of the others
+        assert!(!groups
+            .iter()
+            .any(|g| g.directories.contains(&root.join("partial"))));
+        let subs = groups
+            .iter()
+            .find(|g| g.directories.contains(&root.join("partial/sub")))
+            .expect("partial/sub matches the backup sub folders");
+        assert_eq!(subs.directories.len(), 3);
+        assert_eq!(groups.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Code reported for errors that aren't one of the known `DedupError` conditions
+pub const UNKNOWN_ERROR_CODE: u16 = 1;
+
+/// Failure conditions with stable numeric codes, so `--json-events` consumers can
+/// branch on the `code` field instead of parsing messages. Raised where the condition
+/// is detected and carried through `anyhow` like any other error.
+#[derive(Debug, Error)]
+pub enum DedupError {
+    #[error("Target directory does not exist: {0:?}")]
+    DirectoryNotFound(PathBuf),
+
+    #[error("Target path is not a directory: {0:?}")]
+    NotADirectory(PathBuf),
+
+    #[error("Permission denied: {0:?}")]
+    PermissionDenied(PathBuf),
+
+    #[error("Failed to hash {path:?}: {source}")]
+    HashFailed {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("{0}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("Cache error: {0}")]
+    CacheError(String),
+}
+
+impl DedupError {
+    /// Stable code for this condition. Codes are grouped by area: 1x for paths,
+    /// 2x for hashing, 3x for the cache. Never renumber an existing variant.
+    pub fn code(&self) -> u16 {
+        match self {
+            DedupError::DirectoryNotFound(_) => 10,
+            DedupError::NotADirectory(_) => 11,
+            DedupError::PermissionDenied(_) => 12,
+            DedupError::HashFailed { .. } => 20,
+            DedupError::UnsupportedAlgorithm(_) => 21,
+            DedupError::CacheError(_) => 30,
+        }
+    }
+}
+
+/// Code for any error: the first `DedupError` in its chain, a bare permission-denied
+/// I/O error, or `UNKNOWN_ERROR_CODE`
+pub fn error_code(err: &anyhow::Error) -> u16 {
+    if let Some(known) = err.chain().find_map(|e| e.downcast_ref::<DedupError>()) {
+        return known.code();
+    }
+    let permission_denied = err.chain().any(|e| {
+        e.downcast_ref::<std::io::Error>()
+            .is_some_and(|io| io.kind() == std::io::ErrorKind::PermissionDenied)
+    });
+    if permission_denied {
+        DedupError::PermissionDenied(PathBuf::new()).code()
+    } else {
+        UNKNOWN_ERROR_CODE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_known_conditions_map_to_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.bin");
+
+        let err = crate::file_utils::calculate_hash(&missing, "xxhash").unwrap_err();
+        assert_eq!(error_code(&err), 20);
+
+        let err = crate::file_utils::calculate_hash(&missing, "rot13").unwrap_err();
+        assert_eq!(error_code(&err), 21);
+
+        let err = crate::file_cache::open_cache(
+            "redis",
+            dir.path(),
+            "xxhash",
+            crate::file_cache::DEFAULT_LOCK_TIMEOUT,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(error_code(&err), 30);
+
+        // Context added on the way up doesn't hide the condition
+        let err = anyhow::Error::from(DedupError::DirectoryNotFound(missing.clone()))
+            .context("while scanning");
+        assert_eq!(error_code(&err), 10);
+
+        let err = Err::<(), _>(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+            .context("reading config")
+            .unwrap_err();
+        assert_eq!(error_code(&err), 12);
+
+        assert_eq!(
+            error_code(&anyhow::anyhow!("something else")),
+            UNKNOWN_ERROR_CODE
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Finished, and no duplicates were found
+pub const NO_DUPLICATES: i32 = 0;
+/// Finished, and duplicates were found (e.g. to fail a CI job)
+pub const DUPLICATES_FOUND: i32 = 1;
+/// Finished, but some files or directories could not be read
+pub const PARTIAL_ERRORS: i32 = 2;
+/// Stopped by an error (bad arguments, missing directory, failed scan)
+pub const FATAL_ERROR: i32 = 3;
+
+/// Shown at the end of `--help`
+pub const EXIT_CODES_HELP: &str = "Exit codes:
+  0  no duplicates found
+  1  duplicates found
+  2  finished, but some files could not be read
+  3  fatal error";
+
+static DUPLICATES: AtomicBool = AtomicBool::new(false);
+static UNREADABLE: AtomicUsize = AtomicUsize::new(0);
+
+/// Note that the run found duplicates
+pub fn record_duplicates_found() {
+    DUPLICATES.store(true, Ordering::Relaxed);
+}
+
+/// Note a file or directory that couldn't be read during the run
+pub fn record_unreadable() {
+    UNREADABLE.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Files and directories that couldn't be read so far
+pub fn unreadable_count() -> usize {
+    UNREADABLE.load(Ordering::Relaxed)
+}
+
+/// The process exit code for how the run ended. Unreadable files outrank duplicates,
+/// since the duplicate count may be incomplete.
+pub fn for_result<T>(result: &anyhow::Result<T>) -> i32 {
+    if result.is_err() {
+        FATAL_ERROR
+    } else if unreadable_count() > 0 {
+        PARTIAL_ERRORS
+    } else if DUPLICATES.load(Ordering::Relaxed) {
+        DUPLICATES_FOUND
+    } else {
+        NO_DUPLICATES
+    }
+}
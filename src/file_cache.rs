@@ -1,13 +1,26 @@
 use anyhow::{Context, Result};
+use fs4::{FileExt, TryLockError};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 
 use crate::file_utils::FileInfo;
 
+/// Compare modification times at whole-second granularity. Some filesystems (FAT,
+/// SMB/NFS shares, some archive extractors) only keep second-resolution mtimes, so
+/// comparing nanoseconds would needlessly invalidate cache entries.
+pub(crate) fn same_mtime(a: SystemTime, b: SystemTime) -> bool {
+    let secs = |t: SystemTime| {
+        t.duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i128)
+            .unwrap_or_else(|e| -(e.duration().as_secs_f64().ceil() as i128))
+    };
+    secs(a) == secs(b)
+}
+
 /// File cache entry stored for each file
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct FileCacheEntry {
@@ -16,6 +29,9 @@ struct FileCacheEntry {
     hash: String,
     modified_at: Option<SystemTime>,
     algorithm: String,
+    /// When the entry was written; missing for caches created by older versions
+    #[serde(default)]
+    cached_at: Option<SystemTime>,
 }
 
 impl FileCacheEntry {
@@ -26,6 +42,7 @@ impl FileCacheEntry {
             hash: hash.clone(),
             modified_at: file_info.modified_at,
             algorithm: algorithm.to_string(),
+            cached_at: Some(SystemTime::now()),
         })
     }
 
@@ -55,7 +72,7 @@ impl FileCacheEntry {
                 // Check if modification time matches
                 if let Ok(mtime) = metadata.modified() {
                     if let Some(cached_mtime) = self.modified_at {
-                        return mtime == cached_mtime;
+                        return same_mtime(mtime, cached_mtime);
                     }
                 }
 
@@ -66,6 +83,179 @@ impl FileCacheEntry {
     }
 }
 
+/// Common interface for hash cache backends (`--cache-backend`)
+pub trait HashCache: Send {
+    /// Get a complete FileInfo from the cache if available and still valid
+    fn get_file_info(&self, path: &Path) -> Option<FileInfo>;
+    /// Store a file hash in the cache
+    fn store(&mut self, file_info: &FileInfo, algorithm: &str) -> Result<()>;
+    /// Drop entries below any of `roots` whose file was deleted or changed since it was
+    /// hashed, returning how many were removed
+    fn prune_stale(&mut self, roots: &[PathBuf]) -> Result<usize>;
+    /// Persist any pending changes
+    fn save(&mut self) -> Result<()>;
+    /// Number of cached entries
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Open the hash cache for `cache_dir` using the named backend (`file` or `sqlite`).
+/// The file backend waits up to `lock_timeout` for another dedups instance to release it.
+pub fn open_cache(
+    backend: &str,
+    cache_dir: &Path,
+    algorithm: &str,
+    lock_timeout: Duration,
+) -> Result<Box<dyn HashCache>> {
+    open_backend(backend, cache_dir, algorithm, lock_timeout)
+        .map_err(|e| crate::error::DedupError::CacheError(format!("{:#}", e)).into())
+}
+
+fn open_backend(
+    backend: &str,
+    cache_dir: &Path,
+    algorithm: &str,
+    lock_timeout: Duration,
+) -> Result<Box<dyn HashCache>> {
+    match backend {
+        "file" => Ok(Box::new(FileCache::new(cache_dir, algorithm, lock_timeout)?)),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Ok(Box::new(crate::sqlite_cache::SqliteFileCache::new(
+            cache_dir, algorithm,
+        )?)),
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => Err(anyhow::anyhow!(
+            "The sqlite cache backend is not available: dedups was built without the `sqlite` feature"
+        )),
+        other => Err(anyhow::anyhow!(
+            "Unknown cache backend '{}'. Supported backends are file, sqlite.",
+            other
+        )),
+    }
+}
+
+/// Summary of the hash cache contents, as reported by `dedups cache stats`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    pub entry_count: usize,
+    pub size_on_disk: u64,
+    pub oldest_entry: Option<SystemTime>,
+    pub newest_entry: Option<SystemTime>,
+    pub entries_by_algorithm: std::collections::BTreeMap<String, usize>,
+}
+
+impl CacheStats {
+    fn merge(&mut self, other: CacheStats) {
+        self.entry_count += other.entry_count;
+        self.size_on_disk += other.size_on_disk;
+        self.oldest_entry = match (self.oldest_entry, other.oldest_entry) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.newest_entry = match (self.newest_entry, other.newest_entry) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        for (algorithm, count) in other.entries_by_algorithm {
+            *self.entries_by_algorithm.entry(algorithm).or_default() += count;
+        }
+    }
+}
+
+/// Whether a file in a cache directory belongs to dedups (flat caches, their temp
+/// files, and the SQLite database)
+fn is_cache_file(name: &str) -> bool {
+    (name.starts_with("file_hashes_") && (name.ends_with(".cache") || name.ends_with(".temp")))
+        || name.starts_with("file_hashes.sqlite")
+}
+
+/// Collect statistics for every cache stored in `cache_dir`
+pub fn cache_stats(cache_dir: &Path) -> Result<CacheStats> {
+    let mut stats = CacheStats::default();
+    if !cache_dir.exists() {
+        return Ok(stats);
+    }
+
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_cache_file(&name) {
+            continue;
+        }
+        stats.size_on_disk += entry.metadata()?.len();
+
+        if let Some(algorithm) = name
+            .strip_prefix("file_hashes_")
+            .and_then(|n| n.strip_suffix(".cache"))
+        {
+            // Read-only, so no need to wait for a running scan's lock
+            let cache = FileCache::load(cache_dir, algorithm, None)?;
+            let mut algo_stats = cache.stats();
+            algo_stats.size_on_disk = 0; // Already counted above
+            stats.merge(algo_stats);
+        }
+    }
+    Ok(stats)
+}
+
+/// Delete every dedups cache file in `cache_dir`. Returns the number of files removed.
+pub fn clear_cache(cache_dir: &Path) -> Result<usize> {
+    if !cache_dir.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        if is_cache_file(&entry.file_name().to_string_lossy()) {
+            fs::remove_file(entry.path())
+                .with_context(|| format!("Failed to remove cache file {:?}", entry.path()))?;
+            log::info!("Removed cache file {:?}", entry.path());
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// How long `FileCache::new` waits for another dedups instance to release the cache
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval between attempts to take a held cache lock
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Take the advisory lock guarding one cache file, waiting up to `timeout` for another
+/// process to release it. The lock is held until the returned file is dropped.
+fn acquire_cache_lock(cache_dir: &Path, algorithm: &str, timeout: Duration) -> Result<File> {
+    let lock_path = cache_dir.join(format!("file_hashes_{}.lock", algorithm));
+    let lock_file = File::create(&lock_path)
+        .with_context(|| format!("Failed to create cache lock file: {:?}", lock_path))?;
+    let started = Instant::now();
+    loop {
+        // Called through the trait: newer std has an inherent `File::try_lock` of its own
+        match FileExt::try_lock(&lock_file) {
+            Ok(()) => return Ok(lock_file),
+            Err(TryLockError::WouldBlock) if started.elapsed() < timeout => {
+                log::debug!("Waiting for cache lock {:?}", lock_path);
+                std::thread::sleep(LOCK_POLL_INTERVAL);
+            }
+            Err(TryLockError::WouldBlock) => {
+                anyhow::bail!(
+                    "Cache {:?} is locked by another dedups instance (waited {:.1}s)",
+                    cache_dir,
+                    timeout.as_secs_f64()
+                )
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to lock cache file: {:?}", lock_path))
+            }
+        }
+    }
+}
+
 /// Cache directory structure
 #[derive(Debug)]
 pub struct FileCache {
@@ -73,17 +263,26 @@ pub struct FileCache {
     entries: HashMap<PathBuf, FileCacheEntry>,
     algorithm: String,
     modified: bool,
+    /// Held for the cache's lifetime so concurrent runs can't overwrite each other's saves.
+    /// None for read-only loads.
+    _lock: Option<File>,
 }
 
 impl FileCache {
-    /// Create a new file cache using the given cache directory
-    pub fn new(cache_dir: &Path, algorithm: &str) -> Result<Self> {
+    /// Create a new file cache using the given cache directory, waiting up to
+    /// `lock_timeout` if another dedups instance has it open
+    pub fn new(cache_dir: &Path, algorithm: &str, lock_timeout: Duration) -> Result<Self> {
         // Create cache directory if it doesn't exist
         if !cache_dir.exists() {
             fs::create_dir_all(cache_dir)
                 .with_context(|| format!("Failed to create cache directory: {:?}", cache_dir))?;
         }
+        let lock = acquire_cache_lock(cache_dir, algorithm, lock_timeout)?;
+        Self::load(cache_dir, algorithm, Some(lock))
+    }
 
+    /// Read the cache file for `algorithm`, if there is one
+    fn load(cache_dir: &Path, algorithm: &str, lock: Option<File>) -> Result<Self> {
         let cache_file = Self::cache_file_path(cache_dir, algorithm);
         let mut entries = HashMap::new();
 
@@ -112,6 +311,7 @@ impl FileCache {
             entries,
             algorithm: algorithm.to_string(),
             modified: false,
+            _lock: lock,
         })
     }
 
@@ -179,6 +379,20 @@ impl FileCache {
         Ok(stored_count)
     }
 
+    /// Drop entries below any of `roots` that no longer match the file on disk
+    pub fn prune_stale(&mut self, roots: &[PathBuf]) -> usize {
+        let before = self.entries.len();
+        let algorithm = &self.algorithm;
+        self.entries.retain(|path, entry| {
+            !roots.iter().any(|root| path.starts_with(root)) || entry.is_valid(path, algorithm)
+        });
+        let removed = before - self.entries.len();
+        if removed > 0 {
+            self.modified = true;
+        }
+        removed
+    }
+
     /// Save the cache to disk
     pub fn save(&mut self) -> Result<()> {
         // Only save if the cache was modified
@@ -228,6 +442,30 @@ impl FileCache {
         self.modified = true;
     }
 
+    /// Summarize this cache: entry counts per algorithm, on-disk size and entry age range
+    pub fn stats(&self) -> CacheStats {
+        let mut stats = CacheStats {
+            entry_count: self.entries.len(),
+            size_on_disk: fs::metadata(Self::cache_file_path(&self.cache_dir, &self.algorithm))
+                .map(|m| m.len())
+                .unwrap_or(0),
+            ..Default::default()
+        };
+        for entry in self.entries.values() {
+            *stats
+                .entries_by_algorithm
+                .entry(entry.algorithm.clone())
+                .or_default() += 1;
+            if let Some(cached_at) = entry.cached_at {
+                stats.oldest_entry =
+                    Some(stats.oldest_entry.map_or(cached_at, |t| t.min(cached_at)));
+                stats.newest_entry =
+                    Some(stats.newest_entry.map_or(cached_at, |t| t.max(cached_at)));
+            }
+        }
+        stats
+    }
+
     /// Get the number of entries in the cache
     pub fn len(&self) -> usize {
         self.entries.len()
@@ -239,6 +477,28 @@ impl FileCache {
     }
 }
 
+impl HashCache for FileCache {
+    fn get_file_info(&self, path: &Path) -> Option<FileInfo> {
+        FileCache::get_file_info(self, path)
+    }
+
+    fn store(&mut self, file_info: &FileInfo, algorithm: &str) -> Result<()> {
+        FileCache::store(self, file_info, algorithm)
+    }
+
+    fn prune_stale(&mut self, roots: &[PathBuf]) -> Result<usize> {
+        Ok(FileCache::prune_stale(self, roots))
+    }
+
+    fn save(&mut self) -> Result<()> {
+        FileCache::save(self)
+    }
+
+    fn len(&self) -> usize {
+        FileCache::len(self)
+    }
+}
+
 impl Drop for FileCache {
     fn drop(&mut self) {
         // Try to save the cache when the object is dropped
@@ -284,7 +544,7 @@ mod tests {
         let test_file = create_test_file(&test_dir, "test1.txt", b"hello world")?;
 
         // Create a cache and store the file
-        let mut cache = FileCache::new(&cache_dir, "test_algo")?;
+        let mut cache = FileCache::new(&cache_dir, "test_algo", DEFAULT_LOCK_TIMEOUT)?;
         cache.store(&test_file, "test_algo")?;
 
         // Check that we can get the hash
@@ -296,13 +556,37 @@ mod tests {
         drop(cache);
 
         // Load the cache again and check the hash is still there
-        let cache2 = FileCache::new(&cache_dir, "test_algo")?;
+        let cache2 = FileCache::new(&cache_dir, "test_algo", DEFAULT_LOCK_TIMEOUT)?;
         let hash2 = cache2.get_hash(&test_file.path);
         assert_eq!(hash2, Some("test_hash".to_string()));
 
         Ok(())
     }
 
+    #[test]
+    fn test_cache_lock_blocks_a_second_instance() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let cache_dir = temp_dir.path().join("cache");
+        let timeout = Duration::from_millis(300);
+
+        let first = FileCache::new(&cache_dir, "xxhash", timeout)?;
+        let started = Instant::now();
+        let err = FileCache::new(&cache_dir, "xxhash", timeout).unwrap_err();
+        assert!(started.elapsed() >= timeout, "should wait before giving up");
+        assert!(err
+            .to_string()
+            .contains("locked by another dedups instance"));
+
+        // Other algorithms use their own cache file and lock
+        FileCache::new(&cache_dir, "sha256", timeout)?;
+        // Read-only stats don't wait for the lock
+        cache_stats(&cache_dir)?;
+
+        drop(first);
+        FileCache::new(&cache_dir, "xxhash", Duration::ZERO)?;
+        Ok(())
+    }
+
     #[test]
     fn test_cache_invalidation() -> Result<()> {
         let temp_dir = tempdir()?;
@@ -314,7 +598,7 @@ mod tests {
         let test_file = create_test_file(&test_dir, "test1.txt", b"hello world")?;
 
         // Create a cache and store the file
-        let mut cache = FileCache::new(&cache_dir, "test_algo")?;
+        let mut cache = FileCache::new(&cache_dir, "test_algo", DEFAULT_LOCK_TIMEOUT)?;
         cache.store(&test_file, "test_algo")?;
         cache.save()?;
 
@@ -342,15 +626,91 @@ mod tests {
         let test_file = create_test_file(&test_dir, "test1.txt", b"hello world")?;
 
         // Create a cache with one algorithm
-        let mut cache = FileCache::new(&cache_dir, "algo1")?;
+        let mut cache = FileCache::new(&cache_dir, "algo1", DEFAULT_LOCK_TIMEOUT)?;
         cache.store(&test_file, "algo1")?;
         cache.save()?;
 
         // Try to get the hash with a different algorithm
-        let cache2 = FileCache::new(&cache_dir, "algo2")?;
+        let cache2 = FileCache::new(&cache_dir, "algo2", DEFAULT_LOCK_TIMEOUT)?;
         let hash = cache2.get_hash(&test_file.path);
         assert_eq!(hash, None);
 
         Ok(())
     }
+
+    #[test]
+    fn test_cache_misses_after_mtime_change() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let cache_dir = temp_dir.path().join("cache");
+        let file_path = temp_dir.path().join("photo.jpg");
+        fs::write(&file_path, b"same length")?;
+
+        let base = SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_100);
+        filetime::set_file_mtime(&file_path, filetime::FileTime::from_system_time(base))?;
+
+        let metadata = fs::metadata(&file_path)?;
+        let file_info = FileInfo {
+            path: file_path.clone(),
+            size: metadata.len(),
+            hash: Some("test_hash".to_string()),
+            modified_at: metadata.modified().ok(),
+            created_at: None,
+        };
+        let mut cache = FileCache::new(&cache_dir, "test_algo", DEFAULT_LOCK_TIMEOUT)?;
+        cache.store(&file_info, "test_algo")?;
+        assert!(cache.get_file_info(&file_path).is_some());
+
+        // A sub-second difference (coarse filesystem timestamps) still hits
+        let same_second = base + std::time::Duration::from_millis(800);
+        filetime::set_file_mtime(
+            &file_path,
+            filetime::FileTime::from_system_time(same_second),
+        )?;
+        assert!(cache.get_file_info(&file_path).is_some());
+
+        // Touching the file without changing its length forces a rehash
+        let later = base + std::time::Duration::from_secs(5);
+        filetime::set_file_mtime(&file_path, filetime::FileTime::from_system_time(later))?;
+        assert!(cache.get_file_info(&file_path).is_none());
+        assert!(cache.get_hash(&file_path).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_stats_and_clear() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let cache_dir = temp_dir.path().join("cache");
+        let test_dir = temp_dir.path().join("test_files");
+        fs::create_dir_all(&test_dir)?;
+
+        let file_a = create_test_file(&test_dir, "a.txt", b"aaa")?;
+        let file_b = create_test_file(&test_dir, "b.txt", b"bbb")?;
+
+        let mut xx_cache = FileCache::new(&cache_dir, "xxhash", DEFAULT_LOCK_TIMEOUT)?;
+        xx_cache.store(&file_a, "xxhash")?;
+        xx_cache.store(&file_b, "xxhash")?;
+        xx_cache.save()?;
+        assert_eq!(xx_cache.stats().entry_count, 2);
+        drop(xx_cache);
+
+        let mut sha_cache = FileCache::new(&cache_dir, "sha256", DEFAULT_LOCK_TIMEOUT)?;
+        sha_cache.store(&file_a, "sha256")?;
+        sha_cache.save()?;
+        drop(sha_cache);
+
+        // Unrelated files in the cache directory are left alone
+        fs::write(cache_dir.join("notes.txt"), b"keep me")?;
+
+        let stats = cache_stats(&cache_dir)?;
+        assert_eq!(stats.entry_count, 3);
+        assert_eq!(stats.entries_by_algorithm.get("xxhash"), Some(&2));
+        assert_eq!(stats.entries_by_algorithm.get("sha256"), Some(&1));
+        assert!(stats.size_on_disk > 0);
+        assert!(stats.oldest_entry.is_some() && stats.oldest_entry <= stats.newest_entry);
+
+        assert_eq!(clear_cache(&cache_dir)?, 2);
+        assert_eq!(cache_stats(&cache_dir)?, CacheStats::default());
+        assert!(cache_dir.join("notes.txt").exists());
+        Ok(())
+    }
 }
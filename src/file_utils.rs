@@ -2,19 +2,27 @@ use anyhow::Result;
 use glob::{Pattern, PatternError};
 use num_cpus;
 use rayon::prelude::*;
+use regex::Regex;
 use sha1::Digest;
 use std::collections::HashMap;
 use std::fs::{self, File};
 use std::hash::Hasher;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::SystemTime;
 use walkdir::WalkDir;
 
+use crate::config::HashOverride;
+use crate::error::DedupError;
+use crate::journal::{self, JournalAction};
+use crate::transfer::{ConflictPolicy, TransferOptions};
 use crate::tui_app::ScanMessage;
 use crate::Cli;
+use indicatif::ProgressBar;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender as StdMpscSender;
+use std::sync::{Arc, Condvar, Mutex};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortCriterion {
@@ -79,6 +87,130 @@ impl std::fmt::Display for SortOrder {
     }
 }
 
+/// How `--order-by` sorts the duplicate sets in the CLI listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOrder {
+    /// Reclaimable bytes: `size * (files - 1)`
+    Size,
+    /// Number of files in the set
+    Count,
+    /// The set's alphabetically first path
+    Path,
+}
+
+impl FromStr for SetOrder {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "size" => Ok(Self::Size),
+            "count" => Ok(Self::Count),
+            "path" => Ok(Self::Path),
+            _ => Err(anyhow::anyhow!(
+                "Invalid set order: {} (expected size, count or path)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for SetOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Size => write!(f, "size"),
+            Self::Count => write!(f, "count"),
+            Self::Path => write!(f, "path"),
+        }
+    }
+}
+
+/// Parse a human-readable size such as `512`, `4K`, `10M`, `1.5G` or `2TiB` into bytes.
+/// Suffixes are binary multiples (1K = 1024 bytes) and case-insensitive.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim();
+    let split_at = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, suffix) = trimmed.split_at(split_at);
+
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size '{}': expected a number like 10M or 4096", s))?;
+
+    let multiplier: u64 = match suffix.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        "t" | "tb" | "tib" => 1024 * 1024 * 1024 * 1024,
+        other => {
+            return Err(format!(
+                "Invalid size suffix '{}' in '{}': expected one of B, K, M, G, T",
+                other, s
+            ))
+        }
+    };
+
+    Ok((value * multiplier as f64).round() as u64)
+}
+
+/// Expand a path given by the user: a leading `~` becomes the home directory, `~user`
+/// (best effort) a sibling of it, and relative paths are made absolute against the
+/// current directory. Absolute paths and `-` (stdin) pass through unchanged.
+pub fn expand_path(input: &str) -> PathBuf {
+    if input == "-" {
+        return PathBuf::from(input);
+    }
+    let expanded = expand_tilde(input);
+    if expanded.is_absolute() {
+        return expanded;
+    }
+    match std::env::current_dir() {
+        // `./data` becomes `<cwd>/data` rather than `<cwd>/./data`
+        Ok(cwd) => cwd
+            .join(expanded)
+            .components()
+            .filter(|c| *c != std::path::Component::CurDir)
+            .collect(),
+        Err(_) => expanded,
+    }
+}
+
+/// `expand_path` for a path already parsed by clap or serde. Paths that aren't valid
+/// UTF-8 can't start with a `~` we recognise, so only relative-path resolution applies.
+pub fn expand_path_buf(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(s) => expand_path(s),
+        None => expand_path(".").join(path),
+    }
+}
+
+fn expand_tilde(input: &str) -> PathBuf {
+    let Some(rest) = input.strip_prefix('~') else {
+        return PathBuf::from(input);
+    };
+    let (user, tail) = match rest.find(std::path::is_separator) {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    let Some(home) = dirs::home_dir() else {
+        return PathBuf::from(input);
+    };
+    let base = if user.is_empty() {
+        home
+    } else {
+        // Users' homes usually share a parent (/home, /Users, C:\Users)
+        match home.parent() {
+            Some(homes) => homes.join(user),
+            None => return PathBuf::from(input),
+        }
+    };
+    if tail.is_empty() {
+        base
+    } else {
+        base.join(tail)
+    }
+}
+
 // Represents information about a single file, including its hash if calculated.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct FileInfo {
@@ -89,12 +221,40 @@ pub struct FileInfo {
     pub created_at: Option<SystemTime>,
 }
 
+#[cfg(test)]
+impl FileInfo {
+    /// A file with just a path and size: no hash or timestamps
+    pub(crate) fn for_test(path: impl Into<PathBuf>, size: u64) -> Self {
+        Self {
+            path: path.into(),
+            size,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        }
+    }
+}
+
+/// Read size used when comparing files byte-for-byte in `files_are_identical`
+const VERIFY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Synthetic hash given to the set of zero-byte files found with `--include-empty`.
+pub const EMPTY_FILE_HASH: &str = "empty";
+
 // Represents a set of duplicate files (same size, same hash).
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DuplicateSet {
     pub files: Vec<FileInfo>,
     pub size: u64,
     pub hash: String,
+    /// True when the hash only covered a prefix of the files (see `--hash-limit`),
+    /// so the match was not verified byte-for-byte.
+    #[serde(default)]
+    pub approximate: bool,
+    /// Paths that are hardlinks to a file already in `files`. They share storage,
+    /// so they are listed for information only and never actioned.
+    #[serde(default)]
+    pub hardlinks: Vec<FileInfo>,
 }
 
 // New struct for the output log format
@@ -102,12 +262,117 @@ pub struct DuplicateSet {
 struct HashEntryContent {
     size: u64,
     files: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    approximate: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hardlinks: Vec<PathBuf>,
 }
 
+/// File and directory names skipped in every scan unless `--no-default-excludes` is
+/// given or the config's `default_excludes` replaces them. Matched against each name
+/// (not the full path), so a `.git` directory is pruned wherever it appears.
+pub const DEFAULT_EXCLUDES: [&str; 5] = [
+    ".DS_Store",
+    "Thumbs.db",
+    "ehthumbs.db",
+    "desktop.ini",
+    ".git",
+];
+
 #[derive(Debug, Default)]
 pub struct FilterRules {
     includes: Vec<Pattern>,
     excludes: Vec<Pattern>,
+    include_regexes: Vec<Regex>,
+    exclude_regexes: Vec<Regex>,
+    /// Junk file and directory names from `DEFAULT_EXCLUDES` (or the config's list)
+    default_excludes: Vec<Pattern>,
+    /// Canonicalized --exclude-dir prefixes; matching directories are pruned from the walk
+    exclude_dirs: Vec<PathBuf>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    newer_than: Option<SystemTime>,
+    older_than: Option<SystemTime>,
+    /// Walk into dot-files and dot-directories instead of skipping them
+    include_hidden: bool,
+    /// Follow symlinked files and directories instead of skipping them
+    follow_symlinks: bool,
+    /// Deepest level walked below each root; 1 is the root's own files
+    max_depth: Option<usize>,
+}
+
+/// Canonical paths already seen during a walk that follows symlinks. A symlink cycle,
+/// or a second link to an already-visited file or directory, is skipped instead of
+/// being walked again.
+struct VisitedPaths {
+    enabled: bool,
+    seen: std::collections::HashSet<PathBuf>,
+}
+
+impl VisitedPaths {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            seen: std::collections::HashSet::new(),
+        }
+    }
+
+    /// Returns true the first time an entry's canonical path is seen
+    fn first_visit(&mut self, entry: &walkdir::DirEntry) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        match fs::canonicalize(entry.path()) {
+            Ok(canonical) => self.seen.insert(canonical),
+            Err(e) => {
+                log::warn!("Skipping {:?}: failed to resolve path: {}", entry.path(), e);
+                false
+            }
+        }
+    }
+}
+
+/// Parse a time threshold (`--since`, filter-file `newer-than`/`older-than`): either a
+/// date (`2023-01-01`, RFC 3339) or an age relative to now such as `30d`, `12h` or `2w`
+/// (units: s, m, h, d, w, y).
+pub fn parse_time_threshold(value: &str) -> Result<SystemTime, String> {
+    let value = value.trim();
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+        return Ok(midnight.into());
+    }
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.into());
+    }
+
+    let split_at = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split_at);
+    let amount: u64 = number.parse().map_err(|_| {
+        format!(
+            "Invalid age or date '{}': expected YYYY-MM-DD or an age like 30d",
+            value
+        )
+    })?;
+    let unit_secs: u64 = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        other => {
+            return Err(format!(
+                "Invalid age unit '{}' in '{}': expected one of s, m, h, d, w, y",
+                other, value
+            ))
+        }
+    };
+    let age = std::time::Duration::from_secs(amount.saturating_mul(unit_secs));
+    Ok(SystemTime::now()
+        .checked_sub(age)
+        .unwrap_or(SystemTime::UNIX_EPOCH))
 }
 
 impl FilterRules {
@@ -120,10 +385,13 @@ impl FilterRules {
             let file = File::open(filter_file_path).map_err(|e| {
                 anyhow::anyhow!("Failed to open filter file {:?}: {}", filter_file_path, e)
             })?;
-            let reader = BufReader::new(file);
-            for (line_num, line_result) in reader.lines().enumerate() {
-                let line = line_result
-                    .map_err(|e| anyhow::anyhow!("Failed to read line from filter file: {}", e))?;
+            let mut content = String::new();
+            BufReader::new(file)
+                .read_to_string(&mut content)
+                .map_err(|e| anyhow::anyhow!("Failed to read filter file: {}", e))?;
+            // --null-input: one rule per NUL-terminated entry, so rules may contain newlines
+            let separator = if cli.null_input { '\0' } else { '\n' };
+            for (line_num, line) in content.split(separator).enumerate() {
                 let trimmed_line = line.trim();
                 if trimmed_line.is_empty()
                     || trimmed_line.starts_with('#')
@@ -132,10 +400,46 @@ impl FilterRules {
                     continue;
                 }
 
-                if let Some(pattern_str) = trimmed_line.strip_prefix("+ ") {
+                // Regex rules are checked first since "R+ " would never match "+ "
+                if let Some(regex_str) = trimmed_line.strip_prefix("R+ ") {
+                    rules.add_include_regex(regex_str.trim()).map_err(|e| {
+                        anyhow::anyhow!(
+                            "Invalid include regex in filter file {:?} at line {}: {}",
+                            filter_file_path,
+                            line_num + 1,
+                            e
+                        )
+                    })?;
+                } else if let Some(regex_str) = trimmed_line.strip_prefix("R- ") {
+                    rules.add_exclude_regex(regex_str.trim()).map_err(|e| {
+                        anyhow::anyhow!(
+                            "Invalid exclude regex in filter file {:?} at line {}: {}",
+                            filter_file_path,
+                            line_num + 1,
+                            e
+                        )
+                    })?;
+                } else if let Some(pattern_str) = trimmed_line.strip_prefix("+ ") {
                     rules.add_include(pattern_str.trim())?;
                 } else if let Some(pattern_str) = trimmed_line.strip_prefix("- ") {
                     rules.add_exclude(pattern_str.trim())?;
+                } else if let Some((directive, value)) = trimmed_line
+                    .split_once(char::is_whitespace)
+                    .filter(|(d, _)| {
+                        matches!(*d, "min-size" | "max-size" | "newer-than" | "older-than")
+                    })
+                {
+                    rules
+                        .add_metadata_directive(directive, value.trim())
+                        .map_err(|e| {
+                            anyhow::anyhow!(
+                                "Invalid {} directive in filter file {:?} at line {}: {}",
+                                directive,
+                                filter_file_path,
+                                line_num + 1,
+                                e
+                            )
+                        })?;
                 } else {
                     log::warn!(
                         "Invalid line in filter file {:?} at line {}: {}",
@@ -157,6 +461,101 @@ impl FilterRules {
             rules.add_exclude(pattern_str)?;
         }
 
+        // Process --include-regex / --exclude-regex flags
+        for regex_str in &cli.include_regex {
+            rules
+                .add_include_regex(regex_str)
+                .map_err(|e| anyhow::anyhow!("Invalid --include-regex '{}': {}", regex_str, e))?;
+        }
+        for regex_str in &cli.exclude_regex {
+            rules
+                .add_exclude_regex(regex_str)
+                .map_err(|e| anyhow::anyhow!("Invalid --exclude-regex '{}': {}", regex_str, e))?;
+        }
+
+        for dir in &cli.exclude_dir {
+            rules.add_exclude_dir(dir);
+        }
+
+        if !cli.no_default_excludes {
+            let names: Vec<&str> = match &cli.default_excludes {
+                Some(names) => names.iter().map(String::as_str).collect(),
+                None => DEFAULT_EXCLUDES.to_vec(),
+            };
+            for name in names {
+                let pattern = Pattern::new(name)
+                    .map_err(|e| anyhow::anyhow!("Invalid default exclude '{}': {}", name, e))?;
+                rules.default_excludes.push(pattern);
+            }
+        }
+
+        rules.include_hidden = cli.include_hidden;
+        rules.follow_symlinks = cli.follow_symlinks;
+        if cli.max_depth == Some(0) {
+            anyhow::bail!("--max-depth must be at least 1");
+        }
+        rules.max_depth = cli.max_depth;
+
+        // --min-size / --max-size on the command line override filter-file directives
+        if cli.min_size.is_some() {
+            rules.min_size = cli.min_size;
+        }
+        if cli.max_size.is_some() {
+            rules.max_size = cli.max_size;
+        }
+        // --since likewise overrides a filter-file newer-than
+        if cli.since.is_some() {
+            rules.newer_than = cli.since;
+        }
+        if let (Some(min), Some(max)) = (rules.min_size, rules.max_size) {
+            if min > max {
+                return Err(anyhow::anyhow!(
+                    "Minimum size ({} bytes) is larger than maximum size ({} bytes)",
+                    min,
+                    max
+                ));
+            }
+        }
+        if !rules.include_regexes.is_empty() || !rules.exclude_regexes.is_empty() {
+            log::info!(
+                "Regex rules active: include [{}], exclude [{}]",
+                rules
+                    .include_regexes
+                    .iter()
+                    .map(|r| r.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                rules
+                    .exclude_regexes
+                    .iter()
+                    .map(|r| r.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        if rules.has_metadata_rules() {
+            log::info!(
+                "Metadata limits active: min {:?} bytes, max {:?} bytes, newer than {:?}, older than {:?}",
+                rules.min_size,
+                rules.max_size,
+                rules.newer_than,
+                rules.older_than
+            );
+        }
+
+        if !rules.exclude_dirs.is_empty() {
+            log::info!("Excluded directories: {:?}", rules.exclude_dirs);
+        }
+        log::debug!(
+            "Default excludes: {}",
+            rules
+                .default_excludes
+                .iter()
+                .map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
         if !rules.includes.is_empty() {
             log::info!(
                 "Include rules active: {}",
@@ -209,141 +608,908 @@ impl FilterRules {
         }
     }
 
+    fn add_include_regex(&mut self, regex_str: &str) -> Result<(), regex::Error> {
+        self.include_regexes.push(Regex::new(regex_str)?);
+        Ok(())
+    }
+
+    fn add_exclude_regex(&mut self, regex_str: &str) -> Result<(), regex::Error> {
+        self.exclude_regexes.push(Regex::new(regex_str)?);
+        Ok(())
+    }
+
+    fn add_exclude_dir(&mut self, dir: &Path) {
+        // A directory that doesn't exist (yet) can still be excluded by its absolute path
+        let prefix = fs::canonicalize(dir)
+            .or_else(|_| std::path::absolute(dir))
+            .unwrap_or_else(|_| dir.to_path_buf());
+        self.exclude_dirs.push(prefix);
+    }
+
+    /// Whether a directory entry lies under an --exclude-dir prefix
+    fn is_excluded_dir(&self, entry: &walkdir::DirEntry) -> bool {
+        if self.exclude_dirs.is_empty() || !entry.file_type().is_dir() {
+            return false;
+        }
+        let path = fs::canonicalize(entry.path()).unwrap_or_else(|_| entry.path().to_path_buf());
+        self.exclude_dirs
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+    }
+
+    /// Whether a file or directory name is one of the default junk excludes
+    fn is_default_excluded(&self, name: &std::ffi::OsStr) -> bool {
+        let Some(name) = name.to_str() else {
+            return false;
+        };
+        self.default_excludes.iter().any(|p| p.matches(name))
+    }
+
+    /// Whether a path given by --files-from passes the include/exclude patterns and
+    /// isn't under an --exclude-dir or a default-excluded directory
+    pub(crate) fn accepts_listed_path(&self, path: &Path) -> bool {
+        if path
+            .components()
+            .any(|c| self.is_default_excluded(c.as_os_str()))
+        {
+            return false;
+        }
+        let Some(path_str) = path.to_str() else {
+            log::warn!(
+                "[ScanThread] Path {:?} is not valid UTF-8, excluding.",
+                path
+            );
+            return false;
+        };
+        if !self.is_match(path_str) {
+            return false;
+        }
+        if self.exclude_dirs.is_empty() {
+            return true;
+        }
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        !self
+            .exclude_dirs
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+    }
+
+    /// Start a directory walk at `root`, following symlinks if --follow-symlinks is set
+    /// and stopping at --max-depth
+    fn walk(&self, root: &Path) -> walkdir::IntoIter {
+        let walker = WalkDir::new(root).follow_links(self.follow_symlinks);
+        match self.max_depth {
+            Some(depth) => walker.max_depth(depth),
+            None => walker,
+        }
+        .into_iter()
+    }
+
+    /// Whether a walk entry is skipped outright because it is hidden, a symlink, a
+    /// default-excluded name, or an excluded directory. Used in `filter_entry`, so
+    /// skipped directories aren't descended into.
+    fn skips_entry(&self, entry: &walkdir::DirEntry) -> bool {
+        (!self.include_hidden && is_hidden(entry))
+            || (entry.depth() > 0 && self.is_default_excluded(entry.file_name()))
+            || (!self.follow_symlinks && entry.path_is_symlink())
+            || self.is_excluded_dir(entry)
+    }
+
+    fn add_metadata_directive(&mut self, directive: &str, value: &str) -> Result<(), String> {
+        match directive {
+            "min-size" => self.min_size = Some(parse_size(value)?),
+            "max-size" => self.max_size = Some(parse_size(value)?),
+            "newer-than" => self.newer_than = Some(parse_time_threshold(value)?),
+            "older-than" => self.older_than = Some(parse_time_threshold(value)?),
+            other => return Err(format!("Unknown directive '{}'", other)),
+        }
+        Ok(())
+    }
+
+    /// Whether any size or age constraint is configured
+    pub fn has_metadata_rules(&self) -> bool {
+        self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.newer_than.is_some()
+            || self.older_than.is_some()
+    }
+
+    /// Check a file's size and modification time against the configured constraints.
+    /// Size bounds are inclusive. Files with an unknown mtime fail any age constraint.
+    pub fn matches_metadata(&self, file: &FileInfo) -> bool {
+        self.matches_size_and_mtime(file.size, file.modified_at)
+    }
+
+    pub(crate) fn matches_size_and_mtime(&self, size: u64, modified: Option<SystemTime>) -> bool {
+        if !(self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max))
+        {
+            return false;
+        }
+        if self.newer_than.is_none() && self.older_than.is_none() {
+            return true;
+        }
+        match modified {
+            Some(mtime) => {
+                self.newer_than.is_none_or(|t| mtime >= t)
+                    && self.older_than.is_none_or(|t| mtime < t)
+            }
+            None => false,
+        }
+    }
+
     pub fn is_match(&self, path_str: &str) -> bool {
-        // 1. Check excludes: if any exclude pattern matches, path is excluded.
-        if self.excludes.iter().any(|p| p.matches(path_str)) {
+        // 1. Check excludes: if any exclude glob or regex matches, path is excluded.
+        if self.excludes.iter().any(|p| p.matches(path_str))
+            || self.exclude_regexes.iter().any(|r| r.is_match(path_str))
+        {
             return false;
         }
 
         // 2. Check includes:
-        //    - If include patterns exist, path must match at least one.
-        //    - If no include patterns exist, path is included by default (if not excluded).
-        if !self.includes.is_empty() {
-            return self.includes.iter().any(|p| p.matches(path_str));
+        //    - If include globs or regexes exist, path must match at least one of them.
+        //    - If no include rules exist, path is included by default (if not excluded).
+        if !self.includes.is_empty() || !self.include_regexes.is_empty() {
+            return self.includes.iter().any(|p| p.matches(path_str))
+                || self.include_regexes.iter().any(|r| r.is_match(path_str));
         }
 
         true // Not excluded, and no include rules to restrict further OR matches an include rule.
     }
 }
 
-pub fn calculate_hash(path: &Path, algorithm: &str) -> Result<String> {
+/// Default size of the read buffer used when streaming file contents into a hasher.
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Smallest and largest `--read-buffer`; values outside are clamped to these
+pub const MIN_READ_BUFFER: usize = 4 * 1024;
+pub const MAX_READ_BUFFER: usize = 16 * 1024 * 1024;
+
+/// Parse `--read-buffer`: a size like `parse_size` accepts, which must not be zero
+pub fn parse_read_buffer(s: &str) -> Result<u64, String> {
+    match parse_size(s)? {
+        0 => Err(format!(
+            "Invalid read buffer '{}': must be larger than 0",
+            s
+        )),
+        size => Ok(size),
+    }
+}
+
+/// The read buffer used for a `--read-buffer` of `size`: rounded up to a power of two
+/// and clamped to `MIN_READ_BUFFER..=MAX_READ_BUFFER`
+pub fn read_buffer_size(size: u64) -> usize {
+    usize::try_from(size)
+        .unwrap_or(MAX_READ_BUFFER)
+        .clamp(MIN_READ_BUFFER, MAX_READ_BUFFER)
+        .next_power_of_two()
+}
+
+/// Read `path` in `buffer_size` chunks, passing each chunk to `update`.
+/// This keeps memory usage constant regardless of file size. When `limit` is set,
+/// at most that many bytes are read from the start of the file.
+fn stream_file_chunks<F>(
+    path: &Path,
+    limit: Option<u64>,
+    buffer_size: usize,
+    mut update: F,
+) -> Result<()>
+where
+    F: FnMut(&[u8]),
+{
     let mut file = File::open(path)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
+    let mut buffer = vec![0u8; buffer_size];
+    let mut remaining = limit.unwrap_or(u64::MAX);
+    while remaining > 0 {
+        let to_read = (buffer.len() as u64).min(remaining) as usize;
+        let bytes_read = match file.read(&mut buffer[..to_read]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        update(&buffer[..bytes_read]);
+        remaining -= bytes_read as u64;
+    }
+    Ok(())
+}
+
+/// Files at least this large take a permit before they are hashed under `--max-memory`
+pub const LARGE_FILE_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Counting semaphore capping how many large files are hashed at once (`--max-memory`).
+/// Reading many big files in parallel makes a spinning disk seek between them constantly;
+/// holding back the extra hashing threads keeps reads mostly sequential.
+pub struct HashPermits {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl HashPermits {
+    /// A semaphore with `permits` slots (at least one)
+    pub fn new(permits: usize) -> Self {
+        Self {
+            available: Mutex::new(permits.max(1)),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Size the semaphore so that `max_memory` covers files of `average_file_size`
+    pub fn for_budget(max_memory: u64, average_file_size: u64) -> Self {
+        let permits = max_memory / average_file_size.max(1);
+        Self::new(usize::try_from(permits).unwrap_or(usize::MAX))
+    }
+
+    /// Block until a slot is free; it is given back when the permit is dropped
+    pub fn acquire(&self) -> HashPermit<'_> {
+        let mut available = self.available.lock().unwrap_or_else(|e| e.into_inner());
+        while *available == 0 {
+            available = self
+                .released
+                .wait(available)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        *available -= 1;
+        HashPermit(self)
+    }
+}
+
+/// A held `HashPermits` slot
+pub struct HashPermit<'a>(&'a HashPermits);
+
+impl Drop for HashPermit<'_> {
+    fn drop(&mut self) {
+        *self.0.available.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// Whether this build can hash with gxhash (needs the `linux` feature)
+pub const GXHASH_AVAILABLE: bool = cfg!(feature = "linux");
+
+/// Algorithms `--algorithm` can use in this build (gxhash needs the `linux` feature)
+pub fn available_hash_algorithms() -> Vec<&'static str> {
+    let mut algorithms = vec!["md5", "sha1", "sha256", "blake3", "xxhash"];
+    if GXHASH_AVAILABLE {
+        algorithms.push("gxhash");
+    }
+    algorithms.extend(["fnv1a", "crc32"]);
+    algorithms
+}
+
+pub fn calculate_hash(path: &Path, algorithm: &str) -> Result<String> {
+    calculate_hash_with_limit(path, algorithm, None)
+}
+
+/// Like `calculate_hash`, but only hashes the first `limit` bytes when a limit is given.
+/// Files larger than the limit produce an approximate hash that is not a full-content match.
+pub fn calculate_hash_with_limit(
+    path: &Path,
+    algorithm: &str,
+    limit: Option<u64>,
+) -> Result<String> {
+    calculate_hash_buffered(path, algorithm, limit, HASH_BUFFER_SIZE)
+}
 
+/// Like `calculate_hash_with_limit`, reading the file `buffer_size` bytes at a time
+/// (see `read_buffer_size` for turning `--read-buffer` into one)
+pub fn calculate_hash_buffered(
+    path: &Path,
+    algorithm: &str,
+    limit: Option<u64>,
+    buffer_size: usize,
+) -> Result<String> {
+    hash_with(algorithm, |update| {
+        stream_file_chunks(path, limit, buffer_size, update)
+    })
+    .map_err(|e| match e.downcast::<std::io::Error>() {
+        Ok(io) if io.kind() == std::io::ErrorKind::PermissionDenied => {
+            DedupError::PermissionDenied(path.to_path_buf()).into()
+        }
+        Ok(io) => DedupError::HashFailed {
+            path: path.to_path_buf(),
+            source: io,
+        }
+        .into(),
+        Err(other) => other,
+    })
+}
+
+/// Hash an in-memory buffer the same way `calculate_hash` hashes a file
+pub fn hash_bytes(data: &[u8], algorithm: &str) -> Result<String> {
+    hash_with(algorithm, |update| {
+        update(data);
+        Ok(())
+    })
+}
+
+/// Run `algorithm` over whatever chunks `feed` passes to the update callback
+fn hash_with<F>(algorithm: &str, mut feed: F) -> Result<String>
+where
+    F: FnMut(&mut dyn FnMut(&[u8])) -> Result<()>,
+{
     match algorithm {
         "md5" => {
-            let digest = md5::compute(&buffer);
-            Ok(format!("{:x}", digest))
+            let mut context = md5::Context::new();
+            feed(&mut |chunk| context.consume(chunk))?;
+            Ok(format!("{:x}", context.compute()))
         }
         "sha1" => {
             let mut hasher = sha1::Sha1::new();
-            hasher.update(&buffer);
+            feed(&mut |chunk| hasher.update(chunk))?;
             Ok(format!("{:x}", hasher.finalize()))
         }
         "sha256" => {
             let mut hasher = sha2::Sha256::new();
-            hasher.update(&buffer);
+            feed(&mut |chunk| hasher.update(chunk))?;
             Ok(format!("{:x}", hasher.finalize()))
         }
         "blake3" => {
-            let hash = blake3::hash(&buffer);
-            Ok(hash.to_hex().to_string())
+            let mut hasher = blake3::Hasher::new();
+            feed(&mut |chunk| {
+                hasher.update(chunk);
+            })?;
+            Ok(hasher.finalize().to_hex().to_string())
         }
         "xxhash" => {
             let mut hasher = twox_hash::XxHash64::default();
-            hasher.write(&buffer);
+            feed(&mut |chunk| hasher.write(chunk))?;
             Ok(format!("{:016x}", hasher.finish()))
         }
         #[cfg(feature = "linux")]
         "gxhash" => {
             let mut hasher = gxhash::GxHasher::default();
-            hasher.write(&buffer);
+            feed(&mut |chunk| hasher.write(chunk))?;
             Ok(format!("{:016x}", hasher.finish()))
         }
         #[cfg(not(feature = "linux"))]
-        "gxhash" => Err(anyhow::anyhow!(
-            "gxhash is only available on Linux platforms"
-        )),
+        "gxhash" => Err(DedupError::UnsupportedAlgorithm(
+            "gxhash is only available on Linux platforms".to_string(),
+        )
+        .into()),
         "fnv1a" => {
             let mut hasher = fnv::FnvHasher::default();
-            hasher.write(&buffer);
+            feed(&mut |chunk| hasher.write(chunk))?;
             Ok(format!("{:016x}", hasher.finish()))
         }
         "crc32" => {
-            let result = crc32fast::hash(&buffer);
-            Ok(format!("{:08x}", result))
+            let mut hasher = crc32fast::Hasher::new();
+            feed(&mut |chunk| hasher.update(chunk))?;
+            Ok(format!("{:08x}", hasher.finalize()))
         }
-        _ => Err(anyhow::anyhow!("Invalid hash algorithm: {}", algorithm)),
+        _ => Err(DedupError::UnsupportedAlgorithm(format!(
+            "Invalid hash algorithm: {}",
+            algorithm
+        ))
+        .into()),
     }
 }
 
-/// Find duplicate files with progress reporting (TUI mode)
-pub fn find_duplicate_files_with_progress(
-    cli: &Cli,
-    tx_progress: StdMpscSender<ScanMessage>,
-) -> Result<Vec<DuplicateSet>> {
-    // Clone tx_progress before moving it into any closure
-    let tx_progress_for_media = tx_progress.clone();
+/// The `hash_overrides` strategy for this file's extension, unless it hashes exactly
+/// like the global `--algorithm` and `--hash-limit` would
+fn hash_override_for<'a>(cli: &'a Cli, path: &Path) -> Option<&'a HashOverride> {
+    if cli.hash_overrides.is_empty() {
+        return None;
+    }
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    cli.hash_overrides
+        .get(&extension)
+        .filter(|s| s.algorithm != cli.algorithm || s.hash_limit != cli.hash_limit)
+}
 
-    log::info!(
-        "[ScanThread] Starting scan with progress updates for directory: {:?}",
-        cli.directories[0]
-    );
-    let filter_rules = FilterRules::new(cli)?;
+/// Tag a hash made by an override with how it was made, so it can only ever match
+/// files hashed the same way (e.g. "blake3:…" or "xxhash@1048576:…")
+fn override_hash_key(strategy: &HashOverride, hash: &str) -> String {
+    match strategy.hash_limit {
+        Some(limit) => format!("{}@{}:{}", strategy.algorithm, limit, hash),
+        None => format!("{}:{}", strategy.algorithm, hash),
+    }
+}
 
-    // Initialize file cache if using fast mode
-    let file_cache = if cli.fast_mode && cli.cache_location.is_some() {
-        let cache_dir = cli.cache_location.as_ref().unwrap();
-        match crate::file_cache::FileCache::new(cache_dir, &cli.algorithm) {
-            Ok(cache) => {
-                log::info!(
-                    "[ScanThread] Using file cache at {:?} with {} entries",
-                    cache_dir,
-                    cache.len()
-                );
-                Some(std::sync::Arc::new(std::sync::Mutex::new(cache)))
+/// The algorithm a `hash_overrides` entry hashed a set with, read back from its
+/// `override_hash_key` tag. `None` for sets hashed with the global --algorithm.
+pub fn override_algorithm(hash: &str) -> Option<&str> {
+    let (tag, _) = hash.split_once(':')?;
+    Some(tag.split_once('@').map_or(tag, |(algorithm, _)| algorithm))
+}
+
+/// Number of bytes sampled from each end of a file by the pre-filter.
+const PREFILTER_SAMPLE_SIZE: u64 = 4 * 1024;
+
+/// Compute a cheap signature for a file from its size plus its first and last
+/// `PREFILTER_SAMPLE_SIZE` bytes. Files with different signatures cannot be duplicates.
+fn read_file_signature(path: &Path, size: u64) -> Result<blake3::Hash> {
+    let mut file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&size.to_le_bytes());
+
+    let head_len = size.min(PREFILTER_SAMPLE_SIZE);
+    let mut head = vec![0u8; head_len as usize];
+    file.read_exact(&mut head)?;
+    hasher.update(&head);
+
+    // Only read a tail if it isn't already fully covered by the head sample
+    if size > PREFILTER_SAMPLE_SIZE {
+        let tail_start = (size - PREFILTER_SAMPLE_SIZE).max(PREFILTER_SAMPLE_SIZE);
+        let mut tail = vec![0u8; (size - tail_start) as usize];
+        file.seek(SeekFrom::Start(tail_start))?;
+        file.read_exact(&mut tail)?;
+        hasher.update(&tail);
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// Split each same-size group by head/tail signature, dropping any resulting singletons.
+/// Files whose signature cannot be read are skipped with a warning. A group holding a
+/// file with a cached hash is passed through unread: that file needs no reads at all,
+/// and any other file in the group could still match it.
+pub(crate) fn prefilter_size_groups(
+    groups: Vec<(u64, Vec<PathBuf>)>,
+    is_cached: impl Fn(&Path) -> bool + Sync,
+) -> Vec<(u64, Vec<PathBuf>)> {
+    groups
+        .into_par_iter()
+        .flat_map_iter(|(size, paths)| {
+            if paths.iter().any(|path| is_cached(path)) {
+                return vec![(size, paths)];
+            }
+            let mut by_signature: HashMap<blake3::Hash, Vec<PathBuf>> = HashMap::new();
+            for path in paths {
+                match read_file_signature(&path, size) {
+                    Ok(signature) => by_signature.entry(signature).or_default().push(path),
+                    Err(e) => {
+                        log::warn!(
+                            "[ScanThread] Failed to read signature for {:?}: {}",
+                            path,
+                            e
+                        )
+                    }
+                }
+            }
+            by_signature
+                .into_values()
+                .filter(|paths| paths.len() > 1)
+                .map(|paths| (size, paths))
+                .collect()
+        })
+        .collect()
+}
+
+/// One file's outcome from hashing a size group: its hash and info plus whether the hash
+/// came from the cache, or `None` for a file that was skipped
+type HashedFile = Result<Option<(String, FileInfo, bool)>>;
+
+/// Hash the files of one size group and bucket them by hash. Files within the group are
+/// hashed in parallel too, so a single huge group (thousands of same-size files) isn't
+/// left to one thread. Returns the buckets, in path order, and how many hashes came from
+/// the cache. A file that fails to hash is logged, counted as unreadable and left out;
+/// the rest of the group is still bucketed.
+fn hash_size_group(
+    paths: Vec<PathBuf>,
+    hash_file: impl Fn(PathBuf) -> HashedFile + Sync + Send,
+) -> (HashMap<String, Vec<FileInfo>>, usize) {
+    let hashed: Vec<HashedFile> = paths.into_par_iter().map(hash_file).collect();
+
+    let mut by_hash: HashMap<String, Vec<FileInfo>> = HashMap::new();
+    let mut cache_hits = 0;
+    for result in hashed {
+        match result {
+            Ok(Some((hash, file_info, from_cache))) => {
+                if from_cache {
+                    cache_hits += 1;
+                }
+                by_hash.entry(hash).or_default().push(file_info);
             }
+            Ok(None) => {}
             Err(e) => {
-                log::warn!("[ScanThread] Failed to initialize file cache: {}", e);
-                None
+                log::warn!(
+                    "[ScanThread] Skipping a file that could not be hashed: {}",
+                    e
+                );
+                crate::exit_code::record_unreadable();
             }
         }
-    } else {
-        None
-    };
-
-    // Track cache hits using atomic
-    let cache_hits = std::sync::atomic::AtomicUsize::new(0);
+    }
+    (by_hash, cache_hits)
+}
 
-    let send_status = move |stage: u8, msg: String| {
-        if tx_progress
-            .send(ScanMessage::StatusUpdate(stage, msg))
-            .is_err()
-        {
-            log::warn!("[ScanThread] Failed to send status update to TUI (channel closed).");
-        }
-    };
+/// Files found by the discovery stage, bucketed for the size comparison
+struct DiscoveredFiles {
+    files_by_size: HashMap<u64, Vec<PathBuf>>,
+    /// Zero-byte files are identical by definition, so --include-empty groups them without hashing
+    empty_files: Vec<FileInfo>,
+}
 
-    // ========== STAGE 0: PRE-SCAN FOR TOTAL COUNT ==========
-    send_status(
-        0,
-        format!(
-            "Pre-scan: Counting files in {}",
-            cli.directories[0].display()
-        ),
+/// Stat every discovered path on a pool of `threads_io` threads and bucket the files
+/// that pass the size/age filters. Results keep the walk order, so the outcome doesn't
+/// depend on the thread count.
+fn collect_file_metadata(
+    paths: Vec<PathBuf>,
+    filter_rules: &FilterRules,
+    include_empty: bool,
+    threads_io: usize,
+) -> Result<DiscoveredFiles> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads_io.max(1))
+        .build()?;
+    let stats: Vec<(PathBuf, std::io::Result<fs::Metadata>)> = pool.install(|| {
+        paths
+            .into_par_iter()
+            .map(|path| {
+                let metadata = fs::metadata(&path);
+                (path, metadata)
+            })
+            .collect()
+    });
+
+    let mut discovered = DiscoveredFiles {
+        files_by_size: HashMap::new(),
+        empty_files: Vec::new(),
+    };
+    for (path, metadata) in stats {
+        match metadata {
+            Ok(metadata) => {
+                if !metadata.is_file() {
+                    log::warn!("[ScanThread] Skipping {:?}: not a regular file", path);
+                    continue;
+                }
+                if !filter_rules.matches_size_and_mtime(metadata.len(), metadata.modified().ok()) {
+                    continue;
+                }
+                if metadata.len() > 0 {
+                    discovered
+                        .files_by_size
+                        .entry(metadata.len())
+                        .or_default()
+                        .push(path);
+                } else if include_empty {
+                    discovered.empty_files.push(FileInfo {
+                        path,
+                        size: 0,
+                        hash: Some(EMPTY_FILE_HASH.to_string()),
+                        modified_at: metadata.modified().ok(),
+                        created_at: metadata.created().ok(),
+                    });
+                }
+            }
+            Err(e) => {
+                log::warn!("[ScanThread] Failed to get metadata for {:?}: {}", path, e);
+                crate::exit_code::record_unreadable();
+            }
+        }
+    }
+    Ok(discovered)
+}
+
+/// Structured hashing progress, sent alongside the status text so JSON consumers
+/// can draw a progress bar without parsing messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HashProgress {
+    pub percent_complete: f64,
+    /// `None` until enough groups have been hashed to estimate a speed
+    pub estimated_seconds_left: Option<f64>,
+}
+
+/// Moving-average hashing speed, in size groups per second
+struct HashingSpeed {
+    total: usize,
+    done: usize,
+    last_sample: std::time::Instant,
+    groups_per_sec: Option<f64>,
+}
+
+impl HashingSpeed {
+    /// Groups that must be hashed before an ETA is reported
+    const MIN_GROUPS_FOR_ETA: usize = 3;
+    /// Weight of the newest sample in the moving average
+    const SMOOTHING: f64 = 0.3;
+
+    fn new(total: usize, now: std::time::Instant) -> Self {
+        Self {
+            total,
+            done: 0,
+            last_sample: now,
+            groups_per_sec: None,
+        }
+    }
+
+    /// Record that `done` groups have been hashed as of `now`
+    fn record(&mut self, done: usize, now: std::time::Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_sample)
+            .as_secs_f64();
+        if done > self.done && elapsed > 0.0 {
+            let sample = (done - self.done) as f64 / elapsed;
+            self.groups_per_sec = Some(match self.groups_per_sec {
+                Some(avg) => avg + Self::SMOOTHING * (sample - avg),
+                None => sample,
+            });
+            self.done = done;
+            self.last_sample = now;
+        }
+    }
+
+    fn progress(&self) -> HashProgress {
+        let percent_complete = if self.total == 0 {
+            100.0
+        } else {
+            self.done as f64 / self.total as f64 * 100.0
+        };
+        let estimated_seconds_left = self
+            .groups_per_sec
+            .filter(|rate| *rate > 0.0 && self.done >= Self::MIN_GROUPS_FOR_ETA)
+            .map(|rate| self.total.saturating_sub(self.done) as f64 / rate);
+        HashProgress {
+            percent_complete,
+            estimated_seconds_left,
+        }
+    }
+}
+
+/// Minimum time between progress messages unless --progress-interval says otherwise
+pub const DEFAULT_PROGRESS_INTERVAL_MS: u64 = 400;
+
+/// Read paths for `--files-from`, where `-` means stdin. Paths are newline-separated, or
+/// NUL-separated with `null_separated` (--null-input). Blank entries and repeated paths
+/// are skipped so a file can't be reported as its own duplicate.
+pub fn read_file_list(list: &Path, null_separated: bool) -> Result<Vec<PathBuf>> {
+    let separator = if null_separated { b'\0' } else { b'\n' };
+    if list == Path::new("-") {
+        parse_file_list(std::io::stdin().lock(), separator)
+    } else {
+        let file = File::open(list)
+            .map_err(|e| anyhow::anyhow!("Failed to open file list {:?}: {}", list, e))?;
+        parse_file_list(BufReader::new(file), separator)
+    }
+}
+
+fn parse_file_list(reader: impl BufRead, separator: u8) -> Result<Vec<PathBuf>> {
+    let mut seen = std::collections::HashSet::new();
+    let mut paths = Vec::new();
+    for entry in reader.split(separator) {
+        let mut entry = entry?;
+        if separator == b'\n' && entry.last() == Some(&b'\r') {
+            entry.pop();
+        }
+        if entry.is_empty() {
+            continue;
+        }
+        let path = path_from_bytes(entry);
+        if seen.insert(path.clone()) {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+/// Write every file in `sets` followed by a NUL byte, like `find -print0` (--print0).
+/// Paths are written as raw bytes, so any name a filesystem allows survives the trip.
+pub fn write_paths_null_separated(
+    sets: &[DuplicateSet],
+    out: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    for file in sets.iter().flat_map(|set| &set.files) {
+        out.write_all(&path_to_bytes(&file.path))?;
+        out.write_all(b"\0")?;
+    }
+    out.flush()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    std::borrow::Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        std::borrow::Cow::Borrowed(s) => std::borrow::Cow::Borrowed(s.as_bytes()),
+        std::borrow::Cow::Owned(s) => std::borrow::Cow::Owned(s.into_bytes()),
+    }
+}
+
+/// Set by the Ctrl-C handler from `install_interrupt_handler`
+static INTERRUPTED: std::sync::OnceLock<Arc<AtomicBool>> = std::sync::OnceLock::new();
+
+/// The flag `find_duplicate_files_with_progress` checks for cancellation
+pub fn interrupt_flag() -> Arc<AtomicBool> {
+    INTERRUPTED
+        .get_or_init(|| Arc::new(AtomicBool::new(false)))
+        .clone()
+}
+
+/// Make the first Ctrl-C cancel the running scan, which then returns what it found so
+/// far and saves the hash cache. A second Ctrl-C exits immediately.
+pub fn install_interrupt_handler() -> Result<()> {
+    let flag = interrupt_flag();
+    // Registered first so it sees the flag as it was before this signal
+    signal_hook::flag::register_conditional_shutdown(
+        signal_hook::consts::SIGINT,
+        130,
+        flag.clone(),
+    )?;
+    signal_hook::flag::register(signal_hook::consts::SIGINT, flag)?;
+    Ok(())
+}
+
+/// Find duplicate files with progress reporting (TUI mode)
+pub fn find_duplicate_files_with_progress(
+    cli: &Cli,
+    tx_progress: StdMpscSender<ScanMessage>,
+) -> Result<Vec<DuplicateSet>> {
+    find_duplicate_files_cancellable(cli, tx_progress, &interrupt_flag()).map(|(sets, _)| sets)
+}
+
+/// Like `find_duplicate_files_with_progress`, but stops walking and hashing once `cancel`
+/// is set. A cancelled scan still returns `Ok` with the sets found so far; they are real
+/// duplicates, though sets may be missing members that weren't hashed yet.
+/// Also returns whether discovery stopped at `--limit`, so the sets only cover a sample.
+pub fn find_duplicate_files_cancellable(
+    cli: &Cli,
+    tx_progress: StdMpscSender<ScanMessage>,
+    cancel: &AtomicBool,
+) -> Result<(Vec<DuplicateSet>, bool)> {
+    scan_for_duplicates(cli, tx_progress, cancel, None)
+}
+
+/// `find_duplicate_files_cancellable` that also adds every file discovery found to `index`,
+/// with its size but no hash or timestamps, so `--watch` can start from this scan rather
+/// than walking the tree again
+pub fn find_duplicate_files_indexed(
+    cli: &Cli,
+    tx_progress: StdMpscSender<ScanMessage>,
+    cancel: &AtomicBool,
+    index: &mut Vec<FileInfo>,
+) -> Result<(Vec<DuplicateSet>, bool)> {
+    scan_for_duplicates(cli, tx_progress, cancel, Some(index))
+}
+
+fn scan_for_duplicates(
+    cli: &Cli,
+    tx_progress: StdMpscSender<ScanMessage>,
+    cancel: &AtomicBool,
+    index: Option<&mut Vec<FileInfo>>,
+) -> Result<(Vec<DuplicateSet>, bool)> {
+    // auto: group with a fast hash, verify_before_action() checks the sets before acting
+    let cli = &*with_resolved_algorithm(cli);
+    let mut sampled = false;
+
+    // Clone tx_progress before moving it into any closure
+    let tx_progress_for_media = tx_progress.clone();
+
+    // --union walks every directory into the same size map; otherwise only the first is
+    // scanned. --files-from replaces the walk entirely.
+    let scan_roots = if cli.files_from.is_some() {
+        Vec::new()
+    } else if cli.union {
+        union_scan_roots(&cli.directories)
+    } else {
+        cli.directories[..1].to_vec()
+    };
+    let roots_display = match &cli.files_from {
+        Some(list) => format!("file list {}", list.display()),
+        None => scan_roots
+            .iter()
+            .map(|d| d.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+
+    log::info!(
+        "[ScanThread] Starting scan with progress updates for directories: {:?}",
+        scan_roots
     );
+    let filter_rules = FilterRules::new(cli)?;
+
+    // Initialize file cache if using fast mode (--incremental and --refresh-cache always use it)
+    // Prefix hashes from --hash-limit must never be mixed with full hashes in the cache
+    let use_cache = cli.fast_mode || cli.incremental || cli.refresh_cache;
+    if use_cache && cli.hash_limit.is_some() {
+        log::warn!("[ScanThread] Hash cache is disabled while --hash-limit is in effect.");
+    }
+    let file_cache = match cli
+        .cache_location
+        .as_ref()
+        .filter(|_| use_cache && cli.hash_limit.is_none())
+    {
+        Some(cache_dir) => {
+            match crate::file_cache::open_cache(
+                &cli.cache_backend,
+                cache_dir,
+                &cli.algorithm,
+                std::time::Duration::from_secs(cli.cache_lock_timeout),
+            ) {
+                Ok(cache) => {
+                    log::info!(
+                        "[ScanThread] Using {} cache at {:?} with {} entries",
+                        cli.cache_backend,
+                        cache_dir,
+                        cache.len()
+                    );
+                    Some(std::sync::Arc::new(std::sync::Mutex::new(cache)))
+                }
+                Err(e) => {
+                    log::warn!("[ScanThread] Failed to initialize file cache: {}", e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    // --incremental checks every cached entry under the scan roots up front, so deleted
+    // and modified files never linger in the cache
+    let mut stale_entries = 0;
+    if cli.incremental {
+        if let Some(cache) = &file_cache {
+            if let Ok(mut cache_guard) = cache.lock() {
+                match cache_guard.prune_stale(&scan_roots) {
+                    Ok(removed) => stale_entries = removed,
+                    Err(e) => log::warn!("[ScanThread] Failed to prune stale cache entries: {}", e),
+                }
+            }
+        }
+    }
+
+    // Track cache hits (and, for --incremental, fresh hashes) using atomics
+    let cache_hits = std::sync::atomic::AtomicUsize::new(0);
+    let files_hashed = std::sync::atomic::AtomicUsize::new(0);
 
-    // Pre-scan to count total files
-    let total_files = match count_files_in_directory(&cli.directories[0], &filter_rules) {
-        Ok(count) => {
-            send_status(0, format!("Pre-scan complete: Found {} total files", count));
-            count
+    let tx_hash_progress = tx_progress.clone();
+    let send_status = move |stage: u8, msg: String| {
+        if tx_progress
+            .send(ScanMessage::StatusUpdate(stage, msg))
+            .is_err()
+        {
+            log::warn!("[ScanThread] Failed to send status update to TUI (channel closed).");
+        }
+    };
+    let send_hash_progress = move |msg: String, progress: HashProgress| {
+        if tx_hash_progress
+            .send(ScanMessage::HashProgress(msg, progress))
+            .is_err()
+        {
+            log::warn!("[ScanThread] Failed to send hash progress to TUI (channel closed).");
         }
-        Err(e) => {
-            log::warn!("[ScanThread] Failed to count files: {}", e);
-            send_status(0, format!("Pre-scan failed: {}", e));
-            0 // Continue without total count
+    };
+
+    // ========== STAGE 0: PRE-SCAN FOR TOTAL COUNT ==========
+    // Counting would walk the whole tree, which --limit is there to avoid
+    let total_files = if let Some(limit) = cli.limit {
+        send_status(
+            0,
+            format!("Pre-scan skipped: sampling at most {} files", limit),
+        );
+        0
+    } else {
+        send_status(0, format!("Pre-scan: Counting files in {}", roots_display));
+        match scan_roots
+            .iter()
+            .map(|root| count_files_in_directory(root, &filter_rules))
+            .sum::<Result<usize>>()
+        {
+            Ok(count) => {
+                send_status(0, format!("Pre-scan complete: Found {} total files", count));
+                count
+            }
+            Err(e) => {
+                log::warn!("[ScanThread] Failed to count files: {}", e);
+                send_status(0, format!("Pre-scan failed: {}", e));
+                0 // Continue without total count
+            }
         }
     };
 
@@ -352,7 +1518,7 @@ pub fn find_duplicate_files_with_progress(
         1,
         format!(
             "Stage 1/3: 📁 Starting file discovery in {} (0/{} files)",
-            cli.directories[0].display(),
+            roots_display,
             if total_files > 0 {
                 total_files.to_string()
             } else {
@@ -361,86 +1527,157 @@ pub fn find_duplicate_files_with_progress(
         ),
     );
 
-    let mut files_by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
-    let walker = WalkDir::new(&cli.directories[0]).into_iter();
+    // The walk itself is sequential; stat-ing the files it finds is fanned out below
+    let mut candidate_paths: Vec<PathBuf> = Vec::new();
+    let mut visited = VisitedPaths::new(filter_rules.follow_symlinks);
     let mut files_scanned_count = 0;
     let mut last_update_time = std::time::Instant::now();
-    let update_interval = std::time::Duration::from_millis(400); // Less frequent updates (400ms)
-
-    for entry in walker
-        .filter_entry(|e| {
-            if is_hidden(e) || is_symlink(e) {
-                return false;
-            }
-            if let Some(path_str) = e.path().to_str() {
-                filter_rules.is_match(path_str)
-            } else {
-                log::warn!(
-                    "[ScanThread] Path {:?} is not valid UTF-8, excluding.",
-                    e.path()
-                );
-                false
+    // --progress-interval overrides the time cadence, --progress-every the count cadence
+    let update_interval = std::time::Duration::from_millis(
+        cli.progress_interval
+            .unwrap_or(DEFAULT_PROGRESS_INTERVAL_MS),
+    );
+    let progress_every = cli.progress_every.map(|n| n.max(1));
+
+    'walk: for root in &scan_roots {
+        let walker = filter_rules.walk(root);
+        for entry in walker
+            .filter_entry(|e| {
+                if filter_rules.skips_entry(e) {
+                    return false;
+                }
+                if let Some(path_str) = e.path().to_str() {
+                    filter_rules.is_match(path_str) && visited.first_visit(e)
+                } else {
+                    log::warn!(
+                        "[ScanThread] Path {:?} is not valid UTF-8, excluding.",
+                        e.path()
+                    );
+                    false
+                }
+            })
+            .filter_map(|entry| match entry {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    // Typically a directory without read permission
+                    log::warn!("[ScanThread] Failed to read {:?}: {}", e.path(), e);
+                    crate::exit_code::record_unreadable();
+                    None
+                }
+            })
+        {
+            if cancel.load(Ordering::Relaxed) {
+                break 'walk;
             }
-        })
-        .flatten()
-    {
-        if entry.file_type().is_file() {
-            let path = entry.path().to_path_buf();
-            files_scanned_count += 1;
-
-            // Determine update frequency based on file count
-            let should_update = if files_scanned_count < 100 {
-                files_scanned_count % 10 == 0
-            } else if files_scanned_count < 500 {
-                files_scanned_count % 20 == 0
-            } else if files_scanned_count < 1000 {
-                files_scanned_count % 50 == 0
-            } else if files_scanned_count < 5000 {
-                files_scanned_count % 100 == 0
-            } else if files_scanned_count < 10000 {
-                files_scanned_count % 200 == 0
-            } else if files_scanned_count < 50000 {
-                files_scanned_count % 500 == 0
-            } else {
-                files_scanned_count % 1000 == 0
-            };
-
-            if should_update || last_update_time.elapsed() >= update_interval {
-                last_update_time = std::time::Instant::now();
-                // Show progress percentage if total is known
-                if total_files > 0 {
-                    let percent = (files_scanned_count as f64 / total_files as f64) * 100.0;
+            if entry.file_type().is_file() {
+                // Only flagged as a sample when there was more to find
+                if cli.limit == Some(files_scanned_count) {
+                    sampled = true;
                     send_status(
                         1,
                         format!(
-                            "Stage 1/3: 📁 Scanning files: {}/{} ({:.1}%)",
-                            files_scanned_count, total_files, percent
+                            "Stage 1/3: 📁 Stopped discovery at --limit {}; results are a partial sample",
+                            files_scanned_count
                         ),
                     );
-                } else {
-                    // Remove file name from status update to reduce repaints
-                    send_status(
-                        1,
-                        format!("Stage 1/3: 📁 Found {} files...", files_scanned_count),
-                    );
+                    break 'walk;
                 }
-            }
-
-            match fs::metadata(&path) {
-                Ok(metadata) => {
-                    if metadata.len() > 0 {
-                        files_by_size.entry(metadata.len()).or_default().push(path);
+                let path = entry.path().to_path_buf();
+                files_scanned_count += 1;
+
+                // Determine update frequency based on file count
+                let should_update = if let Some(every) = progress_every {
+                    files_scanned_count % every == 0
+                } else if files_scanned_count < 100 {
+                    files_scanned_count % 10 == 0
+                } else if files_scanned_count < 500 {
+                    files_scanned_count % 20 == 0
+                } else if files_scanned_count < 1000 {
+                    files_scanned_count % 50 == 0
+                } else if files_scanned_count < 5000 {
+                    files_scanned_count % 100 == 0
+                } else if files_scanned_count < 10000 {
+                    files_scanned_count % 200 == 0
+                } else if files_scanned_count < 50000 {
+                    files_scanned_count % 500 == 0
+                } else {
+                    files_scanned_count % 1000 == 0
+                };
+
+                if should_update || last_update_time.elapsed() >= update_interval {
+                    last_update_time = std::time::Instant::now();
+                    // Show progress percentage if total is known
+                    if total_files > 0 {
+                        let percent = (files_scanned_count as f64 / total_files as f64) * 100.0;
+                        send_status(
+                            1,
+                            format!(
+                                "Stage 1/3: 📁 Scanning files: {}/{} ({:.1}%)",
+                                files_scanned_count, total_files, percent
+                            ),
+                        );
+                    } else {
+                        // Remove file name from status update to reduce repaints
+                        send_status(
+                            1,
+                            format!("Stage 1/3: 📁 Found {} files...", files_scanned_count),
+                        );
                     }
                 }
-                Err(e) => {
-                    log::warn!("[ScanThread] Failed to get metadata for {:?}: {}", path, e)
-                }
+
+                candidate_paths.push(path);
             }
         }
     }
 
+    if let Some(list) = &cli.files_from {
+        candidate_paths = read_file_list(list, cli.null_input)?
+            .into_iter()
+            .filter(|path| filter_rules.accepts_listed_path(path))
+            .collect();
+        if let Some(limit) = cli.limit.filter(|&limit| candidate_paths.len() > limit) {
+            candidate_paths.truncate(limit);
+            sampled = true;
+        }
+        files_scanned_count = candidate_paths.len();
+        send_status(
+            1,
+            format!(
+                "Stage 1/3: 📁 Read {} paths from {}",
+                files_scanned_count,
+                list.display()
+            ),
+        );
+    }
+
+    // A --files-from list can only be read once (it may be stdin), so media mode reuses it
+    let listed_paths =
+        (cli.files_from.is_some() && cli.media_mode).then(|| candidate_paths.clone());
+
+    // Metadata collection is I/O bound, so it gets its own pool sized by --threads-io
+    let DiscoveredFiles {
+        mut files_by_size,
+        empty_files,
+    } = collect_file_metadata(
+        candidate_paths,
+        &filter_rules,
+        cli.include_empty,
+        cli.threads_io,
+    )?;
     let file_count = files_by_size.values().map(|v| v.len()).sum::<usize>();
     let size_group_count = files_by_size.len();
+    if let Some(index) = index {
+        index.extend(files_by_size.iter().flat_map(|(&size, paths)| {
+            paths.iter().map(move |path| FileInfo {
+                path: path.clone(),
+                size,
+                hash: None,
+                modified_at: None,
+                created_at: None,
+            })
+        }));
+        index.extend(empty_files.iter().cloned());
+    }
 
     if total_files > 0 {
         let percent_found = (files_scanned_count as f64 / total_files as f64) * 100.0;
@@ -467,8 +1704,38 @@ pub fn find_duplicate_files_with_progress(
         size_group_count
     );
 
+    let num_threads = cli.parallel.unwrap_or_else(num_cpus::get);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()?;
+
     // ========== STAGE 2: SIZE COMPARISON ==========
     let mut duplicate_sets: Vec<DuplicateSet> = Vec::new();
+    if cli.ignore_metadata {
+        send_status(
+            2,
+            "Stage 2/3: 🔍 Hashing the content of tagged media files (ignoring metadata)..."
+                .to_string(),
+        );
+        duplicate_sets.extend(find_metadata_insensitive_duplicates(
+            &mut files_by_size,
+            &cli.algorithm,
+            &pool,
+        ));
+    }
+    if empty_files.len() > 1 {
+        log::info!(
+            "[ScanThread] Grouped {} empty files into one set.",
+            empty_files.len()
+        );
+        duplicate_sets.push(DuplicateSet {
+            files: empty_files,
+            size: 0,
+            hash: EMPTY_FILE_HASH.to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        });
+    }
     let potential_duplicates: Vec<_> = files_by_size
         .into_iter()
         .filter(|(_, paths)| paths.len() > 1)
@@ -477,20 +1744,44 @@ pub fn find_duplicate_files_with_progress(
     let _potential_duplicate_count = potential_duplicates.len();
 
     if potential_duplicates.is_empty() {
-        send_status(
-            3,
-            "Scan complete. No potential duplicates found.".to_string(),
-        );
+        if cancel.load(Ordering::Relaxed) {
+            send_status(
+                3,
+                "Scan cancelled during file discovery. No duplicates found before stopping."
+                    .to_string(),
+            );
+        } else {
+            send_status(
+                3,
+                "Scan complete. No potential duplicates found.".to_string(),
+            );
+        }
         log::info!("[ScanThread] No potential duplicates found after size grouping.");
 
         // No duplicates found, but if media mode is enabled, we should handle it separately
-        if cli.media_mode && cli.media_dedup_options.enabled {
+        if cli.media_mode && cli.media_dedup_options.enabled && !cancel.load(Ordering::Relaxed) {
             // Clone before moving tx_progress into closure
             let tx_clone = tx_progress_for_media.clone();
-            return find_similar_media_files_with_progress(cli, tx_clone);
+            return find_similar_media_files_with_progress(cli, listed_paths, tx_clone)
+                .map(|sets| (sets, sampled));
         }
 
-        return Ok(Vec::new());
+        // Only the --include-empty set (if any) remains
+        if cli.same_name_only {
+            split_by_file_name(&mut duplicate_sets, cli.case_insensitive_names());
+        }
+        if cli.compare_xattrs {
+            split_by_xattrs(&mut duplicate_sets);
+        }
+        collapse_hardlinks(&mut duplicate_sets, cli.show_hardlinks);
+        if cli.exclude_same_dir {
+            exclude_same_dir_duplicates(&mut duplicate_sets);
+        }
+        retain_min_set_size(&mut duplicate_sets, cli.min_set_size);
+        if cli.deterministic {
+            sort_sets_deterministically(&mut duplicate_sets);
+        }
+        return Ok((duplicate_sets, sampled));
     }
 
     let potential_groups = potential_duplicates.len();
@@ -512,15 +1803,48 @@ pub fn find_duplicate_files_with_progress(
         potential_groups
     );
 
+    // Cheap head/tail comparison so same-size files that obviously differ are never fully hashed
+    let potential_duplicates = if cli.no_prefilter {
+        potential_duplicates
+    } else {
+        send_status(
+            2,
+            format!(
+                "Stage 2/3: 🔍 Pre-filtering {} files by first/last {} KiB...",
+                potential_files,
+                PREFILTER_SAMPLE_SIZE / 1024
+            ),
+        );
+        // Same rule as the hashing stage below: only global-algorithm hashes are cached
+        let is_cached = |path: &Path| {
+            file_cache
+                .as_ref()
+                .filter(|_| !cli.refresh_cache && hash_override_for(cli, path).is_none())
+                .and_then(|cache| cache.lock().ok()?.get_file_info(path))
+                .is_some_and(|info| info.hash.is_some())
+        };
+        let filtered = pool.install(|| prefilter_size_groups(potential_duplicates, is_cached));
+        let remaining_files: usize = filtered.iter().map(|(_, paths)| paths.len()).sum();
+        log::info!(
+            "[ScanThread] Pre-filter kept {} of {} candidate files.",
+            remaining_files,
+            potential_files
+        );
+        send_status(
+            2,
+            format!(
+                "Stage 2/3: 🔍 Pre-filter kept {} of {} potential duplicate files",
+                remaining_files, potential_files
+            ),
+        );
+        filtered
+    };
+
     // ========== STAGE 3: HASH CALCULATION ==========
-    let num_threads = cli.parallel.unwrap_or_else(num_cpus::get);
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()?;
     log::info!("[ScanThread] Using {} threads for hashing.", num_threads);
 
     // For MPSC between hashing threads and this function's aggregation logic
-    let (local_tx, local_rx) = std::sync::mpsc::channel::<Result<HashMap<String, Vec<FileInfo>>>>();
+    let (local_tx, local_rx) = std::sync::mpsc::channel::<HashMap<String, Vec<FileInfo>>>();
     let total_groups_to_hash = potential_duplicates.len();
     let mut groups_hashed_count = 0;
     let total_files_to_hash = potential_duplicates
@@ -536,92 +1860,114 @@ pub fn find_duplicate_files_with_progress(
         ),
     );
 
+    let read_buffer = read_buffer_size(cli.read_buffer);
+
+    // --max-memory: limit how many large files are read at the same time
+    let large_file_permits = cli.max_memory.and_then(|max_memory| {
+        let large_sizes: Vec<u64> = potential_duplicates
+            .iter()
+            .filter(|(size, _)| *size >= LARGE_FILE_THRESHOLD)
+            .flat_map(|(size, paths)| std::iter::repeat_n(*size, paths.len()))
+            .collect();
+        if large_sizes.is_empty() {
+            return None;
+        }
+        let average = large_sizes.iter().sum::<u64>() / large_sizes.len() as u64;
+        let permits = HashPermits::for_budget(max_memory, average);
+        log::info!(
+            "[ScanThread] Hashing at most {} large files at once (average {} bytes).",
+            permits.available.lock().map_or(1, |n| *n),
+            average
+        );
+        Some(permits)
+    });
+
     // Keep track of all collected FileInfos for possible media processing later
     let mut all_file_infos = Vec::new();
 
     pool.install(|| {
-        potential_duplicates
-            .into_par_iter()
-            .for_each_with(local_tx, |thread_local_tx, (size, paths)| {
-                let mut hashes_in_group: HashMap<String, Vec<FileInfo>> = HashMap::new();
-
-                // Thread-local cache hits counter
-                let mut thread_cache_hits = 0;
-
-                for path in paths {
-                    // Try to get hash from cache first if fast mode is enabled
-                    let mut hash_from_cache = None;
-                    if let Some(cache) = file_cache.as_ref() {
-                        if let Ok(cache_guard) = cache.lock() {
-                            hash_from_cache = cache_guard.get_file_info(&path);
-                            if hash_from_cache.is_some() {
-                                thread_cache_hits += 1;
-                            }
+        potential_duplicates.into_par_iter().for_each_with(
+            local_tx,
+            |thread_local_tx, (size, paths)| {
+                let hash_file = |path: &Path, strategy: Option<&HashOverride>| {
+                    let _permit = large_file_permits
+                        .as_ref()
+                        .filter(|_| size >= LARGE_FILE_THRESHOLD)
+                        .map(HashPermits::acquire);
+                    calculate_hash_buffered(
+                        path,
+                        strategy.map_or(&cli.algorithm, |s| &s.algorithm),
+                        strategy.map_or(cli.hash_limit, |s| s.hash_limit),
+                        read_buffer,
+                    )
+                };
+
+                let hashed = hash_size_group(paths, |path| {
+                    // Whatever this group hashed so far is still reported
+                    if cancel.load(Ordering::Relaxed) {
+                        return Ok(None);
+                    }
+                    // The cache only holds hashes made with the global algorithm.
+                    // --refresh-cache never reads it, so every file is hashed and re-stored.
+                    let strategy = hash_override_for(cli, &path);
+                    let hash_from_cache = file_cache
+                        .as_ref()
+                        .filter(|_| strategy.is_none() && !cli.refresh_cache)
+                        .and_then(|cache| cache.lock().ok()?.get_file_info(&path));
+                    if let Some(file_info) = hash_from_cache {
+                        return Ok(file_info.hash.clone().map(|hash| (hash, file_info, true)));
+                    }
+
+                    // A failure skips just this file (see `hash_size_group`)
+                    let hash_str = hash_file(&path, strategy)?;
+                    let hash_str = match strategy {
+                        Some(strategy) => override_hash_key(strategy, &hash_str),
+                        None => hash_str,
+                    };
+                    files_hashed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let metadata = match fs::metadata(&path) {
+                        Ok(m) => m,
+                        Err(e) => {
+                            log::warn!("Failed to get metadata for {:?}: {}", path, e);
+                            return Ok(None);
+                        }
+                    };
+                    let file_info = FileInfo {
+                        path,
+                        size,
+                        hash: Some(hash_str.clone()),
+                        modified_at: metadata.modified().ok(),
+                        created_at: metadata.created().ok(),
+                    };
+
+                    // Update cache if available
+                    if let Some(cache) = file_cache.as_ref().filter(|_| strategy.is_none()) {
+                        if let Ok(mut cache_guard) = cache.lock() {
+                            let _ = cache_guard.store(&file_info, &cli.algorithm);
                         }
                     }
+                    Ok(Some((hash_str, file_info, false)))
+                });
 
-                    match hash_from_cache {
-                        // Use cached hash if available
-                        Some(file_info) => {
-                            if let Some(hash_str) = &file_info.hash {
-                                hashes_in_group.entry(hash_str.clone()).or_default().push(file_info);
-                            }
-                        },
-                        // Calculate hash if not cached or cache miss
-                        None => match calculate_hash(&path, &cli.algorithm) {
-                            Ok(hash_str) => {
-                                let metadata = match fs::metadata(&path) {
-                                    Ok(m) => m,
-                                    Err(e) => {
-                                        log::warn!("Failed to get metadata for {:?}: {}", path, e);
-                                        continue;
-                                    }
-                                };
-                                let file_info = FileInfo {
-                                    path: path.clone(),
-                                    size,
-                                    hash: Some(hash_str.clone()),
-                                    modified_at: metadata.modified().ok(),
-                                    created_at: metadata.created().ok(),
-                                };
-
-                                // Update cache if available
-                                if let Some(cache) = &file_cache {
-                                    if let Ok(mut cache_guard) = cache.lock() {
-                                        let _ = cache_guard.store(&file_info, &cli.algorithm);
-                                    }
-                                }
-
-                                hashes_in_group.entry(hash_str).or_default().push(file_info);
-                            }
-                            Err(e) => {
-                                log::warn!("[ScanThread] Failed to hash {:?}: {}", path, e);
-                                if thread_local_tx.send(Err(e)).is_err() {
-                                    log::error!("[ScanThread] Hashing thread failed to send error (channel closed).");
-                                }
-                                return;
-                            }
-                        }
-                    }
-                }
-
+                let (hashes_in_group, thread_cache_hits) = hashed;
                 // Update global cache hits
-                if thread_cache_hits > 0 {
-                    cache_hits.fetch_add(thread_cache_hits, std::sync::atomic::Ordering::Relaxed);
-                }
-
-                if thread_local_tx.send(Ok(hashes_in_group)).is_err() {
-                    log::error!("[ScanThread] Hashing thread failed to send result (channel closed).");
+                cache_hits.fetch_add(thread_cache_hits, std::sync::atomic::Ordering::Relaxed);
+                if thread_local_tx.send(hashes_in_group).is_err() {
+                    log::error!(
+                        "[ScanThread] Hashing thread failed to send result (channel closed)."
+                    );
                 }
-            });
+            },
+        );
     });
 
     let mut actual_duplicate_sets = 0;
+    let mut hashing_speed = HashingSpeed::new(total_groups_to_hash, std::time::Instant::now());
 
     for i in 0..total_groups_to_hash {
         match local_rx.recv() {
             // This will block until a message is received
-            Ok(Ok(hashed_group)) => {
+            Ok(hashed_group) => {
                 for (hash, file_infos_vec) in hashed_group {
                     // Keep all file infos for media processing if needed
                     if cli.media_mode {
@@ -631,19 +1977,20 @@ pub fn find_duplicate_files_with_progress(
                     if file_infos_vec.len() > 1 {
                         actual_duplicate_sets += 1;
                         let first_file_size = file_infos_vec[0].size; // Get size before move
+
+                        // Every file in the set was hashed the same way, so the first decides
+                        let hash_limit = hash_override_for(cli, &file_infos_vec[0].path)
+                            .map_or(cli.hash_limit, |s| s.hash_limit);
                         duplicate_sets.push(DuplicateSet {
                             files: file_infos_vec, // file_infos_vec is moved here
                             size: first_file_size,
                             hash,
+                            approximate: hash_limit.is_some_and(|limit| first_file_size > limit),
+                            hardlinks: Vec::new(),
                         });
                     }
                 }
             }
-            Ok(Err(e)) => {
-                log::error!("[ScanThread] Error hashing a file group: {}", e);
-                // Decide if we should propagate this error or just log and continue
-                // For now, just log. The overall function might still succeed with partial results.
-            }
             Err(e) => {
                 // mpsc::RecvError - local_tx dropped and channel empty
                 log::error!(
@@ -661,9 +2008,12 @@ pub fn find_duplicate_files_with_progress(
             }
         }
         groups_hashed_count += 1;
+        hashing_speed.record(groups_hashed_count, std::time::Instant::now());
 
         // Determine update frequency for hash progress
-        let should_update = if total_groups_to_hash < 20 {
+        let should_update = if let Some(every) = progress_every {
+            groups_hashed_count % every == 0 || groups_hashed_count == total_groups_to_hash
+        } else if total_groups_to_hash < 20 {
             true // Always update for small hash groups
         } else if total_groups_to_hash < 100 {
             groups_hashed_count % 5 == 0 || groups_hashed_count == total_groups_to_hash
@@ -675,8 +2025,7 @@ pub fn find_duplicate_files_with_progress(
 
         if should_update || last_update_time.elapsed() >= update_interval {
             last_update_time = std::time::Instant::now();
-            let progress_percent =
-                (groups_hashed_count as f64 / total_groups_to_hash as f64) * 100.0;
+            let progress = hashing_speed.progress();
 
             let cache_status = if cache_hits.load(std::sync::atomic::Ordering::Relaxed) > 0 {
                 format!(
@@ -687,20 +2036,43 @@ pub fn find_duplicate_files_with_progress(
                 "".to_string()
             };
 
-            send_status(
-                3,
+            let eta = progress
+                .estimated_seconds_left
+                .map(|secs| format!(", ~{:.0}s left", secs.ceil()))
+                .unwrap_or_default();
+
+            send_hash_progress(
                 format!(
-                    "Stage 3/3: 🔄 Hashed {}/{} groups ({:.1}%){}... Found {} duplicate sets",
+                    "Stage 3/3: 🔄 Hashed {}/{} groups ({:.1}%{}){}... Found {} duplicate sets",
                     groups_hashed_count,
                     total_groups_to_hash,
-                    progress_percent,
+                    progress.percent_complete,
+                    eta,
                     cache_status,
                     actual_duplicate_sets
                 ),
+                progress,
             );
         }
     }
 
+    if cli.same_name_only {
+        split_by_file_name(&mut duplicate_sets, cli.case_insensitive_names());
+    }
+    if cli.compare_xattrs {
+        split_by_xattrs(&mut duplicate_sets);
+    }
+
+    // Hardlinked paths share storage, so only one of them can ever reclaim space
+    collapse_hardlinks(&mut duplicate_sets, cli.show_hardlinks);
+    if cli.exclude_same_dir {
+        exclude_same_dir_duplicates(&mut duplicate_sets);
+    }
+    retain_min_set_size(&mut duplicate_sets, cli.min_set_size);
+    if cli.deterministic {
+        sort_sets_deterministically(&mut duplicate_sets);
+    }
+
     // Save file cache if it was used
     if let Some(cache) = &file_cache {
         if let Ok(mut cache_guard) = cache.lock() {
@@ -716,7 +2088,23 @@ pub fn find_duplicate_files_with_progress(
         }
     }
 
-    let message = if cache_hits.load(std::sync::atomic::Ordering::Relaxed) > 0 {
+    if cli.incremental && file_cache.is_some() {
+        let summary = format!(
+            "Incremental scan: skipped {} unchanged files via cache, hashed {} new or changed files, dropped {} stale cache entries.",
+            cache_hits.load(std::sync::atomic::Ordering::Relaxed),
+            files_hashed.load(std::sync::atomic::Ordering::Relaxed),
+            stale_entries
+        );
+        log::info!("[ScanThread] {}", summary);
+        send_status(3, summary);
+    }
+
+    let message = if cancel.load(Ordering::Relaxed) {
+        format!(
+            "Scan cancelled. Found {} sets of duplicate files before stopping.",
+            duplicate_sets.len()
+        )
+    } else if cache_hits.load(std::sync::atomic::Ordering::Relaxed) > 0 {
         format!(
             "All stages complete. Found {} sets of duplicate files. Used {} cached hashes.",
             duplicate_sets.len(),
@@ -741,12 +2129,238 @@ pub fn find_duplicate_files_with_progress(
         log::info!("Media mode is enabled but placeholder implementation");
     }
 
-    Ok(duplicate_sets)
+    Ok((duplicate_sets, sampled))
+}
+
+/// Scan roots for `--union`: duplicate roots and roots nested inside another root are
+/// dropped, so no file is walked twice and reported as a duplicate of itself.
+fn union_scan_roots(directories: &[PathBuf]) -> Vec<PathBuf> {
+    let canonical: Vec<PathBuf> = directories
+        .iter()
+        .map(|d| fs::canonicalize(d).unwrap_or_else(|_| d.clone()))
+        .collect();
+    let mut roots = Vec::new();
+    for (i, dir) in directories.iter().enumerate() {
+        let covered = canonical.iter().enumerate().any(|(j, other)| {
+            j != i && canonical[i].starts_with(other) && (canonical[i] != *other || j < i)
+        });
+        if covered {
+            log::info!(
+                "[ScanThread] Skipping {:?}: already covered by another --union directory",
+                dir
+            );
+        } else {
+            roots.push(dir.clone());
+        }
+    }
+    roots
+}
+
+/// Returns the `(device, inode)` pair identifying the underlying file, if the platform exposes one.
+#[cfg(unix)]
+fn file_identity(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+/// `--same-name-only`: split each set so only files with the same file name stay grouped.
+/// Files whose name no other copy shares are dropped, as are sets left with one file.
+pub(crate) fn split_by_file_name(sets: &mut Vec<DuplicateSet>, case_insensitive: bool) {
+    let mut split_sets = Vec::with_capacity(sets.len());
+    for set in sets.drain(..) {
+        let mut by_name: std::collections::BTreeMap<std::ffi::OsString, Vec<FileInfo>> =
+            std::collections::BTreeMap::new();
+        for file in &set.files {
+            let name = name_key(file.path.file_name().unwrap_or_default(), case_insensitive);
+            by_name.entry(name).or_default().push(file.clone());
+        }
+        split_sets.extend(
+            by_name
+                .into_values()
+                .filter(|files| files.len() >= 2)
+                .map(|files| DuplicateSet {
+                    files,
+                    ..set.clone()
+                }),
+        );
+    }
+    *sets = split_sets;
+}
+
+/// --compare-xattrs: split each set into groups whose files carry the same extended
+/// attributes. Files whose attributes can't be read are left out of every set.
+pub(crate) fn split_by_xattrs(sets: &mut Vec<DuplicateSet>) {
+    if !crate::xattrs::XATTRS_SUPPORTED {
+        log::warn!(
+            "--compare-xattrs has no effect: extended attributes aren't supported on this platform"
+        );
+        return;
+    }
+    let mut split_sets = Vec::with_capacity(sets.len());
+    for set in sets.drain(..) {
+        let mut by_xattrs: std::collections::BTreeMap<crate::xattrs::Xattrs, Vec<FileInfo>> =
+            std::collections::BTreeMap::new();
+        for file in &set.files {
+            match crate::xattrs::read_xattrs(&file.path) {
+                Ok(xattrs) => by_xattrs.entry(xattrs).or_default().push(file.clone()),
+                Err(e) => log::warn!("Leaving {:?} out of its duplicate set: {}", file.path, e),
+            }
+        }
+        split_sets.extend(
+            by_xattrs
+                .into_values()
+                .filter(|files| files.len() >= 2)
+                .map(|files| DuplicateSet {
+                    files,
+                    ..set.clone()
+                }),
+        );
+    }
+    *sets = split_sets;
+}
+
+/// --ignore-metadata: take files whose format carries strippable metadata (mp3, PNG,
+/// JPEG) out of the size groups, since tags change their size, and group them by the
+/// hash of their content alone
+fn find_metadata_insensitive_duplicates(
+    files_by_size: &mut HashMap<u64, Vec<PathBuf>>,
+    algorithm: &str,
+    pool: &rayon::ThreadPool,
+) -> Vec<DuplicateSet> {
+    let mut candidates = Vec::new();
+    for (size, paths) in files_by_size.iter_mut() {
+        paths.retain(|path| {
+            let strippable = crate::content_hash::has_strippable_metadata(path);
+            if strippable {
+                candidates.push((path.clone(), *size));
+            }
+            !strippable
+        });
+    }
+    files_by_size.retain(|_, paths| !paths.is_empty());
+
+    let hashed: Vec<FileInfo> = pool.install(|| {
+        candidates
+            .into_par_iter()
+            .filter_map(|(path, size)| {
+                let hash = match crate::content_hash::normalized_hash(&path, algorithm) {
+                    Ok(hash) => hash,
+                    Err(e) => {
+                        log::warn!("[ScanThread] Failed to hash {:?}: {}", path, e);
+                        return None;
+                    }
+                };
+                let metadata = fs::metadata(&path).ok();
+                Some(FileInfo {
+                    path,
+                    size,
+                    hash: Some(format!(
+                        "{}{}",
+                        crate::content_hash::CONTENT_HASH_PREFIX,
+                        hash
+                    )),
+                    modified_at: metadata.as_ref().and_then(|m| m.modified().ok()),
+                    created_at: metadata.as_ref().and_then(|m| m.created().ok()),
+                })
+            })
+            .collect()
+    });
+
+    let mut by_hash: HashMap<String, Vec<FileInfo>> = HashMap::new();
+    for file in hashed {
+        by_hash
+            .entry(file.hash.clone().unwrap_or_default())
+            .or_default()
+            .push(file);
+    }
+    by_hash
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(hash, files)| DuplicateSet {
+            size: files[0].size,
+            files,
+            hash,
+            approximate: false,
+            hardlinks: Vec::new(),
+        })
+        .collect()
+}
+
+/// Drop sets with fewer than `min_files` copies (--min-set-size). Runs after hardlinks are
+/// collapsed, so only independent copies count.
+pub(crate) fn retain_min_set_size(sets: &mut Vec<DuplicateSet>, min_files: usize) {
+    sets.retain(|set| set.files.len() >= min_files.max(2));
+}
+
+/// Put sets in a stable order (--deterministic): files (and hardlinks) within each set by
+/// path, and sets by hash, then size. Hashing runs in parallel, so without this both orders
+/// depend on which thread finished first.
+pub fn sort_sets_deterministically(sets: &mut [DuplicateSet]) {
+    for set in sets.iter_mut() {
+        set.files.sort_by(|a, b| a.path.cmp(&b.path));
+        set.hardlinks.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    sets.sort_by(|a, b| a.hash.cmp(&b.hash).then_with(|| a.size.cmp(&b.size)));
+}
+
+/// Keep only cross-directory duplicates (--exclude-same-dir): each set retains its first
+/// file from every parent directory, so copies sitting side by side in one folder are
+/// left alone. Sets that all live in a single folder are dropped.
+pub(crate) fn exclude_same_dir_duplicates(sets: &mut Vec<DuplicateSet>) {
+    for set in sets.iter_mut() {
+        let mut seen_dirs = std::collections::HashSet::new();
+        set.files
+            .retain(|f| seen_dirs.insert(f.path.parent().map(Path::to_path_buf)));
+    }
+    sets.retain(|set| set.files.len() > 1);
+}
+
+/// Key for comparing file names or relative paths, folded to lowercase when names are
+/// case-insensitive
+fn name_key(name: &std::ffi::OsStr, case_insensitive: bool) -> std::ffi::OsString {
+    if case_insensitive {
+        name.to_string_lossy().to_lowercase().into()
+    } else {
+        name.to_os_string()
+    }
+}
+
+/// Collapse files in each set that are hardlinks of one another into a single representative.
+/// The extra links are kept in `DuplicateSet::hardlinks` when `keep_as_info` is set, and
+/// sets left with fewer than two independent files are dropped.
+pub(crate) fn collapse_hardlinks(sets: &mut Vec<DuplicateSet>, keep_as_info: bool) {
+    for set in sets.iter_mut() {
+        let mut seen: std::collections::HashSet<(u64, u64)> = std::collections::HashSet::new();
+        let mut independent = Vec::with_capacity(set.files.len());
+        for file_info in std::mem::take(&mut set.files) {
+            match file_identity(&file_info.path) {
+                Some(identity) if !seen.insert(identity) => {
+                    log::info!(
+                        "[ScanThread] {:?} is a hardlink to another file in its set, skipping.",
+                        file_info.path
+                    );
+                    if keep_as_info {
+                        set.hardlinks.push(file_info);
+                    }
+                }
+                _ => independent.push(file_info),
+            }
+        }
+        set.files = independent;
+    }
+    sets.retain(|set| set.files.len() > 1);
 }
 
-/// Find similar media files with progress reporting
+/// Find similar media files with progress reporting. `listed_paths` are the files read
+/// from --files-from; without it the first directory is walked.
 fn find_similar_media_files_with_progress(
     cli: &Cli,
+    listed_paths: Option<Vec<PathBuf>>,
     tx_progress: StdMpscSender<ScanMessage>,
 ) -> Result<Vec<DuplicateSet>> {
     // Helper to send status updates through the channel
@@ -764,114 +2378,87 @@ fn find_similar_media_files_with_progress(
     // First, collect all files recursively
     let filter_rules = FilterRules::new(cli)?;
 
-    send_status(
-        4,
-        format!(
-            "Scanning directory for media files: {}",
-            cli.directories[0].display()
-        ),
-    );
+    let candidates: Vec<PathBuf> = match listed_paths {
+        Some(paths) => {
+            send_status(
+                4,
+                format!("Checking {} listed files for media", paths.len()),
+            );
+            paths
+        }
+        None => {
+            let root = cli
+                .directories
+                .first()
+                .ok_or_else(|| anyhow::anyhow!("No directory to scan for media files"))?;
+            send_status(
+                4,
+                format!("Scanning directory for media files: {}", root.display()),
+            );
+            let mut visited = VisitedPaths::new(filter_rules.follow_symlinks);
+            filter_rules
+                .walk(root)
+                .filter_entry(|e| {
+                    if filter_rules.skips_entry(e) {
+                        return false;
+                    }
+                    if let Some(path_str) = e.path().to_str() {
+                        filter_rules.is_match(path_str) && visited.first_visit(e)
+                    } else {
+                        log::warn!(
+                            "[ScanThread] Path {:?} is not valid UTF-8, excluding.",
+                            e.path()
+                        );
+                        false
+                    }
+                })
+                .flatten()
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.path().to_path_buf())
+                .collect()
+        }
+    };
 
     let mut file_infos = Vec::new();
-    let walker = WalkDir::new(&cli.directories[0]).into_iter();
-
-    for entry in walker
-        .filter_entry(|e| {
-            if is_hidden(e) || is_symlink(e) {
-                return false;
-            }
-            if let Some(path_str) = e.path().to_str() {
-                filter_rules.is_match(path_str)
-            } else {
-                log::warn!(
-                    "[ScanThread] Path {:?} is not valid UTF-8, excluding.",
-                    e.path()
-                );
-                false
-            }
-        })
-        .flatten()
-    {
-        if entry.file_type().is_file() {
-            let path = entry.path().to_path_buf();
-
-            match fs::metadata(&path) {
-                Ok(metadata) => {
-                    if metadata.len() > 0 {
-                        let file_info = FileInfo {
-                            path: path.clone(),
-                            size: metadata.len(),
-                            hash: None, // We don't need hash for media comparison
-                            modified_at: metadata.modified().ok(),
-                            created_at: metadata.created().ok(),
-                        };
-
+    for path in candidates {
+        match fs::metadata(&path) {
+            Ok(metadata) => {
+                if metadata.len() > 0 {
+                    let file_info = FileInfo {
+                        path: path.clone(),
+                        size: metadata.len(),
+                        hash: None, // We don't need hash for media comparison
+                        modified_at: metadata.modified().ok(),
+                        created_at: metadata.created().ok(),
+                    };
+
+                    if filter_rules.matches_metadata(&file_info) {
                         file_infos.push(file_info);
                     }
                 }
-                Err(e) => {
-                    log::warn!("[ScanThread] Failed to get metadata for {:?}: {}", path, e)
-                }
+            }
+            Err(e) => {
+                log::warn!("[ScanThread] Failed to get metadata for {:?}: {}", path, e)
             }
         }
     }
 
-    // Now process for media similarities
-    let mut media_files: Vec<crate::media_dedup::MediaFileInfo> = Vec::new();
-    let mut processed = 0;
-    let total_files = file_infos.len();
-
-    for file_info in &file_infos {
-        let mut media_file = crate::media_dedup::MediaFileInfo::from(file_info.clone());
-
-        // Only process media files
-        let media_kind = crate::media_dedup::detect_media_type(&file_info.path);
-        if media_kind != crate::media_dedup::MediaKind::Unknown {
-            media_file.metadata = match crate::media_dedup::extract_media_metadata(&file_info.path)
-            {
-                Ok(metadata) => Some(metadata),
-                Err(e) => {
-                    log::warn!(
-                        "[ScanThread] Failed to extract media metadata for {:?}: {}",
-                        file_info.path,
-                        e
-                    );
-                    None
-                }
-            };
-        }
-
-        // Update progress
-        processed += 1;
-        send_status(
-            4,
-            format!(
-                "Processing media files: {}/{} ({:.1}%)",
-                processed,
-                total_files,
-                (processed as f64 / total_files as f64) * 100.0
-            ),
-        );
-
-        if media_file.metadata.is_some() {
-            media_files.push(media_file);
-        }
-    }
-
-    log::info!(
-        "[ScanThread] Extracted metadata for {} media files",
-        media_files.len()
-    );
-
-    // Group by media type for more efficient comparison
-    let mut similar_groups: Vec<Vec<crate::media_dedup::MediaFileInfo>> = Vec::new();
+    // Extract media metadata in parallel, bounded by --parallel
+    let media_files =
+        crate::media_dedup::extract_media_files(&file_infos, cli.parallel, &|processed, total| {
+            send_status(
+                4,
+                format!(
+                    "Processing media files: {}/{} ({:.1}%)",
+                    processed,
+                    total,
+                    (processed as f64 / total as f64) * 100.0
+                ),
+            );
+        })?;
 
-    // Process media files to find similar groups
-    crate::media_dedup::process_media_type_similarity(
-        &media_files.iter().collect::<Vec<_>>(),
-        &cli.media_dedup_options,
-        &mut similar_groups,
-    )?;
+    let similar_groups =
+        crate::media_dedup::group_similar_media_files(&media_files, &cli.media_dedup_options)?;
 
     // Convert to duplicate sets
     let duplicate_sets =
@@ -901,10 +2488,6 @@ fn is_hidden(entry: &walkdir::DirEntry) -> bool {
         .unwrap_or(false)
 }
 
-fn is_symlink(entry: &walkdir::DirEntry) -> bool {
-    entry.file_type().is_symlink()
-}
-
 pub fn output_duplicates(
     duplicate_sets: &[DuplicateSet],
     output_path: &Path,
@@ -928,12 +2511,14 @@ pub fn output_duplicates(
                 HashEntryContent {
                     size: set.size,
                     files: file_paths,
+                    approximate: set.approximate,
+                    hardlinks: set.hardlinks.iter().map(|f| f.path.clone()).collect(),
                 },
             );
         }
     }
 
-    if output_map.is_empty() {
+    if output_map.is_empty() && format != "fdupes" {
         log::info!("No duplicate sets with 2 or more files to output.");
         // Optionally, write an empty map or a message to the file, or just do nothing.
         // For now, if the map is empty, we won't create/overwrite the output file.
@@ -945,9 +2530,12 @@ pub fn output_duplicates(
     let output_content = match format {
         "json" => serde_json::to_string_pretty(&output_map)?,
         "toml" => toml::to_string_pretty(&output_map)?,
+        "csv" => duplicates_to_csv(&output_map)?,
+        // fdupes prints nothing when there are no duplicates, so this may be an empty file
+        "fdupes" => duplicates_to_fdupes(duplicate_sets),
         _ => {
             return Err(anyhow::anyhow!(
-                "Unsupported output format: {}. Supported formats are json, toml.",
+                "Unsupported output format: {}. Supported formats are json, toml, csv, fdupes.",
                 format
             ));
         }
@@ -968,308 +2556,1707 @@ pub fn output_duplicates(
     Ok(())
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum SelectionStrategy {
-    ShortestPath,
-    LongestPath,
-    NewestModified,
-    OldestModified,
-}
-
-impl FromStr for SelectionStrategy {
-    type Err = anyhow::Error;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "shortest_path" => Ok(Self::ShortestPath),
-            "longest_path" => Ok(Self::LongestPath),
-            "newest_modified" => Ok(Self::NewestModified),
-            "oldest_modified" => Ok(Self::OldestModified),
-            _ => Err(anyhow::anyhow!("Invalid selection strategy: {}", s)),
+/// Escape text for inclusion in HTML element content or attribute values
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
         }
     }
+    escaped
 }
 
-// Given a set of duplicate files, determines which one to keep and which ones are to be processed (deleted/moved).
-// Returns a tuple: (file_to_keep, files_to_process)
-pub fn determine_action_targets(
-    set: &DuplicateSet,
-    strategy: SelectionStrategy,
-) -> Result<(FileInfo, Vec<FileInfo>)> {
-    if set.files.len() < 2 {
-        // Not a duplicate set for action, or only one file left.
-        return Err(anyhow::anyhow!(
-            "Cannot determine action targets for a set with less than 2 files."
-        ));
+/// Space freed by delete/move/link actions. Only files that were actioned count, so
+/// the kept file of each set is never included.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReclaimedSpace {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl ReclaimedSpace {
+    /// Account for the files an action reported as actioned; failed ones are never included
+    pub fn record(&mut self, actioned: &[FileInfo]) {
+        self.files += actioned.len();
+        self.bytes += actioned.iter().map(|f| f.size).sum::<u64>();
     }
 
-    let mut files = set.files.clone(); // Clone to allow modification/sorting if needed
+    /// e.g. "Reclaimed 4.2 GB across 57 files"
+    pub fn summary(&self, raw_sizes: bool, dry_run: bool) -> String {
+        let size = if raw_sizes {
+            format!("{} bytes", self.bytes)
+        } else {
+            humansize::format_size(self.bytes, humansize::DECIMAL)
+        };
+        format!(
+            "{}eclaimed {} across {} files",
+            if dry_run {
+                "[DRY RUN] Would have r"
+            } else {
+                "R"
+            },
+            size,
+            self.files
+        )
+    }
+}
 
-    let kept_file_info = match strategy {
-        SelectionStrategy::ShortestPath => files
-            .into_iter()
-            .min_by_key(|f| f.path.as_os_str().len())
-            .unwrap(), // Safe because len >= 2
-        SelectionStrategy::LongestPath => files
-            .into_iter()
-            .max_by_key(|f| f.path.as_os_str().len())
-            .unwrap(), // Safe
-        SelectionStrategy::NewestModified => {
-            files.sort_by_key(|f| {
-                fs::metadata(&f.path)
-                    .and_then(|m| m.modified())
-                    .map(std::cmp::Reverse)
-                    .unwrap_or_else(|_| std::cmp::Reverse(std::time::SystemTime::UNIX_EPOCH))
-            });
-            files.remove(0) // After sorting by Reverse(modified_time), the first is newest
-        }
-        SelectionStrategy::OldestModified => {
-            files.sort_by_key(|f| {
-                fs::metadata(&f.path)
-                    .and_then(|m| m.modified())
-                    .unwrap_or_else(|_| std::time::SystemTime::now())
-            });
-            files.remove(0) // After sorting by modified_time, the first is oldest
-        }
-    };
+/// Bucket used by `summarize_by_extension` for files without an extension
+pub const NO_EXTENSION: &str = "(none)";
+
+/// Duplicate totals for one (lowercased) file extension
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ExtensionStat {
+    pub extension: String,
+    /// Duplicate sets containing at least one file with this extension
+    pub sets: usize,
+    pub files: usize,
+    /// Bytes held by redundant copies, i.e. every file but the first of each set
+    pub redundant_bytes: u64,
+}
 
-    let mut files_to_process: Vec<FileInfo> = Vec::new();
-    for file_info in &set.files {
-        if file_info.path != kept_file_info.path {
-            files_to_process.push(file_info.clone());
-        }
-    }
+/// Duplicate totals for one scanned directory (multi-directory mode)
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct DirDuplicateStat {
+    pub directory: PathBuf,
+    /// Files under this directory that belong to a duplicate set
+    pub files: usize,
+    /// Bytes this directory could free while every set keeps one copy somewhere: all of
+    /// its copies when another directory also has one, otherwise all but one
+    pub redundant_bytes: u64,
+}
 
-    Ok((kept_file_info, files_to_process))
+/// The `n` sets that would free the most space (`size * (files - 1)`), largest first
+pub fn top_sets_by_reclaimable(sets: &[DuplicateSet], n: usize) -> Vec<&DuplicateSet> {
+    let mut ranked: Vec<&DuplicateSet> = sets.iter().collect();
+    ranked.sort_by_key(|set| {
+        std::cmp::Reverse(set.size * (set.files.len().saturating_sub(1) as u64))
+    });
+    ranked.truncate(n);
+    ranked
 }
 
-pub fn delete_files(files_to_delete: &[FileInfo], dry_run: bool) -> Result<(usize, Vec<String>)> {
-    let mut count = 0;
-    let mut logs = Vec::new();
-    if dry_run {
-        logs.push("[DRY RUN] Would delete the following files:".to_string());
-        for file_info in files_to_delete {
-            logs.push(format!("[DRY RUN]    - {}", file_info.path.display()));
-            count += 1;
-        }
-    } else {
-        logs.push("Deleting the following files:".to_string());
-        for file_info in files_to_delete {
-            match fs::remove_file(&file_info.path) {
-                Ok(_) => {
-                    logs.push(format!("Deleted: {}", file_info.path.display()));
-                    count += 1;
-                }
-                Err(e) => {
-                    logs.push(format!(
-                        "Error deleting {}: {}",
-                        file_info.path.display(),
-                        e
-                    ));
-                }
+/// Sort duplicate sets for listing (`--order-by`). Sets that compare equal are ordered
+/// by hash, so the listing is the same on every run.
+pub fn sort_duplicate_sets(sets: &mut [DuplicateSet], order: SetOrder, direction: SortOrder) {
+    let first_path = |set: &DuplicateSet| set.files.iter().map(|f| &f.path).min().cloned();
+    sets.sort_by(|a, b| {
+        let ordering = match order {
+            SetOrder::Size => (a.size * (a.files.len().saturating_sub(1) as u64))
+                .cmp(&(b.size * (b.files.len().saturating_sub(1) as u64))),
+            SetOrder::Count => a.files.len().cmp(&b.files.len()),
+            SetOrder::Path => first_path(a).cmp(&first_path(b)),
+        };
+        let ordering = match direction {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        };
+        ordering.then_with(|| a.hash.cmp(&b.hash))
+    });
+}
+
+/// Break duplicate sets down by file extension, largest redundant bytes first
+pub fn summarize_by_extension(sets: &[DuplicateSet]) -> Vec<ExtensionStat> {
+    let mut stats: HashMap<String, ExtensionStat> = HashMap::new();
+    for set in sets {
+        let mut seen_in_set: Vec<String> = Vec::new();
+        for (i, file) in set.files.iter().enumerate() {
+            let extension = file
+                .path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| NO_EXTENSION.to_string());
+            let stat = stats
+                .entry(extension.clone())
+                .or_insert_with(|| ExtensionStat {
+                    extension: extension.clone(),
+                    sets: 0,
+                    files: 0,
+                    redundant_bytes: 0,
+                });
+            stat.files += 1;
+            if i > 0 {
+                stat.redundant_bytes += set.size;
+            }
+            if !seen_in_set.contains(&extension) {
+                stat.sets += 1;
+                seen_in_set.push(extension);
             }
         }
     }
-    Ok((count, logs))
+
+    let mut stats: Vec<ExtensionStat> = stats.into_values().collect();
+    stats.sort_by(|a, b| {
+        b.redundant_bytes
+            .cmp(&a.redundant_bytes)
+            .then_with(|| a.extension.cmp(&b.extension))
+    });
+    stats
 }
 
-pub fn move_files(
-    files_to_move: &[FileInfo],
-    target_dir: &Path,
-    dry_run: bool,
-) -> Result<(usize, Vec<String>)> {
-    let mut count = 0;
-    let mut logs = Vec::new();
+/// Break duplicate sets down by the directory each file was found under, largest
+/// redundant bytes first. With nested directories a file counts toward the innermost one;
+/// files outside every directory are left out.
+pub fn summarize_by_directory(sets: &[DuplicateSet], dirs: &[PathBuf]) -> Vec<DirDuplicateStat> {
+    let mut stats: Vec<DirDuplicateStat> = dirs
+        .iter()
+        .map(|dir| DirDuplicateStat {
+            directory: dir.clone(),
+            files: 0,
+            redundant_bytes: 0,
+        })
+        .collect();
+    let owner = |path: &Path| {
+        dirs.iter()
+            .enumerate()
+            .filter(|(_, dir)| path.starts_with(dir))
+            .max_by_key(|(_, dir)| dir.components().count())
+            .map(|(index, _)| index)
+    };
 
-    if !target_dir.exists() {
-        if dry_run {
-            logs.push(format!(
-                "[DRY RUN] Target directory {} does not exist. Would create it.",
-                target_dir.display()
-            ));
-            log::info!(
-                "[DRY RUN] Target directory {:?} does not exist. Would attempt to create it.",
-                target_dir
-            );
-        } else {
-            log::info!(
-                "Target directory {:?} does not exist. Creating it.",
-                target_dir
-            );
-            fs::create_dir_all(target_dir)?;
-            logs.push(format!(
-                "Created target directory: {}",
-                target_dir.display()
-            ));
+    for set in sets {
+        let mut per_dir: HashMap<usize, u64> = HashMap::new();
+        for file in &set.files {
+            if let Some(index) = owner(&file.path) {
+                *per_dir.entry(index).or_default() += 1;
+            }
+        }
+        let elsewhere = |count: u64| set.files.len() as u64 > count;
+        for (index, count) in per_dir {
+            let redundant = if elsewhere(count) { count } else { count - 1 };
+            stats[index].files += count as usize;
+            stats[index].redundant_bytes += redundant * set.size;
         }
-    } else if !target_dir.is_dir() {
-        return Err(anyhow::anyhow!(
-            "Target move path {:?} exists but is not a directory.",
-            target_dir
-        ));
     }
 
-    if dry_run {
-        logs.push(format!(
-            "[DRY RUN] Would move the following files to {}:",
-            target_dir.display()
+    stats.sort_by_key(|stat| std::cmp::Reverse(stat.redundant_bytes));
+    stats
+}
+
+/// How many extensions the text summary lists; JSON output has all of them
+pub const EXTENSION_SUMMARY_LIMIT: usize = 5;
+
+/// Write where the redundant bytes live, e.g. ".mov  8.0 MB redundant (80.0%)"
+pub fn write_extension_summary(
+    sets: &[DuplicateSet],
+    raw_sizes: bool,
+    out: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    let stats = summarize_by_extension(sets);
+    let total: u64 = stats.iter().map(|s| s.redundant_bytes).sum();
+    if total == 0 {
+        return Ok(());
+    }
+    writeln!(out, "\nDuplicates by extension:")?;
+    for stat in stats.iter().take(EXTENSION_SUMMARY_LIMIT) {
+        let size = if raw_sizes {
+            format!("{} bytes", stat.redundant_bytes)
+        } else {
+            humansize::format_size(stat.redundant_bytes, humansize::DECIMAL)
+        };
+        let name = if stat.extension == NO_EXTENSION {
+            stat.extension.clone()
+        } else {
+            format!(".{}", stat.extension)
+        };
+        writeln!(
+            out,
+            "  {:<10} {} redundant ({:.1}%), {} files in {} sets",
+            name,
+            size,
+            stat.redundant_bytes as f64 / total as f64 * 100.0,
+            stat.files,
+            stat.sets
+        )?;
+    }
+    if stats.len() > EXTENSION_SUMMARY_LIMIT {
+        writeln!(
+            out,
+            "  ... and {} more extensions",
+            stats.len() - EXTENSION_SUMMARY_LIMIT
+        )?;
+    }
+    Ok(())
+}
+
+/// The `--summary-only` report: totals and the per-extension breakdown, without any paths
+pub fn write_duplicate_summary(
+    sets: &[DuplicateSet],
+    raw_sizes: bool,
+    out: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    let total_files: usize = sets.iter().map(|s| s.files.len()).sum();
+    let reclaimable: u64 = sets
+        .iter()
+        .map(|s| s.size * (s.files.len().saturating_sub(1) as u64))
+        .sum();
+    let reclaimable = if raw_sizes {
+        format!("{} bytes", reclaimable)
+    } else {
+        humansize::format_size(reclaimable, humansize::DECIMAL)
+    };
+    writeln!(
+        out,
+        "Found {} sets of duplicate files ({} files, {} reclaimable)",
+        sets.len(),
+        total_files,
+        reclaimable
+    )?;
+    write_extension_summary(sets, raw_sizes, out)
+}
+
+/// Write a self-contained HTML report of the duplicate sets (inline CSS, no external assets).
+/// Reclaimable space is the size of every file beyond the first in each set.
+pub fn write_html_report(sets: &[DuplicateSet], path: &Path, raw_sizes: bool) -> Result<()> {
+    let fmt_size = |size: u64| {
+        if raw_sizes {
+            format!("{} bytes", size)
+        } else {
+            humansize::format_size(size, humansize::DECIMAL)
+        }
+    };
+    let total_files: usize = sets.iter().map(|s| s.files.len()).sum();
+    let reclaimable: u64 = sets
+        .iter()
+        .map(|s| s.size * (s.files.len().saturating_sub(1) as u64))
+        .sum();
+
+    let mut html = String::new();
+    html.push_str(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>dedups duplicate report</title>
+<style>
+body { font-family: -apple-system, "Segoe UI", sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.5em; }
+.summary { background: #f3f5f7; border-radius: 6px; padding: 1em; margin-bottom: 1.5em; }
+.summary span { display: inline-block; margin-right: 2em; }
+details { border: 1px solid #ddd; border-radius: 6px; margin: 0.5em 0; padding: 0.5em 1em; }
+summary { cursor: pointer; font-weight: 600; }
+.hash { font-family: monospace; color: #777; font-weight: normal; }
+.approx { color: #b26a00; font-weight: normal; }
+table { border-collapse: collapse; width: 100%; margin-top: 0.5em; }
+td { padding: 0.2em 0.5em; border-top: 1px solid #eee; font-family: monospace; word-break: break-all; }
+td.size { text-align: right; white-space: nowrap; width: 10em; }
+</style>
+</head>
+<body>
+<h1>Duplicate file report</h1>
+"#,
+    );
+    html.push_str(&format!(
+        "<div class=\"summary\"><span><b>Duplicate sets:</b> {}</span><span><b>Files:</b> {}</span><span><b>Reclaimable space:</b> {}</span><span><b>Generated:</b> {}</span></div>\n",
+        sets.len(),
+        total_files,
+        html_escape(&fmt_size(reclaimable)),
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+    ));
+
+    for (index, set) in sets.iter().enumerate() {
+        let short_hash: String = set.hash.chars().take(12).collect();
+        html.push_str(&format!(
+            "<details><summary>Set {} &mdash; {} files, {} each <span class=\"hash\">{}</span>{}</summary>\n<table>\n",
+            index + 1,
+            set.files.len(),
+            html_escape(&fmt_size(set.size)),
+            html_escape(&short_hash),
+            if set.approximate {
+                " <span class=\"approx\">(approximate)</span>"
+            } else {
+                ""
+            }
         ));
-        for file_info in files_to_move {
-            let target_path = target_dir.join(
-                file_info
-                    .path
-                    .file_name()
-                    .unwrap_or_else(|| file_info.path.as_os_str()),
-            );
-            logs.push(format!(
-                "[DRY RUN]    - {} -> {}",
-                file_info.path.display(),
-                target_path.display()
+        for file in &set.files {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td class=\"size\">{}</td></tr>\n",
+                html_escape(&file.path.display().to_string()),
+                html_escape(&fmt_size(file.size))
             ));
-            log::info!("[DRY RUN]    - {:?} -> {:?}", file_info.path, target_path);
-            count += 1;
         }
-    } else {
-        logs.push(format!(
-            "Moving the following files to {}:",
-            target_dir.display()
-        ));
-        for file_info in files_to_move {
-            let file_name = file_info
-                .path
-                .file_name()
-                .unwrap_or_else(|| file_info.path.as_os_str());
-            let mut target_path = target_dir.join(file_name);
-
-            // Handle potential name collisions in the target directory
-            let mut counter = 1;
-            while target_path.exists() {
-                let stem = target_path
-                    .file_stem()
-                    .unwrap_or_default()
-                    .to_string_lossy();
-                let ext = target_path
-                    .extension()
-                    .unwrap_or_default()
-                    .to_string_lossy();
-                let new_name = format!(
-                    "{}_copy({}){}{}",
-                    stem.trim_end_matches(&format!("_copy({})", counter - 1))
-                        .trim_end_matches("_copy"),
-                    counter,
-                    if ext.is_empty() { "" } else { "." },
-                    ext
-                );
-                target_path = target_dir.join(new_name);
-                counter += 1;
-            }
+        html.push_str("</table></details>\n");
+    }
+    html.push_str("</body>\n</html>\n");
 
-            match fs::rename(&file_info.path, &target_path) {
-                // Using rename for move
-                Ok(_) => {
-                    logs.push(format!(
-                        "Moved: {} -> {}",
-                        file_info.path.display(),
-                        target_path.display()
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    fs::write(path, html)?;
+    log::info!("Wrote HTML report for {} sets to {:?}", sets.len(), path);
+    Ok(())
+}
+
+/// Flatten duplicate sets into CSV with one `set_hash,size,file_path` row per file.
+/// Sets are ordered by hash so the output is stable between runs.
+fn duplicates_to_csv(output_map: &HashMap<String, HashEntryContent>) -> Result<String> {
+    let mut hashes: Vec<&String> = output_map.keys().collect();
+    hashes.sort();
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["set_hash", "size", "file_path"])?;
+    for hash in hashes {
+        let entry = &output_map[hash];
+        let size = entry.size.to_string();
+        for file in &entry.files {
+            writer.write_record([
+                hash.as_str(),
+                size.as_str(),
+                file.to_string_lossy().as_ref(),
+            ])?;
+        }
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| anyhow::anyhow!("Failed to finish CSV output: {}", e))?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// fdupes/jdupes output: each set's paths on consecutive lines, every set followed by a
+/// blank line. Sets keep their scan order.
+fn duplicates_to_fdupes(duplicate_sets: &[DuplicateSet]) -> String {
+    let mut output = String::new();
+    for set in duplicate_sets.iter().filter(|set| set.files.len() >= 2) {
+        for file in &set.files {
+            output.push_str(&file.path.to_string_lossy());
+            output.push('\n');
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    ShortestPath,
+    LongestPath,
+    NewestModified,
+    OldestModified,
+    /// Keep the biggest file (only differs from others in media/approximate sets); newest wins ties
+    LargestSize,
+    /// Keep the smallest file; newest wins ties
+    SmallestSize,
+    /// Keep the file under this directory (newest wins if several or none are under it)
+    PreferDirectory(PathBuf),
+}
+
+impl FromStr for SelectionStrategy {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "shortest_path" => Ok(Self::ShortestPath),
+            "longest_path" => Ok(Self::LongestPath),
+            "newest_modified" => Ok(Self::NewestModified),
+            "oldest_modified" => Ok(Self::OldestModified),
+            "largest_size" | "largest" => Ok(Self::LargestSize),
+            "smallest_size" | "smallest" => Ok(Self::SmallestSize),
+            lower if lower.starts_with("prefer_directory:") => {
+                // Slice the original string so the path keeps its case
+                let dir = s["prefer_directory:".len()..].trim();
+                if dir.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "prefer_directory needs a path, e.g. prefer_directory:/photos/library"
                     ));
-                    log::info!("    Moved: {:?} -> {:?}", file_info.path, target_path);
-                    count += 1;
-                }
-                Err(e) => {
-                    let error_msg = format!("Error moving {}: {}", file_info.path.display(), e);
-                    logs.push(error_msg);
-                    log::error!(
-                        "Failed to move {:?} to {:?}: {}",
-                        file_info.path,
-                        target_path,
-                        e
-                    );
                 }
+                Ok(Self::PreferDirectory(PathBuf::from(dir)))
             }
+            _ => Err(anyhow::anyhow!("Invalid selection strategy: {}", s)),
         }
     }
-    Ok((count, logs))
 }
 
-// Helper function to sort a Vec<FileInfo>
-pub(crate) fn sort_file_infos(files: &mut [FileInfo], criterion: SortCriterion, order: SortOrder) {
-    files.sort_by(|a, b| {
-        let mut comparison = match criterion {
-            SortCriterion::FileName => a.path.file_name().cmp(&b.path.file_name()),
-            SortCriterion::FileSize => a.size.cmp(&b.size),
-            SortCriterion::CreatedAt => a.created_at.cmp(&b.created_at), // Assumes created_at is Option<SystemTime>
-            SortCriterion::ModifiedAt => a.modified_at.cmp(&b.modified_at), // Assumes modified_at is Option<SystemTime>
-            SortCriterion::PathLength => a.path.as_os_str().len().cmp(&b.path.as_os_str().len()),
-        };
-        if order == SortOrder::Descending {
-            comparison = comparison.reverse();
+/// How a tie left by the selection strategy is broken (`--tiebreak`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreaker {
+    /// Keep the file with the shortest full path
+    ShortestPath,
+    /// Keep the file whose name comes first alphabetically
+    Name,
+    /// Keep the most recently modified file
+    Newest,
+    /// Keep the least recently modified file
+    Oldest,
+}
+
+impl FromStr for TieBreaker {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "path" | "shortest_path" => Ok(Self::ShortestPath),
+            "name" => Ok(Self::Name),
+            "newest" | "newest_modified" => Ok(Self::Newest),
+            "oldest" | "oldest_modified" => Ok(Self::Oldest),
+            _ => Err(anyhow::anyhow!(
+                "Invalid tie-breaker: {} (expected path, name, newest or oldest)",
+                s
+            )),
         }
-        comparison
-    });
+    }
 }
 
-// Structure to represent file comparison results between directories
-pub struct DirectoryComparisonResult {
-    pub missing_in_target: Vec<FileInfo>, // Files in source but not in target
-    pub duplicates: Vec<DuplicateSet>,    // Duplicate files across directories
+impl std::fmt::Display for TieBreaker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ShortestPath => write!(f, "path"),
+            Self::Name => write!(f, "name"),
+            Self::Newest => write!(f, "newest"),
+            Self::Oldest => write!(f, "oldest"),
+        }
+    }
 }
 
-// Determine target directory from CLI arguments
-pub fn determine_target_directory(cli: &Cli) -> Result<PathBuf> {
-    if let Some(target) = &cli.target {
-        // User explicitly specified a target directory
-        if !target.exists() {
-            return Err(anyhow::anyhow!(
-                "Specified target directory does not exist: {:?}",
-                target
-            ));
+/// Parse one `--tiebreak` entry for clap
+pub fn parse_tiebreaker(s: &str) -> Result<TieBreaker, String> {
+    TieBreaker::from_str(s).map_err(|e| e.to_string())
+}
+
+/// Modification time read from disk, or `fallback` when it can't be read
+fn modified_or(file: &FileInfo, fallback: SystemTime) -> SystemTime {
+    fs::metadata(&file.path)
+        .and_then(|m| m.modified())
+        .unwrap_or(fallback)
+}
+
+/// The files that share the greatest `key`. `files` must not be empty.
+fn best_by_key<K: Ord>(files: Vec<FileInfo>, key: impl Fn(&FileInfo) -> K) -> Vec<FileInfo> {
+    let mut keyed: Vec<(K, FileInfo)> = files.into_iter().map(|f| (key(&f), f)).collect();
+    keyed.sort_by(|a, b| b.0.cmp(&a.0));
+    let mut keyed = keyed.into_iter();
+    let (best, first) = keyed.next().unwrap(); // Safe: not empty
+    std::iter::once(first)
+        .chain(keyed.take_while(|(k, _)| *k == best).map(|(_, f)| f))
+        .collect()
+}
+
+/// The most recently modified files (files without a readable mtime sort last).
+/// `files` must not be empty.
+fn newest_modified(files: Vec<FileInfo>) -> Vec<FileInfo> {
+    best_by_key(files, |f| modified_or(f, SystemTime::UNIX_EPOCH))
+}
+
+/// Narrow `candidates` with each tie-breaker in turn. Whatever is still tied after that
+/// goes to the lexicographically first path, so the pick never depends on the order the
+/// scan happened to find the files in. `candidates` must not be empty.
+fn break_tie(mut candidates: Vec<FileInfo>, tiebreak: &[TieBreaker]) -> FileInfo {
+    for breaker in tiebreak {
+        if candidates.len() == 1 {
+            break;
         }
-        if !target.is_dir() {
-            return Err(anyhow::anyhow!(
-                "Specified target path is not a directory: {:?}",
-                target
-            ));
+        candidates = match breaker {
+            TieBreaker::ShortestPath => {
+                best_by_key(candidates, |f| std::cmp::Reverse(f.path.as_os_str().len()))
+            }
+            TieBreaker::Name => best_by_key(candidates, |f| {
+                std::cmp::Reverse(f.path.file_name().map(|n| n.to_os_string()))
+            }),
+            TieBreaker::Newest => newest_modified(candidates),
+            TieBreaker::Oldest => best_by_key(candidates, |f| {
+                std::cmp::Reverse(modified_or(f, SystemTime::now()))
+            }),
+        };
+    }
+    candidates
+        .into_iter()
+        .min_by(|a, b| a.path.cmp(&b.path))
+        .unwrap() // Safe: not empty
+}
+
+// Given a set of duplicate files, determines which one to keep and which ones are to be processed (deleted/moved).
+// Returns a tuple: (file_to_keep, files_to_process)
+pub fn determine_action_targets(
+    set: &DuplicateSet,
+    strategy: SelectionStrategy,
+) -> Result<(FileInfo, Vec<FileInfo>)> {
+    determine_action_targets_with_tiebreak(set, strategy, &[])
+}
+
+/// `determine_action_targets` with `tiebreak` deciding between files the strategy rates
+/// equally (e.g. two copies with the same mtime under newest_modified)
+pub fn determine_action_targets_with_tiebreak(
+    set: &DuplicateSet,
+    strategy: SelectionStrategy,
+    tiebreak: &[TieBreaker],
+) -> Result<(FileInfo, Vec<FileInfo>)> {
+    if set.files.len() < 2 {
+        // Not a duplicate set for action, or only one file left.
+        return Err(anyhow::anyhow!(
+            "Cannot determine action targets for a set with less than 2 files."
+        ));
+    }
+
+    let files = set.files.clone(); // Clone to allow modification/sorting if needed
+
+    let candidates = match strategy.clone() {
+        SelectionStrategy::ShortestPath => {
+            best_by_key(files, |f| std::cmp::Reverse(f.path.as_os_str().len()))
         }
-        Ok(target.clone())
-    } else if cli.directories.len() > 1 {
-        // Use the last directory as target by default
-        let target = cli.directories.last().unwrap().clone();
-        if !target.exists() {
-            return Err(anyhow::anyhow!(
-                "Last specified directory (default target) does not exist: {:?}",
-                target
-            ));
+        SelectionStrategy::LongestPath => best_by_key(files, |f| f.path.as_os_str().len()),
+        SelectionStrategy::NewestModified => newest_modified(files),
+        SelectionStrategy::OldestModified => best_by_key(files, |f| {
+            std::cmp::Reverse(modified_or(f, SystemTime::now()))
+        }),
+        SelectionStrategy::LargestSize | SelectionStrategy::SmallestSize => {
+            let sizes = files.iter().map(|f| f.size);
+            let target_size = if strategy == SelectionStrategy::LargestSize {
+                sizes.max()
+            } else {
+                sizes.min()
+            }
+            .unwrap(); // Safe because len >= 2
+
+            // Byte-identical duplicates all share one size, so ties fall back to newest
+            newest_modified(
+                files
+                    .into_iter()
+                    .filter(|f| f.size == target_size)
+                    .collect(),
+            )
         }
-        if !target.is_dir() {
-            return Err(anyhow::anyhow!(
-                "Last specified path is not a directory: {:?}",
-                target
-            ));
+        SelectionStrategy::PreferDirectory(dir) => {
+            let preferred_dir = fs::canonicalize(&dir).unwrap_or(dir);
+            let (preferred, others): (Vec<FileInfo>, Vec<FileInfo>) =
+                files.into_iter().partition(|f| {
+                    fs::canonicalize(&f.path)
+                        .unwrap_or_else(|_| f.path.clone())
+                        .starts_with(&preferred_dir)
+                });
+            if preferred.is_empty() {
+                newest_modified(others)
+            } else {
+                newest_modified(preferred)
+            }
+        }
+    };
+    let kept_file_info = break_tie(candidates, tiebreak);
+
+    let mut files_to_process: Vec<FileInfo> = Vec::new();
+    for file_info in &set.files {
+        if file_info.path != kept_file_info.path {
+            files_to_process.push(file_info.clone());
         }
-        Ok(target)
-    } else {
-        Err(anyhow::anyhow!(
-            "No target directory specified and only one directory provided"
-        ))
     }
+
+    Ok((kept_file_info, files_to_process))
 }
 
-// Get source directories from CLI arguments
-pub fn get_source_directories(cli: &Cli, target: &Path) -> Vec<PathBuf> {
-    if let Some(t) = &cli.target {
-        // If target is explicitly specified, all directories are sources
-        cli.directories
-            .iter()
-            .filter(|d| d != &t)
-            .cloned()
-            .collect()
-    } else {
-        // Otherwise, all but the last directory are sources
-        cli.directories
-            .iter()
-            .filter(|d| d.as_path() != target)
-            .cloned()
-            .collect()
-    }
+/// Directories from `--protect` and the config's `protected_paths`. Files under them are
+/// never deleted, moved or replaced by a link.
+#[derive(Debug, Clone, Default)]
+pub struct ProtectedPaths {
+    prefixes: Vec<PathBuf>,
+}
+
+impl ProtectedPaths {
+    pub fn new(paths: &[PathBuf]) -> Self {
+        Self {
+            prefixes: paths
+                .iter()
+                .map(|p| fs::canonicalize(p).unwrap_or_else(|_| p.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn from_cli(cli: &Cli) -> Self {
+        Self::new(&cli.protect)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        if self.prefixes.is_empty() {
+            return false;
+        }
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.prefixes.iter().any(|prefix| path.starts_with(prefix))
+    }
+
+    /// Whether no file in `files` may be actioned
+    pub fn protects_all(&self, files: &[FileInfo]) -> bool {
+        !self.prefixes.is_empty() && files.iter().all(|f| self.contains(&f.path))
+    }
+}
+
+/// `determine_action_targets` that never actions a protected file. When a set has
+/// protected copies, the kept file is chosen among them (by `strategy`) and only the
+/// unprotected files are returned for action. Errors if every file is protected.
+pub fn determine_protected_action_targets(
+    set: &DuplicateSet,
+    strategy: SelectionStrategy,
+    tiebreak: &[TieBreaker],
+    protected: &ProtectedPaths,
+) -> Result<(FileInfo, Vec<FileInfo>)> {
+    if protected.is_empty() {
+        return determine_action_targets_with_tiebreak(set, strategy, tiebreak);
+    }
+    let (protected_files, unprotected): (Vec<FileInfo>, Vec<FileInfo>) = set
+        .files
+        .iter()
+        .cloned()
+        .partition(|f| protected.contains(&f.path));
+
+    if unprotected.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Every file in duplicate set (hash: {}...) is protected",
+            set.hash.chars().take(8).collect::<String>()
+        ));
+    }
+    let kept = match protected_files.len() {
+        0 => return determine_action_targets_with_tiebreak(set, strategy, tiebreak),
+        1 => protected_files[0].clone(),
+        _ => {
+            let protected_set = DuplicateSet {
+                files: protected_files,
+                ..set.clone()
+            };
+            determine_action_targets_with_tiebreak(&protected_set, strategy, tiebreak)?.0
+        }
+    };
+    Ok((kept, unprotected))
+}
+
+/// Refuse to delete from a set whose match was only approximate (hashed with `--hash-limit`)
+/// unless the user explicitly opted in with `--allow-approximate-delete`.
+pub fn check_approximate_delete_allowed(
+    set: &DuplicateSet,
+    allow_approximate_delete: bool,
+) -> Result<()> {
+    if set.approximate && !allow_approximate_delete {
+        return Err(anyhow::anyhow!(
+            "Refusing to remove or replace files in approximate duplicate set (hash: {}...): \
+             files were only partially hashed. Use --allow-approximate-delete to override.",
+            set.hash.chars().take(8).collect::<String>()
+        ));
+    }
+    Ok(())
+}
+
+/// Hash used to group files under `--algorithm auto`
+pub const AUTO_HASH_ALGORITHM: &str = "xxhash";
+
+/// The algorithm a scan actually hashes with: `--algorithm`, with `auto` resolved
+pub fn resolved_algorithm(cli: &Cli) -> &str {
+    if cli.algorithm == "auto" {
+        AUTO_HASH_ALGORITHM
+    } else {
+        &cli.algorithm
+    }
+}
+
+/// Resolve `--algorithm auto` to the hash it scans with. The caller's `Cli` keeps
+/// `auto`, so `verify_before_action` still holds for the sets that come back.
+fn with_resolved_algorithm(cli: &Cli) -> std::borrow::Cow<'_, Cli> {
+    let algorithm = resolved_algorithm(cli);
+    if algorithm == cli.algorithm {
+        std::borrow::Cow::Borrowed(cli)
+    } else {
+        std::borrow::Cow::Owned(Cli {
+            algorithm: algorithm.to_string(),
+            verify: true,
+            ..cli.clone()
+        })
+    }
+}
+
+/// Hash algorithms whose matches are trusted without a byte-for-byte check
+pub fn is_cryptographic_hash(algorithm: &str) -> bool {
+    matches!(algorithm, "md5" | "sha1" | "sha256" | "blake3")
+}
+
+/// Stream both files in chunks and report whether their contents are identical
+pub fn files_are_identical(a: &Path, b: &Path) -> Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut reader_a = BufReader::with_capacity(VERIFY_CHUNK_SIZE, File::open(a)?);
+    let mut reader_b = BufReader::with_capacity(VERIFY_CHUNK_SIZE, File::open(b)?);
+    loop {
+        let chunk_a = reader_a.fill_buf()?;
+        let chunk_b = reader_b.fill_buf()?;
+        if chunk_a.is_empty() || chunk_b.is_empty() {
+            return Ok(chunk_a.is_empty() && chunk_b.is_empty());
+        }
+        let n = chunk_a.len().min(chunk_b.len());
+        if chunk_a[..n] != chunk_b[..n] {
+            return Ok(false);
+        }
+        reader_a.consume(n);
+        reader_b.consume(n);
+    }
+}
+
+/// Split a set into groups of byte-identical files, comparing each file against the
+/// first file of every group so far. Groups with a single file are dropped, so an
+/// empty result means nothing in the set was really a duplicate.
+pub fn verify_duplicate_set(set: &DuplicateSet) -> Result<Vec<DuplicateSet>> {
+    let mut groups: Vec<Vec<FileInfo>> = Vec::new();
+    for file in &set.files {
+        let mut placed = false;
+        for group in groups.iter_mut() {
+            if files_are_identical(&group[0].path, &file.path)? {
+                group.push(file.clone());
+                placed = true;
+                break;
+            }
+        }
+        if !placed {
+            groups.push(vec![file.clone()]);
+        }
+    }
+
+    if groups.len() == 1 {
+        return Ok(vec![set.clone()]);
+    }
+    Ok(groups
+        .into_iter()
+        .filter(|files| files.len() >= 2)
+        .map(|files| DuplicateSet {
+            files,
+            hardlinks: Vec::new(),
+            ..set.clone()
+        })
+        .collect())
+}
+
+/// Media similarity and `--ignore-metadata` sets group files that are alike rather than
+/// byte-identical, so they are never byte-verified
+pub(crate) fn is_similarity_set(set: &DuplicateSet) -> bool {
+    set.hash.starts_with(crate::media_dedup::MEDIA_HASH_PREFIX)
+        || set
+            .hash
+            .starts_with(crate::content_hash::CONTENT_HASH_PREFIX)
+}
+
+/// Byte-for-byte verify every set before it's acted on (`--verify`). Sets whose
+/// files turn out to differ are split; media similarity and `--ignore-metadata` sets
+/// are passed through because their files are similar, not identical.
+pub fn verify_duplicate_sets(sets: &[DuplicateSet]) -> (Vec<DuplicateSet>, Vec<String>) {
+    let mut verified = Vec::new();
+    let mut logs = Vec::new();
+    for set in sets {
+        if is_similarity_set(set) {
+            verified.push(set.clone());
+            continue;
+        }
+        match verify_duplicate_set(set) {
+            Ok(split) => {
+                if split.len() != 1 || split[0].files.len() != set.files.len() {
+                    let msg = format!(
+                        "Hash collision in set (hash: {}...): contents differ, split into {} verified set(s)",
+                        set.hash.chars().take(8).collect::<String>(),
+                        split.len()
+                    );
+                    log::warn!("{}", msg);
+                    logs.push(msg);
+                }
+                verified.extend(split);
+            }
+            Err(e) => {
+                let msg = format!(
+                    "Skipping set (hash: {}...): verification failed: {}",
+                    set.hash.chars().take(8).collect::<String>(),
+                    e
+                );
+                log::warn!("{}", msg);
+                logs.push(msg);
+            }
+        }
+    }
+    (verified, logs)
+}
+
+/// Retries after a failed delete/move/copy when --retries isn't given
+pub const DEFAULT_FS_RETRIES: usize = 2;
+
+/// First wait between attempts; doubled after each further failure
+const RETRY_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Errors that can clear up on their own, typically a busy network mount. Anything
+/// else (missing file, permissions, full disk) fails the same way every time.
+fn is_transient_io_error(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::ResourceBusy
+            | std::io::ErrorKind::WouldBlock
+            | std::io::ErrorKind::Interrupted
+            | std::io::ErrorKind::TimedOut
+    )
+}
+
+/// Run `op` up to `attempts` times, sleeping `base_delay`, then twice that, and so on
+/// between tries. Only transient errors are retried; the last error is returned.
+pub fn with_retry<T, F>(
+    mut op: F,
+    attempts: usize,
+    base_delay: std::time::Duration,
+) -> std::io::Result<T>
+where
+    F: FnMut() -> std::io::Result<T>,
+{
+    let mut delay = base_delay;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < attempts && is_transient_io_error(&e) => {
+                log::warn!(
+                    "Attempt {}/{} failed ({}); retrying in {:?}",
+                    attempt,
+                    attempts,
+                    e,
+                    delay
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// `with_retry` for a file operation: the first try plus `retries` (--retries) more
+fn with_fs_retry<T>(retries: usize, op: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+    with_retry(op, retries + 1, RETRY_BASE_DELAY)
+}
+
+/// Delete the given files, returning the ones that were deleted along with the log. When
+/// `use_trash` is set, files are moved to the platform's recycle bin via the `trash` crate
+/// instead of being removed permanently. A transient error is retried up to `retries` times.
+pub fn delete_files(
+    files_to_delete: &[FileInfo],
+    dry_run: bool,
+    use_trash: bool,
+    retries: usize,
+) -> Result<(Vec<FileInfo>, Vec<String>)> {
+    let mut actioned = Vec::new();
+    let mut logs = Vec::new();
+    if dry_run {
+        if use_trash {
+            logs.push("[DRY RUN] Would move to trash the following files:".to_string());
+        } else {
+            logs.push("[DRY RUN] Would delete the following files:".to_string());
+        }
+        for file_info in files_to_delete {
+            logs.push(format!("[DRY RUN]    - {}", file_info.path.display()));
+            actioned.push(file_info.clone());
+        }
+    } else if use_trash {
+        logs.push("Moving the following files to trash:".to_string());
+        for file_info in files_to_delete {
+            match trash::delete(&file_info.path) {
+                Ok(_) => {
+                    journal::record_action(JournalAction::Trash, &file_info.path, None);
+                    logs.push(format!("Trashed: {}", file_info.path.display()));
+                    actioned.push(file_info.clone());
+                }
+                Err(e) => {
+                    logs.push(format!(
+                        "Error moving {} to trash: {}",
+                        file_info.path.display(),
+                        e
+                    ));
+                }
+            }
+        }
+    } else {
+        logs.push("Deleting the following files:".to_string());
+        for file_info in files_to_delete {
+            match with_fs_retry(retries, || fs::remove_file(&file_info.path)) {
+                Ok(_) => {
+                    journal::record_action(JournalAction::Delete, &file_info.path, None);
+                    logs.push(format!("Deleted: {}", file_info.path.display()));
+                    actioned.push(file_info.clone());
+                }
+                Err(e) => {
+                    logs.push(format!(
+                        "Error deleting {}: {}",
+                        file_info.path.display(),
+                        e
+                    ));
+                }
+            }
+        }
+    }
+    Ok((actioned, logs))
+}
+
+pub fn move_files(
+    files_to_move: &[FileInfo],
+    target_dir: &Path,
+    dry_run: bool,
+    on_conflict: ConflictPolicy,
+    retries: usize,
+) -> Result<(Vec<FileInfo>, Vec<String>)> {
+    move_files_to(
+        files_to_move,
+        target_dir,
+        dry_run,
+        on_conflict,
+        retries,
+        |path| target_dir.join(path.file_name().unwrap_or(path.as_os_str())),
+    )
+}
+
+/// Like `move_files`, but each file keeps its path relative to the scan root it is under,
+/// so `<root>/a/b/c.jpg` moves to `<target_dir>/a/b/c.jpg` (`--preserve-structure`).
+/// With nested roots the innermost one applies.
+pub fn move_files_preserving_structure(
+    files_to_move: &[FileInfo],
+    target_dir: &Path,
+    source_roots: &[PathBuf],
+    dry_run: bool,
+    on_conflict: ConflictPolicy,
+    retries: usize,
+) -> Result<(Vec<FileInfo>, Vec<String>)> {
+    move_files_to(
+        files_to_move,
+        target_dir,
+        dry_run,
+        on_conflict,
+        retries,
+        |path| {
+            let root = source_roots
+                .iter()
+                .filter(|root| path.starts_with(root))
+                .max_by_key(|root| root.components().count())
+                .map_or(Path::new(""), |root| root.as_path());
+            target_dir.join(path_below_root(path, root))
+        },
+    )
+}
+
+/// Move each file to `destination(path)`, creating directories below `target_dir` as needed,
+/// and return the files that were moved.
+/// A destination that already exists is handled according to `on_conflict`, and a
+/// transient rename error is retried up to `retries` times.
+fn move_files_to(
+    files_to_move: &[FileInfo],
+    target_dir: &Path,
+    dry_run: bool,
+    on_conflict: ConflictPolicy,
+    retries: usize,
+    destination: impl Fn(&Path) -> PathBuf,
+) -> Result<(Vec<FileInfo>, Vec<String>)> {
+    let mut actioned = Vec::new();
+    let mut logs = Vec::new();
+
+    if !target_dir.exists() {
+        if dry_run {
+            logs.push(format!(
+                "[DRY RUN] Target directory {} does not exist. Would create it.",
+                target_dir.display()
+            ));
+            log::info!(
+                "[DRY RUN] Target directory {:?} does not exist. Would attempt to create it.",
+                target_dir
+            );
+        } else {
+            log::info!(
+                "Target directory {:?} does not exist. Creating it.",
+                target_dir
+            );
+            fs::create_dir_all(target_dir)?;
+            logs.push(format!(
+                "Created target directory: {}",
+                target_dir.display()
+            ));
+        }
+    } else if !target_dir.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Target move path {:?} exists but is not a directory.",
+            target_dir
+        ));
+    }
+
+    if dry_run {
+        logs.push(format!(
+            "[DRY RUN] Would move the following files to {}:",
+            target_dir.display()
+        ));
+        for file_info in files_to_move {
+            let Some(target_path) = resolve_conflict(destination(&file_info.path), on_conflict)
+            else {
+                logs.push(format!(
+                    "[DRY RUN]    - {} (skipped, destination exists)",
+                    file_info.path.display()
+                ));
+                continue;
+            };
+            logs.push(format!(
+                "[DRY RUN]    - {} -> {}",
+                file_info.path.display(),
+                target_path.display()
+            ));
+            log::info!("[DRY RUN]    - {:?} -> {:?}", file_info.path, target_path);
+            actioned.push(file_info.clone());
+        }
+    } else {
+        logs.push(format!(
+            "Moving the following files to {}:",
+            target_dir.display()
+        ));
+        for file_info in files_to_move {
+            let target_path = destination(&file_info.path);
+            if let Some(parent) = target_path.parent() {
+                if let Err(e) = fs::create_dir_all(parent) {
+                    logs.push(format!(
+                        "Error creating directory {}: {}",
+                        parent.display(),
+                        e
+                    ));
+                    continue;
+                }
+            }
+            let Some(target_path) = resolve_conflict(target_path, on_conflict) else {
+                let msg = format!(
+                    "Skipped: {} (destination {} exists)",
+                    file_info.path.display(),
+                    destination(&file_info.path).display()
+                );
+                log::info!("{}", msg);
+                logs.push(msg);
+                continue;
+            };
+
+            match with_fs_retry(retries, || fs::rename(&file_info.path, &target_path)) {
+                // Using rename for move
+                Ok(_) => {
+                    journal::record_action(
+                        JournalAction::Move,
+                        &file_info.path,
+                        Some(&target_path),
+                    );
+                    logs.push(format!(
+                        "Moved: {} -> {}",
+                        file_info.path.display(),
+                        target_path.display()
+                    ));
+                    log::info!("    Moved: {:?} -> {:?}", file_info.path, target_path);
+                    actioned.push(file_info.clone());
+                }
+                Err(e) => {
+                    let error_msg = format!("Error moving {}: {}", file_info.path.display(), e);
+                    logs.push(error_msg);
+                    log::error!(
+                        "Failed to move {:?} to {:?}: {}",
+                        file_info.path,
+                        target_path,
+                        e
+                    );
+                }
+            }
+        }
+    }
+    Ok((actioned, logs))
+}
+
+/// Every symlink under `roots`, hidden or not. Links are not followed.
+pub fn collect_symlinks(roots: &[PathBuf]) -> Vec<PathBuf> {
+    roots
+        .iter()
+        .flat_map(|root| WalkDir::new(root).follow_links(false))
+        .flatten()
+        .filter(|entry| entry.path_is_symlink())
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Before `deleted` is removed, point every symlink under `roots` that resolves to it at
+/// `kept` instead, so the links don't dangle (--fix-symlinks)
+pub fn repoint_symlinks(
+    deleted: &Path,
+    kept: &Path,
+    roots: &[PathBuf],
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    repoint_symlinks_in(&collect_symlinks(roots), deleted, kept, dry_run)
+}
+
+/// `repoint_symlinks` over an already collected list of links, so a batch of deletions
+/// only walks the roots once
+pub fn repoint_symlinks_in(
+    links: &[PathBuf],
+    deleted: &Path,
+    kept: &Path,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let deleted = fs::canonicalize(deleted)?;
+    let kept = fs::canonicalize(kept)?;
+    let mut logs = Vec::new();
+    for link in links {
+        // Resolves chains of links too; dangling links simply don't match
+        if fs::canonicalize(link).ok().as_deref() != Some(deleted.as_path()) {
+            continue;
+        }
+        if dry_run {
+            logs.push(format!(
+                "[DRY RUN] Would repoint symlink {} -> {}",
+                link.display(),
+                kept.display()
+            ));
+            continue;
+        }
+        match fs::remove_file(link).and_then(|_| make_symlink(&kept, link)) {
+            Ok(_) => logs.push(format!(
+                "Repointed symlink {} -> {}",
+                link.display(),
+                kept.display()
+            )),
+            Err(e) => {
+                log::error!("Failed to repoint symlink {:?}: {}", link, e);
+                logs.push(format!(
+                    "Error repointing symlink {}: {}",
+                    link.display(),
+                    e
+                ));
+            }
+        }
+    }
+    Ok(logs)
+}
+
+#[cfg(unix)]
+fn make_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn make_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Return `target_path`, or a `name_copy(N).ext` sibling of it if that is already taken
+fn non_colliding_path(mut target_path: PathBuf) -> PathBuf {
+    let target_dir = target_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let mut counter = 1;
+    while target_path.exists() {
+        let stem = target_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let ext = target_path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let new_name = format!(
+            "{}_copy({}){}{}",
+            stem.trim_end_matches(&format!("_copy({})", counter - 1))
+                .trim_end_matches("_copy"),
+            counter,
+            if ext.is_empty() { "" } else { "." },
+            ext
+        );
+        target_path = target_dir.join(new_name);
+        counter += 1;
+    }
+    target_path
+}
+
+/// Where a move or copy to `target_path` goes under `policy`: the path itself if it is
+/// free or may be overwritten, a `_copy(N)` sibling for `Rename`, or `None` to skip it
+pub fn resolve_conflict(target_path: PathBuf, policy: ConflictPolicy) -> Option<PathBuf> {
+    if fs::symlink_metadata(&target_path).is_err() {
+        return Some(target_path);
+    }
+    match policy {
+        ConflictPolicy::Skip => None,
+        ConflictPolicy::Overwrite => Some(target_path),
+        ConflictPolicy::Rename => Some(non_colliding_path(target_path)),
+    }
+}
+
+/// `path` relative to `source_root`. Files outside `source_root` keep their whole path,
+/// minus the root/drive prefix, so the result can always be joined below a target.
+fn path_below_root(path: &Path, source_root: &Path) -> PathBuf {
+    path.strip_prefix(source_root)
+        .unwrap_or(path)
+        .components()
+        .filter(|c| matches!(c, std::path::Component::Normal(_)))
+        .collect()
+}
+
+/// Where a quarantined file goes: `base_dir/<date>/<path relative to source_root>`.
+/// Files outside `source_root` keep their whole path, minus the root/drive prefix.
+pub fn quarantine_destination(
+    path: &Path,
+    base_dir: &Path,
+    source_root: &Path,
+    date: &str,
+) -> PathBuf {
+    base_dir.join(date).join(path_below_root(path, source_root))
+}
+
+/// Move files into a dated review folder, `base_dir/<YYYY-MM-DD>/`, keeping each file's
+/// directory structure relative to `source_root`. Name collisions are handled like
+/// `move_files`, and moves are recorded in the undo journal.
+pub fn quarantine_files(
+    files: &[FileInfo],
+    base_dir: &Path,
+    source_root: &Path,
+    dry_run: bool,
+) -> Result<(Vec<FileInfo>, Vec<String>)> {
+    if base_dir.exists() && !base_dir.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Quarantine path {:?} exists but is not a directory.",
+            base_dir
+        ));
+    }
+
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let mut actioned = Vec::new();
+    let mut logs = Vec::new();
+    for file_info in files {
+        let destination = quarantine_destination(&file_info.path, base_dir, source_root, &date);
+        if dry_run {
+            logs.push(format!(
+                "[DRY RUN] Would quarantine: {} -> {}",
+                file_info.path.display(),
+                destination.display()
+            ));
+            actioned.push(file_info.clone());
+            continue;
+        }
+
+        if let Some(parent) = destination.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                logs.push(format!(
+                    "Error creating quarantine directory {}: {}",
+                    parent.display(),
+                    e
+                ));
+                continue;
+            }
+        }
+        let destination = non_colliding_path(destination);
+        match fs::rename(&file_info.path, &destination) {
+            Ok(_) => {
+                journal::record_action(JournalAction::Move, &file_info.path, Some(&destination));
+                logs.push(format!(
+                    "Quarantined: {} -> {}",
+                    file_info.path.display(),
+                    destination.display()
+                ));
+                actioned.push(file_info.clone());
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to quarantine {:?} to {:?}: {}",
+                    file_info.path,
+                    destination,
+                    e
+                );
+                logs.push(format!(
+                    "Error quarantining {}: {}",
+                    file_info.path.display(),
+                    e
+                ));
+            }
+        }
+    }
+    Ok((actioned, logs))
+}
+
+/// Clone `src` into a new file at `dest` sharing the same extents (copy-on-write).
+#[cfg(target_os = "linux")]
+fn reflink_file(src: &Path, dest: &Path) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let src_file = File::open(src)?;
+    let dest_file = File::create(dest)?;
+    // SAFETY: both descriptors are valid open files for the duration of the call.
+    let ret = unsafe { libc::ioctl(dest_file.as_raw_fd(), libc::FICLONE, src_file.as_raw_fd()) };
+    if ret == -1 {
+        let err = std::io::Error::last_os_error();
+        drop(dest_file);
+        let _ = fs::remove_file(dest);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink_file(_src: &Path, _dest: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "reflinks are only supported on Linux",
+    ))
+}
+
+/// Turn a low-level reflink error into a message that explains the usual cause.
+fn describe_reflink_error(e: &std::io::Error) -> String {
+    #[cfg(target_os = "linux")]
+    {
+        match e.raw_os_error() {
+            Some(libc::EOPNOTSUPP) | Some(libc::EINVAL) | Some(libc::ENOTTY) => {
+                return format!("filesystem does not support reflinks ({})", e)
+            }
+            Some(libc::EXDEV) => return format!("files are on different filesystems ({})", e),
+            _ => {}
+        }
+    }
+    e.to_string()
+}
+
+/// Replace each redundant file with a reflink (copy-on-write clone) of `kept`, so both
+/// paths remain but share storage. Files are cloned to a temporary sibling and renamed
+/// over the original, so a failure never leaves a redundant file half-written.
+/// Files on filesystems without reflink support are logged and skipped.
+pub fn reflink_files(
+    kept: &FileInfo,
+    redundants: &[FileInfo],
+    dry_run: bool,
+) -> Result<(Vec<FileInfo>, Vec<String>)> {
+    let mut actioned = Vec::new();
+    let mut logs = Vec::new();
+
+    if dry_run {
+        logs.push(format!(
+            "[DRY RUN] Would reflink the following files to {}:",
+            kept.path.display()
+        ));
+        for file_info in redundants {
+            logs.push(format!("[DRY RUN]    - {}", file_info.path.display()));
+            log::info!("[DRY RUN]    - {:?}", file_info.path);
+            actioned.push(file_info.clone());
+        }
+        return Ok((actioned, logs));
+    }
+
+    logs.push(format!(
+        "Reflinking the following files to {}:",
+        kept.path.display()
+    ));
+    for file_info in redundants {
+        if file_info.path == kept.path {
+            continue;
+        }
+        let file_name = file_info
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let temp_path = file_info
+            .path
+            .with_file_name(format!(".{}.dedups-reflink.tmp", file_name));
+
+        let result = reflink_file(&kept.path, &temp_path).and_then(|_| {
+            if let Ok(metadata) = fs::metadata(&file_info.path) {
+                fs::set_permissions(&temp_path, metadata.permissions())?;
+            }
+            fs::rename(&temp_path, &file_info.path).inspect_err(|_| {
+                let _ = fs::remove_file(&temp_path);
+            })
+        });
+
+        match result {
+            Ok(_) => {
+                logs.push(format!("Reflinked: {}", file_info.path.display()));
+                log::info!("    Reflinked: {:?} -> {:?}", file_info.path, kept.path);
+                actioned.push(file_info.clone());
+            }
+            Err(e) => {
+                let reason = describe_reflink_error(&e);
+                logs.push(format!(
+                    "Error reflinking {}: {}",
+                    file_info.path.display(),
+                    reason
+                ));
+                log::error!("Failed to reflink {:?}: {}", file_info.path, reason);
+            }
+        }
+    }
+    Ok((actioned, logs))
+}
+
+/// Replace each redundant file with a hardlink to `kept`. The link is created at a temporary
+/// sibling path and renamed over the redundant file, so the original is only replaced once
+/// the link exists. Files on a different device than `kept` (`EXDEV`) are reported and skipped.
+pub fn hardlink_files(
+    kept: &FileInfo,
+    redundants: &[FileInfo],
+    dry_run: bool,
+) -> Result<(Vec<FileInfo>, Vec<String>)> {
+    let mut actioned = Vec::new();
+    let mut logs = Vec::new();
+
+    if dry_run {
+        logs.push(format!(
+            "[DRY RUN] Would hardlink the following files to {}:",
+            kept.path.display()
+        ));
+        for file_info in redundants {
+            logs.push(format!("[DRY RUN]    - {}", file_info.path.display()));
+            log::info!("[DRY RUN]    - {:?}", file_info.path);
+            actioned.push(file_info.clone());
+        }
+        return Ok((actioned, logs));
+    }
+
+    logs.push(format!(
+        "Hardlinking the following files to {}:",
+        kept.path.display()
+    ));
+    for file_info in redundants {
+        if file_info.path == kept.path {
+            continue;
+        }
+        let file_name = file_info
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let temp_path = file_info
+            .path
+            .with_file_name(format!(".{}.dedups-hardlink.tmp", file_name));
+
+        let result = fs::hard_link(&kept.path, &temp_path).and_then(|_| {
+            fs::rename(&temp_path, &file_info.path).inspect_err(|_| {
+                let _ = fs::remove_file(&temp_path);
+            })
+        });
+
+        match result {
+            Ok(_) => {
+                logs.push(format!("Hardlinked: {}", file_info.path.display()));
+                log::info!("    Hardlinked: {:?} -> {:?}", file_info.path, kept.path);
+                actioned.push(file_info.clone());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                logs.push(format!(
+                    "Skipped {}: on a different device than {}",
+                    file_info.path.display(),
+                    kept.path.display()
+                ));
+                log::warn!(
+                    "Cannot hardlink {:?} to {:?} across devices, skipping.",
+                    file_info.path,
+                    kept.path
+                );
+            }
+            Err(e) => {
+                logs.push(format!(
+                    "Error hardlinking {}: {}",
+                    file_info.path.display(),
+                    e
+                ));
+                log::error!("Failed to hardlink {:?}: {}", file_info.path, e);
+            }
+        }
+    }
+    Ok((actioned, logs))
+}
+
+/// Replace each redundant file with a symlink to `kept`, so the old paths keep working
+/// while only one copy takes up space. Links point at `kept`'s canonical path, or at a
+/// path relative to the link's directory when `relative` is set (--relative-symlinks),
+/// which survives moving the whole tree. As with hardlinks, the link is created at a
+/// temporary sibling and renamed over the redundant file.
+pub fn symlink_to_kept(
+    kept: &FileInfo,
+    redundants: &[FileInfo],
+    relative: bool,
+    dry_run: bool,
+) -> Result<(Vec<FileInfo>, Vec<String>)> {
+    let mut actioned = Vec::new();
+    let mut logs = Vec::new();
+
+    if dry_run {
+        logs.push(format!(
+            "[DRY RUN] Would replace the following files with symlinks to {}:",
+            kept.path.display()
+        ));
+        for file_info in redundants {
+            logs.push(format!("[DRY RUN]    - {}", file_info.path.display()));
+            log::info!("[DRY RUN]    - {:?}", file_info.path);
+            actioned.push(file_info.clone());
+        }
+        return Ok((actioned, logs));
+    }
+
+    // A link target is resolved against the link's directory, so a relative scan path
+    // can't be used as-is
+    let kept_path = fs::canonicalize(&kept.path)
+        .map_err(|e| anyhow::anyhow!("Failed to resolve kept file {:?}: {}", kept.path, e))?;
+    logs.push(format!(
+        "Replacing the following files with symlinks to {}:",
+        kept.path.display()
+    ));
+    for file_info in redundants {
+        if file_info.path == kept.path {
+            continue;
+        }
+        let file_name = file_info
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let temp_path = file_info
+            .path
+            .with_file_name(format!(".{}.dedups-symlink.tmp", file_name));
+
+        let result = symlink_target(&kept_path, &file_info.path, relative).and_then(|target| {
+            make_symlink(&target, &temp_path)?;
+            fs::rename(&temp_path, &file_info.path).inspect_err(|_| {
+                let _ = fs::remove_file(&temp_path);
+            })
+        });
+
+        match result {
+            Ok(_) => {
+                logs.push(format!("Symlinked: {}", file_info.path.display()));
+                log::info!("    Symlinked: {:?} -> {:?}", file_info.path, kept_path);
+                actioned.push(file_info.clone());
+            }
+            Err(e) => {
+                let reason = describe_symlink_error(&e);
+                logs.push(format!(
+                    "Error symlinking {}: {}",
+                    file_info.path.display(),
+                    reason
+                ));
+                log::error!("Failed to symlink {:?}: {}", file_info.path, reason);
+            }
+        }
+    }
+    Ok((actioned, logs))
+}
+
+/// What a symlink at `link` should contain to reach the (canonical) `kept` path
+fn symlink_target(kept: &Path, link: &Path, relative: bool) -> std::io::Result<PathBuf> {
+    if !relative {
+        return Ok(kept.to_path_buf());
+    }
+    let link_dir = fs::canonicalize(link.parent().unwrap_or(Path::new(".")))?;
+    Ok(relative_path(kept, &link_dir))
+}
+
+/// Path from directory `from` to `to`, both absolute, e.g. `../b/file` from `/a/c` to
+/// `/a/b/file`. Paths on different Windows drives share no prefix; `to` is returned whole.
+fn relative_path(to: &Path, from: &Path) -> PathBuf {
+    let to_parts: Vec<_> = to.components().collect();
+    let from_parts: Vec<_> = from.components().collect();
+    let common = to_parts
+        .iter()
+        .zip(&from_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return to.to_path_buf();
+    }
+    std::iter::repeat_n(std::path::Component::ParentDir, from_parts.len() - common)
+        .chain(to_parts[common..].iter().copied())
+        .collect()
+}
+
+/// Explain a failed symlink. Windows only lets administrators (or Developer Mode)
+/// create symlinks, which otherwise fails with a bare "privilege not held".
+fn describe_symlink_error(e: &std::io::Error) -> String {
+    #[cfg(windows)]
+    {
+        // ERROR_PRIVILEGE_NOT_HELD
+        if e.raw_os_error() == Some(1314) {
+            return format!(
+                "symlinks require privilege; run as administrator or enable Developer Mode ({})",
+                e
+            );
+        }
+    }
+    e.to_string()
+}
+
+// Helper function to sort a Vec<FileInfo>
+pub(crate) fn sort_file_infos(files: &mut [FileInfo], criterion: SortCriterion, order: SortOrder) {
+    files.sort_by(|a, b| {
+        let mut comparison = match criterion {
+            SortCriterion::FileName => a.path.file_name().cmp(&b.path.file_name()),
+            SortCriterion::FileSize => a.size.cmp(&b.size),
+            SortCriterion::CreatedAt => a.created_at.cmp(&b.created_at), // Assumes created_at is Option<SystemTime>
+            SortCriterion::ModifiedAt => a.modified_at.cmp(&b.modified_at), // Assumes modified_at is Option<SystemTime>
+            SortCriterion::PathLength => a.path.as_os_str().len().cmp(&b.path.as_os_str().len()),
+        };
+        if order == SortOrder::Descending {
+            comparison = comparison.reverse();
+        }
+        comparison
+    });
+}
+
+// Structure to represent file comparison results between directories
+pub struct DirectoryComparisonResult {
+    pub missing_in_target: Vec<FileInfo>, // Files in source but not in target
+    pub duplicates: Vec<DuplicateSet>,    // Duplicate files across directories
+}
+
+// Determine target directory from CLI arguments
+pub fn determine_target_directory(cli: &Cli) -> Result<PathBuf> {
+    if let Some(target) = &cli.target {
+        // User explicitly specified a target directory
+        if !target.exists() {
+            return Err(anyhow::anyhow!(
+                "Specified target directory does not exist: {:?}",
+                target
+            ));
+        }
+        if !target.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Specified target path is not a directory: {:?}",
+                target
+            ));
+        }
+        Ok(target.clone())
+    } else if cli.directories.len() > 1 {
+        // Use the last directory as target by default
+        let target = cli.directories.last().unwrap().clone();
+        if !target.exists() {
+            return Err(anyhow::anyhow!(
+                "Last specified directory (default target) does not exist: {:?}",
+                target
+            ));
+        }
+        if !target.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Last specified path is not a directory: {:?}",
+                target
+            ));
+        }
+        Ok(target)
+    } else {
+        Err(anyhow::anyhow!(
+            "No target directory specified and only one directory provided"
+        ))
+    }
+}
+
+// Get source directories from CLI arguments
+pub fn get_source_directories(cli: &Cli, target: &Path) -> Vec<PathBuf> {
+    if let Some(t) = &cli.target {
+        // If target is explicitly specified, all directories are sources
+        cli.directories
+            .iter()
+            .filter(|d| d != &t)
+            .cloned()
+            .collect()
+    } else {
+        // Otherwise, all but the last directory are sources
+        cli.directories
+            .iter()
+            .filter(|d| d.as_path() != target)
+            .cloned()
+            .collect()
+    }
 }
 
 // Compare directories to find missing files and optionally duplicates
@@ -1277,450 +4264,2697 @@ pub fn compare_directories(cli: &Cli) -> Result<DirectoryComparisonResult> {
     let target_dir = determine_target_directory(cli)?;
     let source_dirs = get_source_directories(cli, &target_dir);
 
-    log::info!(
-        "Comparing directories: Sources: {:?}, Target: {:?}",
-        source_dirs,
-        target_dir
-    );
+    log::info!(
+        "Comparing directories: Sources: {:?}, Target: {:?}",
+        source_dirs,
+        target_dir
+    );
+
+    if source_dirs.is_empty() {
+        return Err(anyhow::anyhow!("No source directories specified"));
+    }
+
+    // Create a modified CLI for target directory scan
+    let mut target_cli = cli.clone();
+    target_cli.directories = vec![target_dir.clone()];
+
+    // Scan target directory
+    log::info!("Scanning target directory: {:?}", target_dir);
+    let target_files = scan_directory(&target_cli, &target_dir, true)?;
+    log::info!("Found {} files in target directory", target_files.len());
+
+    // Map of target file hashes for quick lookup
+    let target_hash_map: HashMap<String, &FileInfo> = target_files
+        .iter()
+        .filter_map(|file| file.hash.as_ref().map(|hash| (hash.clone(), file)))
+        .collect();
+
+    // On case-insensitive filesystems `README.md` and `readme.md` are the same file, so a
+    // source file whose relative path matches a target file's ignoring case is not missing
+    // (copying it would overwrite that file)
+    let case_insensitive = cli.case_insensitive_names();
+    let target_names: std::collections::HashSet<std::ffi::OsString> = if case_insensitive {
+        target_files
+            .iter()
+            .filter_map(|file| file.path.strip_prefix(&target_dir).ok())
+            .map(|rel| name_key(rel.as_os_str(), true))
+            .collect()
+    } else {
+        std::collections::HashSet::new()
+    };
+
+    let mut missing_files = Vec::new();
+    let mut all_duplicate_sets = Vec::new();
+
+    // Scan each source directory and find missing files
+    for source_dir in &source_dirs {
+        log::info!("Scanning source directory: {:?}", source_dir);
+
+        // Create a modified CLI for source directory scan
+        let mut source_cli = cli.clone();
+        source_cli.directories = vec![source_dir.clone()];
+
+        let source_files = scan_directory(&source_cli, source_dir, true)?;
+        log::info!(
+            "Found {} files in source directory: {:?}",
+            source_files.len(),
+            source_dir
+        );
+
+        // Find files missing in target
+        for file in &source_files {
+            // Skip files with no hash
+            if let Some(hash) = &file.hash {
+                let name_in_target = case_insensitive
+                    && file
+                        .path
+                        .strip_prefix(source_dir)
+                        .is_ok_and(|rel| target_names.contains(&name_key(rel.as_os_str(), true)));
+                if name_in_target && !target_hash_map.contains_key(hash) {
+                    log::info!(
+                        "{:?} differs from the target file with the same name (ignoring case); not copying",
+                        file.path
+                    );
+                } else if !target_hash_map.contains_key(hash) {
+                    missing_files.push(file.clone());
+                    log::debug!("File missing in target: {:?}", file.path);
+                }
+
+                // If deduplication is enabled, collect duplicate sets
+                if cli.deduplicate {
+                    // This part will be expanded if deduplication is requested
+                    // For now we'll just identify duplicates across directories
+                }
+            }
+        }
+    }
+
+    // If deduplication is requested, we need additional processing
+    if cli.deduplicate {
+        // Scan all directories together to find duplicates across them
+        let mut all_dirs_cli = cli.clone();
+        let mut all_dirs = source_dirs.clone();
+        all_dirs.push(target_dir.clone());
+        all_dirs_cli.directories = all_dirs;
+        all_dirs_cli.union = true;
+
+        log::info!("Finding duplicates across all directories for deduplication");
+
+        // Use find_duplicate_files_with_progress instead of find_duplicate_files
+        // Create a dummy channel for the progress updates
+        let (tx, _rx) = std::sync::mpsc::channel::<ScanMessage>();
+        let duplicates = find_duplicate_files_with_progress(&all_dirs_cli, tx)?;
+
+        // Filter for duplicate sets that span across source and target
+        let cross_dir_duplicates: Vec<DuplicateSet> = duplicates
+            .into_iter()
+            .filter(|set| {
+                // Check if this set has files from both source and target
+                let has_source_file = set.files.iter().any(|file| {
+                    source_dirs
+                        .iter()
+                        .any(|source_dir| file.path.starts_with(source_dir))
+                });
+
+                let has_target_file = set
+                    .files
+                    .iter()
+                    .any(|file| file.path.starts_with(&target_dir));
+
+                has_source_file && has_target_file
+            })
+            .collect();
+
+        all_duplicate_sets = cross_dir_duplicates;
+        log::info!(
+            "Found {} duplicate sets spanning source and target directories",
+            all_duplicate_sets.len()
+        );
+    }
+
+    Ok(DirectoryComparisonResult {
+        missing_in_target: missing_files,
+        duplicates: all_duplicate_sets,
+    })
+}
+
+/// List the files under `directory` that pass the CLI filters, hashing them when
+/// `with_hashes` is set (content comparison needs hashes, `--update` only needs mtimes)
+pub fn scan_directory(cli: &Cli, directory: &Path, with_hashes: bool) -> Result<Vec<FileInfo>> {
+    let cli = &*with_resolved_algorithm(cli);
+    let filter_rules = FilterRules::new(cli)?;
+
+    let mut files = Vec::new();
+    let walker = filter_rules.walk(directory);
+    let mut visited = VisitedPaths::new(filter_rules.follow_symlinks);
+
+    for entry in walker
+        .filter_entry(|e| {
+            if filter_rules.skips_entry(e) {
+                return false;
+            }
+            if let Some(path_str) = e.path().to_str() {
+                filter_rules.is_match(path_str) && visited.first_visit(e)
+            } else {
+                false
+            }
+        })
+        .flatten()
+    {
+        if entry.file_type().is_file() {
+            let path = entry.path().to_path_buf();
+            match fs::metadata(&path) {
+                Ok(metadata) => {
+                    if (metadata.len() > 0 || cli.include_empty)
+                        && filter_rules
+                            .matches_size_and_mtime(metadata.len(), metadata.modified().ok())
+                    {
+                        let size = metadata.len();
+
+                        // Calculate hash
+                        let hash = if !with_hashes {
+                            None
+                        } else if size == 0 {
+                            Some(EMPTY_FILE_HASH.to_string())
+                        } else {
+                            match calculate_hash_buffered(
+                                &path,
+                                &cli.algorithm,
+                                None,
+                                read_buffer_size(cli.read_buffer),
+                            ) {
+                                Ok(h) => Some(h),
+                                Err(e) => {
+                                    log::warn!("Failed to hash file {:?}: {}", path, e);
+                                    None
+                                }
+                            }
+                        };
+
+                        let file_info = FileInfo {
+                            path,
+                            size,
+                            hash,
+                            modified_at: metadata.modified().ok(),
+                            created_at: metadata.created().ok(),
+                        };
+
+                        files.push(file_info);
+                    }
+                }
+                Err(e) => log::warn!("Failed to get metadata for {:?}: {}", path, e),
+            }
+        }
+    }
+
+    log::info!("Found {} files in directory: {:?}", files.len(), directory);
+    Ok(files)
+}
+
+/// Apply the source file's modification time to a freshly copied file.
+/// Uses `modified_at` from the scan when available, falling back to the source metadata.
+/// Creation time cannot be set portably, so only mtime (and atime, to match) is carried over.
+pub fn preserve_file_timestamps(source: &FileInfo, dest: &Path) -> std::io::Result<()> {
+    let modified = match source.modified_at {
+        Some(modified) => modified,
+        None => fs::metadata(&source.path)?.modified()?,
+    };
+    let mtime = filetime::FileTime::from_system_time(modified);
+    filetime::set_file_times(dest, mtime, mtime)
+}
+
+/// Where `copy_missing_files` puts `file`: its parent folder and name, below `target_dir`
+pub fn copy_destination(file: &FileInfo, target_dir: &Path) -> PathBuf {
+    let relative_path = match file
+        .path
+        .parent()
+        .and_then(Path::parent)
+        .and_then(|grandparent| file.path.strip_prefix(grandparent).ok())
+    {
+        Some(rel) => rel.to_path_buf(),
+        // If we can't determine a good relative path, just use the filename
+        None => PathBuf::from(file.path.file_name().unwrap_or_default()),
+    };
+    target_dir.join(relative_path)
+}
+
+/// Re-hash each (source, copy) pair and describe every copy that doesn't match its
+/// source, or couldn't be read. An empty result means every copy verified.
+pub fn verify_copies(pairs: &[(PathBuf, PathBuf)], algorithm: &str) -> Result<Vec<String>> {
+    let results: Vec<Result<Option<String>>> = pairs
+        .par_iter()
+        .map(|(source, copy)| {
+            let source_hash = calculate_hash(source, algorithm)?;
+            Ok(match calculate_hash(copy, algorithm) {
+                Ok(copy_hash) if copy_hash == source_hash => None,
+                Ok(_) => Some(format!(
+                    "Verification failed: {} does not match {}",
+                    copy.display(),
+                    source.display()
+                )),
+                Err(e) => Some(format!(
+                    "Verification failed: could not read {}: {:#}",
+                    copy.display(),
+                    e
+                )),
+            })
+        })
+        .collect();
+
+    let mut mismatches = Vec::new();
+    for result in results {
+        if let Some(message) = result? {
+            mismatches.push(message);
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Source and destination of one copied file
+pub type CopiedFile = (PathBuf, PathBuf);
+
+// Copy missing files to target directory
+pub fn copy_missing_files(
+    missing_files: &[FileInfo],
+    target_dir: &Path,
+    dry_run: bool,
+    preserve_timestamps: bool,
+    transfer: &TransferOptions,
+) -> Result<(usize, Vec<String>)> {
+    copy_missing_files_with_progress(
+        missing_files,
+        target_dir,
+        dry_run,
+        preserve_timestamps,
+        transfer,
+        &ProgressBar::hidden(),
+        &ProgressBar::hidden(),
+    )
+    .map(|(copied, logs)| (copied.len(), logs))
+}
+
+/// `copy_missing_files`, advancing `overall` once per file and showing the file
+/// being copied on `current`. Returns every file copied (or that would be, in a dry run)
+/// with its real destination, since `--on-conflict rename` can move it off `copy_destination`.
+pub fn copy_missing_files_with_progress(
+    missing_files: &[FileInfo],
+    target_dir: &Path,
+    dry_run: bool,
+    preserve_timestamps: bool,
+    transfer: &TransferOptions,
+    overall: &ProgressBar,
+    current: &ProgressBar,
+) -> Result<(Vec<CopiedFile>, Vec<String>)> {
+    let mut copied = Vec::new();
+    let mut logs = Vec::new();
+    overall.set_length(missing_files.len() as u64);
+    // A destination that is a partial copy of the source gets resumed, so it is not a
+    // conflict. Any other existing file goes through --on-conflict.
+    let copy_target = |file: &FileInfo| {
+        let target_path = copy_destination(file, target_dir);
+        let resumable = transfer.resume_transfers
+            && crate::transfer::resumable_prefix_len(&file.path, &target_path).unwrap_or(0) > 0;
+        if resumable {
+            Some(target_path)
+        } else {
+            resolve_conflict(target_path, transfer.on_conflict)
+        }
+    };
+
+    if !target_dir.exists() {
+        if dry_run {
+            let msg = format!(
+                "[DRY RUN] Target directory {} does not exist. Would create it.",
+                target_dir.display()
+            );
+            log::info!("{}", msg);
+            logs.push(msg);
+        } else {
+            log::info!(
+                "Target directory {:?} does not exist. Creating it.",
+                target_dir
+            );
+            fs::create_dir_all(target_dir)?;
+            logs.push(format!(
+                "Created target directory: {}",
+                target_dir.display()
+            ));
+        }
+    } else if !target_dir.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Target path {:?} exists but is not a directory.",
+            target_dir
+        ));
+    }
+
+    if dry_run {
+        logs.push(format!(
+            "[DRY RUN] Would copy {} missing files to {}",
+            missing_files.len(),
+            target_dir.display()
+        ));
+
+        for file in missing_files {
+            overall.inc(1);
+            let Some(target_path) = copy_target(file) else {
+                let msg = format!(
+                    "[DRY RUN] Would skip {} (destination {} exists)",
+                    file.path.display(),
+                    copy_destination(file, target_dir).display()
+                );
+                log::info!("{}", msg);
+                logs.push(msg);
+                continue;
+            };
+
+            logs.push(format!(
+                "[DRY RUN] Would copy {} to {}",
+                file.path.display(),
+                target_path.display()
+            ));
+            log::info!("[DRY RUN] Would copy {:?} to {:?}", file.path, target_path);
+            copied.push((file.path.clone(), target_path));
+        }
+    } else {
+        logs.push(format!(
+            "Copying {} missing files to {}",
+            missing_files.len(),
+            target_dir.display()
+        ));
+
+        for file in missing_files {
+            let Some(target_path) = copy_target(file) else {
+                overall.inc(1);
+                let msg = format!(
+                    "Skipped: {} (destination {} exists)",
+                    file.path.display(),
+                    copy_destination(file, target_dir).display()
+                );
+                log::info!("{}", msg);
+                logs.push(msg);
+                continue;
+            };
+
+            // Ensure parent directory exists
+            if let Some(parent) = target_path.parent() {
+                if !parent.exists() {
+                    fs::create_dir_all(parent)?;
+                    let msg = format!("Created parent directory: {}", parent.display());
+                    logs.push(msg.clone());
+                    log::debug!("{}", msg);
+                }
+            }
+
+            current.set_length(file.size);
+            current.set_position(0);
+            current.set_message(file.path.display().to_string());
+            let copy_result = with_fs_retry(transfer.retries, || {
+                crate::transfer::copy_file(&file.path, &target_path, transfer)
+            });
+            current.set_position(file.size);
+            overall.inc(1);
+
+            match copy_result {
+                Ok(_) => {
+                    journal::record_action(JournalAction::Copy, &file.path, Some(&target_path));
+                    if preserve_timestamps {
+                        if let Err(e) = preserve_file_timestamps(file, &target_path) {
+                            let msg = format!(
+                                "Warning: could not preserve timestamps on {}: {}",
+                                target_path.display(),
+                                e
+                            );
+                            logs.push(msg.clone());
+                            log::warn!("{}", msg);
+                        }
+                    }
+                    let msg = format!(
+                        "Copied: {} -> {}",
+                        file.path.display(),
+                        target_path.display()
+                    );
+                    logs.push(msg.clone());
+                    log::info!("{}", msg);
+                    copied.push((file.path.clone(), target_path));
+                }
+                Err(e) => {
+                    let error_msg = format!(
+                        "Failed to copy {} to {}: {}",
+                        file.path.display(),
+                        target_path.display(),
+                        e
+                    );
+                    logs.push(error_msg.clone());
+                    log::error!("{}", error_msg);
+                    // Continue with other files
+                }
+            }
+        }
+    }
+
+    Ok((copied, logs))
+}
+
+// Add this new function for counting files in a directory
+pub fn count_files_in_directory(directory: &Path, filter_rules: &FilterRules) -> Result<usize> {
+    let mut count = 0;
+    let walker = filter_rules.walk(directory);
+    let mut visited = VisitedPaths::new(filter_rules.follow_symlinks);
+
+    for entry in walker
+        .filter_entry(|e| {
+            if filter_rules.skips_entry(e) {
+                return false;
+            }
+            if let Some(path_str) = e.path().to_str() {
+                filter_rules.is_match(path_str) && visited.first_visit(e)
+            } else {
+                false
+            }
+        })
+        .flatten()
+    {
+        if entry.file_type().is_file() {
+            // Only stat files when size/age limits apply, so the plain count stays cheap
+            if filter_rules.has_metadata_rules() {
+                match entry.metadata() {
+                    Ok(metadata)
+                        if filter_rules
+                            .matches_size_and_mtime(metadata.len(), metadata.modified().ok()) => {}
+                    _ => continue,
+                }
+            }
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn create_test_file(content: &[u8]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_md5_hash() {
+        let test_content = b"The quick brown fox jumps over the lazy dog";
+        let file = create_test_file(test_content);
+        let hash = calculate_hash(file.path(), "md5").unwrap();
+        assert_eq!(hash, "9e107d9d372bb6826bd81d3542a419d6");
+    }
+
+    #[test]
+    fn test_sha1_hash() {
+        let test_content = b"The quick brown fox jumps over the lazy dog";
+        let file = create_test_file(test_content);
+        let hash = calculate_hash(file.path(), "sha1").unwrap();
+        assert_eq!(hash, "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12");
+    }
+
+    #[test]
+    fn test_sha256_hash() {
+        let test_content = b"The quick brown fox jumps over the lazy dog";
+        let file = create_test_file(test_content);
+        let hash = calculate_hash(file.path(), "sha256").unwrap();
+        assert_eq!(
+            hash,
+            "d7a8fbb307d7809469ca9abcb0082e4f8d5651e46d3cdb762d02d0bf37c9e592"
+        );
+    }
+
+    #[test]
+    fn test_blake3_hash() {
+        let test_content = b"The quick brown fox jumps over the lazy dog";
+        let file = create_test_file(test_content);
+        let hash = calculate_hash(file.path(), "blake3").unwrap();
+        // Blake3 has a different hash length and value
+        assert_eq!(hash.len(), 64);
+        // Update the expected hash value with the actual one from our implementation
+        assert_eq!(
+            hash,
+            "2f1514181aadccd913abd94cfa592701a5686ab23f8df1dff1b74710febc6d4a"
+        );
+    }
+
+    #[test]
+    fn test_xxhash() {
+        let test_content = b"The quick brown fox jumps over the lazy dog";
+        let file = create_test_file(test_content);
+        let hash = calculate_hash(file.path(), "xxhash").unwrap();
+        // xxHash has a fixed length of 16 hex characters (64 bits)
+        assert_eq!(hash.len(), 16);
+        // We check the format rather than exact value as implementation might vary
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[cfg(feature = "linux")]
+    #[test]
+    fn test_gxhash() {
+        let file = create_test_file(b"test content");
+        let hash = calculate_hash(file.path(), "gxhash").unwrap();
+        assert_eq!(hash.len(), 16); // gxhash produces 64-bit output (8 bytes = 16 hex chars)
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        let test_content = b"The quick brown fox jumps over the lazy dog";
+        let file = create_test_file(test_content);
+        let hash = calculate_hash(file.path(), "fnv1a").unwrap();
+        // FNV-1a has a fixed length of 16 hex characters (64 bits)
+        assert_eq!(hash.len(), 16);
+        // We check the format rather than exact value as implementation might vary
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn test_crc32() {
+        let test_content = b"The quick brown fox jumps over the lazy dog";
+        let file = create_test_file(test_content);
+        let hash = calculate_hash(file.path(), "crc32").unwrap();
+        // CRC-32 produces a 32-bit value, resulting in 8 hex characters
+        assert_eq!(hash.len(), 8);
+        // We check the format rather than exact value as implementation might vary
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    /// Reference implementation that hashes the whole file in one buffer,
+    /// used to check the streaming implementation produces identical output.
+    fn whole_buffer_hash(content: &[u8], algorithm: &str) -> String {
+        match algorithm {
+            "md5" => format!("{:x}", md5::compute(content)),
+            "sha1" => {
+                let mut hasher = sha1::Sha1::new();
+                hasher.update(content);
+                format!("{:x}", hasher.finalize())
+            }
+            "sha256" => {
+                let mut hasher = sha2::Sha256::new();
+                hasher.update(content);
+                format!("{:x}", hasher.finalize())
+            }
+            "blake3" => blake3::hash(content).to_hex().to_string(),
+            "xxhash" => {
+                let mut hasher = twox_hash::XxHash64::default();
+                hasher.write(content);
+                format!("{:016x}", hasher.finish())
+            }
+            "fnv1a" => {
+                let mut hasher = fnv::FnvHasher::default();
+                hasher.write(content);
+                format!("{:016x}", hasher.finish())
+            }
+            "crc32" => format!("{:08x}", crc32fast::hash(content)),
+            _ => unreachable!("unexpected algorithm {}", algorithm),
+        }
+    }
+
+    #[test]
+    fn test_read_buffer_size_does_not_change_hashes() -> Result<()> {
+        let content: Vec<u8> = (0..(HASH_BUFFER_SIZE * 5 + 777))
+            .map(|i| (i % 241) as u8)
+            .collect();
+        let file = create_test_file(&content);
+
+        for algorithm in ["sha256", "blake3", "xxhash", "crc32"] {
+            let hashes: Vec<String> = [MIN_READ_BUFFER, 1024 * 1024]
+                .into_iter()
+                .map(|buffer_size| {
+                    calculate_hash_buffered(file.path(), algorithm, None, buffer_size)
+                })
+                .collect::<Result<_>>()?;
+            assert_eq!(hashes[0], hashes[1], "{} differs by buffer size", algorithm);
+            assert_eq!(hashes[0], whole_buffer_hash(&content, algorithm));
+        }
+
+        assert_eq!(parse_read_buffer("1M"), Ok(1024 * 1024));
+        assert!(parse_read_buffer("0").is_err());
+        assert_eq!(read_buffer_size(1), MIN_READ_BUFFER);
+        assert_eq!(read_buffer_size(1 << 40), MAX_READ_BUFFER);
+        assert_eq!(read_buffer_size(100_000), 128 * 1024);
+        assert_eq!(read_buffer_size(HASH_BUFFER_SIZE as u64), HASH_BUFFER_SIZE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_streaming_hash_matches_whole_buffer() {
+        // Larger than the read buffer and not a multiple of it, so the final chunk is partial
+        let content: Vec<u8> = (0..(HASH_BUFFER_SIZE * 3 + 1234))
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let file = create_test_file(&content);
+
+        for algorithm in [
+            "md5", "sha1", "sha256", "blake3", "xxhash", "fnv1a", "crc32",
+        ] {
+            let streamed = calculate_hash(file.path(), algorithm).unwrap();
+            assert_eq!(
+                streamed,
+                whole_buffer_hash(&content, algorithm),
+                "Streaming {} hash differs from whole-buffer hash",
+                algorithm
+            );
+        }
+    }
+
+    #[test]
+    fn test_prefilter_drops_same_size_files_with_different_heads() {
+        let size = (PREFILTER_SAMPLE_SIZE * 4) as usize;
+        let mut content_a = vec![7u8; size];
+        let mut content_b = vec![7u8; size];
+        content_a[0] = 1;
+        content_b[0] = 2;
+        let file_a = create_test_file(&content_a);
+        let file_b = create_test_file(&content_b);
+        let file_c = create_test_file(&content_a);
+
+        let groups = vec![(
+            size as u64,
+            vec![
+                file_a.path().to_path_buf(),
+                file_b.path().to_path_buf(),
+                file_c.path().to_path_buf(),
+            ],
+        )];
+        let filtered = prefilter_size_groups(groups, |_| false);
+
+        // Only the two files with matching heads and tails should reach full hashing
+        assert_eq!(filtered.len(), 1);
+        let (filtered_size, paths) = &filtered[0];
+        assert_eq!(*filtered_size, size as u64);
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(&file_a.path().to_path_buf()));
+        assert!(paths.contains(&file_c.path().to_path_buf()));
+        assert!(!paths.contains(&file_b.path().to_path_buf()));
+
+        // A pair that differs only in the head never forms a group at all
+        let groups = vec![(
+            size as u64,
+            vec![file_a.path().to_path_buf(), file_b.path().to_path_buf()],
+        )];
+        assert!(prefilter_size_groups(groups.clone(), |_| false).is_empty());
+
+        // Once one of them has a cached hash, the group goes to hashing unsplit
+        let cached = file_a.path().to_path_buf();
+        assert_eq!(
+            prefilter_size_groups(groups.clone(), |p| p == cached),
+            groups
+        );
+    }
+
+    #[test]
+    fn test_hash_limit_only_reads_prefix() {
+        let mut content_a = vec![1u8; HASH_BUFFER_SIZE * 2];
+        let content_b = content_a.clone();
+        content_a[HASH_BUFFER_SIZE + 10] = 9;
+        let file_a = create_test_file(&content_a);
+        let file_b = create_test_file(&content_b);
+
+        let limit = Some(HASH_BUFFER_SIZE as u64);
+        let hash_a = calculate_hash_with_limit(file_a.path(), "xxhash", limit).unwrap();
+        let hash_b = calculate_hash_with_limit(file_b.path(), "xxhash", limit).unwrap();
+        assert_eq!(hash_a, hash_b);
+
+        // Without a limit the differing byte is seen
+        assert_ne!(
+            calculate_hash(file_a.path(), "xxhash").unwrap(),
+            calculate_hash(file_b.path(), "xxhash").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hash_permits_serialize_large_file_hashing() {
+        use std::sync::atomic::AtomicUsize;
+
+        // A budget for one average file gives a single permit
+        let permits = HashPermits::for_budget(100 * 1024 * 1024, 80 * 1024 * 1024);
+        assert_eq!(*permits.available.lock().unwrap(), 1);
+        assert_eq!(
+            *HashPermits::for_budget(1024, 0).available.lock().unwrap(),
+            1024
+        );
+
+        let active = AtomicUsize::new(0);
+        let max_active = AtomicUsize::new(0);
+        let files: Vec<_> = (0..6u8)
+            .map(|i| create_test_file(&vec![i; HASH_BUFFER_SIZE * 4]))
+            .collect();
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap()
+            .install(|| {
+                files.par_iter().for_each(|file| {
+                    let _permit = permits.acquire();
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(now, Ordering::SeqCst);
+                    calculate_hash(file.path(), "sha256").unwrap();
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            });
+        assert_eq!(max_active.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            *permits.available.lock().unwrap(),
+            1,
+            "every permit returned"
+        );
+    }
+
+    #[test]
+    fn test_single_size_group_is_hashed_on_several_threads() -> Result<()> {
+        use clap::Parser;
+        use std::sync::atomic::AtomicUsize;
+
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        fs::create_dir_all(&root)?;
+        // One size group: identical heads and tails pass the pre-filter, so all of them get
+        // fully hashed, and only the middle byte decides which set a file lands in
+        let mut content = vec![7u8; 256 * 1024];
+        for i in 0..24u8 {
+            content[128 * 1024] = i % 3;
+            fs::write(root.join(format!("file{:02}.bin", i)), &content)?;
+        }
+
+        let paths: Vec<PathBuf> = fs::read_dir(&root)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        let active = AtomicUsize::new(0);
+        let max_active = AtomicUsize::new(0);
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build()?;
+        let (by_hash, cache_hits) = pool.install(|| {
+            hash_size_group(paths, |path| {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                max_active.fetch_max(now, Ordering::SeqCst);
+                let hash = calculate_hash(&path, "sha256");
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                active.fetch_sub(1, Ordering::SeqCst);
+                Ok(Some((hash?, FileInfo::for_test(path, 256 * 1024), false)))
+            })
+        });
+        assert!(
+            max_active.load(Ordering::SeqCst) > 1,
+            "files of one size group were hashed one at a time"
+        );
+        assert_eq!(cache_hits, 0);
+        assert_eq!(by_hash.len(), 3);
+        assert!(by_hash.values().all(|files| files.len() == 8));
+
+        // And the scan groups them the same way
+        let cli = Cli::parse_from([
+            "dedups",
+            "--parallel",
+            "4",
+            "--algorithm",
+            "sha256",
+            root.to_str().unwrap(),
+        ]);
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = find_duplicate_files_with_progress(&cli, tx)?;
+        assert_eq!(sets.len(), 3);
+        for set in &sets {
+            assert_eq!(set.files.len(), 8);
+            let mut hashes = set.files.iter().map(|file| file.hash.as_deref());
+            assert!(hashes.all(|hash| hash == Some(set.hash.as_str())));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_file_is_skipped_without_dropping_its_group() {
+        let paths: Vec<PathBuf> = ["a", "b", "broken", "c"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let unreadable_before = crate::exit_code::unreadable_count();
+        let (by_hash, _) = hash_size_group(paths, |path| {
+            if path == Path::new("broken") {
+                return Err(DedupError::HashFailed {
+                    path,
+                    source: std::io::Error::from(std::io::ErrorKind::Other),
+                }
+                .into());
+            }
+            Ok(Some((
+                "same".to_string(),
+                FileInfo::for_test(path, 10),
+                false,
+            )))
+        });
+
+        let kept: Vec<&Path> = by_hash["same"].iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(kept, [Path::new("a"), Path::new("b"), Path::new("c")]);
+        assert!(crate::exit_code::unreadable_count() > unreadable_before);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_newline_in_name_round_trips_through_print0() -> Result<()> {
+        use clap::Parser;
+
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        fs::create_dir_all(&root)?;
+        let tricky = root.join("line one\nline two.txt");
+        fs::write(&tricky, "same")?;
+        fs::write(root.join("plain.txt"), "same")?;
+
+        let cli = Cli::parse_from(["dedups", root.to_str().unwrap()]);
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = find_duplicate_files_with_progress(&cli, tx)?;
+        assert_eq!(sets.len(), 1);
+
+        let mut output = Vec::new();
+        write_paths_null_separated(&sets, &mut output)?;
+        assert_eq!(output.iter().filter(|b| **b == 0).count(), 2);
+        let expected: Vec<PathBuf> = sets[0].files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(parse_file_list(&output[..], b'\0')?, expected);
+        assert!(expected.contains(&tricky));
+        // Read line by line, the name would have been split in two
+        assert_eq!(parse_file_list(&output[..], b'\n')?.len(), 2);
+        assert!(!parse_file_list(&output[..], b'\n')?.contains(&tricky));
+
+        // The same bytes feed --files-from with --null-input
+        let list = dir.path().join("list");
+        fs::write(&list, &output)?;
+        let cli = Cli::parse_from([
+            "dedups",
+            "--null-input",
+            "--files-from",
+            list.to_str().unwrap(),
+        ]);
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = find_duplicate_files_with_progress(&cli, tx)?;
+        assert_eq!(sets.len(), 1);
+        assert!(sets[0].files.iter().any(|f| f.path == tricky));
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_path_handles_tilde_and_relative_paths() {
+        use clap::Parser;
+
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(expand_path("./data"), cwd.join("data"));
+        assert_eq!(expand_path("data/../photos"), cwd.join("data/../photos"));
+        assert_eq!(expand_path("."), cwd);
+        assert_eq!(expand_path("-"), PathBuf::from("-"));
+        let absolute = cwd.join("already/absolute");
+        assert_eq!(expand_path(absolute.to_str().unwrap()), absolute);
+
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(expand_path("~"), home);
+            assert_eq!(expand_path("~/Pictures"), home.join("Pictures"));
+            if let Some(homes) = home.parent() {
+                assert_eq!(
+                    expand_path("~someone/Pictures"),
+                    homes.join("someone").join("Pictures")
+                );
+            }
+
+            let mut cli = Cli::parse_from([
+                "dedups",
+                "--move-to",
+                "~/dups",
+                "--protect",
+                "keep",
+                "~/Pictures",
+            ]);
+            cli.expand_paths();
+            assert_eq!(cli.directories, [home.join("Pictures")]);
+            assert_eq!(cli.move_to, Some(home.join("dups")));
+            assert_eq!(cli.protect, [cwd.join("keep")]);
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("4K").unwrap(), 4096);
+        assert_eq!(parse_size("10M").unwrap(), 10 * 1024 * 1024);
+        assert_eq!(parse_size("2gib").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1.5K").unwrap(), 1536);
+        assert!(parse_size("abc").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    #[test]
+    fn test_approximate_delete_gating() {
+        let file_info = |name: &str| FileInfo {
+            path: PathBuf::from(name),
+            size: 10,
+            hash: Some("abc".to_string()),
+            modified_at: None,
+            created_at: None,
+        };
+        let mut set = DuplicateSet {
+            files: vec![file_info("/a"), file_info("/b")],
+            size: 10,
+            hash: "abcdef0123".to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+
+        // Exact sets are always allowed
+        assert!(check_approximate_delete_allowed(&set, false).is_ok());
+
+        // Approximate sets need the explicit opt-in
+        set.approximate = true;
+        assert!(check_approximate_delete_allowed(&set, false).is_err());
+        assert!(check_approximate_delete_allowed(&set, true).is_ok());
+    }
+
+    fn file_info_for(path: &Path) -> FileInfo {
+        FileInfo {
+            path: path.to_path_buf(),
+            size: fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        }
+    }
+
+    #[test]
+    fn test_delete_files_trash_dry_run_logs() {
+        let file = create_test_file(b"trash me");
+        let files = vec![file_info_for(file.path())];
+
+        let (deleted, logs) = delete_files(&files, true, true, 0).unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(
+            logs[0],
+            "[DRY RUN] Would move to trash the following files:"
+        );
+        assert_eq!(logs[1], format!("[DRY RUN]    - {}", file.path().display()));
+        assert!(file.path().exists());
+    }
+
+    #[test]
+    fn test_delete_files_to_trash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("to_trash.txt");
+        fs::write(&path, b"trash me").unwrap();
+        let files = vec![file_info_for(&path)];
+
+        let (deleted, logs) = delete_files(&files, false, true, 0).unwrap();
+        if deleted.is_empty() {
+            // Headless CI containers may have no usable trash location
+            println!("Skipping trash assertions, trash unavailable: {:?}", logs);
+            return;
+        }
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(logs[0], "Moving the following files to trash:");
+        assert_eq!(logs[1], format!("Trashed: {}", path.display()));
+        assert!(!path.exists());
+    }
+
+    // Meow Hash test temporarily removed due to build issues
+    // #[test]
+    // fn test_meow_hash() { ... }
+
+    #[test]
+    fn test_invalid_algorithm() {
+        let test_content = b"test content";
+        let file = create_test_file(test_content);
+        let result = calculate_hash(file.path(), "invalid_algorithm");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let test_content = b"";
+        let file = create_test_file(test_content);
+
+        // MD5 empty file hash
+        let hash = calculate_hash(file.path(), "md5").unwrap();
+        assert_eq!(hash, "d41d8cd98f00b204e9800998ecf8427e");
+
+        // SHA1 empty file hash
+        let hash = calculate_hash(file.path(), "sha1").unwrap();
+        assert_eq!(hash, "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+
+        // SHA256 empty file hash
+        let hash = calculate_hash(file.path(), "sha256").unwrap();
+        assert_eq!(
+            hash,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        // Blake3 empty file hash - update with the actual value from our implementation
+        let hash = calculate_hash(file.path(), "blake3").unwrap();
+        let expected_empty_blake3 = hash.clone();
+        assert_eq!(hash, expected_empty_blake3);
+    }
+
+    fn filter_rules_from_args(args: &[&str]) -> Result<FilterRules> {
+        use clap::Parser;
+        let mut argv = vec!["dedups"];
+        argv.extend_from_slice(args);
+        argv.push(".");
+        FilterRules::new(&Cli::try_parse_from(argv)?)
+    }
+
+    #[test]
+    fn test_regex_and_glob_filter_precedence() -> Result<()> {
+        // Regex include broadens the glob include set
+        let rules = filter_rules_from_args(&["--include", "*.jpg", "--include-regex", r"\.png$"])?;
+        assert!(rules.is_match("/photos/a.jpg"));
+        assert!(rules.is_match("/photos/a.png"));
+        assert!(!rules.is_match("/photos/a.txt"));
+
+        // A regex exclude wins over a glob include
+        let rules = filter_rules_from_args(&["--include", "*.jpg", "--exclude-regex", "thumb"])?;
+        assert!(rules.is_match("/photos/a.jpg"));
+        assert!(!rules.is_match("/photos/thumb_a.jpg"));
+
+        // A glob exclude wins over a regex include
+        let rules = filter_rules_from_args(&["--include-regex", "jpg$", "--exclude", "*/cache/*"])?;
+        assert!(rules.is_match("/photos/a.jpg"));
+        assert!(!rules.is_match("/photos/cache/a.jpg"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_dir_prunes_subtree() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        // Walk from a non-hidden root (temp dirs are often named ".tmpXXXX")
+        let root = tmp.path().join("root");
+        let kept = root.join("photos");
+        let excluded = root.join("backup");
+        fs::create_dir_all(&kept)?;
+        fs::create_dir_all(excluded.join("nested/deeper"))?;
+        fs::write(kept.join("a.jpg"), "a")?;
+        fs::write(excluded.join("a.jpg"), "a")?;
+        fs::write(excluded.join("nested/deeper/b.jpg"), "b")?;
+
+        let rules = filter_rules_from_args(&["--exclude-dir", excluded.to_str().unwrap()])?;
+        let mut yielded = Vec::new();
+        for entry in rules
+            .walk(&root)
+            .filter_entry(|e| {
+                yielded.push(e.path().to_path_buf());
+                !rules.skips_entry(e)
+            })
+            .flatten()
+        {
+            assert!(!entry.path().starts_with(&excluded), "{:?}", entry.path());
+        }
+
+        // The excluded directory itself is offered once, and nothing below it ever is
+        assert!(yielded.contains(&excluded));
+        assert!(yielded.contains(&kept.join("a.jpg")));
+        assert!(!yielded
+            .iter()
+            .any(|p| p.starts_with(&excluded) && p != &excluded));
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_excludes_skip_junk_files() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path().join("root");
+        fs::create_dir_all(root.join(".git/objects"))?;
+        fs::write(root.join(".DS_Store"), "junk")?;
+        fs::write(root.join(".git/objects/ab"), "junk")?;
+        fs::write(root.join("photo.jpg"), "photo")?;
+
+        let walked = |args: &[&str]| -> Result<Vec<PathBuf>> {
+            let rules = filter_rules_from_args(args)?;
+            let mut files: Vec<PathBuf> = rules
+                .walk(&root)
+                .filter_entry(|e| !rules.skips_entry(e))
+                .flatten()
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.path().strip_prefix(&root).unwrap().to_path_buf())
+                .collect();
+            files.sort();
+            Ok(files)
+        };
 
-    if source_dirs.is_empty() {
-        return Err(anyhow::anyhow!("No source directories specified"));
+        // Hidden files have to be allowed for the default excludes to make a difference
+        assert_eq!(walked(&["--include-hidden"])?, [PathBuf::from("photo.jpg")]);
+        assert_eq!(
+            walked(&["--include-hidden", "--no-default-excludes"])?,
+            [
+                PathBuf::from(".DS_Store"),
+                PathBuf::from(".git/objects/ab"),
+                PathBuf::from("photo.jpg")
+            ]
+        );
+        Ok(())
     }
 
-    // Create a modified CLI for target directory scan
-    let mut target_cli = cli.clone();
-    target_cli.directories = vec![target_dir.clone()];
+    #[test]
+    fn test_regex_rules_from_filter_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let filter_path = dir.path().join("filters.txt");
+        fs::write(
+            &filter_path,
+            "# photos only\nR+ \\.(jpe?g|heic)$\nR- /\\.trash/\n- *.tmp\n",
+        )?;
+        let rules = filter_rules_from_args(&["--filter-from", filter_path.to_str().unwrap()])?;
+        assert!(rules.is_match("/a/IMG_1.jpeg"));
+        assert!(
+            !rules.is_match("/a/IMG_2.HEIC"),
+            "regexes are case-sensitive"
+        );
+        assert!(!rules.is_match("/a/.trash/IMG_1.jpg"));
+        assert!(!rules.is_match("/a/notes.txt"));
+
+        fs::write(&filter_path, "+ *.jpg\nR- ([unclosed\n")?;
+        let err = filter_rules_from_args(&["--filter-from", filter_path.to_str().unwrap()])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("line 2"),
+            "error should name the line: {}",
+            err
+        );
 
-    // Scan target directory
-    log::info!("Scanning target directory: {:?}", target_dir);
-    let target_files = scan_directory(&target_cli, &target_dir)?;
-    log::info!("Found {} files in target directory", target_files.len());
+        assert!(filter_rules_from_args(&["--include-regex", "(["]).is_err());
+        Ok(())
+    }
 
-    // Map of target file hashes for quick lookup
-    let target_hash_map: HashMap<String, &FileInfo> = target_files
-        .iter()
-        .filter_map(|file| file.hash.as_ref().map(|hash| (hash.clone(), file)))
-        .collect();
+    #[test]
+    fn test_filter_file_metadata_directives() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let filter_path = dir.path().join("filters.txt");
+        fs::write(
+            &filter_path,
+            "# size and age limits\n\nmin-size 1K\nmax-size 2M\nnewer-than 2023-01-01\nolder-than 30d\n+ *.jpg\n",
+        )?;
+        let rules = filter_rules_from_args(&["--filter-from", filter_path.to_str().unwrap()])?;
+        assert_eq!(rules.min_size, Some(1024));
+        assert_eq!(rules.max_size, Some(2 * 1024 * 1024));
+        let newer_than = rules.newer_than.expect("newer-than parsed");
+        assert_eq!(
+            newer_than.duration_since(SystemTime::UNIX_EPOCH)?.as_secs(),
+            1_672_531_200
+        );
+        let older_than = rules.older_than.expect("older-than parsed");
+        let age = SystemTime::now().duration_since(older_than)?.as_secs();
+        assert!((30 * 86400..30 * 86400 + 60).contains(&age));
+        assert!(rules.is_match("/a/photo.jpg"));
+
+        let in_window = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let file = |size, modified_at| FileInfo {
+            modified_at,
+            ..FileInfo::for_test("/a/photo.jpg", size)
+        };
+        assert!(rules.matches_metadata(&file(1024, Some(in_window))));
+        assert!(!rules.matches_metadata(&file(1023, Some(in_window))));
+        assert!(!rules.matches_metadata(&file(3 * 1024 * 1024, Some(in_window))));
+        assert!(!rules.matches_metadata(&file(4096, Some(SystemTime::now()))));
+        assert!(!rules.matches_metadata(&file(4096, Some(SystemTime::UNIX_EPOCH))));
+        assert!(!rules.matches_metadata(&file(4096, None)));
+        Ok(())
+    }
 
-    let mut missing_files = Vec::new();
-    let mut all_duplicate_sets = Vec::new();
+    #[test]
+    fn test_filter_file_rejects_malformed_directives() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let filter_path = dir.path().join("filters.txt");
+        for (content, directive) in [
+            ("# ok\nmin-size lots\n", "min-size"),
+            ("+ *.jpg\nmax-size 5Q\n", "max-size"),
+            ("\nnewer-than yesterday\n", "newer-than"),
+            ("; c\nolder-than 30x\n", "older-than"),
+        ] {
+            fs::write(&filter_path, content)?;
+            let err = filter_rules_from_args(&["--filter-from", filter_path.to_str().unwrap()])
+                .unwrap_err()
+                .to_string();
+            assert!(
+                err.contains("line 2") && err.contains(directive),
+                "unexpected error: {}",
+                err
+            );
+        }
+        Ok(())
+    }
 
-    // Scan each source directory and find missing files
-    for source_dir in &source_dirs {
-        log::info!("Scanning source directory: {:?}", source_dir);
+    #[test]
+    fn test_output_duplicates_csv() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = |name: &str, size| FileInfo {
+            path: dir.path().join(name),
+            size,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let set = |hash: &str, files: Vec<FileInfo>| DuplicateSet {
+            size: files[0].size,
+            files,
+            hash: hash.to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        let sets = vec![
+            set(
+                "bbb",
+                vec![file("a.jpg", 10), file("b, with comma.jpg", 10)],
+            ),
+            set(
+                "aaa",
+                vec![file("c.txt", 5), file("d.txt", 5), file("e.txt", 5)],
+            ),
+        ];
 
-        // Create a modified CLI for source directory scan
-        let mut source_cli = cli.clone();
-        source_cli.directories = vec![source_dir.clone()];
+        let output_path = dir.path().join("dupes.csv");
+        output_duplicates(&sets, &output_path, "csv")?;
 
-        let source_files = scan_directory(&source_cli, source_dir)?;
-        log::info!(
-            "Found {} files in source directory: {:?}",
-            source_files.len(),
-            source_dir
+        let mut reader = csv::Reader::from_path(&output_path)?;
+        assert_eq!(
+            reader.headers()?.iter().collect::<Vec<_>>(),
+            vec!["set_hash", "size", "file_path"]
         );
+        let rows: Vec<csv::StringRecord> = reader.records().collect::<Result<_, _>>()?;
+        assert_eq!(rows.len(), 5);
+        assert_eq!(&rows[0][0], "aaa");
+        assert_eq!(&rows[0][1], "5");
+        assert!(rows
+            .iter()
+            .any(|r| r[2].ends_with("b, with comma.jpg") && &r[1] == "10"));
 
-        // Find files missing in target
-        for file in &source_files {
-            // Skip files with no hash
-            if let Some(hash) = &file.hash {
-                if !target_hash_map.contains_key(hash) {
-                    missing_files.push(file.clone());
-                    log::debug!("File missing in target: {:?}", file.path);
-                }
-
-                // If deduplication is enabled, collect duplicate sets
-                if cli.deduplicate {
-                    // This part will be expanded if deduplication is requested
-                    // For now we'll just identify duplicates across directories
-                }
-            }
-        }
+        // Nothing is written when there are no real duplicate sets
+        let empty_path = dir.path().join("empty.csv");
+        output_duplicates(&[], &empty_path, "csv")?;
+        assert!(!empty_path.exists());
+        Ok(())
     }
 
-    // If deduplication is requested, we need additional processing
-    if cli.deduplicate {
-        // Scan all directories together to find duplicates across them
-        let mut all_dirs_cli = cli.clone();
-        let mut all_dirs = source_dirs.clone();
-        all_dirs.push(target_dir.clone());
-        all_dirs_cli.directories = all_dirs;
+    #[test]
+    fn test_output_duplicates_fdupes_round_trips() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = |name: &str| FileInfo {
+            path: dir.path().join(name),
+            size: 3,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let set = |hash: &str, files: Vec<FileInfo>| DuplicateSet {
+            size: files[0].size,
+            files,
+            hash: hash.to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        let sets = vec![
+            set("bbb", vec![file("a.jpg"), file("b with space.jpg")]),
+            set("aaa", vec![file("c.txt"), file("d.txt"), file("e.txt")]),
+        ];
+
+        let output_path = dir.path().join("dupes.txt");
+        output_duplicates(&sets, &output_path, "fdupes")?;
+        let contents = fs::read_to_string(&output_path)?;
+        assert!(contents.ends_with("e.txt\n\n"));
+
+        // Parse it the way fdupes consumers do: blank lines separate groups
+        let groups: Vec<Vec<PathBuf>> = contents
+            .split("\n\n")
+            .filter(|group| !group.is_empty())
+            .map(|group| group.lines().map(PathBuf::from).collect())
+            .collect();
+        let expected: Vec<Vec<PathBuf>> = sets
+            .iter()
+            .map(|s| s.files.iter().map(|f| f.path.clone()).collect())
+            .collect();
+        assert_eq!(groups, expected);
 
-        log::info!("Finding duplicates across all directories for deduplication");
+        // No duplicates: an empty file, as fdupes would print nothing
+        let empty_path = dir.path().join("empty.txt");
+        output_duplicates(&[], &empty_path, "fdupes")?;
+        assert_eq!(fs::read_to_string(&empty_path)?, "");
+        Ok(())
+    }
 
-        // Use find_duplicate_files_with_progress instead of find_duplicate_files
-        // Create a dummy channel for the progress updates
-        let (tx, _rx) = std::sync::mpsc::channel::<ScanMessage>();
-        let duplicates = find_duplicate_files_with_progress(&all_dirs_cli, tx)?;
+    #[test]
+    fn test_write_html_report_lists_every_path() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = |name: &str| FileInfo {
+            path: dir.path().join(name),
+            size: 2048,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let sets = vec![DuplicateSet {
+            files: vec![file("a.jpg"), file("b <copy>.jpg"), file("c.jpg")],
+            size: 2048,
+            hash: "0123456789abcdef".to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        }];
+
+        let report_path = dir.path().join("report.html");
+        write_html_report(&sets, &report_path, true)?;
+        let html = fs::read_to_string(&report_path)?;
+        for file in &sets[0].files {
+            assert!(html.contains(&html_escape(&file.path.display().to_string())));
+        }
+        assert!(html.contains("b &lt;copy&gt;.jpg"));
+        assert!(
+            html.contains("4096 bytes"),
+            "reclaimable space is size * (files - 1)"
+        );
+        Ok(())
+    }
 
-        // Filter for duplicate sets that span across source and target
-        let cross_dir_duplicates: Vec<DuplicateSet> = duplicates
-            .into_iter()
-            .filter(|set| {
-                // Check if this set has files from both source and target
-                let has_source_file = set.files.iter().any(|file| {
-                    source_dirs
-                        .iter()
-                        .any(|source_dir| file.path.starts_with(source_dir))
-                });
+    #[test]
+    fn test_tiebreak_keeps_the_same_file_across_runs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let nested = dir.path().join("nested");
+        fs::create_dir_all(&nested)?;
+        let mtime = SystemTime::now() - std::time::Duration::from_secs(3600);
+        let make = |path: PathBuf| -> Result<FileInfo> {
+            fs::write(&path, b"same")?;
+            filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(mtime))?;
+            Ok(FileInfo {
+                path,
+                size: 4,
+                hash: None,
+                modified_at: Some(mtime),
+                created_at: None,
+            })
+        };
+        let files = vec![
+            make(nested.join("a.jpg"))?,
+            make(dir.path().join("zz.jpg"))?,
+            make(dir.path().join("bb.jpg"))?,
+        ];
+        let tiebreak = vec![TieBreaker::ShortestPath, TieBreaker::Name];
+
+        // Every file has the same mtime; the scan order must not decide which survives
+        for rotation in 0..files.len() {
+            let mut shuffled = files.clone();
+            shuffled.rotate_left(rotation);
+            let set = DuplicateSet {
+                files: shuffled,
+                size: 4,
+                hash: "h".to_string(),
+                approximate: false,
+                hardlinks: Vec::new(),
+            };
+            let (kept, others) = determine_action_targets_with_tiebreak(
+                &set,
+                SelectionStrategy::NewestModified,
+                &tiebreak,
+            )?;
+            assert_eq!(kept.path, dir.path().join("bb.jpg"));
+            assert_eq!(others.len(), 2);
+
+            // Without tie-breakers the alphabetically first path is kept
+            let (kept, _) = determine_action_targets(&set, SelectionStrategy::NewestModified)?;
+            assert_eq!(kept.path, dir.path().join("bb.jpg"));
+            let (kept, _) = determine_action_targets_with_tiebreak(
+                &set,
+                SelectionStrategy::NewestModified,
+                &[TieBreaker::Name],
+            )?;
+            assert_eq!(kept.path, nested.join("a.jpg"));
+        }
 
-                let has_target_file = set
-                    .files
-                    .iter()
-                    .any(|file| file.path.starts_with(&target_dir));
+        assert_eq!(parse_tiebreaker("PATH"), Ok(TieBreaker::ShortestPath));
+        assert!(parse_tiebreaker("random").is_err());
+        use clap::Parser;
+        let cli = Cli::parse_from(["dedups", "--tiebreak", "name,oldest", "."]);
+        assert_eq!(cli.tiebreak, vec![TieBreaker::Name, TieBreaker::Oldest]);
+        Ok(())
+    }
 
-                has_source_file && has_target_file
+    #[test]
+    fn test_prefer_directory_selection() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let library = dir.path().join("library");
+        let inbox = dir.path().join("inbox");
+        fs::create_dir_all(&library)?;
+        fs::create_dir_all(&inbox)?;
+
+        let base = SystemTime::now() - std::time::Duration::from_secs(3600);
+        let make = |path: PathBuf, age_secs: u64| -> Result<FileInfo> {
+            fs::write(&path, b"same")?;
+            let mtime = base - std::time::Duration::from_secs(age_secs);
+            filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(mtime))?;
+            Ok(FileInfo {
+                path,
+                size: 4,
+                hash: None,
+                modified_at: Some(mtime),
+                created_at: None,
             })
-            .collect();
+        };
+        let set_of = |files: Vec<FileInfo>| DuplicateSet {
+            files,
+            size: 4,
+            hash: "h".to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        let strategy = SelectionStrategy::PreferDirectory(library.clone());
+
+        // One file under keep-dir: it is kept even though it is the oldest
+        let set = set_of(vec![
+            make(inbox.join("a.jpg"), 0)?,
+            make(library.join("a.jpg"), 500)?,
+        ]);
+        let (kept, others) = determine_action_targets(&set, strategy.clone())?;
+        assert_eq!(kept.path, library.join("a.jpg"));
+        assert_eq!(others.len(), 1);
+
+        // Several under keep-dir: the newest of those wins
+        let set = set_of(vec![
+            make(library.join("old.jpg"), 300)?,
+            make(library.join("new.jpg"), 100)?,
+            make(inbox.join("newest.jpg"), 0)?,
+        ]);
+        let (kept, others) = determine_action_targets(&set, strategy.clone())?;
+        assert_eq!(kept.path, library.join("new.jpg"));
+        assert_eq!(others.len(), 2);
+
+        // None under keep-dir: fall back to newest overall
+        let set = set_of(vec![
+            make(inbox.join("x.jpg"), 200)?,
+            make(inbox.join("y.jpg"), 10)?,
+        ]);
+        let (kept, _) = determine_action_targets(&set, strategy)?;
+        assert_eq!(kept.path, inbox.join("y.jpg"));
 
-        all_duplicate_sets = cross_dir_duplicates;
-        log::info!(
-            "Found {} duplicate sets spanning source and target directories",
-            all_duplicate_sets.len()
+        assert_eq!(
+            SelectionStrategy::from_str("prefer_directory:/Photos/Library")?,
+            SelectionStrategy::PreferDirectory(PathBuf::from("/Photos/Library"))
         );
+        assert!(SelectionStrategy::from_str("prefer_directory:").is_err());
+        Ok(())
     }
 
-    Ok(DirectoryComparisonResult {
-        missing_in_target: missing_files,
-        duplicates: all_duplicate_sets,
-    })
-}
+    #[test]
+    fn test_protected_files_are_kept_and_never_actioned() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let originals = dir.path().join("Originals");
+        let imports = dir.path().join("imports");
+        fs::create_dir_all(&originals)?;
+        fs::create_dir_all(&imports)?;
+        let base = SystemTime::now() - std::time::Duration::from_secs(3600);
+        let make = |path: PathBuf, age_secs: u64| -> Result<FileInfo> {
+            fs::write(&path, "photo")?;
+            let mtime = base - std::time::Duration::from_secs(age_secs);
+            filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(mtime))?;
+            Ok(FileInfo {
+                path,
+                size: 5,
+                hash: Some("h".to_string()),
+                modified_at: Some(mtime),
+                created_at: None,
+            })
+        };
+        let set = DuplicateSet {
+            files: vec![
+                make(imports.join("newest.jpg"), 0)?,
+                make(originals.join("a.jpg"), 300)?,
+                make(originals.join("b.jpg"), 100)?,
+                make(imports.join("older.jpg"), 200)?,
+            ],
+            size: 5,
+            hash: "h".to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        let protected = ProtectedPaths::new(std::slice::from_ref(&originals));
+
+        // Newest overall is in imports, but the kept file must be a protected copy
+        let (kept, actioned) = determine_protected_action_targets(
+            &set,
+            SelectionStrategy::NewestModified,
+            &[],
+            &protected,
+        )?;
+        assert_eq!(kept.path, originals.join("b.jpg"));
+        let actioned: Vec<_> = actioned.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            actioned,
+            vec![imports.join("newest.jpg"), imports.join("older.jpg")]
+        );
+        assert!(!actioned.iter().any(|p| protected.contains(p)));
 
-// Scans a single directory and returns FileInfo objects with hashes
-fn scan_directory(cli: &Cli, directory: &Path) -> Result<Vec<FileInfo>> {
-    let filter_rules = FilterRules::new(cli)?;
+        // A set made only of protected files is refused outright
+        let all_protected = DuplicateSet {
+            files: set.files[1..3].to_vec(),
+            ..set.clone()
+        };
+        assert!(protected.protects_all(&all_protected.files));
+        assert!(determine_protected_action_targets(
+            &all_protected,
+            SelectionStrategy::NewestModified,
+            &[],
+            &protected
+        )
+        .is_err());
+
+        // Without protection the usual strategy applies
+        let (kept, _) = determine_protected_action_targets(
+            &set,
+            SelectionStrategy::NewestModified,
+            &[],
+            &ProtectedPaths::default(),
+        )?;
+        assert_eq!(kept.path, imports.join("newest.jpg"));
+        Ok(())
+    }
 
-    let mut files = Vec::new();
-    let walker = WalkDir::new(directory).into_iter();
+    #[test]
+    fn test_size_selection_strategies() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let base = SystemTime::now() - std::time::Duration::from_secs(3600);
+        let make = |name: &str, len: usize, age_secs: u64| -> Result<FileInfo> {
+            let path = dir.path().join(name);
+            fs::write(&path, vec![b'x'; len])?;
+            let mtime = base - std::time::Duration::from_secs(age_secs);
+            filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(mtime))?;
+            Ok(FileInfo {
+                path,
+                size: len as u64,
+                hash: None,
+                modified_at: Some(mtime),
+                created_at: None,
+            })
+        };
+        // A media/approximate set whose members differ in size
+        let set = DuplicateSet {
+            files: vec![
+                make("thumb.jpg", 100, 0)?,
+                make("full_old.jpg", 900, 200)?,
+                make("full_new.jpg", 900, 50)?,
+                make("tiny_old.jpg", 10, 300)?,
+            ],
+            size: 900,
+            hash: "media".to_string(),
+            approximate: true,
+            hardlinks: Vec::new(),
+        };
 
-    for entry in walker
-        .filter_entry(|e| {
-            if is_hidden(e) || is_symlink(e) {
-                return false;
-            }
-            if let Some(path_str) = e.path().to_str() {
-                filter_rules.is_match(path_str)
-            } else {
-                false
-            }
-        })
-        .flatten()
-    {
-        if entry.file_type().is_file() {
-            let path = entry.path().to_path_buf();
-            match fs::metadata(&path) {
-                Ok(metadata) => {
-                    if metadata.len() > 0 {
-                        let size = metadata.len();
+        let (kept, others) = determine_action_targets(&set, SelectionStrategy::LargestSize)?;
+        assert_eq!(
+            kept.path,
+            dir.path().join("full_new.jpg"),
+            "newest wins ties"
+        );
+        assert_eq!(others.len(), 3);
 
-                        // Calculate hash
-                        let hash = match calculate_hash(&path, &cli.algorithm) {
-                            Ok(h) => Some(h),
-                            Err(e) => {
-                                log::warn!("Failed to hash file {:?}: {}", path, e);
-                                None
-                            }
-                        };
+        let (kept, _) = determine_action_targets(&set, SelectionStrategy::SmallestSize)?;
+        assert_eq!(kept.path, dir.path().join("tiny_old.jpg"));
 
-                        let file_info = FileInfo {
-                            path,
-                            size,
-                            hash,
-                            modified_at: metadata.modified().ok(),
-                            created_at: metadata.created().ok(),
-                        };
+        assert_eq!(
+            SelectionStrategy::from_str("largest_size")?,
+            SelectionStrategy::LargestSize
+        );
+        assert_eq!(
+            SelectionStrategy::from_str("smallest")?,
+            SelectionStrategy::SmallestSize
+        );
+        Ok(())
+    }
 
-                        files.push(file_info);
-                    }
-                }
-                Err(e) => log::warn!("Failed to get metadata for {:?}: {}", path, e),
-            }
+    #[test]
+    fn test_discovery_independent_of_io_thread_count() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut paths = Vec::new();
+        for i in 0..40 {
+            let path = dir.path().join(format!("f{:02}.dat", i));
+            fs::write(&path, "x".repeat(i % 7))?;
+            paths.push(path);
         }
-    }
+        paths.push(dir.path().join("vanished.dat"));
+        let rules = filter_rules_from_args(&["--min-size", "2"])?;
+
+        let discover = |threads: usize| -> Result<Vec<(u64, Vec<PathBuf>)>> {
+            let found = collect_file_metadata(paths.clone(), &rules, true, threads)?;
+            // Empty files fall below --min-size
+            assert!(found.empty_files.is_empty());
+            let mut by_size: Vec<_> = found.files_by_size.into_iter().collect();
+            by_size.sort();
+            Ok(by_size)
+        };
 
-    log::info!("Found {} files in directory: {:?}", files.len(), directory);
-    Ok(files)
-}
+        let single = discover(1)?;
+        assert_eq!(single, discover(8)?);
+        // Sizes 2..=6 survive --min-size; the missing file is skipped, not an error
+        assert_eq!(single.len(), 5);
+        Ok(())
+    }
 
-// Copy missing files to target directory
-pub fn copy_missing_files(
-    missing_files: &[FileInfo],
-    target_dir: &Path,
-    dry_run: bool,
-) -> Result<(usize, Vec<String>)> {
-    let mut count = 0;
-    let mut logs = Vec::new();
+    #[test]
+    fn test_files_are_identical() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        // Larger than one read chunk, differing only in the final byte
+        let mut content = vec![7u8; VERIFY_CHUNK_SIZE * 2 + 10];
+        let a = dir.path().join("a.bin");
+        let b = dir.path().join("b.bin");
+        let c = dir.path().join("c.bin");
+        fs::write(&a, &content)?;
+        fs::write(&b, &content)?;
+        *content.last_mut().unwrap() = 8;
+        fs::write(&c, &content)?;
+
+        assert!(files_are_identical(&a, &b)?);
+        assert!(!files_are_identical(&a, &c)?);
+        fs::write(&c, "short")?;
+        assert!(!files_are_identical(&a, &c)?);
+        Ok(())
+    }
 
-    if !target_dir.exists() {
-        if dry_run {
-            let msg = format!(
-                "[DRY RUN] Target directory {} does not exist. Would create it.",
-                target_dir.display()
-            );
-            log::info!("{}", msg);
-            logs.push(msg);
-        } else {
-            log::info!(
-                "Target directory {:?} does not exist. Creating it.",
-                target_dir
-            );
-            fs::create_dir_all(target_dir)?;
-            logs.push(format!(
-                "Created target directory: {}",
-                target_dir.display()
-            ));
+    #[test]
+    fn test_verify_splits_simulated_hash_collision() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut files = Vec::new();
+        for (name, content) in [
+            ("a1", "AAAA"),
+            ("b1", "BBBB"),
+            ("a2", "AAAA"),
+            ("c1", "CCCC"),
+        ] {
+            let path = dir.path().join(name);
+            fs::write(&path, content)?;
+            files.push(FileInfo {
+                path,
+                size: 4,
+                hash: Some("collide".to_string()),
+                modified_at: None,
+                created_at: None,
+            });
         }
-    } else if !target_dir.is_dir() {
-        return Err(anyhow::anyhow!(
-            "Target path {:?} exists but is not a directory.",
-            target_dir
-        ));
-    }
+        // Four same-size files forced into one set, as a colliding weak hash would
+        let set = DuplicateSet {
+            files: files.clone(),
+            size: 4,
+            hash: "collide".to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
 
-    if dry_run {
-        logs.push(format!(
-            "[DRY RUN] Would copy {} missing files to {}",
-            missing_files.len(),
-            target_dir.display()
-        ));
+        let split = verify_duplicate_set(&set)?;
+        assert_eq!(split.len(), 1, "b1 and c1 have no real duplicate");
+        let paths: Vec<_> = split[0].files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, vec![files[0].path.clone(), files[2].path.clone()]);
 
-        for file in missing_files {
-            let relative_path = match file
-                .path
-                .strip_prefix(file.path.parent().unwrap().parent().unwrap())
-            {
-                Ok(rel) => rel.to_path_buf(),
-                Err(_) => {
-                    // If we can't determine a good relative path, just use the filename
-                    PathBuf::from(file.path.file_name().unwrap_or_default())
-                }
-            };
+        let (verified, logs) = verify_duplicate_sets(std::slice::from_ref(&set));
+        assert_eq!(verified.len(), 1);
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].contains("Hash collision"));
 
-            let target_path = target_dir.join(relative_path);
+        // A genuine set passes through unchanged and silently
+        let genuine = DuplicateSet {
+            files: vec![files[0].clone(), files[2].clone()],
+            ..set
+        };
+        let (verified, logs) = verify_duplicate_sets(std::slice::from_ref(&genuine));
+        assert_eq!(verified.len(), 1);
+        assert_eq!(verified[0].files, genuine.files);
+        assert!(logs.is_empty());
+        Ok(())
+    }
 
-            logs.push(format!(
-                "[DRY RUN] Would copy {} to {}",
-                file.path.display(),
-                target_path.display()
-            ));
-            log::info!("[DRY RUN] Would copy {:?} to {:?}", file.path, target_path);
-            count += 1;
-        }
-    } else {
-        logs.push(format!(
-            "Copying {} missing files to {}",
-            missing_files.len(),
-            target_dir.display()
-        ));
+    #[test]
+    fn test_auto_algorithm_verifies_collisions_before_action() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        fs::create_dir(&root)?;
+        fs::write(root.join("a.txt"), "same bytes")?;
+        fs::write(root.join("b.txt"), "same bytes")?;
+        fs::write(root.join("c.txt"), "diff bytes")?;
+
+        use clap::Parser;
+        let cli = Cli::parse_from(["dedups", "--algorithm", "auto", root.to_str().unwrap()]);
+        assert!(cli.verify_before_action());
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = find_duplicate_files_with_progress(&cli, tx)?;
+        assert_eq!(sets.len(), 1);
+        assert_eq!(
+            sets[0].hash,
+            calculate_hash(&root.join("a.txt"), AUTO_HASH_ALGORITHM)?
+        );
+
+        // Force c.txt into the set as if the fast hash had collided; an auto-mode delete
+        // verifies the set first, so c.txt is split out and survives
+        let mut collided = sets[0].clone();
+        collided.files.push(FileInfo {
+            path: root.join("c.txt"),
+            ..collided.files[0].clone()
+        });
+        let delete_cli = Cli::parse_from([
+            "dedups",
+            "--algorithm",
+            "auto",
+            "--delete",
+            "--mode",
+            "shortest_path",
+            root.to_str().unwrap(),
+        ]);
+        let totals = crate::actions::apply_actions(&delete_cli, &[collided], false)?;
+        assert_eq!(totals.deleted, 1);
+        assert!(root.join("c.txt").exists());
+        assert_ne!(
+            root.join("a.txt").exists(),
+            root.join("b.txt").exists(),
+            "exactly one of the real duplicates is deleted"
+        );
+        Ok(())
+    }
 
-        for file in missing_files {
-            let relative_path = match file
-                .path
-                .strip_prefix(file.path.parent().unwrap().parent().unwrap())
-            {
-                Ok(rel) => rel.to_path_buf(),
-                Err(_) => {
-                    // If we can't determine a good relative path, just use the filename
-                    PathBuf::from(file.path.file_name().unwrap_or_default())
-                }
-            };
+    #[test]
+    fn test_incremental_rescan_only_rehashes_changed_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        let cache_dir = dir.path().join("cache");
+        fs::create_dir(&root)?;
+        for name in ["a.txt", "b.txt", "c.txt", "d.txt"] {
+            fs::write(root.join(name), "identical contents")?;
+        }
 
-            let target_path = target_dir.join(relative_path);
+        use clap::Parser;
+        let cli = Cli::parse_from([
+            "dedups",
+            "--incremental",
+            "--cache-location",
+            cache_dir.to_str().unwrap(),
+            root.to_str().unwrap(),
+        ]);
+        let scan = || -> Result<(Vec<DuplicateSet>, String)> {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let sets = find_duplicate_files_with_progress(&cli, tx)?;
+            let summary = rx
+                .try_iter()
+                .filter_map(|msg| match msg {
+                    ScanMessage::StatusUpdate(_, text) if text.starts_with("Incremental") => {
+                        Some(text)
+                    }
+                    _ => None,
+                })
+                .last()
+                .expect("incremental summary");
+            Ok((sets, summary))
+        };
 
-            // Ensure parent directory exists
-            if let Some(parent) = target_path.parent() {
-                if !parent.exists() {
-                    fs::create_dir_all(parent)?;
-                    let msg = format!("Created parent directory: {}", parent.display());
-                    logs.push(msg.clone());
-                    log::debug!("{}", msg);
-                }
-            }
+        let (sets, summary) = scan()?;
+        assert_eq!(sets[0].files.len(), 4);
+        assert!(summary.contains("skipped 0 unchanged"), "{}", summary);
+        assert!(summary.contains("hashed 4 new or changed"), "{}", summary);
+
+        // Same length, different bytes, newer mtime; and one cached file goes away
+        fs::write(root.join("c.txt"), "different contents")?;
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(120);
+        filetime::set_file_mtime(
+            root.join("c.txt"),
+            filetime::FileTime::from_system_time(later),
+        )?;
+        fs::remove_file(root.join("d.txt"))?;
+
+        let (sets, summary) = scan()?;
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].files.len(), 2);
+        assert!(summary.contains("skipped 2 unchanged"), "{}", summary);
+        assert!(summary.contains("hashed 1 new or changed"), "{}", summary);
+        assert!(summary.contains("dropped 2 stale"), "{}", summary);
+        Ok(())
+    }
 
-            match fs::copy(&file.path, &target_path) {
-                Ok(_) => {
-                    let msg = format!(
-                        "Copied: {} -> {}",
-                        file.path.display(),
-                        target_path.display()
-                    );
-                    logs.push(msg.clone());
-                    log::info!("{}", msg);
-                    count += 1;
-                }
-                Err(e) => {
-                    let error_msg = format!(
-                        "Failed to copy {} to {}: {}",
-                        file.path.display(),
-                        target_path.display(),
-                        e
-                    );
-                    logs.push(error_msg.clone());
-                    log::error!("{}", error_msg);
-                    // Continue with other files
-                }
+    #[test]
+    fn test_deterministic_scans_serialize_identically() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        for sub in ["z", "m", "a"] {
+            fs::create_dir_all(root.join(sub))?;
+            for i in 0..8 {
+                fs::write(
+                    root.join(sub).join(format!("{}.txt", i)),
+                    format!("set {}", i),
+                )?;
             }
         }
-    }
 
-    Ok((count, logs))
-}
+        use clap::Parser;
+        let cli = Cli::parse_from(["dedups", "--deterministic", root.to_str().unwrap()]);
+        let scan = || -> Result<String> {
+            let (tx, _rx) = std::sync::mpsc::channel();
+            let sets = find_duplicate_files_with_progress(&cli, tx)?;
+            Ok(serde_json::to_string(&sets)?)
+        };
 
-// Add this new function for counting files in a directory
-pub fn count_files_in_directory(directory: &Path, filter_rules: &FilterRules) -> Result<usize> {
-    let mut count = 0;
-    let walker = WalkDir::new(directory).into_iter();
+        let first = scan()?;
+        assert_eq!(first, scan()?);
+        let sets: Vec<DuplicateSet> = serde_json::from_str(&first)?;
+        assert_eq!(sets.len(), 8);
+        assert!(sets.windows(2).all(|w| w[0].hash <= w[1].hash));
+        for set in &sets {
+            let paths: Vec<&PathBuf> = set.files.iter().map(|f| &f.path).collect();
+            assert!(paths.windows(2).all(|w| w[0] < w[1]), "{:?}", paths);
+            assert!(paths[0].starts_with(root.join("a")));
+        }
+        Ok(())
+    }
 
-    for entry in walker
-        .filter_entry(|e| {
-            if is_hidden(e) || is_symlink(e) {
-                return false;
-            }
-            if let Some(path_str) = e.path().to_str() {
-                filter_rules.is_match(path_str)
-            } else {
-                false
-            }
-        })
-        .flatten()
-    {
-        if entry.file_type().is_file() {
-            count += 1;
+    #[test]
+    fn test_refresh_cache_rehashes_and_corrects_wrong_entry() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        let cache_dir = dir.path().join("cache");
+        fs::create_dir(&root)?;
+        fs::write(root.join("a.txt"), "identical contents")?;
+        fs::write(root.join("b.txt"), "identical contents")?;
+
+        use clap::Parser;
+        let cli_with = |flag: &str| {
+            Cli::parse_from([
+                "dedups",
+                flag,
+                "--no-prefilter",
+                "--cache-location",
+                cache_dir.to_str().unwrap(),
+                root.to_str().unwrap(),
+            ])
+        };
+        let scan = |cli: &Cli| -> Result<Vec<DuplicateSet>> {
+            let (tx, _rx) = std::sync::mpsc::channel();
+            find_duplicate_files_with_progress(cli, tx)
+        };
+        assert_eq!(scan(&cli_with("--fast-mode"))?.len(), 1);
+
+        // Corrupt a.txt's entry while its size and mtime still match the file
+        let cached_hash = || -> Result<Option<String>> {
+            let cache = crate::file_cache::open_cache(
+                "file",
+                &cache_dir,
+                "xxhash",
+                crate::file_cache::DEFAULT_LOCK_TIMEOUT,
+            )?;
+            Ok(cache
+                .get_file_info(&root.join("a.txt"))
+                .and_then(|info| info.hash))
+        };
+        {
+            let mut cache = crate::file_cache::open_cache(
+                "file",
+                &cache_dir,
+                "xxhash",
+                crate::file_cache::DEFAULT_LOCK_TIMEOUT,
+            )?;
+            let mut info = cache.get_file_info(&root.join("a.txt")).unwrap();
+            info.hash = Some("0000000000000bad".to_string());
+            cache.store(&info, "xxhash")?;
+            cache.save()?;
         }
+        assert_eq!(cached_hash()?.as_deref(), Some("0000000000000bad"));
+        assert!(
+            scan(&cli_with("--fast-mode"))?.is_empty(),
+            "fast mode trusts the wrong entry"
+        );
+
+        let sets = scan(&cli_with("--refresh-cache"))?;
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].files.len(), 2);
+        let expected = calculate_hash(&root.join("a.txt"), "xxhash")?;
+        assert_eq!(cached_hash()?, Some(expected));
+        assert_eq!(scan(&cli_with("--fast-mode"))?.len(), 1);
+        Ok(())
     }
 
-    Ok(count)
-}
+    #[test]
+    fn test_override_sets_are_verified_by_their_own_algorithm() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = |name: &str, content: &str| -> Result<FileInfo> {
+            let path = dir.path().join(name);
+            fs::write(&path, content)?;
+            Ok(FileInfo {
+                path,
+                size: content.len() as u64,
+                hash: None,
+                modified_at: None,
+                created_at: None,
+            })
+        };
+        let set = |hash: &str, files: Vec<FileInfo>| DuplicateSet {
+            size: files[0].size,
+            files,
+            hash: hash.to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        assert_eq!(override_algorithm("crc32:1a2b"), Some("crc32"));
+        assert_eq!(override_algorithm("xxhash@1048576:1a2b"), Some("xxhash"));
+        assert_eq!(override_algorithm("1a2b"), None);
+
+        use clap::Parser;
+        let root = dir.path().to_str().unwrap();
+        let sha256 = Cli::parse_from(["dedups", "--algorithm", "sha256", "--delete", root]);
+        let weak = set(
+            "fnv1a:1a2b",
+            vec![file("a.log", "aaaa")?, file("b.log", "bbbb")?],
+        );
+        assert!(!sha256.verify_before_action());
+        assert!(sha256.verify_set_before_action(&weak));
+        assert!(!sha256.verify_set_before_action(&set("1a2b", weak.files.clone())));
+        let fast = set("xxhash:1a2b", weak.files.clone());
+        assert!(!sha256.verify_set_before_action(&fast));
+        let verify = Cli::parse_from(["dedups", "--algorithm", "blake3", "--verify", root]);
+        assert!(verify.verify_set_before_action(&fast));
+
+        // The fnv1a override "matched" two different files; nothing may be deleted
+        let totals = crate::actions::apply_actions(&sha256, &[weak], false)?;
+        assert_eq!(totals.deleted, 0);
+        assert!(dir.path().join("a.log").exists() && dir.path().join("b.log").exists());
+        Ok(())
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
+    #[test]
+    fn test_hash_overrides_apply_per_extension() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        fs::create_dir(&root)?;
+        // The videos only differ past their first 4 KiB, the documents are identical
+        let mut video: Vec<u8> = (0..16 * 1024u32).map(|i| (i % 251) as u8).collect();
+        fs::write(root.join("a.mov"), &video)?;
+        video[8000] ^= 0xff;
+        fs::write(root.join("b.mov"), &video)?;
+        fs::write(root.join("c.pdf"), "same document")?;
+        fs::write(root.join("d.pdf"), "same document")?;
+        fs::write(root.join("e.txt"), "plain text!!!")?;
+        fs::write(root.join("f.txt"), "plain text!!!")?;
+
+        let config: crate::config::DedupConfig = toml::from_str(
+            r#"
+            [hash_overrides.MOV]
+            algorithm = "xxhash"
+            hash_limit = 4096
+
+            [hash_overrides.pdf]
+            algorithm = "blake3"
+            "#,
+        )?;
+        use clap::Parser;
+        let mut cli = Cli::parse_from(["dedups", "--algorithm", "sha1", root.to_str().unwrap()]);
+        cli.apply_config(config);
+        assert!(cli.hash_overrides.contains_key("mov"));
+
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = find_duplicate_files_with_progress(&cli, tx)?;
+        assert_eq!(sets.len(), 3);
+        let set_for = |ext: &str| {
+            sets.iter()
+                .find(|s| s.files[0].path.extension().unwrap() == ext)
+                .unwrap()
+        };
 
-    fn create_test_file(content: &[u8]) -> NamedTempFile {
-        let mut file = NamedTempFile::new().unwrap();
-        file.write_all(content).unwrap();
-        file
+        let videos = set_for("mov");
+        assert_eq!(
+            videos.hash,
+            format!(
+                "xxhash@4096:{}",
+                calculate_hash_with_limit(&root.join("a.mov"), "xxhash", Some(4096))?
+            )
+        );
+        assert!(videos.approximate);
+
+        let documents = set_for("pdf");
+        assert_eq!(
+            documents.hash,
+            format!("blake3:{}", calculate_hash(&root.join("c.pdf"), "blake3")?)
+        );
+        assert!(!documents.approximate);
+
+        let text = set_for("txt");
+        assert_eq!(text.hash, calculate_hash(&root.join("e.txt"), "sha1")?);
+        Ok(())
     }
 
     #[test]
-    fn test_md5_hash() {
-        let test_content = b"The quick brown fox jumps over the lazy dog";
-        let file = create_test_file(test_content);
-        let hash = calculate_hash(file.path(), "md5").unwrap();
-        assert_eq!(hash, "9e107d9d372bb6826bd81d3542a419d6");
+    fn test_cancelled_scan_returns_partial_results() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        fs::create_dir(&root)?;
+        for i in 0..2000 {
+            let content = format!("pair {}", i);
+            fs::write(root.join(format!("{}_a.txt", i)), &content)?;
+            fs::write(root.join(format!("{}_b.txt", i)), &content)?;
+        }
+
+        use clap::Parser;
+        let cli = Cli::parse_from(["dedups", root.to_str().unwrap()]);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = std::sync::mpsc::channel();
+        // Cancel as soon as discovery starts reporting progress
+        let watcher_cancel = cancel.clone();
+        let watcher = std::thread::spawn(move || {
+            let mut messages = Vec::new();
+            for message in rx {
+                if let ScanMessage::StatusUpdate(stage, text) = message {
+                    if stage == 1 {
+                        watcher_cancel.store(true, Ordering::Relaxed);
+                    }
+                    messages.push(text);
+                }
+            }
+            messages
+        });
+
+        let started = std::time::Instant::now();
+        let (sets, _) = find_duplicate_files_cancellable(&cli, tx, &cancel)?;
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        assert!(sets.len() < 2000, "scan ran to completion");
+        for set in &sets {
+            assert!(set.files.len() >= 2);
+        }
+
+        let messages = watcher.join().unwrap();
+        assert!(messages.iter().any(|m| m.starts_with("Scan cancelled")));
+        Ok(())
     }
 
     #[test]
-    fn test_sha1_hash() {
-        let test_content = b"The quick brown fox jumps over the lazy dog";
-        let file = create_test_file(test_content);
-        let hash = calculate_hash(file.path(), "sha1").unwrap();
-        assert_eq!(hash, "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12");
+    fn test_summary_only_lists_totals_without_paths() -> Result<()> {
+        let file = |path: &str| FileInfo {
+            path: PathBuf::from(path),
+            size: 1000,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let set = |hash: &str, paths: &[&str]| DuplicateSet {
+            files: paths.iter().map(|p| file(p)).collect(),
+            size: 1000,
+            hash: hash.to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        let sets = vec![
+            set("aa", &["/photos/a.jpg", "/backup/a.jpg", "/old/a.jpg"]),
+            set("bb", &["/docs/b.pdf", "/backup/b.pdf"]),
+        ];
+
+        let mut out = Vec::new();
+        write_duplicate_summary(&sets, true, &mut out)?;
+        let text = String::from_utf8(out)?;
+        assert!(
+            text.contains("Found 2 sets of duplicate files (5 files, 3000 bytes reclaimable)"),
+            "{}",
+            text
+        );
+        assert!(text.contains(".jpg"), "{}", text);
+        assert!(!text.contains('/'), "no paths expected: {}", text);
+
+        let event =
+            crate::json_events::JsonOutput::final_result(&sets, Vec::new(), None).without_listing();
+        let json = serde_json::to_value(&event)?;
+        assert_eq!(json["total_sets"], 2);
+        assert_eq!(json["duplicate_files"], 5);
+        assert_eq!(json["reclaimable_bytes"], 3000);
+        assert_eq!(json["duplicate_sets"], serde_json::json!([]));
+        assert!(!json.to_string().contains("/photos/"));
+        Ok(())
     }
 
     #[test]
-    fn test_sha256_hash() {
-        let test_content = b"The quick brown fox jumps over the lazy dog";
-        let file = create_test_file(test_content);
-        let hash = calculate_hash(file.path(), "sha256").unwrap();
+    fn test_order_by_sorts_sets_by_reclaimable_size() {
+        let set = |hash: &str, size: u64, paths: &[&str]| DuplicateSet {
+            files: paths
+                .iter()
+                .map(|p| FileInfo {
+                    path: PathBuf::from(p),
+                    size,
+                    hash: None,
+                    modified_at: None,
+                    created_at: None,
+                })
+                .collect(),
+            size,
+            hash: hash.to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        let mut sets = vec![
+            // 100 bytes reclaimable
+            set("small", 100, &["/c/1", "/c/2"]),
+            // 3 * 50 = 150 bytes reclaimable, though each file is the smallest
+            set("many", 50, &["/b/1", "/b/2", "/b/3", "/b/4"]),
+            // 1000 bytes reclaimable
+            set("big", 1000, &["/a/1", "/a/2"]),
+        ];
+        let order = |sets: &[DuplicateSet]| -> Vec<String> {
+            sets.iter().map(|s| s.hash.clone()).collect()
+        };
+
+        sort_duplicate_sets(&mut sets, SetOrder::Size, SortOrder::Descending);
+        assert_eq!(order(&sets), ["big", "many", "small"]);
+        sort_duplicate_sets(&mut sets, SetOrder::Size, SortOrder::Ascending);
+        assert_eq!(order(&sets), ["small", "many", "big"]);
+        sort_duplicate_sets(&mut sets, SetOrder::Count, SortOrder::Descending);
+        assert_eq!(order(&sets), ["many", "big", "small"]);
+        sort_duplicate_sets(&mut sets, SetOrder::Path, SortOrder::Ascending);
+        assert_eq!(order(&sets), ["big", "many", "small"]);
+    }
+
+    #[test]
+    fn test_summarize_by_extension() {
+        let file = |path: &str, size: u64| FileInfo {
+            path: PathBuf::from(path),
+            size,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let set = |files: Vec<FileInfo>| DuplicateSet {
+            size: files[0].size,
+            hash: files[0].path.display().to_string(),
+            files,
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        let sets = vec![
+            set(vec![
+                file("/a/clip.MOV", 1000),
+                file("/b/clip.mov", 1000),
+                file("/c/clip.mov", 1000),
+            ]),
+            set(vec![file("/a/notes.txt", 10), file("/b/notes.txt", 10)]),
+            // A renamed copy counts against the extension it was given
+            set(vec![file("/a/photo.jpg", 300), file("/b/photo.jpeg", 300)]),
+            set(vec![file("/a/Makefile", 50), file("/b/Makefile", 50)]),
+        ];
+
+        let stats = summarize_by_extension(&sets);
+        let order: Vec<&str> = stats.iter().map(|s| s.extension.as_str()).collect();
+        assert_eq!(order, vec!["mov", "jpeg", NO_EXTENSION, "txt", "jpg"]);
+
         assert_eq!(
-            hash,
-            "d7a8fbb307d7809469ca9abcb0082e4f8d5651e46d3cdb762d02d0bf37c9e592"
+            stats[0],
+            ExtensionStat {
+                extension: "mov".to_string(),
+                sets: 1,
+                files: 3,
+                redundant_bytes: 2000,
+            }
         );
+        assert_eq!(stats[1].redundant_bytes, 300);
+        assert_eq!((stats[2].files, stats[2].redundant_bytes), (2, 50));
+        assert_eq!(stats[4].files, 1);
+        assert_eq!(stats[4].redundant_bytes, 0);
     }
 
     #[test]
-    fn test_blake3_hash() {
-        let test_content = b"The quick brown fox jumps over the lazy dog";
-        let file = create_test_file(test_content);
-        let hash = calculate_hash(file.path(), "blake3").unwrap();
-        // Blake3 has a different hash length and value
-        assert_eq!(hash.len(), 64);
-        // Update the expected hash value with the actual one from our implementation
+    fn test_summarize_by_directory_splits_counts_per_input_dir() {
+        let file = |path: &str| FileInfo {
+            path: PathBuf::from(path),
+            size: 100,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let set = |hash: &str, paths: &[&str]| DuplicateSet {
+            files: paths.iter().map(|p| file(p)).collect(),
+            size: 100,
+            hash: hash.to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        let sets = vec![
+            // One copy on each drive: each could drop its own
+            set("aa", &["/drive1/a.jpg", "/drive2/a.jpg"]),
+            // Three copies on drive1 and one on drive2
+            set(
+                "bb",
+                &[
+                    "/drive1/b.jpg",
+                    "/drive1/x/b.jpg",
+                    "/drive1/y/b.jpg",
+                    "/drive2/b.jpg",
+                ],
+            ),
+            // Only on drive2: one copy has to stay
+            set("cc", &["/drive2/c.jpg", "/drive2/old/c.jpg"]),
+        ];
+
+        let stats =
+            summarize_by_directory(&sets, &[PathBuf::from("/drive2"), PathBuf::from("/drive1")]);
         assert_eq!(
-            hash,
-            "2f1514181aadccd913abd94cfa592701a5686ab23f8df1dff1b74710febc6d4a"
+            stats,
+            vec![
+                DirDuplicateStat {
+                    directory: PathBuf::from("/drive1"),
+                    files: 4,
+                    redundant_bytes: 400,
+                },
+                DirDuplicateStat {
+                    directory: PathBuf::from("/drive2"),
+                    files: 4,
+                    redundant_bytes: 300,
+                },
+            ]
         );
     }
 
     #[test]
-    fn test_xxhash() {
-        let test_content = b"The quick brown fox jumps over the lazy dog";
-        let file = create_test_file(test_content);
-        let hash = calculate_hash(file.path(), "xxhash").unwrap();
-        // xxHash has a fixed length of 16 hex characters (64 bits)
-        assert_eq!(hash.len(), 16);
-        // We check the format rather than exact value as implementation might vary
-        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    fn test_reclaimed_space_excludes_kept_files() -> Result<()> {
+        let file = |path: &str, size: u64, age_secs: u64| FileInfo {
+            path: PathBuf::from(path),
+            size,
+            hash: None,
+            modified_at: Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(age_secs)),
+            created_at: None,
+        };
+        let sets = [
+            DuplicateSet {
+                files: vec![
+                    file("/a/1", 100, 1),
+                    file("/a/2", 100, 2),
+                    file("/a/3", 100, 3),
+                ],
+                size: 100,
+                hash: "aaaa".to_string(),
+                approximate: false,
+                hardlinks: Vec::new(),
+            },
+            DuplicateSet {
+                files: vec![file("/b/1", 40, 1), file("/b/2", 40, 2)],
+                size: 40,
+                hash: "bbbb".to_string(),
+                approximate: false,
+                hardlinks: Vec::new(),
+            },
+        ];
+
+        let mut reclaimed = ReclaimedSpace::default();
+        for set in &sets {
+            let (_, to_action) = determine_action_targets(set, SelectionStrategy::NewestModified)?;
+            reclaimed.record(&to_action);
+        }
+        assert_eq!(reclaimed.files, 3);
+        assert_eq!(reclaimed.bytes, 2 * 100 + 40);
+        assert_eq!(
+            reclaimed.summary(true, false),
+            "Reclaimed 240 bytes across 3 files"
+        );
+        assert!(reclaimed
+            .summary(false, true)
+            .starts_with("[DRY RUN] Would have reclaimed"));
+        Ok(())
     }
 
-    #[cfg(feature = "linux")]
     #[test]
-    fn test_gxhash() {
-        let file = create_test_file(b"test content");
-        let hash = calculate_hash(file.path(), "gxhash").unwrap();
-        assert_eq!(hash.len(), 16); // gxhash produces 64-bit output (8 bytes = 16 hex chars)
+    fn test_reclaimed_space_counts_only_linked_files_of_a_failed_batch() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = |path: PathBuf| FileInfo {
+            path,
+            size: 4,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let kept = dir.path().join("kept");
+        let dup = dir.path().join("dup");
+        fs::write(&kept, b"same")?;
+        fs::write(&dup, b"same")?;
+        // Its directory doesn't exist, so this one can't be linked
+        let missing = dir.path().join("gone").join("dup");
+
+        // The linked file is still at its path and the failed one never was, so only the
+        // returned list tells them apart
+        let (actioned, _) = hardlink_files(&file(kept), &[file(dup), file(missing)], false)?;
+        let mut reclaimed = ReclaimedSpace::default();
+        reclaimed.record(&actioned);
+        assert_eq!(reclaimed, ReclaimedSpace { files: 1, bytes: 4 });
+        Ok(())
     }
 
     #[test]
-    fn test_fnv1a() {
-        let test_content = b"The quick brown fox jumps over the lazy dog";
-        let file = create_test_file(test_content);
-        let hash = calculate_hash(file.path(), "fnv1a").unwrap();
-        // FNV-1a has a fixed length of 16 hex characters (64 bits)
-        assert_eq!(hash.len(), 16);
-        // We check the format rather than exact value as implementation might vary
-        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    fn test_hashing_speed_reports_eta_mid_scan() {
+        let start = std::time::Instant::now();
+        let mut speed = HashingSpeed::new(10, start);
+
+        // Too few groups for a trustworthy speed yet
+        speed.record(1, start + std::time::Duration::from_secs(1));
+        let early = speed.progress();
+        assert_eq!(early.estimated_seconds_left, None);
+        assert!((early.percent_complete - 10.0).abs() < 1e-9);
+
+        // One group per second, so the remaining 5 groups take about 5 seconds
+        for done in 2..=5 {
+            speed.record(done, start + std::time::Duration::from_secs(done as u64));
+        }
+        let event = crate::json_events::JsonOutput::hash_progress("hashing", speed.progress());
+        let crate::json_events::JsonOutput::Progress(info) = event else {
+            panic!("expected a progress event");
+        };
+        assert_eq!(info.stage, 3);
+        assert_eq!(info.percent_complete, Some(50.0));
+        let eta = info
+            .estimated_seconds_left
+            .expect("ETA once enough groups are hashed");
+        assert!((eta - 5.0).abs() < 1e-6, "eta {}", eta);
     }
 
     #[test]
-    fn test_crc32() {
-        let test_content = b"The quick brown fox jumps over the lazy dog";
-        let file = create_test_file(test_content);
-        let hash = calculate_hash(file.path(), "crc32").unwrap();
-        // CRC-32 produces a 32-bit value, resulting in 8 hex characters
-        assert_eq!(hash.len(), 8);
-        // We check the format rather than exact value as implementation might vary
-        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    fn test_move_preserving_structure_mirrors_source_tree() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("photos");
+        let nested_root = root.join("imports");
+        fs::create_dir_all(root.join("a/b"))?;
+        fs::create_dir_all(nested_root.join("x"))?;
+        let file = |path: PathBuf| FileInfo {
+            path,
+            size: 4,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let nested = root.join("a/b/c.jpg");
+        let imported = nested_root.join("x/c.jpg");
+        fs::write(&nested, b"same")?;
+        fs::write(&imported, b"same")?;
+        let target = dir.path().join("dups");
+        let roots = vec![root.clone(), nested_root.clone()];
+
+        let (moved, logs) = move_files_preserving_structure(
+            &[file(nested.clone()), file(imported.clone())],
+            &target,
+            &roots,
+            true,
+            ConflictPolicy::Rename,
+            0,
+        )?;
+        assert_eq!(moved.len(), 2);
+        assert!(logs
+            .iter()
+            .any(|l| l.ends_with(&format!("-> {}", target.join("a/b/c.jpg").display()))));
+        assert!(nested.exists(), "dry run must not move anything");
+
+        move_files_preserving_structure(
+            &[file(nested.clone()), file(imported.clone())],
+            &target,
+            &roots,
+            false,
+            ConflictPolicy::Rename,
+            0,
+        )?;
+        assert!(target.join("a/b/c.jpg").is_file());
+        // The innermost scan root applies
+        assert!(target.join("x/c.jpg").is_file());
+        assert!(!nested.exists() && !imported.exists());
+
+        // The flat layout is unchanged
+        fs::write(&nested, b"same")?;
+        move_files(&[file(nested)], &target, false, ConflictPolicy::Rename, 0)?;
+        assert!(target.join("c.jpg").is_file());
+        Ok(())
     }
 
-    // Meow Hash test temporarily removed due to build issues
-    // #[test]
-    // fn test_meow_hash() { ... }
+    #[test]
+    fn test_verify_copies_reports_corrupted_destination() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let source_dir = dir.path().join("source/album");
+        let target = dir.path().join("target");
+        fs::create_dir_all(&source_dir)?;
+        let files: Vec<FileInfo> = ["good.jpg", "bad.jpg"]
+            .iter()
+            .map(|name| {
+                let path = source_dir.join(name);
+                fs::write(&path, format!("pixels of {}", name)).unwrap();
+                FileInfo {
+                    path,
+                    size: 0,
+                    hash: None,
+                    modified_at: None,
+                    created_at: None,
+                }
+            })
+            .collect();
+        let (count, _) =
+            copy_missing_files(&files, &target, false, true, &TransferOptions::default())?;
+        assert_eq!(count, 2);
+
+        let pairs: Vec<(PathBuf, PathBuf)> = files
+            .iter()
+            .map(|f| (f.path.clone(), copy_destination(f, &target)))
+            .collect();
+        assert_eq!(pairs[0].1, target.join("album/good.jpg"));
+        assert!(verify_copies(&pairs, "sha256")?.is_empty());
+
+        // Same length, different bytes
+        fs::write(&pairs[1].1, "pixels of BAD.jpg")?;
+        let mismatches = verify_copies(&pairs, "sha256")?;
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].contains("bad.jpg"));
+
+        fs::remove_file(&pairs[0].1)?;
+        assert_eq!(verify_copies(&pairs, "sha256")?.len(), 2);
+        Ok(())
+    }
 
     #[test]
-    fn test_invalid_algorithm() {
-        let test_content = b"test content";
-        let file = create_test_file(test_content);
-        let result = calculate_hash(file.path(), "invalid_algorithm");
-        assert!(result.is_err());
+    fn test_quarantine_preserves_relative_structure() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("photos");
+        fs::create_dir_all(root.join("2023/trip"))?;
+        let dup = root.join("2023/trip/img.jpg");
+        fs::write(&dup, b"same")?;
+        let quarantine = dir.path().join("review");
+        let file = FileInfo {
+            path: dup.clone(),
+            size: 4,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+
+        let (quarantined, _) =
+            quarantine_files(std::slice::from_ref(&file), &quarantine, &root, false)?;
+        assert_eq!(quarantined.len(), 1);
+        let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let expected = quarantine.join(&date).join("2023/trip/img.jpg");
+        assert!(expected.is_file(), "expected {:?}", expected);
+        assert!(!dup.exists());
+
+        // A second file at the same relative path gets a _copy suffix
+        fs::write(&dup, b"same")?;
+        quarantine_files(&[file], &quarantine, &root, false)?;
+        assert!(quarantine
+            .join(&date)
+            .join("2023/trip/img_copy(1).jpg")
+            .is_file());
+
+        // Files outside the source root keep their full path
+        assert_eq!(
+            quarantine_destination(Path::new("/elsewhere/a.txt"), &quarantine, &root, "d"),
+            quarantine.join("d/elsewhere/a.txt")
+        );
+        Ok(())
     }
 
     #[test]
-    fn test_empty_file() {
-        let test_content = b"";
-        let file = create_test_file(test_content);
+    fn test_top_sets_by_reclaimable() {
+        let set = |size: u64, copies: usize| DuplicateSet {
+            files: (0..copies)
+                .map(|i| FileInfo {
+                    path: PathBuf::from(format!("/d/{}_{}", size, i)),
+                    size,
+                    hash: None,
+                    modified_at: None,
+                    created_at: None,
+                })
+                .collect(),
+            size,
+            hash: format!("h{}", size),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        // Reclaimable: 100, 300 (3 copies of 150), 200
+        let sets = vec![set(100, 2), set(150, 3), set(200, 2)];
 
-        // MD5 empty file hash
-        let hash = calculate_hash(file.path(), "md5").unwrap();
-        assert_eq!(hash, "d41d8cd98f00b204e9800998ecf8427e");
+        let top = top_sets_by_reclaimable(&sets, 2);
+        assert_eq!(
+            top.iter().map(|s| s.size).collect::<Vec<_>>(),
+            vec![150, 200]
+        );
 
-        // SHA1 empty file hash
-        let hash = calculate_hash(file.path(), "sha1").unwrap();
-        assert_eq!(hash, "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        // Asking for more than exist returns every set
+        assert_eq!(top_sets_by_reclaimable(&sets, 10).len(), 3);
+    }
 
-        // SHA256 empty file hash
-        let hash = calculate_hash(file.path(), "sha256").unwrap();
+    #[test]
+    fn test_retain_min_set_size() {
+        let set = |copies: usize| DuplicateSet {
+            files: (0..copies)
+                .map(|i| FileInfo {
+                    path: PathBuf::from(format!("/d/{}_{}", copies, i)),
+                    size: 10,
+                    hash: None,
+                    modified_at: None,
+                    created_at: None,
+                })
+                .collect(),
+            size: 10,
+            hash: format!("h{}", copies),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        let mut sets = vec![set(2), set(3), set(6)];
+
+        retain_min_set_size(&mut sets, 3);
         assert_eq!(
-            hash,
-            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            sets.iter().map(|s| s.files.len()).collect::<Vec<_>>(),
+            vec![3, 6]
         );
+    }
 
-        // Blake3 empty file hash - update with the actual value from our implementation
-        let hash = calculate_hash(file.path(), "blake3").unwrap();
-        let expected_empty_blake3 = hash.clone();
-        assert_eq!(hash, expected_empty_blake3);
+    #[test]
+    fn test_with_retry_recovers_from_transient_errors() {
+        let mut calls = 0;
+        let result = with_retry(
+            || {
+                calls += 1;
+                if calls < 3 {
+                    Err(std::io::Error::from(std::io::ErrorKind::ResourceBusy))
+                } else {
+                    Ok("done")
+                }
+            },
+            3,
+            std::time::Duration::ZERO,
+        );
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(calls, 3);
+
+        // Permanent errors fail straight away
+        let mut calls = 0;
+        let result: std::io::Result<()> = with_retry(
+            || {
+                calls += 1;
+                Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+            },
+            3,
+            std::time::Duration::ZERO,
+        );
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(calls, 1);
+
+        // --retries 0 makes a single attempt, even for transient errors
+        let mut calls = 0;
+        let result: std::io::Result<()> = with_fs_retry(0, || {
+            calls += 1;
+            Err(std::io::Error::from(std::io::ErrorKind::ResourceBusy))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_exclude_same_dir_duplicates() {
+        let file = |path: &str| FileInfo {
+            path: PathBuf::from(path),
+            size: 10,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let set = |hash: &str, paths: &[&str]| DuplicateSet {
+            files: paths.iter().map(|p| file(p)).collect(),
+            size: 10,
+            hash: hash.to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+        let mut sets = vec![
+            set("mixed", &["/a/x.txt", "/a/x_backup.txt", "/b/x.txt"]),
+            set("local", &["/a/y.txt", "/a/y_copy.txt"]),
+        ];
+
+        exclude_same_dir_duplicates(&mut sets);
+        assert_eq!(sets.len(), 1, "the same-folder-only set is dropped");
+        assert_eq!(sets[0].hash, "mixed");
+        assert_eq!(
+            sets[0]
+                .files
+                .iter()
+                .map(|f| f.path.clone())
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("/a/x.txt"), PathBuf::from("/b/x.txt")]
+        );
+    }
+
+    #[test]
+    fn test_since_skips_files_modified_before_threshold() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        fs::create_dir(&root)?;
+        let old = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_685_577_600); // 2023-06-01
+        for name in ["old_a.txt", "old_b.txt"] {
+            let path = root.join(name);
+            fs::write(&path, "old pair")?;
+            filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(old))?;
+        }
+        fs::write(root.join("new_a.txt"), "new pair")?;
+        fs::write(root.join("new_b.txt"), "new pair")?;
+
+        use clap::Parser;
+        for since in ["2024-01-01", "7d"] {
+            let cli = Cli::parse_from(["dedups", "--since", since, root.to_str().unwrap()]);
+            let rules = FilterRules::new(&cli)?;
+            assert_eq!(
+                count_files_in_directory(&root, &rules)?,
+                2,
+                "--since {}",
+                since
+            );
+
+            let (tx, rx) = std::sync::mpsc::channel();
+            let drain = std::thread::spawn(move || rx.into_iter().count());
+            let sets = find_duplicate_files_with_progress(&cli, tx)?;
+            drain.join().unwrap();
+            assert_eq!(sets.len(), 1, "--since {}", since);
+            let mut names: Vec<_> = sets[0]
+                .files
+                .iter()
+                .map(|f| f.path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            assert_eq!(names, ["new_a.txt", "new_b.txt"]);
+        }
+
+        let bad = Cli::try_parse_from(["dedups", "--since", "last tuesday", "."]);
+        assert!(bad.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_each_scan_reports_whether_it_was_sampled() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        fs::create_dir(&root)?;
+        fs::write(root.join("a.txt"), "same")?;
+        fs::write(root.join("b.txt"), "same")?;
+
+        use clap::Parser;
+        let root = root.to_str().unwrap();
+        let scan = |args: &[&str]| {
+            let cli = Cli::parse_from(args);
+            let (tx, _rx) = std::sync::mpsc::channel();
+            find_duplicate_files_cancellable(&cli, tx, &AtomicBool::new(false))
+        };
+        assert!(scan(&["dedups", "--limit", "1", root])?.1);
+        // A later scan of the whole tree isn't flagged by the earlier sample
+        let (sets, sampled) = scan(&["dedups", root])?;
+        assert!(!sampled);
+        assert_eq!(sets.len(), 1);
+        // Stopping exactly at the last file isn't a sample either
+        assert!(!scan(&["dedups", "--limit", "2", root])?.1);
+        Ok(())
     }
 }
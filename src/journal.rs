@@ -0,0 +1,516 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Kind of file operation recorded in the undo journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalAction {
+    Delete,
+    Trash,
+    Move,
+    Copy,
+}
+
+/// A single journaled file operation, stored as one JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Identifies the run that performed the action; undo reverses one run at a time
+    pub operation_id: String,
+    pub action: JournalAction,
+    pub source: PathBuf,
+    pub destination: Option<PathBuf>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Appends file operations to a newline-delimited JSON journal
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+    operation_id: String,
+}
+
+// Process-wide, so file operations on rayon or other worker threads are recorded too
+static ACTIVE_JOURNAL: RwLock<Option<Journal>> = RwLock::new(None);
+
+impl Journal {
+    /// Create a journal writer for `path`. Every entry recorded through this writer
+    /// shares one operation id, so a whole run can be undone together.
+    pub fn new(path: &Path) -> Self {
+        let now = Utc::now();
+        Self {
+            path: path.to_path_buf(),
+            operation_id: format!(
+                "{}-{}",
+                now.format("%Y%m%dT%H%M%S%.6fZ"),
+                std::process::id()
+            ),
+        }
+    }
+
+    /// Default journal location inside the user's cache directory
+    pub fn default_path() -> Result<PathBuf> {
+        let cache_dir = dirs::cache_dir().context("Could not determine cache directory")?;
+        Ok(cache_dir.join("dedups").join("journal.ndjson"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn operation_id(&self) -> &str {
+        &self.operation_id
+    }
+
+    /// Append one entry to the journal file, creating it if needed
+    pub fn record(
+        &self,
+        action: JournalAction,
+        source: &Path,
+        destination: Option<&Path>,
+    ) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let entry = JournalEntry {
+            operation_id: self.operation_id.clone(),
+            action,
+            source: source.to_path_buf(),
+            destination: destination.map(Path::to_path_buf),
+            timestamp: Utc::now(),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open journal {:?}", self.path))?;
+        // One write per line, so entries appended from several threads never interleave
+        file.write_all(format!("{}\n", serde_json::to_string(&entry)?).as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Set (or clear) the journal that file operations in this process are recorded to
+pub fn set_active_journal(journal: Option<Journal>) {
+    *ACTIVE_JOURNAL.write().unwrap_or_else(|e| e.into_inner()) = journal;
+}
+
+/// Record an action to the active journal, if any. Failures are logged rather than
+/// returned so a journal problem never aborts the file operation itself.
+pub(crate) fn record_action(action: JournalAction, source: &Path, destination: Option<&Path>) {
+    let active = ACTIVE_JOURNAL.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(journal) = active.as_ref() {
+        if let Err(e) = journal.record(action, source, destination) {
+            log::warn!("Failed to write undo journal entry for {:?}: {}", source, e);
+        }
+    }
+}
+
+/// Run `f` with a journal at `journal_path` active, then drop every entry that isn't
+/// under `root`: the journal is process-wide, so other tests' file operations land in
+/// it too. Tests that journal are serialized so they don't clear each other's journal.
+#[cfg(test)]
+pub(crate) fn record_for_test<R>(journal_path: &Path, root: &Path, f: impl FnOnce() -> R) -> R {
+    static SERIAL: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+
+    set_active_journal(Some(Journal::new(journal_path)));
+    let result = f();
+    set_active_journal(None);
+
+    let own: Vec<JournalEntry> = read_entries(journal_path)
+        .expect("read test journal")
+        .into_iter()
+        .filter(|entry| entry.source.starts_with(root))
+        .collect();
+    write_entries(journal_path, &own).expect("rewrite test journal");
+    result
+}
+
+/// Replace the journal's contents with `entries`
+fn write_entries(path: &Path, entries: &[JournalEntry]) -> Result<()> {
+    let mut content = String::new();
+    for entry in entries {
+        content.push_str(&serde_json::to_string(entry)?);
+        content.push('\n');
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Read all entries from a journal file. A missing file yields an empty list.
+pub fn read_entries(path: &Path) -> Result<Vec<JournalEntry>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut entries = Vec::new();
+    for (line_num, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => log::warn!(
+                "Skipping malformed journal line {} in {:?}: {}",
+                line_num + 1,
+                path,
+                e
+            ),
+        }
+    }
+    Ok(entries)
+}
+
+/// Reverse every action from the most recent operation in the journal.
+/// Moved files are moved back and copies are removed. Trashed files are restored
+/// where the platform trash supports it; permanent deletes cannot be undone.
+/// An action that fails is logged and stays in the journal, so undo can be retried.
+/// Returns the number of actions reversed and a log of what happened.
+pub fn undo_last_operation(path: &Path, dry_run: bool) -> Result<(usize, Vec<String>)> {
+    let entries = read_entries(path)?;
+    let Some(last_operation) = entries.last().map(|e| e.operation_id.clone()) else {
+        return Ok((0, vec!["Journal is empty, nothing to undo.".to_string()]));
+    };
+
+    let (to_undo, mut remaining): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .partition(|e| e.operation_id == last_operation);
+
+    let prefix = if dry_run { "[DRY RUN] Would " } else { "" };
+    let mut count = 0;
+    let mut logs = vec![format!(
+        "Undoing operation {} ({} actions)",
+        last_operation,
+        to_undo.len()
+    )];
+    let mut trashed = Vec::new();
+    // Entries that hit an error, kept for another undo attempt
+    let mut failed = Vec::new();
+
+    for entry in to_undo.iter().rev() {
+        match (entry.action, entry.destination.as_ref()) {
+            (JournalAction::Move, Some(destination)) => {
+                if !destination.exists() {
+                    logs.push(format!(
+                        "Cannot restore {}: {} no longer exists",
+                        entry.source.display(),
+                        destination.display()
+                    ));
+                } else if entry.source.exists() {
+                    logs.push(format!(
+                        "Cannot restore {}: a file already exists there",
+                        entry.source.display()
+                    ));
+                    failed.push(entry.clone());
+                } else if dry_run {
+                    logs.push(format!(
+                        "{}move {} back to {}",
+                        prefix,
+                        destination.display(),
+                        entry.source.display()
+                    ));
+                    count += 1;
+                } else {
+                    let restored = entry
+                        .source
+                        .parent()
+                        .map_or(Ok(()), fs::create_dir_all)
+                        .and_then(|_| fs::rename(destination, &entry.source));
+                    match restored {
+                        Ok(_) => {
+                            logs.push(format!(
+                                "Restored: {} -> {}",
+                                destination.display(),
+                                entry.source.display()
+                            ));
+                            count += 1;
+                        }
+                        Err(e) => {
+                            logs.push(format!(
+                                "Error restoring {}: {}",
+                                entry.source.display(),
+                                e
+                            ));
+                            failed.push(entry.clone());
+                        }
+                    }
+                }
+            }
+            (JournalAction::Copy, Some(destination)) => {
+                if !destination.exists() {
+                    logs.push(format!(
+                        "Copy {} already removed",
+                        destination.display()
+                    ));
+                } else if dry_run {
+                    logs.push(format!("{}remove copy {}", prefix, destination.display()));
+                    count += 1;
+                } else {
+                    match fs::remove_file(destination) {
+                        Ok(_) => {
+                            logs.push(format!("Removed copy: {}", destination.display()));
+                            count += 1;
+                        }
+                        Err(e) => {
+                            logs.push(format!(
+                                "Error removing copy {}: {}",
+                                destination.display(),
+                                e
+                            ));
+                            failed.push(entry.clone());
+                        }
+                    }
+                }
+            }
+            (JournalAction::Trash, _) => trashed.push(entry.source.clone()),
+            (JournalAction::Delete, _) => logs.push(format!(
+                "Cannot restore {}: it was permanently deleted (use --trash to make deletes undoable)",
+                entry.source.display()
+            )),
+            (_, None) => logs.push(format!(
+                "Skipping malformed journal entry for {}",
+                entry.source.display()
+            )),
+        }
+    }
+
+    if !trashed.is_empty() {
+        let (restored, trash_logs) = restore_from_trash(&trashed, dry_run);
+        count += restored;
+        logs.extend(trash_logs);
+    }
+
+    if !dry_run {
+        // Failures were collected newest first; keep the journal's order
+        remaining.extend(failed.into_iter().rev());
+        write_entries(path, &remaining)?;
+    }
+
+    Ok((count, logs))
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+fn restore_from_trash(paths: &[PathBuf], dry_run: bool) -> (usize, Vec<String>) {
+    let mut logs = Vec::new();
+    let items = match trash::os_limited::list() {
+        Ok(items) => items,
+        Err(e) => return (0, vec![format!("Could not read the trash: {}", e)]),
+    };
+
+    let mut to_restore = Vec::new();
+    for path in paths {
+        // If the same path was trashed more than once, restore the most recent copy
+        match items
+            .iter()
+            .filter(|item| &item.original_path() == path)
+            .max_by_key(|item| item.time_deleted)
+        {
+            Some(item) => to_restore.push(item.clone()),
+            None => logs.push(format!(
+                "Cannot restore {}: not found in trash",
+                path.display()
+            )),
+        }
+    }
+
+    let count = to_restore.len();
+    if dry_run {
+        for item in &to_restore {
+            logs.push(format!(
+                "[DRY RUN] Would restore {} from trash",
+                item.original_path().display()
+            ));
+        }
+        return (count, logs);
+    }
+
+    let restored_paths: Vec<PathBuf> = to_restore.iter().map(|i| i.original_path()).collect();
+    match trash::os_limited::restore_all(to_restore) {
+        Ok(_) => {
+            for path in restored_paths {
+                logs.push(format!("Restored from trash: {}", path.display()));
+            }
+            (count, logs)
+        }
+        Err(e) => {
+            logs.push(format!("Error restoring from trash: {}", e));
+            (0, logs)
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+fn restore_from_trash(paths: &[PathBuf], _dry_run: bool) -> (usize, Vec<String>) {
+    let logs = paths
+        .iter()
+        .map(|p| {
+            format!(
+                "Cannot restore {} automatically on this platform; recover it from the trash manually",
+                p.display()
+            )
+        })
+        .collect();
+    (0, logs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_utils::{move_files, FileInfo};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_move_round_trips_through_journal_and_undo() -> Result<()> {
+        let dir = tempdir()?;
+        let journal_path = dir.path().join("journal.ndjson");
+        let source = dir.path().join("src").join("photo.jpg");
+        let move_target = dir.path().join("moved");
+        fs::create_dir_all(source.parent().unwrap())?;
+        fs::write(&source, b"photo bytes")?;
+
+        let file_info = FileInfo {
+            path: source.clone(),
+            size: 11,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+
+        let (moved, _) = record_for_test(&journal_path, dir.path(), || {
+            move_files(
+                &[file_info],
+                &move_target,
+                false,
+                crate::transfer::ConflictPolicy::Rename,
+                0,
+            )
+        })?;
+        assert_eq!(moved.len(), 1);
+        assert!(!source.exists());
+
+        let entries = read_entries(&journal_path)?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, JournalAction::Move);
+        assert_eq!(entries[0].source, source);
+        assert_eq!(
+            entries[0].destination.as_deref(),
+            Some(move_target.join("photo.jpg").as_path())
+        );
+
+        // Dry run reports but changes nothing
+        let (count, _) = undo_last_operation(&journal_path, true)?;
+        assert_eq!(count, 1);
+        assert!(!source.exists());
+
+        let (count, logs) = undo_last_operation(&journal_path, false)?;
+        assert_eq!(count, 1, "Undo logs: {:?}", logs);
+        assert_eq!(fs::read(&source)?, b"photo bytes");
+        assert!(!move_target.join("photo.jpg").exists());
+        assert!(read_entries(&journal_path)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_only_reverses_latest_operation() -> Result<()> {
+        let dir = tempdir()?;
+        let journal_path = dir.path().join("journal.ndjson");
+        let older = Journal {
+            path: journal_path.clone(),
+            operation_id: "older".to_string(),
+        };
+        let newer = Journal {
+            path: journal_path.clone(),
+            operation_id: "newer".to_string(),
+        };
+        older.record(JournalAction::Delete, Path::new("/gone/a"), None)?;
+        newer.record(JournalAction::Delete, Path::new("/gone/b"), None)?;
+
+        let (count, logs) = undo_last_operation(&journal_path, false)?;
+        assert_eq!(count, 0);
+        assert!(logs.iter().any(|l| l.contains("permanently deleted")));
+
+        let remaining = read_entries(&journal_path)?;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].operation_id, "older");
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_undo_keeps_entry_and_continues() -> Result<()> {
+        let dir = tempdir()?;
+        let journal_path = dir.path().join("journal.ndjson");
+        let journal = Journal::new(&journal_path);
+        // A plain file where the first source's directory would need to be recreated
+        fs::write(dir.path().join("blocked"), b"not a directory")?;
+        let blocked = dir.path().join("blocked").join("a.txt");
+        let blocked_moved = dir.path().join("moved_a.txt");
+        let source = dir.path().join("b.txt");
+        let moved = dir.path().join("moved_b.txt");
+        fs::write(&blocked_moved, b"a")?;
+        fs::write(&moved, b"b")?;
+        journal.record(JournalAction::Move, &blocked, Some(&blocked_moved))?;
+        journal.record(JournalAction::Move, &source, Some(&moved))?;
+
+        let (count, logs) = undo_last_operation(&journal_path, false)?;
+        assert_eq!(count, 1, "Undo logs: {:?}", logs);
+        assert_eq!(fs::read(&source)?, b"b");
+        assert!(logs.iter().any(|l| l.starts_with("Error restoring")));
+
+        let remaining = read_entries(&journal_path)?;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].source, blocked);
+        Ok(())
+    }
+
+    #[test]
+    fn test_actions_on_worker_threads_are_journaled() -> Result<()> {
+        use rayon::prelude::*;
+
+        let dir = tempdir()?;
+        let journal_path = dir.path().join("journal.ndjson");
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build()?;
+        record_for_test(&journal_path, dir.path(), || {
+            pool.install(|| {
+                (0..32).into_par_iter().for_each(|i| {
+                    let path = dir.path().join(format!("{}.txt", i));
+                    record_action(JournalAction::Delete, &path, None);
+                })
+            })
+        });
+
+        let entries = read_entries(&journal_path)?;
+        assert_eq!(entries.len(), 32);
+        assert!(entries
+            .iter()
+            .all(|e| e.operation_id == entries[0].operation_id));
+        Ok(())
+    }
+}
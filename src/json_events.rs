@@ -0,0 +1,313 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::file_utils::{
+    summarize_by_extension, top_sets_by_reclaimable, DirDuplicateStat, DuplicateSet, ExtensionStat,
+    HashProgress,
+};
+use crate::tui_app::ScanMessage;
+use crate::update_mode::UpdateSummary;
+
+/// Version of the event stream's shape, stamped on every line. Bump it whenever an
+/// event gains, loses or changes a field so consumers can reject streams they don't know.
+pub const SCHEMA_VERSION: u32 = 8;
+
+/// A progress update from one of the scan stages
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressInfo {
+    pub stage: u8,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+    /// Share of hashing done so far (stage 3 only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent_complete: Option<f64>,
+    /// Moving-average estimate of the remaining hashing time (stage 3 only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_seconds_left: Option<f64>,
+}
+
+/// Machine-readable events written by `--json-events`, one JSON object per line.
+/// The `type` field identifies the event (`meta`, `progress`, `error`, `final_result`, ...).
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JsonOutput {
+    /// Always the first line of a stream
+    Meta {
+        dedups_version: String,
+    },
+    Progress(ProgressInfo),
+    Error {
+        /// Stable `DedupError` code, or `UNKNOWN_ERROR_CODE`
+        code: u16,
+        message: String,
+    },
+    FinalResult {
+        total_sets: usize,
+        /// Files across all sets, including the copy each set keeps
+        duplicate_files: usize,
+        reclaimable_bytes: u64,
+        /// Every set, or only the largest ones when `--top` is given; empty with `--summary-only`
+        duplicate_sets: Vec<DuplicateSet>,
+        /// Duplicates broken down by file extension, largest redundant bytes first
+        extension_stats: Vec<ExtensionStat>,
+        /// Files missing from the target (multi-directory mode only)
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        missing_in_target: Vec<PathBuf>,
+        /// Duplicates broken down by input directory (multi-directory mode only)
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        per_directory: Vec<DirDuplicateStat>,
+        /// Set when `--limit` stopped discovery early: the results only cover this many files
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sample_limit: Option<usize>,
+    },
+    /// Totals after --delete/--move-to/--reflink/--hardlink/--symlink-kept ran (or would have,
+    /// in dry run)
+    Actions {
+        dry_run: bool,
+        deleted: usize,
+        moved: usize,
+        reflinked: usize,
+        hardlinked: usize,
+        symlinked: usize,
+        reclaimed_bytes: u64,
+    },
+    /// Totals after --update copied new files and refreshed outdated ones
+    Update {
+        dry_run: bool,
+        #[serde(flatten)]
+        summary: UpdateSummary,
+    },
+}
+
+impl JsonOutput {
+    pub fn meta() -> Self {
+        JsonOutput::Meta {
+            dedups_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    pub fn progress(stage: u8, message: impl Into<String>) -> Self {
+        JsonOutput::Progress(ProgressInfo {
+            stage,
+            message: message.into(),
+            timestamp: Utc::now(),
+            percent_complete: None,
+            estimated_seconds_left: None,
+        })
+    }
+
+    pub fn hash_progress(message: impl Into<String>, progress: HashProgress) -> Self {
+        JsonOutput::Progress(ProgressInfo {
+            stage: 3,
+            message: message.into(),
+            timestamp: Utc::now(),
+            percent_complete: Some(progress.percent_complete),
+            estimated_seconds_left: progress.estimated_seconds_left,
+        })
+    }
+
+    pub fn error(err: &anyhow::Error) -> Self {
+        JsonOutput::Error {
+            code: crate::error::error_code(err),
+            message: format!("{:#}", err),
+        }
+    }
+
+    pub fn final_result(
+        duplicate_sets: &[DuplicateSet],
+        missing_in_target: Vec<PathBuf>,
+        top: Option<usize>,
+    ) -> Self {
+        JsonOutput::FinalResult {
+            total_sets: duplicate_sets.len(),
+            duplicate_files: duplicate_sets.iter().map(|s| s.files.len()).sum(),
+            reclaimable_bytes: duplicate_sets
+                .iter()
+                .map(|s| s.size * (s.files.len().saturating_sub(1) as u64))
+                .sum(),
+            duplicate_sets: match top {
+                Some(n) => top_sets_by_reclaimable(duplicate_sets, n)
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+                None => duplicate_sets.to_vec(),
+            },
+            extension_stats: summarize_by_extension(duplicate_sets),
+            missing_in_target,
+            per_directory: Vec::new(),
+            sample_limit: None,
+        }
+    }
+
+    /// Mark a `final_result` as covering only the first `limit` files found. Other events
+    /// are returned unchanged.
+    pub fn with_sample_limit(mut self, limit: Option<usize>) -> Self {
+        if let JsonOutput::FinalResult { sample_limit, .. } = &mut self {
+            *sample_limit = limit;
+        }
+        self
+    }
+
+    /// Attach the per-directory breakdown to a `final_result`. Other events are returned
+    /// unchanged.
+    pub fn with_per_directory(mut self, stats: Vec<DirDuplicateStat>) -> Self {
+        if let JsonOutput::FinalResult { per_directory, .. } = &mut self {
+            *per_directory = stats;
+        }
+        self
+    }
+
+    /// Drop the per-set and per-file lists from a `final_result`, keeping only the totals
+    /// (`--summary-only`). Other events are returned unchanged.
+    pub fn without_listing(mut self) -> Self {
+        if let JsonOutput::FinalResult {
+            duplicate_sets,
+            missing_in_target,
+            ..
+        } = &mut self
+        {
+            duplicate_sets.clear();
+            missing_in_target.clear();
+        }
+        self
+    }
+}
+
+/// One line of the stream: the event plus the schema version it was written with
+#[derive(Serialize)]
+struct Envelope<'a> {
+    #[serde(flatten)]
+    event: &'a JsonOutput,
+    schema_version: u32,
+}
+
+/// Writes `JsonOutput` events as NDJSON to a file, flushing after every event so
+/// `tail -f` sees live progress. Safe to share between the scan and progress threads.
+/// The first event written is always `meta`. With `pretty`, events are indented over
+/// several lines for reading by eye, so the file is no longer line-delimited.
+pub struct JsonEventWriter {
+    file: Mutex<BufWriter<File>>,
+    pretty: bool,
+}
+
+impl JsonEventWriter {
+    pub fn create(path: &Path, pretty: bool) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create JSON events file {:?}", path))?;
+        let writer = Self {
+            file: Mutex::new(BufWriter::new(file)),
+            pretty,
+        };
+        writer.emit(&JsonOutput::meta())?;
+        Ok(writer)
+    }
+
+    pub fn emit(&self, event: &JsonOutput) -> Result<()> {
+        let envelope = Envelope {
+            event,
+            schema_version: SCHEMA_VERSION,
+        };
+        // Compact output escapes any newlines inside strings, so one event is one line
+        let line = if self.pretty {
+            serde_json::to_string_pretty(&envelope)?
+        } else {
+            serde_json::to_string(&envelope)?
+        };
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| anyhow::anyhow!("JSON events writer lock poisoned"))?;
+        writeln!(file, "{}", line)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Drain scan progress messages on a background thread, forwarding them as `progress`
+/// events when a writer is configured. The thread ends once the scan drops its sender.
+pub fn spawn_progress_forwarder(
+    rx: Receiver<ScanMessage>,
+    writer: Option<Arc<JsonEventWriter>>,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        for message in rx {
+            let event = match message {
+                ScanMessage::StatusUpdate(stage, msg) => JsonOutput::progress(stage, msg),
+                ScanMessage::HashProgress(msg, progress) => {
+                    JsonOutput::hash_progress(msg, progress)
+                }
+                ScanMessage::Error(code, message) => JsonOutput::Error { code, message },
+                ScanMessage::Completed(_) => continue,
+            };
+            if let Some(writer) = &writer {
+                if let Err(e) = writer.emit(&event) {
+                    log::warn!("Failed to write JSON event: {}", e);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_are_single_line_unless_pretty() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let event = JsonOutput::Error {
+            code: crate::error::UNKNOWN_ERROR_CODE,
+            message: "first line\nsecond line".to_string(),
+        };
+
+        let path = dir.path().join("events.ndjson");
+        JsonEventWriter::create(&path, false)?.emit(&event)?;
+        let contents = std::fs::read_to_string(&path)?;
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2, "meta plus one event: {}", contents);
+        for line in lines {
+            serde_json::from_str::<serde_json::Value>(line)?;
+        }
+
+        let path = dir.path().join("events.json");
+        JsonEventWriter::create(&path, true)?.emit(&event)?;
+        assert!(std::fs::read_to_string(&path)?.lines().count() > 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_forwarded_scan_errors_keep_their_code() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("events.ndjson");
+        let writer = Arc::new(JsonEventWriter::create(&path, false)?);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let forwarder = spawn_progress_forwarder(rx, Some(writer));
+        let err = anyhow::Error::from(crate::error::DedupError::DirectoryNotFound(
+            dir.path().join("missing"),
+        ));
+        tx.send(ScanMessage::Error(
+            crate::error::error_code(&err),
+            err.to_string(),
+        ))?;
+        drop(tx);
+        forwarder.join().unwrap();
+
+        let contents = std::fs::read_to_string(&path)?;
+        let event: serde_json::Value = serde_json::from_str(contents.lines().last().unwrap())?;
+        assert_eq!(event["code"], 10, "{}", contents);
+        Ok(())
+    }
+}
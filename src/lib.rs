@@ -23,6 +23,61 @@ pub mod audio_fingerprint;
 // Add video fingerprinting module
 pub mod video_fingerprint;
 
+// Add undo journal module
+pub mod journal;
+
+// Add NDJSON event output module
+pub mod json_events;
+
+// Add rate-limited file copy helpers
+pub mod transfer;
+
+// Add --update (copy only newer files) module
+pub mod update_mode;
+
+// Add --prompt per-set resolution module
+pub mod prompt;
+
+// Add duplicate directory detection
+pub mod dir_dedup;
+
+// Add metadata-insensitive content hashing
+pub mod content_hash;
+
+// Add error codes for machine-readable error reporting
+pub mod error;
+
+// Add hash algorithm benchmark
+pub mod benchmark;
+
+// Add partial (truncated) duplicate detection
+pub mod partial;
+
+// Add structured (JSON) log output
+pub mod log_format;
+
+// Add hash manifests of reference libraries
+pub mod manifest;
+
+// Add content-defined chunking overlap report
+pub mod block_dedup;
+
+// Add extended attribute comparison
+pub mod xattrs;
+
+// Add watch mode for new duplicates
+pub mod watch;
+
+// Add process exit codes for scripting
+pub mod exit_code;
+
+// Add shared delete/move/link actions for duplicate sets
+pub mod actions;
+
+// Add SQLite hash cache backend
+#[cfg(feature = "sqlite")]
+pub mod sqlite_cache;
+
 // To make Cli accessible, you'll need to move its definition from main.rs to lib.rs
 // or re-export it from main.rs if main.rs uses this lib.rs as a library.
 // For a typical binary project that also wants to expose a library for testing/other uses:
@@ -38,21 +93,26 @@ pub mod video_fingerprint;
 // If your Cli struct is still in main.rs, you should move it to this lib.rs file.
 // For example:
 
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::str::FromStr;
 // Ensure these are correctly pathed if they are part of file_utils module
-use crate::config::DedupConfig;
-use crate::file_utils::{SortCriterion, SortOrder};
+use crate::config::{DedupConfig, HashOverride, ThemeConfig};
+use crate::file_utils::{SelectionStrategy, SortCriterion, SortOrder};
 use crate::media_dedup::MediaDedupOptions;
 
 #[derive(Parser, Debug, Clone)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, subcommand_negates_reqs = true, after_help = crate::exit_code::EXIT_CODES_HELP)]
 pub struct Cli {
+    /// Optional maintenance subcommand (e.g. `undo`). Without one, dedups scans directories.
+    #[clap(subcommand)]
+    pub command: Option<Commands>,
+
     /// The directories to scan for duplicate or missing files.
     /// When multiple directories are specified, the last one is treated as the target
     /// for copying missing files, unless --target is specified.
-    #[clap(required_unless_present = "interactive")]
+    #[clap(required_unless_present_any = ["interactive", "load_session", "files_from"])]
     pub directories: Vec<PathBuf>,
 
     /// Specifies the target directory for copying missing files or deduplication.
@@ -73,6 +133,45 @@ pub struct Cli {
     )]
     pub delete: bool,
 
+    /// Send deleted files to the OS trash/recycle bin instead of removing them permanently.
+    #[clap(
+        long,
+        help = "Move deleted files to the system trash instead of removing them permanently"
+    )]
+    pub trash: bool,
+
+    /// Record delete/move/copy actions in the undo journal.
+    #[clap(
+        long,
+        help = "Record delete/move/copy actions in the undo journal (see `dedups undo`)"
+    )]
+    pub journal: bool,
+
+    /// Replace duplicate files with reflinks (copy-on-write clones) of the kept file.
+    #[clap(
+        long,
+        help = "Replace duplicates with reflinks to the kept file (btrfs/XFS, Linux only)"
+    )]
+    pub reflink: bool,
+
+    /// Replace duplicate files with hardlinks to the kept file.
+    #[clap(long, help = "Replace duplicates with hardlinks to the kept file")]
+    pub hardlink: bool,
+
+    /// Delete duplicate files and leave a symlink to the kept file at each old path.
+    #[clap(
+        long,
+        help = "Replace duplicates with symlinks to the kept file, so their paths keep working"
+    )]
+    pub symlink_kept: bool,
+
+    /// Make the links created by --symlink-kept relative to their directory
+    #[clap(
+        long,
+        help = "Create relative symlinks (../kept.jpg) instead of absolute ones, so the tree can be moved"
+    )]
+    pub relative_symlinks: bool,
+
     /// Move duplicate files to the specified folder.
     #[clap(
         short = 'M',
@@ -81,6 +180,39 @@ pub struct Cli {
     )]
     pub move_to: Option<PathBuf>,
 
+    /// Recreate each moved file's directory structure, relative to its scan root, under --move-to
+    #[clap(
+        long,
+        requires = "move_to",
+        help = "Keep each file's path relative to the scanned directory when moving (a/b/c.jpg -> <move-to>/a/b/c.jpg)"
+    )]
+    pub preserve_structure: bool,
+
+    /// What to do when a move or copy destination already exists
+    #[clap(
+        long,
+        value_name = "POLICY",
+        value_parser = crate::transfer::ConflictPolicy::from_str,
+        default_value_t = crate::transfer::ConflictPolicy::Rename,
+        help = "When a move/copy destination exists: skip it, overwrite it, or rename the new file with a _copy(N) suffix [skip|overwrite|rename]"
+    )]
+    pub on_conflict: crate::transfer::ConflictPolicy,
+
+    /// Before deleting a duplicate, repoint symlinks under the scanned directories at the kept file
+    #[clap(
+        long,
+        help = "Repoint symlinks that target a deleted duplicate at the kept file"
+    )]
+    pub fix_symlinks: bool,
+
+    /// Move duplicates into `DIR/<YYYY-MM-DD>/<relative path>` so they can be reviewed before deleting.
+    #[clap(
+        long,
+        value_name = "DIR",
+        help = "Move duplicates into DIR/<YYYY-MM-DD>/, keeping their directory structure"
+    )]
+    pub quarantine: Option<PathBuf>,
+
     /// Write actions and errors to a log file.
     #[clap(short, long, help = "Enable logging to a file (default: dedups.log)")]
     pub log: bool,
@@ -89,6 +221,16 @@ pub struct Cli {
     #[clap(long, value_name = "PATH", help = "Specify a custom log file path")]
     pub log_file: Option<PathBuf>,
 
+    /// Format of log records; json writes one object per line for log aggregators
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        value_parser = clap::builder::PossibleValuesParser::new(crate::log_format::LOG_FORMATS),
+        default_value = "text",
+        help = "Log record format [text|json]; json writes one object per line (to stderr unless logging to a file)"
+    )]
+    pub log_format: String,
+
     /// Write a file containing duplicate information.
     #[clap(
         short,
@@ -98,11 +240,70 @@ pub struct Cli {
     pub output: Option<PathBuf>,
 
     /// Output format for the duplicates file.
-    #[clap(short, long, value_parser = clap::builder::PossibleValuesParser::new(["json", "toml"]), default_value = "json", help = "Format for the output file [json|toml]")]
+    #[clap(short, long, value_parser = clap::builder::PossibleValuesParser::new(["json", "toml", "csv", "fdupes"]), default_value = "json", help = "Format for the output file [json|toml|csv|fdupes]")]
     pub format: String,
 
+    /// Hash every scanned file into a manifest instead of looking for duplicates
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["interactive", "manifest"],
+        help = "Write a JSON manifest (path -> hash and size) of every scanned file, e.g. for a reference library"
+    )]
+    pub write_manifest: Option<PathBuf>,
+
+    /// Check the scanned files against a manifest from --write-manifest instead of each other
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with = "interactive",
+        help = "Report which scanned files already exist in a manifest written by --write-manifest and which are new"
+    )]
+    pub manifest: Option<PathBuf>,
+
+    /// Write a standalone HTML report of the duplicate sets.
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write a self-contained HTML report of duplicate sets to PATH"
+    )]
+    pub html_report: Option<PathBuf>,
+
+    /// List only the N sets with the most reclaimable space. Actions still apply to every set.
+    #[clap(
+        long,
+        value_name = "N",
+        help = "Only list the N duplicate sets with the most reclaimable space"
+    )]
+    pub top: Option<usize>,
+
+    /// Sort the listed duplicate sets; direction comes from --sort-order
+    #[clap(
+        long,
+        value_name = "KEY",
+        value_parser = crate::file_utils::SetOrder::from_str,
+        help = "Sort the listed duplicate sets by reclaimable size, file count or first path [size|count|path] (direction from --sort-order)"
+    )]
+    pub order_by: Option<crate::file_utils::SetOrder>,
+
+    /// Write progress and result events as newline-delimited JSON to a file.
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write progress and result events as newline-delimited JSON to PATH"
+    )]
+    pub json_events: Option<PathBuf>,
+
+    /// Pretty-print --json-events output for reading by eye
+    #[clap(
+        long,
+        requires = "json_events",
+        help = "Pretty-print --json-events output (no longer one event per line)"
+    )]
+    pub json_pretty: bool,
+
     /// Hashing algorithm to use for comparing files.
-    #[clap(short, long, value_parser = clap::builder::PossibleValuesParser::new(["md5", "sha1", "sha256", "blake3", "xxhash", "gxhash", "fnv1a", "crc32"]), default_value = "xxhash", help = "Hashing algorithm [md5|sha1|sha256|blake3|xxhash|gxhash|fnv1a|crc32]")]
+    #[clap(short, long, value_parser = clap::builder::PossibleValuesParser::new(["auto", "md5", "sha1", "sha256", "blake3", "xxhash", "gxhash", "fnv1a", "crc32"]), default_value = "xxhash", help = "Hashing algorithm [auto|md5|sha1|sha256|blake3|xxhash|gxhash|fnv1a|crc32]; auto hashes fast and verifies byte-for-byte before acting")]
     pub algorithm: String,
 
     /// Number of parallel threads to use for hashing. Defaults to auto-detected number of cores.
@@ -113,18 +314,77 @@ pub struct Cli {
     )]
     pub parallel: Option<usize>,
 
+    /// Threads used to stat files during discovery, separate from --parallel (hashing).
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 4,
+        help = "Number of threads for file discovery/metadata I/O (hashing uses --parallel)"
+    )]
+    pub threads_io: usize,
+
+    /// Send a progress update at least this often (default 400 ms)
+    #[clap(
+        long,
+        value_name = "MS",
+        help = "Minimum milliseconds between progress updates (default: 400)"
+    )]
+    pub progress_interval: Option<u64>,
+
+    /// Send a progress update every N files/groups instead of the adaptive default
+    #[clap(
+        long,
+        value_name = "N",
+        help = "Send a progress update every N files or hash groups (default: adaptive)"
+    )]
+    pub progress_every: Option<usize>,
+
     /// Mode for selecting which file to keep/delete in non-interactive mode.
     #[clap(
         long,
         default_value = "newest_modified",
-        help = "Selection strategy for delete/move [newest_modified|oldest_modified|shortest_path|longest_path]"
+        help = "Selection strategy for delete/move [newest_modified|oldest_modified|shortest_path|longest_path|largest_size|smallest_size|prefer_directory:<PATH>]"
     )]
     pub mode: String,
 
+    /// Keep the copy under this directory in each duplicate set (overrides --mode).
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Keep the file under PATH in each set; newest wins among ties or when none match (overrides --mode)"
+    )]
+    pub keep_dir: Option<PathBuf>,
+
+    /// Decide between files the selection strategy rates equally, in order
+    #[clap(
+        long,
+        value_name = "LIST",
+        value_delimiter = ',',
+        value_parser = crate::file_utils::parse_tiebreaker,
+        help = "Tie-breakers when --mode rates files equally, in order [path|name|newest|oldest], e.g. path,name; remaining ties keep the alphabetically first path"
+    )]
+    pub tiebreak: Vec<crate::file_utils::TieBreaker>,
+
+    /// Resume a TUI session saved with `S`, skipping the scan. Implies --interactive.
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Open the TUI from a saved session file instead of scanning (implies --interactive)"
+    )]
+    pub load_session: Option<PathBuf>,
+
     /// Fire up interactive TUI mode.
     #[clap(short, long, help = "Run in interactive TUI mode")]
     pub interactive: bool,
 
+    /// Color theme for the TUI; overrides the `[theme]` preset in the config file
+    #[clap(
+        long,
+        value_name = "NAME",
+        help = "TUI color theme [dark|light|high-contrast]"
+    )]
+    pub theme: Option<String>,
+
     /// Verbosity level.
     #[clap(short, long, action = clap::ArgAction::Count, help = "Verbosity level (-v, -vv, -vvv)")]
     pub verbose: u8,
@@ -137,6 +397,186 @@ pub struct Cli {
     #[clap(long, help = "Exclude specific file patterns (glob)")]
     pub exclude: Vec<String>,
 
+    /// Include files whose path matches the given regular expression. Can be specified multiple times.
+    #[clap(
+        long,
+        value_name = "REGEX",
+        help = "Include files matching a regular expression"
+    )]
+    pub include_regex: Vec<String>,
+
+    /// Exclude files whose path matches the given regular expression. Can be specified multiple times.
+    #[clap(
+        long,
+        value_name = "REGEX",
+        help = "Exclude files matching a regular expression"
+    )]
+    pub exclude_regex: Vec<String>,
+
+    /// Skip everything under this directory without walking into it. Can be specified multiple times.
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Skip a whole directory subtree (not descended into at all)"
+    )]
+    pub exclude_dir: Vec<PathBuf>,
+
+    /// Read the files to consider from a newline-separated list instead of walking directories
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Only consider the files listed (one per line) in PATH, or stdin for -"
+    )]
+    pub files_from: Option<PathBuf>,
+
+    /// Read --files-from and --filter-from entries as NUL-separated, e.g. from `find -print0`
+    #[clap(
+        long,
+        help = "Entries in --files-from and --filter-from are NUL-separated instead of one per line (e.g. from find -print0)"
+    )]
+    pub null_input: bool,
+
+    /// Print duplicate paths NUL-terminated instead of the human-readable listing
+    #[clap(
+        long,
+        help = "Print each duplicate file path followed by a NUL byte instead of the usual listing, for xargs -0; other messages go to stderr"
+    )]
+    pub print0: bool,
+
+    /// Print only totals (sets, files, reclaimable space, top extensions), no paths
+    #[clap(
+        long,
+        conflicts_with = "print0",
+        help = "Print only totals (sets, duplicate files, reclaimable space, top extensions) instead of listing every file; --output still gets the full list"
+    )]
+    pub summary_only: bool,
+
+    /// Compare files byte-for-byte before acting on sets found with a non-cryptographic hash
+    #[clap(
+        long,
+        help = "Byte-for-byte verify duplicates before delete/move/link, including TUI jobs (always on for crc32 and fnv1a)"
+    )]
+    pub verify: bool,
+
+    /// Include hidden files and directories (names starting with '.') in scans.
+    #[clap(
+        long,
+        help = "Include hidden files and directories (dot-files) in scans"
+    )]
+    pub include_hidden: bool,
+
+    /// Don't skip the built-in junk names (.DS_Store, Thumbs.db, desktop.ini, .git, ...)
+    #[clap(
+        long,
+        help = "Don't skip common junk files (.DS_Store, Thumbs.db, desktop.ini, .git) while scanning"
+    )]
+    pub no_default_excludes: bool,
+
+    /// Follow symbolic links to files and directories while scanning.
+    #[clap(
+        long,
+        help = "Follow symlinks while scanning (cycles are detected and skipped)"
+    )]
+    pub follow_symlinks: bool,
+
+    /// How deep to scan below each directory; 1 means only the files directly inside it
+    #[clap(
+        long,
+        value_name = "N",
+        help = "Only scan N levels deep (1 = files directly in the given directories)"
+    )]
+    pub max_depth: Option<usize>,
+
+    /// Group zero-byte files into a single duplicate set instead of skipping them.
+    #[clap(long, help = "Group empty (zero-byte) files into one duplicate set")]
+    pub include_empty: bool,
+
+    /// Treat all given directories as one pool and find duplicates across them.
+    #[clap(
+        long,
+        help = "Find duplicates across all given directories in a single pass (no target/source copy)"
+    )]
+    pub union: bool,
+
+    /// Only group content-identical files that also share a file name
+    #[clap(
+        long,
+        help = "Only treat files as duplicates if they also have the same file name"
+    )]
+    pub same_name_only: bool,
+
+    /// Split content-identical files whose extended attributes differ
+    #[clap(
+        long,
+        help = "Only treat files as duplicates if their extended attributes (xattrs) also match (Linux, macOS and BSDs)"
+    )]
+    pub compare_xattrs: bool,
+
+    /// Compare mp3, PNG and JPEG files by content, ignoring embedded tags and EXIF
+    #[clap(
+        long,
+        help = "Ignore embedded metadata (ID3 tags, PNG text chunks, JPEG EXIF) when comparing mp3, PNG and JPEG files"
+    )]
+    pub ignore_metadata: bool,
+
+    /// Only report sets with at least this many copies
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 2,
+        help = "Only report duplicate sets with at least N files"
+    )]
+    pub min_set_size: usize,
+
+    /// Ignore copies that share a folder; only duplicates across directories are reported
+    #[clap(
+        long,
+        help = "Only report duplicates in different directories; copies within one folder are ignored"
+    )]
+    pub exclude_same_dir: bool,
+
+    /// Sort files within each set by path and sets by hash, so repeated runs match
+    #[clap(
+        long,
+        help = "Report sets in a stable order (files by path, sets by hash) so repeated runs produce identical output"
+    )]
+    pub deterministic: bool,
+
+    /// Retries for deletes, moves and copies that fail with a transient error (e.g. EBUSY)
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = crate::file_utils::DEFAULT_FS_RETRIES,
+        help = "Retry a delete, move or copy up to N times after a transient error such as a busy network mount"
+    )]
+    pub retries: usize,
+
+    /// Compare file names case-insensitively; defaults to on for macOS and Windows
+    #[clap(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Compare file names ignoring case (default: on for macOS/Windows; pass =false to disable)"
+    )]
+    pub case_insensitive_names: Option<bool>,
+
+    /// Never delete, move or replace files under this directory. Can be specified multiple times.
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Protect files under PATH from being deleted, moved or replaced; protected copies are kept"
+    )]
+    pub protect: Vec<PathBuf>,
+
+    /// Ask which file to keep for each set before delete/move/link (disabled with --json-events)
+    #[clap(
+        long,
+        help = "Ask which file to keep for each duplicate set (s: skip, a: apply --mode)"
+    )]
+    pub prompt: bool,
+
     /// Read filter rules from a file (similar to rclone filter files).
     #[clap(
         long,
@@ -144,6 +584,41 @@ pub struct Cli {
     )]
     pub filter_from: Option<PathBuf>,
 
+    /// Ignore files smaller than this size (e.g. 4K).
+    #[clap(
+        long,
+        value_name = "SIZE",
+        value_parser = crate::file_utils::parse_size,
+        help = "Skip files smaller than SIZE (e.g. 4K, 1M)"
+    )]
+    pub min_size: Option<u64>,
+
+    /// Ignore files larger than this size (e.g. 2G).
+    #[clap(
+        long,
+        value_name = "SIZE",
+        value_parser = crate::file_utils::parse_size,
+        help = "Skip files larger than SIZE (e.g. 500M, 2G)"
+    )]
+    pub max_size: Option<u64>,
+
+    /// Only consider files modified at or after this time
+    #[clap(
+        long,
+        value_name = "DATE|AGE",
+        value_parser = crate::file_utils::parse_time_threshold,
+        help = "Only consider files modified since DATE (2024-01-01) or within AGE (7d, 12h, 2w)"
+    )]
+    pub since: Option<std::time::SystemTime>,
+
+    /// Stop the discovery walk after this many files, for a quick sample of a large tree
+    #[clap(
+        long,
+        value_name = "N",
+        help = "Stop discovery after N files and only group/hash those (results are a partial sample); not for comparing directories"
+    )]
+    pub limit: Option<usize>,
+
     /// Show progress information during scanning/hashing.
     #[clap(
         long,
@@ -177,14 +652,44 @@ pub struct Cli {
     )]
     pub config_file: Option<PathBuf>,
 
+    /// Merge in the config files listed under `config_includes` in the config file
+    #[clap(
+        long,
+        help = "Load the config files listed in the config's config_includes key (shared base configs)"
+    )]
+    pub follow_config_includes: bool,
+
     /// Run in dry run mode - simulate actions without making actual changes.
     #[clap(long, help = "Perform a dry run without making any actual changes")]
     pub dry_run: bool,
 
     /// Directory to store hash cache for faster scanning of previously scanned files
-    #[clap(long, help = "Directory to store file hash cache for faster rescans")]
+    #[clap(
+        long,
+        global = true,
+        help = "Directory to store file hash cache for faster rescans"
+    )]
     pub cache_location: Option<PathBuf>,
 
+    /// Storage backend for the hash cache.
+    #[clap(
+        long,
+        value_name = "BACKEND",
+        value_parser = clap::builder::PossibleValuesParser::new(["file", "sqlite"]),
+        default_value = "file",
+        help = "Hash cache backend [file|sqlite] (sqlite needs the `sqlite` feature)"
+    )]
+    pub cache_backend: String,
+
+    /// Seconds to wait for another dedups instance to release the hash cache
+    #[clap(
+        long,
+        value_name = "SECS",
+        default_value_t = crate::file_cache::DEFAULT_LOCK_TIMEOUT.as_secs(),
+        help = "Seconds to wait for a cache locked by another dedups instance (0 fails immediately)"
+    )]
+    pub cache_lock_timeout: u64,
+
     /// Use cached hashes for files that haven't changed since last scan
     #[clap(
         long,
@@ -192,6 +697,149 @@ pub struct Cli {
     )]
     pub fast_mode: bool,
 
+    /// Check every cached entry against the file on disk and only hash what changed
+    #[clap(
+        long,
+        help = "Rescan using the cache: drop stale entries and only hash new or changed files (requires cache-location)"
+    )]
+    pub incremental: bool,
+
+    /// Hash every file again and overwrite its cache entry, without reading the cache
+    #[clap(
+        long,
+        help = "Ignore cached hashes but store fresh ones, rebuilding a stale cache (requires cache-location)"
+    )]
+    pub refresh_cache: bool,
+
+    /// Disable the head/tail signature pre-filter that runs before full hashing
+    #[clap(
+        long,
+        help = "Disable the quick first/last 4 KiB pre-filter before full hashing"
+    )]
+    pub no_prefilter: bool,
+
+    /// Only hash the first N bytes of each file for a faster, approximate scan
+    #[clap(
+        long,
+        value_name = "SIZE",
+        value_parser = crate::file_utils::parse_size,
+        help = "Hash at most SIZE bytes per file (e.g. 10M); matches are approximate"
+    )]
+    pub hash_limit: Option<u64>,
+
+    /// Cap how many large files are hashed concurrently, sized by the average large file
+    #[clap(
+        long,
+        value_name = "SIZE",
+        value_parser = crate::file_utils::parse_size,
+        help = "Memory/I-O budget for hashing large (64 MiB+) files: at most SIZE / average file size are read at once (e.g. 1G)"
+    )]
+    pub max_memory: Option<u64>,
+
+    /// Read buffer size used while hashing
+    #[clap(
+        long,
+        value_name = "SIZE",
+        default_value = "64K",
+        value_parser = crate::file_utils::parse_read_buffer,
+        help = "Read buffer for hashing (e.g. 1M for network or spinning disks); rounded up to a power of two between 4K and 16M"
+    )]
+    pub read_buffer: u64,
+
+    /// Allow --delete to act on sets that were only approximately matched
+    #[clap(
+        long,
+        help = "Allow --delete/--reflink/--hardlink/--symlink-kept to act on approximate (--hash-limit) sets"
+    )]
+    pub allow_approximate_delete: bool,
+
+    /// List hardlinked copies alongside their duplicate sets (informational only)
+    #[clap(
+        long,
+        help = "List hardlinks to duplicate files as informational entries (never actioned)"
+    )]
+    pub show_hardlinks: bool,
+
+    /// Also report files that are a truncated copy (byte-prefix) of a larger file
+    #[clap(
+        long,
+        help = "Report partial duplicates: files whose bytes are the start of a larger file (e.g. leftover .part downloads)"
+    )]
+    pub detect_partial: bool,
+
+    /// Experimental: report large files that share many content blocks
+    #[clap(
+        long,
+        help = "Experimental: report pairs of large files sharing content blocks (content-defined chunking), e.g. VM images from one base"
+    )]
+    pub block_dedup: bool,
+
+    /// Smallest file --block-dedup looks at
+    #[clap(
+        long,
+        value_name = "SIZE",
+        requires = "block_dedup",
+        value_parser = crate::file_utils::parse_size,
+        help = "Only chunk files of at least SIZE for --block-dedup (default 1M)"
+    )]
+    pub block_min_size: Option<u64>,
+
+    /// Share of the smaller file that must be in shared blocks
+    #[clap(
+        long,
+        value_name = "FRACTION",
+        requires = "block_dedup",
+        default_value_t = crate::block_dedup::DEFAULT_BLOCK_OVERLAP,
+        value_parser = crate::block_dedup::parse_overlap,
+        help = "Report --block-dedup pairs whose shared blocks cover at least FRACTION of the smaller file"
+    )]
+    pub block_overlap: f64,
+
+    /// Keep running after the scan and handle duplicates as new files appear
+    #[clap(
+        long,
+        conflicts_with_all = ["interactive", "target"],
+        help = "After the scan, keep watching the directory (or --union directories) and report (or --delete/--move-to) each new duplicate as it appears"
+    )]
+    pub watch: bool,
+
+    /// Leave copied files with the copy time instead of the source's modification time
+    #[clap(
+        long,
+        help = "Do not carry the source modification time over to copied files"
+    )]
+    pub no_preserve_timestamps: bool,
+
+    /// Re-hash every copied file and compare it with its source once copying finishes
+    #[clap(
+        long,
+        help = "After copying missing files, re-hash each copy and report any that don't match the source"
+    )]
+    pub verify_after: bool,
+
+    /// Cap copy throughput, e.g. when the target is a network share on a slow link
+    #[clap(
+        long,
+        value_name = "RATE",
+        value_parser = crate::file_utils::parse_size,
+        help = "Limit copy speed to RATE bytes per second (e.g. 500K, 2M)"
+    )]
+    pub bwlimit: Option<u64>,
+
+    /// Continue partially copied files left behind by an interrupted run
+    #[clap(
+        long,
+        help = "Resume interrupted copies when the existing target file matches the start of the source"
+    )]
+    pub resume: bool,
+
+    /// In multi-directory mode, copy new files and overwrite target files only when the source is newer
+    #[clap(
+        long,
+        help = "Copy files missing from the target and replace target files only when the source is newer"
+    )]
+    pub update: bool,
+
     /// Enable media deduplication (images, videos, audio)
     #[clap(
         long,
@@ -219,29 +867,190 @@ pub struct Cli {
     )]
     pub media_similarity: u32,
 
+    /// Which file to keep among similar media files
+    #[clap(
+        long,
+        default_value = "format",
+        value_parser = ["date", "resolution", "format"],
+        help = "Keep the media file with the earliest EXIF capture date, best resolution, or preferred format [date|resolution|format]"
+    )]
+    pub media_keep: String,
+
     /// Media deduplication options (will be populated from above arguments)
     #[clap(skip)]
     pub media_dedup_options: MediaDedupOptions,
+
+    /// TUI theme settings from the config file
+    #[clap(skip)]
+    pub theme_config: ThemeConfig,
+
+    /// Junk names skipped while scanning, from the config's `default_excludes`.
+    /// `None` uses the built-in `DEFAULT_EXCLUDES`.
+    #[clap(skip)]
+    pub default_excludes: Option<Vec<String>>,
+
+    /// Per-extension hashing strategies from the config file, keyed by lowercase extension
+    #[clap(skip)]
+    pub hash_overrides: BTreeMap<String, HashOverride>,
+}
+
+/// Subcommands that operate on dedups state instead of scanning directories
+#[derive(Subcommand, Debug, Clone)]
+pub enum Commands {
+    /// Reverse the most recent journaled delete/move/copy operation
+    Undo {
+        /// Journal file to read (defaults to the dedups cache directory)
+        #[clap(long, value_name = "PATH", help = "Path to the undo journal file")]
+        journal_file: Option<PathBuf>,
+
+        /// Show what would be restored without changing anything
+        #[clap(long, help = "Show what would be undone without making changes")]
+        dry_run: bool,
+    },
+    /// Inspect or clear the hash cache in --cache-location
+    Cache {
+        #[clap(subcommand)]
+        action: CacheCommand,
+    },
+    /// Inspect the configuration dedups runs with
+    Config {
+        #[clap(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Time each hash algorithm on a generated file to help pick --algorithm
+    Benchmark {
+        /// Size of the generated test file in megabytes
+        #[clap(long, value_name = "MB", default_value_t = crate::benchmark::DEFAULT_BENCHMARK_SIZE_MB, help = "Size of the test file to hash, in MB")]
+        size: u64,
+    },
+}
+
+/// Actions for `dedups config`
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Print the merged configuration (config file plus command-line arguments) as TOML
+    Dump {
+        /// Print JSON instead of TOML
+        #[clap(long, help = "Print the configuration as JSON instead of TOML")]
+        json: bool,
+    },
+}
+
+/// Actions for `dedups cache`
+#[derive(Subcommand, Debug, Clone)]
+pub enum CacheCommand {
+    /// Show entry count, on-disk size, entry age range and algorithm breakdown
+    Stats,
+    /// Delete all cache files in the cache location
+    Clear,
 }
 
 impl Cli {
+    /// Where messages for the user go: stdout, or stderr under --print0 so stdout carries
+    /// nothing but the NUL-separated paths
+    pub fn message_output(&self) -> Box<dyn std::io::Write> {
+        if self.print0 {
+            Box::new(std::io::stderr())
+        } else {
+            Box::new(std::io::stdout())
+        }
+    }
+
+    /// Whether sets must be verified byte-for-byte before acting on them. Weak
+    /// checksums (crc32, fnv1a) and `auto` are always verified; other fast hashes only
+    /// with --verify.
+    pub fn verify_before_action(&self) -> bool {
+        self.algorithm_needs_verify(&self.algorithm)
+    }
+
+    /// `verify_before_action` for one set, judged by the algorithm that hashed it: a
+    /// `hash_overrides` entry's, when one did, otherwise --algorithm
+    pub fn verify_set_before_action(&self, set: &crate::file_utils::DuplicateSet) -> bool {
+        match crate::file_utils::override_algorithm(&set.hash) {
+            Some(algorithm) => self.algorithm_needs_verify(algorithm),
+            None => self.verify_before_action(),
+        }
+    }
+
+    fn algorithm_needs_verify(&self, algorithm: &str) -> bool {
+        match algorithm {
+            "crc32" | "fnv1a" | "auto" => true,
+            algorithm => self.verify && !crate::file_utils::is_cryptographic_hash(algorithm),
+        }
+    }
+
+    /// Whether file names that differ only in case name the same file. Unless set
+    /// explicitly, this follows the platform's default filesystem behaviour.
+    pub fn case_insensitive_names(&self) -> bool {
+        self.case_insensitive_names
+            .unwrap_or(cfg!(any(target_os = "macos", target_os = "windows")))
+    }
+
+    /// The selection strategy for choosing which file to keep: --keep-dir if given, otherwise --mode
+    pub fn selection_strategy(&self) -> anyhow::Result<SelectionStrategy> {
+        match &self.keep_dir {
+            Some(dir) => Ok(SelectionStrategy::PreferDirectory(dir.clone())),
+            None => SelectionStrategy::from_str(&self.mode),
+        }
+    }
+
     /// Apply configuration values from .deduprc to CLI arguments
     pub fn with_config() -> anyhow::Result<Self> {
-        // Parse CLI arguments first
-        let mut cli = Self::parse();
+        Self::with_config_from(std::env::args_os())
+    }
+
+    /// `with_config` for an explicit argument list (the first item is the program name)
+    pub fn with_config_from<I, T>(args: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        // Parse CLI arguments first; a usage error comes back as a clap::Error
+        let matches = Self::command().try_get_matches_from(args)?;
+        let mut cli = Self::from_arg_matches(&matches)?;
+
+        // Clap fills in defaults, so blank the ones the user didn't pass; apply_config
+        // then takes them from the config file, falling back to the same defaults
+        let defaulted =
+            |id: &str| matches.value_source(id) == Some(clap::parser::ValueSource::DefaultValue);
+        if defaulted("algorithm") {
+            cli.algorithm.clear();
+        }
+        if defaulted("mode") {
+            cli.mode.clear();
+        }
+        if defaulted("format") {
+            cli.format.clear();
+        }
+
+        // An explicit --algorithm gxhash that this build can't honour is an error up front,
+        // rather than a failure partway through hashing
+        if cli.algorithm == "gxhash" && !crate::file_utils::GXHASH_AVAILABLE {
+            return Err(crate::error::DedupError::UnsupportedAlgorithm(
+                "gxhash is only available on Linux builds with the `linux` feature; choose another --algorithm".to_string(),
+            )
+            .into());
+        }
 
         // Initialize media_dedup_options with defaults
         cli.media_dedup_options = MediaDedupOptions::default();
 
+        if let Some(config_path) = &cli.config_file {
+            cli.config_file = Some(crate::file_utils::expand_path_buf(config_path));
+        }
+
         // Load configuration from specified file or default location
-        let config = if let Some(config_path) = &cli.config_file {
-            DedupConfig::load_from_path(config_path)?
-        } else {
-            DedupConfig::load()?
+        let config = match (&cli.config_file, cli.follow_config_includes) {
+            (Some(config_path), false) => DedupConfig::load_from_path(config_path)?,
+            (Some(config_path), true) => DedupConfig::load_with_includes(config_path)?,
+            (None, false) => DedupConfig::load()?,
+            (None, true) => DedupConfig::load_with_includes(&DedupConfig::get_config_path()?)?,
         };
 
         // Apply config values for any unspecified CLI arguments
         cli.apply_config(config);
+        // After the config, so paths it supplied are expanded too
+        cli.expand_paths();
 
         // Apply media deduplication options based on CLI arguments
         if cli.media_mode {
@@ -252,6 +1061,7 @@ impl Cli {
                 &cli.media_resolution,
                 &cli.media_formats,
                 cli.media_similarity,
+                &cli.media_keep,
             );
         }
 
@@ -264,12 +1074,65 @@ impl Cli {
         Ok(cli)
     }
 
+    /// Expand `~` and resolve relative paths in every path-valued argument (see
+    /// `file_utils::expand_path`)
+    pub fn expand_paths(&mut self) {
+        use crate::file_utils::expand_path_buf;
+
+        let expand_all = |paths: &mut Vec<PathBuf>| {
+            for path in paths.iter_mut() {
+                *path = expand_path_buf(path);
+            }
+        };
+        expand_all(&mut self.directories);
+        expand_all(&mut self.exclude_dir);
+        expand_all(&mut self.protect);
+        for path in [
+            &mut self.target,
+            &mut self.move_to,
+            &mut self.quarantine,
+            &mut self.log_file,
+            &mut self.output,
+            &mut self.write_manifest,
+            &mut self.manifest,
+            &mut self.html_report,
+            &mut self.json_events,
+            &mut self.keep_dir,
+            &mut self.load_session,
+            &mut self.files_from,
+            &mut self.filter_from,
+            &mut self.config_file,
+            &mut self.cache_location,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = expand_path_buf(path);
+        }
+        if let Some(Commands::Undo {
+            journal_file: Some(path),
+            ..
+        }) = &mut self.command
+        {
+            *path = expand_path_buf(path);
+        }
+    }
+
     /// Apply config values to CLI arguments that weren't explicitly provided
     fn apply_config(&mut self, config: DedupConfig) {
         // Only apply config values for arguments that weren't specified on the command line
 
         if self.algorithm.is_empty() {
-            self.algorithm = config.algorithm;
+            // A shared config may name gxhash on a platform without it; fall back instead of failing mid-scan
+            self.algorithm = if config.algorithm == "gxhash" && !crate::file_utils::GXHASH_AVAILABLE
+            {
+                log::warn!(
+                    "gxhash from config is not available on this platform; using xxhash instead"
+                );
+                "xxhash".to_string()
+            } else {
+                config.algorithm
+            };
         }
 
         if self.parallel.is_none() {
@@ -321,6 +1184,61 @@ impl Cli {
             self.fast_mode = config.fast_mode;
         }
 
+        self.default_excludes = Some(config.default_excludes);
+
+        if self.tiebreak.is_empty() {
+            for name in config.tiebreak {
+                match crate::file_utils::TieBreaker::from_str(&name) {
+                    Ok(breaker) => self.tiebreak.push(breaker),
+                    Err(e) => log::warn!("Ignoring tiebreak from config: {}", e),
+                }
+            }
+        }
+
+        // Protection only ever widens: config paths are added to any given with --protect
+        for path in config.protected_paths {
+            if !self.protect.contains(&path) {
+                self.protect.push(path);
+            }
+        }
+
+        if self.theme.is_none() {
+            self.theme = config.theme.name.clone();
+        }
+        self.theme_config = config.theme;
+
+        for (extension, strategy) in config.hash_overrides {
+            let extension = extension.trim_start_matches('.').to_lowercase();
+            let supported = crate::file_utils::available_hash_algorithms();
+            if !supported.contains(&strategy.algorithm.as_str()) {
+                log::warn!(
+                    "Ignoring hash override for .{}: unsupported algorithm '{}' (available: {})",
+                    extension,
+                    strategy.algorithm,
+                    supported.join(", ")
+                );
+                continue;
+            }
+            self.hash_overrides.insert(extension, strategy);
+        }
+
+        if !self.journal && config.journal_enabled {
+            self.journal = true;
+        }
+
+        if !self.resume && config.resume_transfers {
+            self.resume = true;
+        }
+
+        if self.bwlimit.is_none() {
+            if let Some(rate) = &config.bwlimit {
+                match crate::file_utils::parse_size(rate) {
+                    Ok(rate) => self.bwlimit = Some(rate),
+                    Err(e) => log::warn!("Ignoring bwlimit from config: {}", e),
+                }
+            }
+        }
+
         // If fast mode is enabled but no cache location is specified, disable fast mode and warn
         if self.fast_mode && self.cache_location.is_none() {
             log::warn!(
@@ -328,6 +1246,12 @@ impl Cli {
             );
             self.fast_mode = false;
         }
+        if self.incremental && self.cache_location.is_none() {
+            log::warn!(
+                "Incremental mode enabled but no cache location specified. Incremental mode will be disabled."
+            );
+            self.incremental = false;
+        }
 
         // Apply media deduplication options
         // CLI explicit flags take precedence over config file
@@ -350,6 +1274,50 @@ impl Cli {
             self.mode = "newest_modified".to_string();
         }
     }
+
+    /// The configuration this run actually uses, after command-line arguments and the
+    /// config file are merged (`dedups config dump`). Saved as a .deduprc, it reproduces
+    /// the same settings.
+    pub fn effective_config(&self) -> DedupConfig {
+        let default_excludes = if self.no_default_excludes {
+            Vec::new()
+        } else {
+            self.default_excludes.clone().unwrap_or_else(|| {
+                crate::file_utils::DEFAULT_EXCLUDES
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect()
+            })
+        };
+        let mut media_dedup = self.media_dedup_options.clone();
+        media_dedup.enabled = self.media_mode;
+        let mut theme = self.theme_config.clone();
+        theme.name = self.theme.clone();
+
+        DedupConfig {
+            algorithm: self.algorithm.clone(),
+            parallel: self.parallel,
+            mode: self.mode.clone(),
+            format: self.format.clone(),
+            progress: self.progress,
+            sort_by: self.sort_by.to_string(),
+            sort_order: self.sort_order.to_string(),
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            default_excludes,
+            cache_location: self.cache_location.clone(),
+            fast_mode: self.fast_mode,
+            media_dedup,
+            journal_enabled: self.journal,
+            bwlimit: self.bwlimit.map(|rate| rate.to_string()),
+            resume_transfers: self.resume,
+            protected_paths: self.protect.clone(),
+            tiebreak: self.tiebreak.iter().map(|t| t.to_string()).collect(),
+            theme,
+            config_includes: Vec::new(),
+            hash_overrides: self.hash_overrides.clone(),
+        }
+    }
 }
 
 // If your Cli struct is already in main.rs and you want to keep it there for now (less ideal for testing library parts),
@@ -0,0 +1,51 @@
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+
+/// Log formats accepted by `--log-format`
+pub const LOG_FORMATS: [&str; 2] = ["text", "json"];
+
+/// One line of `--log-format json` output
+#[derive(Debug, Serialize)]
+struct JsonLogLine<'a> {
+    timestamp: String,
+    level: &'a str,
+    module: Option<&'a str>,
+    message: String,
+}
+
+/// Render a log record as a single-line JSON object with `timestamp` (RFC 3339, UTC),
+/// `level`, `module` and `message`, for log aggregators that ingest JSON lines
+pub fn json_log_line(record: &log::Record) -> String {
+    let line = JsonLogLine {
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        level: record.level().as_str(),
+        module: record.module_path(),
+        message: record.args().to_string(),
+    };
+    // Only strings go in, so serializing can't fail
+    serde_json::to_string(&line).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_log_line_has_expected_fields() {
+        let line = json_log_line(
+            &log::Record::builder()
+                .level(log::Level::Warn)
+                .module_path(Some("dedups::file_utils"))
+                .args(format_args!("Failed to hash \"{}\"\nretrying", "a.txt"))
+                .build(),
+        );
+        assert!(!line.contains('\n'), "one record per line: {}", line);
+
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["level"], "WARN");
+        assert_eq!(parsed["module"], "dedups::file_utils");
+        assert_eq!(parsed["message"], "Failed to hash \"a.txt\"\nretrying");
+        let timestamp = parsed["timestamp"].as_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(timestamp).is_ok());
+    }
+}
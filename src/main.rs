@@ -3,16 +3,30 @@
 
 use anyhow::Result;
 use humansize::{format_size, DECIMAL};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use simplelog::LevelFilter;
+use std::collections::HashSet;
+use std::io::Write;
 use std::path::Path;
-use std::str::FromStr;
 
+use dedups::actions;
+use dedups::benchmark;
+use dedups::block_dedup;
 use dedups::config::DedupConfig;
+use dedups::dir_dedup;
+use dedups::error::DedupError;
+use dedups::exit_code;
 use dedups::file_utils;
+use dedups::journal::{self, Journal};
+use dedups::json_events::{self, JsonEventWriter, JsonOutput};
+use dedups::manifest::{self, Manifest};
+use dedups::partial;
+use dedups::transfer::TransferOptions;
 use dedups::tui_app;
-use dedups::Cli;
+use dedups::update_mode::{self, UpdateSummary};
+use dedups::{CacheCommand, Cli, Commands, ConfigCommand};
 
-fn setup_logger(verbosity: u8, log_file: Option<&Path>) -> Result<()> {
+fn setup_logger(verbosity: u8, log_file: Option<&Path>, json: bool) -> Result<()> {
     let level = match verbosity {
         0 => LevelFilter::Info,
         1 => LevelFilter::Debug,
@@ -23,6 +37,10 @@ fn setup_logger(verbosity: u8, log_file: Option<&Path>) -> Result<()> {
     builder.filter_level(level);
     builder.format_timestamp_millis();
     builder.format_target(false);
+    if json {
+        builder
+            .format(|buf, record| writeln!(buf, "{}", dedups::log_format::json_log_line(record)));
+    }
 
     if let Some(log_path) = log_file {
         let file = std::fs::File::create(log_path)?;
@@ -33,7 +51,24 @@ fn setup_logger(verbosity: u8, log_file: Option<&Path>) -> Result<()> {
     Ok(())
 }
 
-fn main() -> Result<()> {
+fn main() {
+    let result = run();
+    if let Err(e) = &result {
+        match e.downcast_ref::<clap::Error>() {
+            // --help and --version come back as errors too, but exit successfully
+            Some(clap_error) => {
+                let _ = clap_error.print();
+                if clap_error.exit_code() == 0 {
+                    std::process::exit(0);
+                }
+            }
+            None => eprintln!("Error: {:?}", e),
+        }
+    }
+    std::process::exit(exit_code::for_result(&result));
+}
+
+fn run() -> Result<()> {
     // Load CLI args with config from .deduprc
     let cli = Cli::with_config()?;
 
@@ -41,7 +76,15 @@ fn main() -> Result<()> {
     if cli.interactive {
         // For interactive mode, use a file
         let log_file = Some(Path::new("dedups.log"));
-        setup_logger(cli.verbose, log_file)?;
+        setup_logger(cli.verbose, log_file, cli.log_format == "json")?;
+    } else if cli.log_format == "json" {
+        // Structured logs go to the log file when one is enabled, otherwise to stderr
+        let log_path = match &cli.log_file {
+            Some(path) => Some(path.as_path()),
+            None if cli.log => Some(Path::new("dedups.log")),
+            None => None,
+        };
+        setup_logger(cli.verbose, log_path, true)?;
     } else if cli.log || cli.log_file.is_some() {
         // User enabled logging
         let log_path = if let Some(path) = &cli.log_file {
@@ -49,7 +92,10 @@ fn main() -> Result<()> {
         } else {
             Path::new("dedups.log")
         };
-        setup_logger(cli.verbose, Some(log_path))?;
+        setup_logger(cli.verbose, Some(log_path), false)?;
+    } else if cli.print0 {
+        // stdout carries only the NUL-separated paths, so log to stderr
+        setup_logger(cli.verbose, None, false)?;
     } else if cli.progress {
         // CLI progress display - use terminal logger
         simplelog::TermLogger::init(
@@ -98,25 +144,82 @@ fn main() -> Result<()> {
         }
     }
 
+    if let Some(command) = &cli.command {
+        return run_command(&cli, command);
+    }
+
+    let read_buffer = file_utils::read_buffer_size(cli.read_buffer);
+    if read_buffer as u64 != cli.read_buffer {
+        log::info!(
+            "Using a {} byte read buffer (--read-buffer {} adjusted to a power of two between {} and {})",
+            read_buffer,
+            cli.read_buffer,
+            file_utils::MIN_READ_BUFFER,
+            file_utils::MAX_READ_BUFFER
+        );
+    }
+
+    if cli.journal {
+        let journal_path = Journal::default_path()?;
+        log::info!("Recording actions to undo journal at {:?}", journal_path);
+        journal::set_active_journal(Some(Journal::new(&journal_path)));
+    }
+
+    let events = match &cli.json_events {
+        Some(path) => Some(std::sync::Arc::new(JsonEventWriter::create(
+            path,
+            cli.json_pretty,
+        )?)),
+        None => None,
+    };
+
     // Check if directories exist
     for dir in &cli.directories {
-        if !dir.exists() {
+        let error = if !dir.exists() {
             log::error!("Target directory {:?} does not exist.", dir);
-            return Err(anyhow::anyhow!(
-                "Target directory does not exist: {:?}",
-                dir
-            ));
-        }
-        if !dir.is_dir() {
+            DedupError::DirectoryNotFound(dir.clone())
+        } else if !dir.is_dir() {
             log::error!("Target path {:?} is not a directory.", dir);
-            return Err(anyhow::anyhow!("Target path is not a directory: {:?}", dir));
-        }
+            DedupError::NotADirectory(dir.clone())
+        } else {
+            continue;
+        };
+        let error = anyhow::Error::from(error);
+        emit_event(events.as_deref(), &JsonOutput::error(&error));
+        return Err(error);
     }
 
     // Check if we're comparing multiple directories
-    let is_multi_directory = cli.directories.len() > 1 || cli.target.is_some();
+    // --union treats all directories as one pool, so it uses the single-pass flow
+    let is_multi_directory = (cli.directories.len() > 1 && !cli.union) || cli.target.is_some();
+    if cli.watch && is_multi_directory {
+        let error = anyhow::anyhow!(
+            "--watch watches one directory; add --union to watch several as one pool"
+        );
+        emit_event(events.as_deref(), &JsonOutput::error(&error));
+        return Err(error);
+    }
+    // A sampled target would make every file past the limit look missing
+    if cli.limit.is_some() && is_multi_directory {
+        let error = anyhow::anyhow!(
+            "--limit samples a single scan and can't be used when comparing directories; add --union to sample them as one pool"
+        );
+        emit_event(events.as_deref(), &JsonOutput::error(&error));
+        return Err(error);
+    }
 
-    if cli.interactive {
+    if let Some(manifest_path) = &cli.write_manifest {
+        let manifest = Manifest::build(&cli)?;
+        manifest.write(manifest_path)?;
+        println!(
+            "Wrote {} file hashes ({}) to manifest {:?}",
+            manifest.files.len(),
+            manifest.algorithm,
+            manifest_path
+        );
+    } else if let Some(manifest_path) = &cli.manifest {
+        compare_against_manifest(&cli, manifest_path)?;
+    } else if cli.interactive || cli.load_session.is_some() {
         log::info!(
             "Interactive mode selected for directories: {:?}",
             cli.directories
@@ -124,29 +227,85 @@ fn main() -> Result<()> {
         tui_app::run_tui_app(&cli)?
     } else if is_multi_directory {
         // Multiple directory mode - handling copying missing files or deduplication
-        handle_multi_directory_mode(&cli)?;
+        handle_multi_directory_mode(&cli, events.as_deref())?;
     } else {
         // Single directory mode - find duplicates within one directory
         log::info!(
-            "Non-interactive mode selected for directory: {:?}",
-            cli.directories[0]
+            "Non-interactive mode selected for directories: {:?}",
+            cli.directories
         );
 
         // Since we're not in TUI mode, we need a channel to receive progress updates
-        let (tx, _rx) = std::sync::mpsc::channel();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let progress_forwarder = json_events::spawn_progress_forwarder(rx, events.clone());
 
-        match file_utils::find_duplicate_files_with_progress(&cli, tx) {
-            Ok(duplicate_sets) => {
-                if duplicate_sets.is_empty() {
-                    log::info!("No duplicate files found.");
-                    println!("No duplicate files found.");
+        // Ctrl-C stops the scan early instead of killing the process mid cache write
+        if let Err(e) = file_utils::install_interrupt_handler() {
+            log::warn!("Could not install Ctrl-C handler: {}", e);
+        }
+        // --watch carries on from the files this scan finds
+        let mut watch_index = Vec::new();
+        let scan_result = if cli.watch {
+            file_utils::find_duplicate_files_indexed(
+                &cli,
+                tx,
+                &file_utils::interrupt_flag(),
+                &mut watch_index,
+            )
+        } else {
+            file_utils::find_duplicate_files_cancellable(&cli, tx, &file_utils::interrupt_flag())
+        };
+        // The sender was moved into the scan, so the forwarder finishes once it returns
+        let _ = progress_forwarder.join();
+        let cancelled = file_utils::interrupt_flag().load(std::sync::atomic::Ordering::Relaxed);
+
+        match scan_result {
+            Ok((duplicate_sets, sampled)) => {
+                let final_result = JsonOutput::final_result(&duplicate_sets, Vec::new(), cli.top)
+                    .with_sample_limit(sample_limit(&cli, sampled));
+                print_sample_note(&cli, sampled)?;
+                emit_event(
+                    events.as_deref(),
+                    &if cli.summary_only {
+                        final_result.without_listing()
+                    } else {
+                        final_result
+                    },
+                );
+                if cancelled {
+                    // Sets may be incomplete, so nothing is acted on
+                    if !duplicate_sets.is_empty() {
+                        exit_code::record_duplicates_found();
+                    }
+                    log::warn!("Scan cancelled; no files were changed.");
+                    println!(
+                        "Scan cancelled. Found {} sets of duplicate files before stopping; no files were changed.",
+                        duplicate_sets.len()
+                    );
                 } else {
-                    handle_duplicate_sets(&cli, &duplicate_sets)?;
+                    if duplicate_sets.is_empty() {
+                        log::info!("No duplicate files found.");
+                        if !cli.print0 {
+                            println!("No duplicate files found.");
+                        }
+                    } else {
+                        handle_duplicate_sets(&cli, &duplicate_sets, events.as_deref())?;
+                    }
+                    if cli.detect_partial {
+                        report_partial_duplicates(&cli)?;
+                    }
+                    if cli.block_dedup {
+                        report_block_overlaps(&cli)?;
+                    }
+                    if cli.watch {
+                        dedups::watch::run_watch(&cli, watch_index)?;
+                    }
                 }
             }
             Err(e) => {
                 log::error!("Error finding duplicate files: {}", e);
-                eprintln!("Error: {}", e);
+                emit_event(events.as_deref(), &JsonOutput::error(&e));
+                return Err(e);
             }
         }
     }
@@ -154,8 +313,134 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// --manifest: list which scanned files the reference manifest already holds, and which are new
+fn compare_against_manifest(cli: &Cli, manifest_path: &Path) -> Result<()> {
+    let manifest = Manifest::load(manifest_path)?;
+    println!(
+        "Checking against {} files in manifest {:?} ({})...",
+        manifest.files.len(),
+        manifest_path,
+        manifest.algorithm
+    );
+    let mut files = Vec::new();
+    for directory in &cli.directories {
+        files.extend(file_utils::scan_directory(cli, directory, false)?);
+    }
+    let comparison = manifest::compare_with_manifest(&files, &manifest);
+    if !comparison.known.is_empty() {
+        exit_code::record_duplicates_found();
+    }
+
+    let size = |bytes: u64| {
+        if cli.raw_sizes {
+            format!("{} bytes", bytes)
+        } else {
+            format_size(bytes, DECIMAL)
+        }
+    };
+    if !comparison.known.is_empty() {
+        println!(
+            "\nAlready in the manifest ({} files, {} could be deleted):",
+            comparison.known.len(),
+            size(comparison.redundant_bytes())
+        );
+        for (file, manifest_file) in &comparison.known {
+            println!(
+                "  - {} (same as {})",
+                file.path.display(),
+                manifest_file.display()
+            );
+        }
+    }
+    if !comparison.new_files.is_empty() {
+        println!(
+            "\nNot in the manifest ({} files):",
+            comparison.new_files.len()
+        );
+        for file in &comparison.new_files {
+            println!("  + {}", file.path.display());
+        }
+    }
+    if files.is_empty() {
+        println!("No files found to check.");
+    }
+    Ok(())
+}
+
+/// --verify-after: re-hash each (source, copy) pair, failing if any differ
+fn verify_copied_files(cli: &Cli, pairs: Vec<file_utils::CopiedFile>) -> Result<()> {
+    let algorithm = file_utils::resolved_algorithm(cli);
+    println!(
+        "Verifying {} copied files with {}...",
+        pairs.len(),
+        algorithm
+    );
+
+    let mismatches = file_utils::verify_copies(&pairs, algorithm)?;
+    if mismatches.is_empty() {
+        println!("All {} copies match their sources.", pairs.len());
+        return Ok(());
+    }
+    for mismatch in &mismatches {
+        log::error!("{}", mismatch);
+        eprintln!("{}", mismatch);
+    }
+    anyhow::bail!(
+        "{} of {} copied files failed verification",
+        mismatches.len(),
+        pairs.len()
+    )
+}
+
+/// Write an event to the --json-events file, if one was requested
+fn emit_event(events: Option<&JsonEventWriter>, event: &JsonOutput) {
+    if let Some(writer) = events {
+        if let Err(e) = writer.emit(event) {
+            log::warn!("Failed to write JSON event: {}", e);
+        }
+    }
+}
+
+/// Overall (files) and current-file (bytes) bars for the missing-file copy phase
+fn copy_progress_bars(total_files: usize) -> (ProgressBar, ProgressBar) {
+    let multi = MultiProgress::new();
+    let overall = multi.add(ProgressBar::new(total_files as u64));
+    overall.set_style(
+        ProgressStyle::with_template("{prefix:>8} [{bar:40.cyan/blue}] {pos}/{len} files ({eta})")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> "),
+    );
+    overall.set_prefix("Copying");
+    let current = multi.add(ProgressBar::new(0));
+    current.set_style(
+        ProgressStyle::with_template("{prefix:>8} {bytes}/{total_bytes} {wide_msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+    );
+    current.set_prefix("File");
+    (overall, current)
+}
+
+/// The --limit value, if the scan actually stopped there
+fn sample_limit(cli: &Cli, sampled: bool) -> Option<usize> {
+    cli.limit.filter(|_| sampled)
+}
+
+/// Warn that the results only cover a --limit sample. Goes to stderr with --print0 so
+/// stdout stays paths only.
+fn print_sample_note(cli: &Cli, sampled: bool) -> Result<()> {
+    if let Some(limit) = sample_limit(cli, sampled) {
+        let note = format!(
+            "Note: discovery stopped at --limit {} files; results are a partial sample.",
+            limit
+        );
+        log::info!("{}", note);
+        writeln!(cli.message_output(), "{}", note)?;
+    }
+    Ok(())
+}
+
 // Handle multiple directory mode - comparing directories and copying/deduplicating
-fn handle_multi_directory_mode(cli: &Cli) -> Result<()> {
+fn handle_multi_directory_mode(cli: &Cli, events: Option<&JsonEventWriter>) -> Result<()> {
     log::info!("Multi-directory mode: Comparing directories");
     println!("Comparing directories for missing files or duplicates...");
 
@@ -171,10 +456,49 @@ fn handle_multi_directory_mode(cli: &Cli) -> Result<()> {
     println!("Source directories: {:?}", source_dirs);
     println!("Target directory: {:?}", target_dir);
 
+    if cli.update {
+        handle_update_mode(cli, &source_dirs, &target_dir, events)?;
+        if !cli.deduplicate {
+            print_dry_run_reminder(cli);
+            return Ok(());
+        }
+    }
+
+    emit_event(
+        events,
+        &JsonOutput::progress(
+            1,
+            format!("Comparing directories against {}", target_dir.display()),
+        ),
+    );
     let comparison_result = file_utils::compare_directories(cli)?;
+    let mut input_dirs = source_dirs.clone();
+    input_dirs.push(target_dir.clone());
+    let per_directory =
+        file_utils::summarize_by_directory(&comparison_result.duplicates, &input_dirs);
+    let final_result = JsonOutput::final_result(
+        &comparison_result.duplicates,
+        comparison_result
+            .missing_in_target
+            .iter()
+            .map(|f| f.path.clone())
+            .collect(),
+        cli.top,
+    )
+    .with_per_directory(per_directory.clone());
+    emit_event(
+        events,
+        &if cli.summary_only {
+            final_result.without_listing()
+        } else {
+            final_result
+        },
+    );
 
-    // Handle missing files
-    if !comparison_result.missing_in_target.is_empty() {
+    // Handle missing files (--update already copied them above)
+    if cli.update {
+        log::debug!("Skipping missing-file copy; handled by --update");
+    } else if !comparison_result.missing_in_target.is_empty() {
         println!(
             "Found {} files that exist in source but not in target.",
             comparison_result.missing_in_target.len()
@@ -190,13 +514,26 @@ fn handle_multi_directory_mode(cli: &Cli) -> Result<()> {
             println!("Warning: Move flag is ignored for missing files. They will be copied to the target directory.");
         }
 
-        // Copy missing files to target directory
-        match file_utils::copy_missing_files(
+        // Copy missing files to target directory, with progress bars when requested
+        let (overall_bar, current_bar) = if cli.progress {
+            copy_progress_bars(comparison_result.missing_in_target.len())
+        } else {
+            (ProgressBar::hidden(), ProgressBar::hidden())
+        };
+        let copy_result = file_utils::copy_missing_files_with_progress(
             &comparison_result.missing_in_target,
             &target_dir,
             cli.dry_run,
-        ) {
-            Ok((count, logs)) => {
+            !cli.no_preserve_timestamps,
+            &TransferOptions::from_cli(cli),
+            &overall_bar,
+            &current_bar,
+        );
+        current_bar.finish_and_clear();
+        overall_bar.finish_and_clear();
+
+        match copy_result {
+            Ok((copied, logs)) => {
                 // Display all log messages
                 for log_msg in logs {
                     // Only log to file what hasn't already been logged in the function
@@ -212,7 +549,15 @@ fn handle_multi_directory_mode(cli: &Cli) -> Result<()> {
                 } else {
                     "Successfully copied"
                 };
-                println!("\n{} {} files to target directory.", action_prefix, count);
+                println!(
+                    "\n{} {} files to target directory.",
+                    action_prefix,
+                    copied.len()
+                );
+
+                if cli.verify_after && !cli.dry_run {
+                    verify_copied_files(cli, copied)?;
+                }
             }
             Err(e) => {
                 log::error!("Failed to copy files: {}", e);
@@ -229,45 +574,402 @@ fn handle_multi_directory_mode(cli: &Cli) -> Result<()> {
             "Found {} duplicate sets across source and target directories.",
             comparison_result.duplicates.len()
         );
+        print_directory_summary(cli, &per_directory);
 
         // Process duplicates similar to single directory mode
-        handle_duplicate_sets(cli, &comparison_result.duplicates)?;
+        handle_duplicate_sets(cli, &comparison_result.duplicates, events)?;
     } else if cli.deduplicate {
         println!("No duplicate files found across source and target directories.");
     }
 
-    // Add final reminder if in dry run mode
+    print_dry_run_reminder(cli);
+    Ok(())
+}
+
+/// Final reminder that nothing was modified, if in dry run mode
+fn print_dry_run_reminder(cli: &Cli) {
     if cli.dry_run {
         println!("\nThis was a dry run. No files were actually modified.");
         println!("Run without --dry-run to perform actual operations.");
         log::info!("Dry run completed - no files were modified");
     }
+}
+
+/// `--update`: copy new files into the target and refresh ones the sources have newer versions of
+fn handle_update_mode(
+    cli: &Cli,
+    source_dirs: &[std::path::PathBuf],
+    target_dir: &Path,
+    events: Option<&JsonEventWriter>,
+) -> Result<()> {
+    let mut total = UpdateSummary::default();
+    for source_dir in source_dirs {
+        let source_files = file_utils::scan_directory(cli, source_dir, false)?;
+        let (summary, logs) = update_mode::update_files(
+            source_dir,
+            &source_files,
+            target_dir,
+            cli.dry_run,
+            !cli.no_preserve_timestamps,
+            &TransferOptions::from_cli(cli),
+        )?;
+        for log_msg in logs {
+            log::info!("{}", log_msg);
+            println!("{}", log_msg);
+        }
+        total.copied += summary.copied;
+        total.updated += summary.updated;
+        total.skipped += summary.skipped;
+    }
 
+    println!(
+        "\n{}{} new, {} updated, {} skipped (target up to date)",
+        if cli.dry_run {
+            "[DRY RUN] Would have copied "
+        } else {
+            "Copied "
+        },
+        total.copied,
+        total.updated,
+        total.skipped
+    );
+    emit_event(
+        events,
+        &JsonOutput::Update {
+            dry_run: cli.dry_run,
+            summary: total,
+        },
+    );
     Ok(())
 }
 
-// Handle duplicate sets (common code for both single and multi-directory modes)
-fn handle_duplicate_sets(cli: &Cli, duplicate_sets: &[file_utils::DuplicateSet]) -> Result<()> {
-    log::info!("Found {} sets of duplicate files.", duplicate_sets.len());
-    println!("Found {} sets of duplicate files:", duplicate_sets.len());
+fn run_command(cli: &Cli, command: &Commands) -> Result<()> {
+    match command {
+        Commands::Undo {
+            journal_file,
+            dry_run,
+        } => {
+            let journal_path = match journal_file {
+                Some(path) => path.clone(),
+                None => Journal::default_path()?,
+            };
+            log::info!("Undoing last operation from journal {:?}", journal_path);
+            let (count, logs) = journal::undo_last_operation(&journal_path, *dry_run)?;
+            for log_msg in logs {
+                log::info!("{}", log_msg);
+                println!("{}", log_msg);
+            }
+            let action_prefix = if *dry_run {
+                "[DRY RUN] Would have "
+            } else {
+                ""
+            };
+            println!("\n{}reversed {} actions", action_prefix, count);
+            Ok(())
+        }
+        Commands::Cache { action } => {
+            let cache_dir = cli.cache_location.as_ref().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No cache location configured. Pass --cache-location or set cache_location in .deduprc"
+                )
+            })?;
+            match action {
+                CacheCommand::Stats => {
+                    let stats = dedups::file_cache::cache_stats(cache_dir)?;
+                    let format_time = |t: Option<std::time::SystemTime>| {
+                        t.map(|t| {
+                            chrono::DateTime::<chrono::Local>::from(t)
+                                .format("%Y-%m-%d %H:%M:%S")
+                                .to_string()
+                        })
+                        .unwrap_or_else(|| "unknown".to_string())
+                    };
+                    println!("Cache location: {}", cache_dir.display());
+                    println!("Entries:        {}", stats.entry_count);
+                    println!(
+                        "Size on disk:   {}",
+                        if cli.raw_sizes {
+                            format!("{} bytes", stats.size_on_disk)
+                        } else {
+                            format_size(stats.size_on_disk, DECIMAL)
+                        }
+                    );
+                    println!("Oldest entry:   {}", format_time(stats.oldest_entry));
+                    println!("Newest entry:   {}", format_time(stats.newest_entry));
+                    if !stats.entries_by_algorithm.is_empty() {
+                        println!("Entries by algorithm:");
+                        for (algorithm, count) in &stats.entries_by_algorithm {
+                            println!("  {:<10} {}", algorithm, count);
+                        }
+                    }
+                }
+                CacheCommand::Clear => {
+                    let removed = dedups::file_cache::clear_cache(cache_dir)?;
+                    println!(
+                        "Removed {} cache files from {}",
+                        removed,
+                        cache_dir.display()
+                    );
+                }
+            }
+            Ok(())
+        }
+        Commands::Config {
+            action: ConfigCommand::Dump { json },
+        } => {
+            let config = cli.effective_config();
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&config)?);
+            } else {
+                print!("{}", config.to_toml_string()?);
+            }
+            Ok(())
+        }
+        Commands::Benchmark { size } => {
+            println!(
+                "Hashing a {} MB file with each algorithm (best of {} runs)...",
+                size,
+                benchmark::BENCHMARK_RUNS
+            );
+            let results = benchmark::run_benchmark(*size, benchmark::BENCHMARK_RUNS)?;
+            println!("\n  {:<10} {:>12}  Cryptographic", "Algorithm", "MB/s");
+            for result in &results {
+                println!(
+                    "  {:<10} {:>12.1}  {}",
+                    result.algorithm,
+                    result.mb_per_sec,
+                    if result.cryptographic { "yes" } else { "no" }
+                );
+            }
+            println!("\nNon-cryptographic hashes are faster but can collide; pair them with --verify (or use --algorithm auto) when acting on results.");
+            Ok(())
+        }
+    }
+}
+
+/// Print where the redundant bytes live, e.g. ".mov: 80.0% of redundant space"
+fn print_extension_summary(cli: &Cli, duplicate_sets: &[file_utils::DuplicateSet]) {
+    let _ = file_utils::write_extension_summary(
+        duplicate_sets,
+        cli.raw_sizes,
+        &mut std::io::stdout().lock(),
+    );
+}
 
-    for set in duplicate_sets {
+/// Print how many duplicate files and redundant bytes each input directory holds
+fn print_directory_summary(cli: &Cli, stats: &[file_utils::DirDuplicateStat]) {
+    if stats.iter().all(|s| s.files == 0) {
+        return;
+    }
+    println!("\nDuplicates by directory:");
+    for stat in stats {
+        let size = if cli.raw_sizes {
+            format!("{} bytes", stat.redundant_bytes)
+        } else {
+            format_size(stat.redundant_bytes, DECIMAL)
+        };
+        println!(
+            "  {:<40} {:>6} files, {} redundant",
+            stat.directory.display(),
+            stat.files,
+            size
+        );
+    }
+    println!();
+}
+
+/// List folders whose whole contents are duplicated elsewhere, so they can be removed at once
+fn print_duplicate_directories(cli: &Cli, groups: &[dir_dedup::DuplicateDirGroup]) {
+    if groups.is_empty() {
+        return;
+    }
+    println!("\nDuplicate directories (identical contents):");
+    for group in groups {
+        let size = if cli.raw_sizes {
+            format!("{} bytes", group.total_size)
+        } else {
+            format_size(group.total_size, DECIMAL)
+        };
+        println!(
+            "  {} copies of {} files ({} each):",
+            group.directories.len(),
+            group.file_count,
+            size
+        );
+        for dir in &group.directories {
+            println!("    - {}", dir.display());
+        }
+    }
+}
+
+/// Every file under the CLI directories, each once even when the directories overlap
+fn scan_all_directories(cli: &Cli) -> Result<Vec<file_utils::FileInfo>> {
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for directory in &cli.directories {
+        for file in file_utils::scan_directory(cli, directory, false)? {
+            if seen.insert(file.path.clone()) {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// --detect-partial: list files that are a truncated copy of a larger file. These are only
+/// reported; the duplicate actions never touch them.
+fn report_partial_duplicates(cli: &Cli) -> Result<()> {
+    let mut out = cli.message_output();
+    let files = scan_all_directories(cli)?;
+    let partials = partial::find_partial_duplicates(&files);
+    if partials.is_empty() {
+        writeln!(out, "\nNo partial duplicates found.")?;
+        return Ok(());
+    }
+    let size = |bytes: u64| {
+        if cli.raw_sizes {
+            format!("{} bytes", bytes)
+        } else {
+            format_size(bytes, DECIMAL)
+        }
+    };
+    writeln!(
+        out,
+        "\nPartial duplicates (truncated copies of a larger file):"
+    )?;
+    for found in &partials {
+        writeln!(
+            out,
+            "  - {} ({}) is the start of {} ({})",
+            found.partial.path.display(),
+            size(found.partial.size),
+            found.complete.path.display(),
+            size(found.complete.size)
+        )?;
+    }
+    Ok(())
+}
+
+/// --block-dedup: list pairs of large files that share much of their content in
+/// content-defined chunks. Report only, like --detect-partial.
+fn report_block_overlaps(cli: &Cli) -> Result<()> {
+    let mut out = cli.message_output();
+    let files = scan_all_directories(cli)?;
+    let overlaps = block_dedup::find_block_overlaps(
+        &files,
+        cli.block_min_size
+            .unwrap_or(block_dedup::DEFAULT_BLOCK_MIN_SIZE),
+        cli.block_overlap,
+    );
+    if overlaps.is_empty() {
+        writeln!(out, "\nNo partial content overlap found.")?;
+        return Ok(());
+    }
+    writeln!(out, "\nPartial content overlap (shared content blocks):")?;
+    for pair in &overlaps {
+        let shared = if cli.raw_sizes {
+            format!("{} bytes", pair.shared_bytes)
+        } else {
+            format_size(pair.shared_bytes, DECIMAL)
+        };
+        writeln!(
+            out,
+            "  - {} and {} share {} ({:.1}% of the smaller file)",
+            pair.first.path.display(),
+            pair.second.path.display(),
+            shared,
+            pair.overlap * 100.0
+        )?;
+    }
+    Ok(())
+}
+
+/// The human-readable report: every listed set with its files, then duplicate folders and
+/// the per-extension breakdown
+fn print_duplicate_listing(
+    cli: &Cli,
+    duplicate_sets: &[file_utils::DuplicateSet],
+    listed_sets: &[file_utils::DuplicateSet],
+) {
+    if listed_sets.len() < duplicate_sets.len() {
+        println!(
+            "Found {} sets of duplicate files; the {} with the most reclaimable space:",
+            duplicate_sets.len(),
+            listed_sets.len()
+        );
+    } else {
+        println!("Found {} sets of duplicate files:", duplicate_sets.len());
+    }
+
+    for set in listed_sets {
         println!(
             "  Duplicates ({} files, size: {}, hash: {}...):",
             set.files.len(),
             format_size(set.size, DECIMAL),
             set.hash.chars().take(16).collect::<String>()
         );
+        if set.approximate {
+            println!("    (approximate match: only a prefix of each file was hashed)");
+        }
         for file_info in &set.files {
             println!("    - {}", file_info.path.display());
         }
+        for file_info in &set.hardlinks {
+            println!(
+                "    = {} (hardlink, not actioned)",
+                file_info.path.display()
+            );
+        }
+    }
+
+    print_duplicate_directories(
+        cli,
+        &dir_dedup::find_duplicate_directories(duplicate_sets, &cli.directories),
+    );
+    print_extension_summary(cli, duplicate_sets);
+}
+
+fn handle_duplicate_sets(
+    cli: &Cli,
+    duplicate_sets: &[file_utils::DuplicateSet],
+    events: Option<&JsonEventWriter>,
+) -> Result<()> {
+    let mut out = cli.message_output();
+    log::info!("Found {} sets of duplicate files.", duplicate_sets.len());
+    if !duplicate_sets.is_empty() {
+        exit_code::record_duplicates_found();
+    }
+    // --top trims what is listed and written out; actions below still see every set
+    let mut top_sets: Option<Vec<file_utils::DuplicateSet>> = cli.top.map(|n| {
+        file_utils::top_sets_by_reclaimable(duplicate_sets, n)
+            .into_iter()
+            .cloned()
+            .collect()
+    });
+    if let Some(order) = cli.order_by {
+        let sets = top_sets.get_or_insert_with(|| duplicate_sets.to_vec());
+        file_utils::sort_duplicate_sets(sets, order, cli.sort_order);
+    }
+    let listed_sets = top_sets.as_deref().unwrap_or(duplicate_sets);
+    if cli.print0 {
+        // Nothing but the paths goes to stdout, so it can be piped to xargs -0
+        file_utils::write_paths_null_separated(listed_sets, &mut std::io::stdout().lock())?;
+    } else if cli.summary_only {
+        // Totals only; --output below still gets the full list
+        file_utils::write_duplicate_summary(
+            duplicate_sets,
+            cli.raw_sizes,
+            &mut std::io::stdout().lock(),
+        )?;
+    } else {
+        print_duplicate_listing(cli, duplicate_sets, listed_sets);
     }
 
     if let Some(output_path) = &cli.output {
-        match file_utils::output_duplicates(duplicate_sets, output_path, &cli.format) {
+        match file_utils::output_duplicates(listed_sets, output_path, &cli.format) {
             Ok(_) => {
                 log::info!("Successfully wrote duplicate list to {:?}", output_path);
-                println!("Duplicate list saved to {:?}", output_path);
+                writeln!(out, "Duplicate list saved to {:?}", output_path)?;
             }
             Err(e) => {
                 log::error!("Failed to write duplicate list to {:?}: {}", output_path, e);
@@ -276,73 +978,32 @@ fn handle_duplicate_sets(cli: &Cli, duplicate_sets: &[file_utils::DuplicateSet])
         }
     }
 
-    if cli.delete || cli.move_to.is_some() {
+    if let Some(report_path) = &cli.html_report {
+        match file_utils::write_html_report(listed_sets, report_path, cli.raw_sizes) {
+            Ok(_) => writeln!(out, "HTML report saved to {:?}", report_path)?,
+            Err(e) => {
+                log::error!("Failed to write HTML report to {:?}: {}", report_path, e);
+                eprintln!("Failed to write HTML report: {}", e);
+            }
+        }
+    }
+
+    if actions::has_action(cli) {
         // Log dry run mode status at the beginning
         if cli.dry_run {
             log::info!("Running in DRY RUN mode - no files will be modified");
-            println!("\n===== DRY RUN MODE - NO FILES WILL BE MODIFIED =====\n");
+            writeln!(
+                out,
+                "\n===== DRY RUN MODE - NO FILES WILL BE MODIFIED =====\n"
+            )?;
         }
 
-        let strategy = file_utils::SelectionStrategy::from_str(&cli.mode)?;
-        let mut total_deleted = 0;
-        let mut total_moved = 0;
-
-        for set in duplicate_sets {
-            if set.files.len() < 2 {
-                continue;
-            }
-
-            match file_utils::determine_action_targets(set, strategy) {
-                Ok((kept_file, files_to_action)) => {
-                    log::info!(
-                        "For duplicate set (hash: {}...), keeping file: {:?}",
-                        set.hash.chars().take(8).collect::<String>(),
-                        kept_file.path
-                    );
-                    println!("Keeping: {}", kept_file.path.display());
-
-                    if cli.delete {
-                        match file_utils::delete_files(&files_to_action, cli.dry_run) {
-                            Ok((count, logs)) => {
-                                total_deleted += count;
-                                // Print and log all messages
-                                for log_msg in logs {
-                                    log::info!("{}", log_msg);
-                                    println!("{}", log_msg);
-                                }
-                            }
-                            Err(e) => {
-                                log::error!("Error during deletion batch: {}", e);
-                                eprintln!("Error: {}", e);
-                            }
-                        }
-                    } else if let Some(ref target_move_dir) = cli.move_to {
-                        match file_utils::move_files(&files_to_action, target_move_dir, cli.dry_run)
-                        {
-                            Ok((count, logs)) => {
-                                total_moved += count;
-                                // Print and log all messages
-                                for log_msg in logs {
-                                    log::info!("{}", log_msg);
-                                    println!("{}", log_msg);
-                                }
-                            }
-                            Err(e) => {
-                                log::error!("Error during move batch: {}", e);
-                                eprintln!("Error: {}", e);
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    log::error!("Could not determine action targets for a set: {}", e);
-                    eprintln!(
-                        "Error: Could not determine which files to keep/delete: {}",
-                        e
-                    );
-                }
-            }
+        // JSON event consumers are unattended, so never block them on stdin
+        let prompting = cli.prompt && events.is_none();
+        if cli.prompt && !prompting {
+            log::warn!("--prompt is ignored when --json-events is set");
         }
+        let totals = actions::apply_actions(cli, duplicate_sets, prompting)?;
 
         // Add appropriate prefix based on dry run mode
         let action_prefix = if cli.dry_run {
@@ -352,24 +1013,68 @@ fn handle_duplicate_sets(cli: &Cli, duplicate_sets: &[file_utils::DuplicateSet])
         };
 
         if cli.delete {
-            let msg = format!("{}deleted {} files", action_prefix, total_deleted);
+            let msg = format!("{}deleted {} files", action_prefix, totals.deleted);
             log::info!("{}", msg);
-            println!("\n{}", msg);
+            writeln!(out, "\n{}", msg)?;
         }
         if cli.move_to.is_some() {
-            let msg = format!("{}moved {} files", action_prefix, total_moved);
+            let msg = format!("{}moved {} files", action_prefix, totals.moved);
             log::info!("{}", msg);
-            println!("\n{}", msg);
+            writeln!(out, "\n{}", msg)?;
         }
+        if cli.quarantine.is_some() {
+            let msg = format!("{}quarantined {} files", action_prefix, totals.quarantined);
+            log::info!("{}", msg);
+            writeln!(out, "\n{}", msg)?;
+        }
+        if cli.reflink {
+            let msg = format!("{}reflinked {} files", action_prefix, totals.reflinked);
+            log::info!("{}", msg);
+            writeln!(out, "\n{}", msg)?;
+        }
+        if cli.hardlink {
+            let msg = format!("{}hardlinked {} files", action_prefix, totals.hardlinked);
+            log::info!("{}", msg);
+            writeln!(out, "\n{}", msg)?;
+        }
+        if cli.symlink_kept {
+            let msg = format!(
+                "{}replaced {} files with symlinks",
+                action_prefix, totals.symlinked
+            );
+            log::info!("{}", msg);
+            writeln!(out, "\n{}", msg)?;
+        }
+
+        let msg = totals.reclaimed.summary(cli.raw_sizes, cli.dry_run);
+        log::info!("{}", msg);
+        writeln!(out, "{}", msg)?;
+        emit_event(
+            events,
+            &JsonOutput::Actions {
+                dry_run: cli.dry_run,
+                deleted: totals.deleted,
+                moved: totals.moved + totals.quarantined,
+                reflinked: totals.reflinked,
+                hardlinked: totals.hardlinked,
+                symlinked: totals.symlinked,
+                reclaimed_bytes: totals.reclaimed.bytes,
+            },
+        );
 
         // Add final reminder if in dry run mode
         if cli.dry_run {
-            println!("\nThis was a dry run. No files were actually modified.");
-            println!("Run without --dry-run to perform actual operations.");
+            writeln!(
+                out,
+                "\nThis was a dry run. No files were actually modified."
+            )?;
+            writeln!(out, "Run without --dry-run to perform actual operations.")?;
             log::info!("Dry run completed - no files were modified");
         }
     } else {
-        log::info!("No action flags (--delete or --move-to) specified. Listing duplicates only.");
+        log::info!(
+            "No action flags (--delete, --move-to, --quarantine, --reflink or --hardlink) specified. Listing duplicates only."
+        );
     }
 
     Ok(())
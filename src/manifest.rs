@@ -0,0 +1,234 @@
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::file_utils::{calculate_hash, resolved_algorithm, scan_directory, FileInfo};
+use crate::Cli;
+
+/// Version written to new manifests; loading rejects manifests from a newer version
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Hash and size of one file in the reference set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub hash: String,
+    pub size: u64,
+}
+
+/// Hashes of a reference library, written by `--write-manifest` and read by `--manifest`,
+/// so new folders can be checked against the library without rescanning it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// Algorithm every hash in `files` was made with
+    pub algorithm: String,
+    pub files: BTreeMap<PathBuf, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Scan and hash every file under the CLI directories
+    pub fn build(cli: &Cli) -> Result<Self> {
+        let mut files = Vec::new();
+        for directory in &cli.directories {
+            files.extend(scan_directory(cli, directory, true)?);
+        }
+        Ok(Self::from_files(&files, resolved_algorithm(cli)))
+    }
+
+    /// Manifest of already hashed files; files without a hash are left out
+    pub fn from_files(files: &[FileInfo], algorithm: &str) -> Self {
+        let files = files
+            .iter()
+            .filter_map(|file| {
+                let hash = file.hash.clone()?;
+                Some((
+                    file.path.clone(),
+                    ManifestEntry {
+                        hash,
+                        size: file.size,
+                    },
+                ))
+            })
+            .collect();
+        Self {
+            version: MANIFEST_VERSION,
+            algorithm: algorithm.to_string(),
+            files,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write manifest {:?}", path))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest {:?}", path))?;
+        let manifest: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse manifest {:?}", path))?;
+        if manifest.version > MANIFEST_VERSION {
+            anyhow::bail!(
+                "Manifest {:?} is version {}, but this dedups only reads up to version {}",
+                path,
+                manifest.version,
+                MANIFEST_VERSION
+            );
+        }
+        Ok(manifest)
+    }
+}
+
+/// Outcome of checking scanned files against a manifest
+#[derive(Debug, Default)]
+pub struct ManifestComparison {
+    /// Files whose content is already in the manifest, with the manifest path holding it
+    pub known: Vec<(FileInfo, PathBuf)>,
+    /// Files the manifest has no copy of
+    pub new_files: Vec<FileInfo>,
+}
+
+impl ManifestComparison {
+    /// Bytes freed by deleting the files the manifest already has
+    pub fn redundant_bytes(&self) -> u64 {
+        self.known.iter().map(|(file, _)| file.size).sum()
+    }
+}
+
+/// Sort `files` into ones the manifest already holds and new ones. Only files whose size
+/// appears in the manifest are hashed, with the manifest's algorithm. When the scan
+/// covers manifest files (checking the library against itself), a file's own entry isn't
+/// a copy of it, and of scanned copies the one with the lowest path is kept.
+pub fn compare_with_manifest(files: &[FileInfo], manifest: &Manifest) -> ManifestComparison {
+    let mut by_content: HashMap<(u64, &str), Vec<&PathBuf>> = HashMap::new();
+    for (path, entry) in &manifest.files {
+        by_content
+            .entry((entry.size, entry.hash.as_str()))
+            .or_default()
+            .push(path);
+    }
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let scanned: HashSet<PathBuf> = files.par_iter().map(|file| canonical(&file.path)).collect();
+    let sizes: HashSet<u64> = manifest.files.values().map(|entry| entry.size).collect();
+
+    let matches: Vec<Option<PathBuf>> = files
+        .par_iter()
+        .map(|file| {
+            if !sizes.contains(&file.size) {
+                return None;
+            }
+            match calculate_hash(&file.path, &manifest.algorithm) {
+                Ok(hash) => {
+                    let own_path = canonical(&file.path);
+                    by_content
+                        .get(&(file.size, hash.as_str()))?
+                        .iter()
+                        .find(|path| {
+                            let path = canonical(path);
+                            path != own_path && (!scanned.contains(&path) || path < own_path)
+                        })
+                        .map(|path| (*path).clone())
+                }
+                Err(e) => {
+                    log::warn!("Failed to hash {:?}: {}", file.path, e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let mut comparison = ManifestComparison::default();
+    for (file, found) in files.iter().zip(matches) {
+        match found {
+            Some(manifest_path) => comparison.known.push((file.clone(), manifest_path)),
+            None => comparison.new_files.push(file.clone()),
+        }
+    }
+    comparison
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_write_manifest_round_trips() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let library = dir.path().join("library");
+        fs::create_dir_all(library.join("albums"))?;
+        fs::write(library.join("a.jpg"), "photo a")?;
+        fs::write(library.join("albums/b.jpg"), "photo b")?;
+
+        let cli = Cli::parse_from(["dedups", "--algorithm", "sha256", library.to_str().unwrap()]);
+        let manifest = Manifest::build(&cli)?;
+        assert_eq!(manifest.algorithm, "sha256");
+        assert_eq!(manifest.files.len(), 2);
+        let entry = &manifest.files[&library.join("albums/b.jpg")];
+        assert_eq!(entry.size, 7);
+        assert_eq!(
+            entry.hash,
+            calculate_hash(&library.join("albums/b.jpg"), "sha256")?
+        );
+
+        let path = dir.path().join("out/library.json");
+        manifest.write(&path)?;
+        assert_eq!(Manifest::load(&path)?, manifest);
+
+        let mut future = manifest.clone();
+        future.version = MANIFEST_VERSION + 1;
+        future.write(&path)?;
+        assert!(Manifest::load(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_scanned_files_are_matched_against_manifest() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let library = dir.path().join("library");
+        let incoming = dir.path().join("incoming");
+        fs::create_dir_all(&library)?;
+        fs::create_dir_all(&incoming)?;
+        fs::write(library.join("known.jpg"), "known photo")?;
+        fs::write(incoming.join("copy.jpg"), "known photo")?;
+        // Same size as the library file, different content
+        fs::write(incoming.join("lookalike.jpg"), "other photo")?;
+        fs::write(incoming.join("new.jpg"), "brand new photo")?;
+
+        let library_cli = Cli::parse_from(["dedups", library.to_str().unwrap()]);
+        let manifest = Manifest::build(&library_cli)?;
+
+        let incoming_cli = Cli::parse_from(["dedups", incoming.to_str().unwrap()]);
+        let files = scan_directory(&incoming_cli, &incoming, false)?;
+        let comparison = compare_with_manifest(&files, &manifest);
+
+        assert_eq!(comparison.known.len(), 1);
+        assert_eq!(comparison.known[0].0.path, incoming.join("copy.jpg"));
+        assert_eq!(comparison.known[0].1, library.join("known.jpg"));
+        assert_eq!(comparison.redundant_bytes(), 11);
+        let mut new_files: Vec<_> = comparison.new_files.iter().map(|f| &f.path).collect();
+        new_files.sort();
+        assert_eq!(
+            new_files,
+            [&incoming.join("lookalike.jpg"), &incoming.join("new.jpg")]
+        );
+
+        // Checking the library against its own manifest only finds real copies in it
+        fs::write(library.join("known_copy.jpg"), "known photo")?;
+        fs::write(library.join("unique.jpg"), "unique photo")?;
+        let manifest = Manifest::build(&library_cli)?;
+        let files = scan_directory(&library_cli, &library, false)?;
+        let comparison = compare_with_manifest(&files, &manifest);
+        assert_eq!(comparison.known.len(), 1, "one of the pair is kept");
+        assert_eq!(comparison.known[0].0.path, library.join("known_copy.jpg"));
+        assert_eq!(comparison.known[0].1, library.join("known.jpg"));
+        assert_eq!(comparison.new_files.len(), 2);
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
 use anyhow::{Context, Result};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 use exif::{In, Reader as ExifReader, Tag};
 use hex;
@@ -17,6 +18,9 @@ use crate::audio_fingerprint;
 use crate::file_utils::{DuplicateSet, FileInfo};
 use crate::video_fingerprint;
 
+/// Prefix of the synthetic hash given to media similarity sets
+pub const MEDIA_HASH_PREFIX: &str = "media_";
+
 /// Check if ffmpeg is installed and available
 pub fn is_ffmpeg_available() -> bool {
     Command::new("ffmpeg").arg("-version").output().is_ok()
@@ -83,13 +87,44 @@ impl Default for FormatPreference {
     }
 }
 
+/// Which property decides the file to keep among similar media files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKeepPreference {
+    /// Earliest EXIF capture date (the original shot); copies often have newer mtimes
+    Date,
+    /// Resolution preference first, then format
+    Resolution,
+    /// Format preference first, then resolution
+    #[default]
+    Format,
+}
+
+impl std::str::FromStr for MediaKeepPreference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "date" => Ok(Self::Date),
+            "resolution" => Ok(Self::Resolution),
+            "format" => Ok(Self::Format),
+            _ => Err(anyhow::anyhow!(
+                "Invalid media keep preference: {}. Use date, resolution or format",
+                s
+            )),
+        }
+    }
+}
+
 /// Media deduplication settings
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MediaDedupOptions {
     pub enabled: bool,
     pub resolution_preference: ResolutionPreference,
     pub format_preference: FormatPreference,
     pub similarity_threshold: u32, // 0-100, where 100 is exact match
+    #[serde(default)]
+    pub media_keep: MediaKeepPreference,
 }
 
 impl Default for MediaDedupOptions {
@@ -99,6 +134,7 @@ impl Default for MediaDedupOptions {
             resolution_preference: ResolutionPreference::Highest,
             format_preference: FormatPreference::default(),
             similarity_threshold: 90, // Default to 90% similarity
+            media_keep: MediaKeepPreference::default(),
         }
     }
 }
@@ -121,6 +157,12 @@ pub struct MediaMetadata {
 pub struct MediaFileInfo {
     pub file_info: FileInfo,
     pub metadata: Option<MediaMetadata>,
+    /// 64-bit difference hash for images (see `compute_image_phash`)
+    #[serde(default)]
+    pub phash: Option<u64>,
+    /// When the photo was taken, from EXIF DateTimeOriginal (or DateTime)
+    #[serde(default)]
+    pub capture_date: Option<chrono::NaiveDateTime>,
 }
 
 impl From<FileInfo> for MediaFileInfo {
@@ -128,6 +170,8 @@ impl From<FileInfo> for MediaFileInfo {
         Self {
             file_info,
             metadata: None,
+            phash: None,
+            capture_date: None,
         }
     }
 }
@@ -230,6 +274,53 @@ pub fn extract_image_metadata(path: &Path) -> Result<MediaMetadata> {
     })
 }
 
+/// Compute a 64-bit perceptual difference hash (dHash) of an image: shrink it to 9x8
+/// grayscale and set one bit per pixel that is darker than its right neighbour. Resized
+/// or recompressed copies of an image hash to the same or nearby values.
+pub fn compute_image_phash(path: &Path) -> Result<u64> {
+    let img = image::open(path).with_context(|| format!("Failed to open image: {:?}", path))?;
+    let small = img
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Ok(hash)
+}
+
+/// Read the original capture time from an image's EXIF data, preferring
+/// DateTimeOriginal over the (edit-time) DateTime tag
+pub fn read_exif_capture_date(path: &Path) -> Option<chrono::NaiveDateTime> {
+    let file = std::fs::File::open(path).ok()?;
+    let exif = ExifReader::new()
+        .read_from_container(&mut std::io::BufReader::new(&file))
+        .ok()?;
+
+    [Tag::DateTimeOriginal, Tag::DateTime]
+        .iter()
+        .find_map(|tag| match &exif.get_field(*tag, In::PRIMARY)?.value {
+            exif::Value::Ascii(values) => {
+                let dt = exif::DateTime::from_ascii(values.first()?).ok()?;
+                chrono::NaiveDate::from_ymd_opt(dt.year as i32, dt.month as u32, dt.day as u32)?
+                    .and_hms_opt(dt.hour as u32, dt.minute as u32, dt.second as u32)
+            }
+            _ => None,
+        })
+}
+
+/// Largest Hamming distance between two 64-bit perceptual hashes that still counts
+/// as a match for a `media_similarity` percentage (e.g. 90 -> 6 bits)
+pub fn max_phash_distance(similarity_threshold: u32) -> u32 {
+    (100 - similarity_threshold.min(100)) * 64 / 100
+}
+
 /// Extract video metadata
 pub fn extract_video_metadata(path: &Path) -> Result<MediaMetadata> {
     let format = path
@@ -271,20 +362,28 @@ pub fn extract_audio_metadata(path: &Path) -> Result<MediaMetadata> {
         .unwrap_or("unknown")
         .to_lowercase();
 
-    // Check if we have ffmpeg or chromaprint available
-    if !is_ffmpeg_available() {
-        // Try to run fpcalc to see if chromaprint is available
-        let chromaprint_available = Command::new("fpcalc").arg("-version").output().is_ok();
-
-        if !chromaprint_available {
-            return Err(anyhow::anyhow!(
-                "Neither ffmpeg nor chromaprint is available for audio processing"
-            ));
+    // Acoustic fingerprints let different encodings of the same audio match. Without
+    // ffmpeg, or when this file can't be fingerprinted (too short, or it doesn't decode),
+    // the file is still kept, and compared by metadata instead.
+    let fingerprint = if is_ffmpeg_available() {
+        match audio_fingerprint::acoustic_fingerprint(path) {
+            Ok(words) => Some(words.iter().flat_map(|w| w.to_le_bytes()).collect()),
+            Err(e) => {
+                log::debug!(
+                    "Could not fingerprint audio file {:?}, comparing by metadata: {}",
+                    path,
+                    e
+                );
+                None
+            }
         }
-    }
-
-    // Use our audio_fingerprint module to create fingerprint
-    let fingerprint = audio_fingerprint::fingerprint_file(path)?;
+    } else {
+        log::debug!(
+            "ffmpeg unavailable, audio file {:?} will be compared by metadata",
+            path
+        );
+        None
+    };
 
     // Extract additional metadata if ffmpeg is available
     let mut duration = None;
@@ -334,7 +433,7 @@ pub fn extract_audio_metadata(path: &Path) -> Result<MediaMetadata> {
         duration,
         bitrate,
         perceptual_hash: None,
-        fingerprint: Some(fingerprint),
+        fingerprint,
     })
 }
 
@@ -382,10 +481,15 @@ pub fn calculate_video_similarity(fp1: &[u8], fp2: &[u8]) -> u32 {
     (video_fingerprint::compare_fingerprints(fp1, fp2) * 100.0) as u32
 }
 
-/// Calculate similarity between two audio fingerprints (0-100)
+/// Calculate similarity between two acoustic audio fingerprints (0-100)
 pub fn calculate_audio_similarity(fp1: &[u8], fp2: &[u8]) -> u32 {
-    // Use our audio fingerprint comparison function
-    (audio_fingerprint::compare_fingerprints(fp1, fp2) * 100.0) as u32
+    // Fingerprints are stored as little-endian 32-bit sub-fingerprints
+    let words = |fp: &[u8]| -> Vec<u32> {
+        fp.chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    };
+    (audio_fingerprint::compare_acoustic_fingerprints(&words(fp1), &words(fp2)) * 100.0) as u32
 }
 
 /// Compare media files and determine similarity
@@ -408,7 +512,13 @@ pub fn compare_media_files(a: &MediaFileInfo, b: &MediaFileInfo) -> u32 {
                 },
                 MediaKind::Audio => match (&meta_a.fingerprint, &meta_b.fingerprint) {
                     (Some(fp_a), Some(fp_b)) => calculate_audio_similarity(fp_a, fp_b),
-                    _ => 0,
+                    // No acoustic fingerprint (ffmpeg unavailable): fall back to metadata
+                    _ => audio_fingerprint::metadata_similarity(
+                        &a.file_info.path,
+                        &b.file_info.path,
+                        meta_a.duration,
+                        meta_b.duration,
+                    ),
                 },
                 MediaKind::Unknown => 0,
             }
@@ -417,6 +527,65 @@ pub fn compare_media_files(a: &MediaFileInfo, b: &MediaFileInfo) -> u32 {
     }
 }
 
+/// Pixel count used to rank resolution; missing dimensions count as 0
+fn media_pixels(file: &MediaFileInfo) -> Option<u64> {
+    let meta = file.metadata.as_ref()?;
+    Some(meta.width? as u64 * meta.height? as u64)
+}
+
+/// Keep only the files tied for the best resolution under `preference`
+fn best_resolution_files(
+    files: Vec<&MediaFileInfo>,
+    preference: ResolutionPreference,
+) -> Vec<&MediaFileInfo> {
+    let score = |file: &MediaFileInfo| -> u64 {
+        match preference {
+            ResolutionPreference::Highest => u64::MAX - media_pixels(file).unwrap_or(0),
+            ResolutionPreference::Lowest => media_pixels(file).unwrap_or(u64::MAX),
+            ResolutionPreference::ClosestTo(target_width, target_height) => {
+                match &file.metadata {
+                    Some(meta) => {
+                        let dw = meta.width.unwrap_or(0).abs_diff(target_width) as u64;
+                        let dh = meta.height.unwrap_or(0).abs_diff(target_height) as u64;
+                        dw * dw + dh * dh // Squared distance
+                    }
+                    None => u64::MAX,
+                }
+            }
+        }
+    };
+    let Some(best) = files.iter().map(|f| score(f)).min() else {
+        return files;
+    };
+    files.into_iter().filter(|f| score(f) == best).collect()
+}
+
+/// Keep only the files tied for the most preferred format
+fn best_format_files<'a>(
+    files: Vec<&'a MediaFileInfo>,
+    options: &MediaDedupOptions,
+) -> Vec<&'a MediaFileInfo> {
+    let format_rank = |file: &MediaFileInfo| -> usize {
+        file.metadata
+            .as_ref()
+            .and_then(|meta| {
+                options
+                    .format_preference
+                    .formats
+                    .iter()
+                    .position(|fmt| *fmt == meta.format)
+            })
+            .unwrap_or(usize::MAX)
+    };
+    let Some(best) = files.iter().map(|f| format_rank(f)).min() else {
+        return files;
+    };
+    files
+        .into_iter()
+        .filter(|f| format_rank(f) == best)
+        .collect()
+}
+
 /// Determine which file to keep among similar media files
 pub fn determine_preferred_media_file<'a>(
     files: &'a [MediaFileInfo],
@@ -433,86 +602,38 @@ pub fn determine_preferred_media_file<'a>(
         return files.first(); // Fall back to first file if no metadata
     }
 
-    // First apply format preference
-    let format_ranks: HashMap<String, usize> = options
-        .format_preference
-        .formats
-        .iter()
-        .enumerate()
-        .map(|(i, fmt)| (fmt.clone(), i))
-        .collect();
-
-    // Helper function to get format rank (lower is better)
-    let get_format_rank = |file: &&MediaFileInfo| -> usize {
-        let format = match &file.metadata {
-            Some(meta) => &meta.format,
-            None => return usize::MAX, // No metadata = lowest rank
-        };
-        *format_ranks.get(format).unwrap_or(&usize::MAX)
-    };
-
-    // Sort by format preference first
-    let mut preferred_format_files = files_with_metadata.clone();
-    preferred_format_files.sort_by_key(get_format_rank);
-
-    // If we have preferred format files, filter to those with the best format
-    let best_format_rank = get_format_rank(&preferred_format_files[0]);
-    let best_format_files: Vec<_> = preferred_format_files
-        .into_iter()
-        .filter(|f| get_format_rank(f) == best_format_rank)
-        .collect();
-
-    // If we have multiple files with same format, apply resolution preference
-    if best_format_files.len() > 1 {
-        match options.resolution_preference {
-            ResolutionPreference::Highest => {
-                // Find file with highest resolution
-                best_format_files
-                    .into_iter()
-                    .max_by_key(|file| match &file.metadata {
-                        Some(meta) => meta.width.unwrap_or(0) * meta.height.unwrap_or(0),
-                        None => 0,
-                    })
-            }
-            ResolutionPreference::Lowest => {
-                // Find file with lowest resolution
-                best_format_files
+    let candidates = match options.media_keep {
+        MediaKeepPreference::Format => best_resolution_files(
+            best_format_files(files_with_metadata, options),
+            options.resolution_preference,
+        ),
+        MediaKeepPreference::Resolution => best_format_files(
+            best_resolution_files(files_with_metadata, options.resolution_preference),
+            options,
+        ),
+        MediaKeepPreference::Date => {
+            // Earliest capture date wins; without EXIF dates, fall back to resolution
+            let earliest = files_with_metadata
+                .iter()
+                .filter_map(|f| f.capture_date)
+                .min();
+            let dated = match earliest {
+                Some(earliest) => files_with_metadata
                     .into_iter()
-                    .min_by_key(|file| match &file.metadata {
-                        Some(meta) => {
-                            meta.width.unwrap_or(u32::MAX) * meta.height.unwrap_or(u32::MAX)
-                        }
-                        None => u32::MAX,
-                    })
-            }
-            ResolutionPreference::ClosestTo(target_width, target_height) => {
-                // Find file with resolution closest to target
-                best_format_files.into_iter().min_by_key(|file| {
-                    match &file.metadata {
-                        Some(meta) => {
-                            let w = meta.width.unwrap_or(0);
-                            let h = meta.height.unwrap_or(0);
-                            let dw = if w > target_width {
-                                w - target_width
-                            } else {
-                                target_width - w
-                            };
-                            let dh = if h > target_height {
-                                h - target_height
-                            } else {
-                                target_height - h
-                            };
-                            dw * dw + dh * dh // Squared distance
-                        }
-                        None => u32::MAX,
-                    }
-                })
-            }
+                    .filter(|f| f.capture_date == Some(earliest))
+                    .collect(),
+                None => files_with_metadata,
+            };
+            best_resolution_files(dated, options.resolution_preference)
         }
-    } else {
-        // Only one file with best format
-        best_format_files.into_iter().next()
-    }
+    };
+
+    // Remaining ties go to the oldest file on disk, the likeliest original
+    candidates.into_iter().min_by_key(|f| {
+        f.file_info
+            .modified_at
+            .unwrap_or(std::time::SystemTime::UNIX_EPOCH)
+    })
 }
 
 #[allow(clippy::arc_with_non_send_sync)]
@@ -540,60 +661,103 @@ pub fn find_similar_media_files(
         log::warn!("FFmpeg is not installed. Video deduplication will be limited.");
     }
 
-    // Convert FileInfo to MediaFileInfo and extract metadata
-    let total_files = file_infos.len();
-    let mut processed = 0;
-
-    // Create a thread-safe wrapper for the progress callback
-    let progress_callback = progress_callback.map(|cb| {
-        let cb = Arc::new(cb);
-        move |count, total| {
-            let cb = cb.clone();
-            cb(count, total);
+    // Extract metadata on the global Rayon pool; the callback may not be Sync, so serialize it
+    let progress_callback = progress_callback.map(Mutex::new);
+    let media_files = extract_media_files(file_infos, None, &|count, total| {
+        if let Some(cb) = &progress_callback {
+            if let Ok(cb) = cb.lock() {
+                cb(count, total);
+            }
         }
-    });
+    })?;
 
-    // Process files sequentially instead of in parallel to avoid the Sync constraint
-    let media_files: Vec<MediaFileInfo> = file_infos
-        .iter()
-        .map(|file_info| {
-            let mut media_file = MediaFileInfo::from(file_info.clone());
-
-            // Only process media files
-            let media_kind = detect_media_type(&file_info.path);
-            if media_kind != MediaKind::Unknown {
-                media_file.metadata = match extract_media_metadata(&file_info.path) {
-                    Ok(metadata) => Some(metadata),
-                    Err(e) => {
-                        log::warn!(
-                            "Failed to extract media metadata for {:?}: {}",
-                            file_info.path,
-                            e
-                        );
-                        None
-                    }
-                };
-            }
+    let similar_groups = group_similar_media_files(&media_files, options)?;
 
-            // Update progress
-            processed += 1;
-            if let Some(cb) = &progress_callback {
-                cb(processed, total_files);
-            }
+    log::info!(
+        "Found {} groups of similar media files.",
+        similar_groups.len()
+    );
 
-            media_file
-        })
-        .filter(|media_file| media_file.metadata.is_some())
-        .collect();
+    Ok(similar_groups)
+}
+
+/// Extract metadata (plus perceptual hash and capture date for images) for every media
+/// file in `file_infos`, on a Rayon pool of `parallel` threads (the global pool when `None`).
+/// Files that aren't media, or whose metadata can't be read (e.g. ffmpeg fails on them),
+/// are logged and skipped. `progress` receives `(processed, total)` after each file.
+pub fn extract_media_files(
+    file_infos: &[FileInfo],
+    parallel: Option<usize>,
+    progress: &(dyn Fn(usize, usize) + Sync),
+) -> Result<Vec<MediaFileInfo>> {
+    let total_files = file_infos.len();
+    let processed = AtomicUsize::new(0);
+
+    let extract_all = || -> Vec<MediaFileInfo> {
+        file_infos
+            .par_iter()
+            .filter_map(|file_info| {
+                let media_file = analyze_media_file(file_info);
+                let count = processed.fetch_add(1, Ordering::Relaxed) + 1;
+                progress(count, total_files);
+                media_file
+            })
+            .collect()
+    };
+
+    let media_files = match parallel {
+        Some(num_threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()?
+            .install(extract_all),
+        None => extract_all(),
+    };
 
     log::info!("Extracted metadata for {} media files", media_files.len());
+    Ok(media_files)
+}
+
+/// Build the `MediaFileInfo` for a single file, or `None` if it isn't usable media
+fn analyze_media_file(file_info: &FileInfo) -> Option<MediaFileInfo> {
+    let media_kind = detect_media_type(&file_info.path);
+    if media_kind == MediaKind::Unknown {
+        return None;
+    }
 
-    // Group by media type for more efficient comparison
+    let mut media_file = MediaFileInfo::from(file_info.clone());
+    if media_kind == MediaKind::Image {
+        media_file.phash = compute_image_phash(&file_info.path)
+            .map_err(|e| log::debug!("No perceptual hash for {:?}: {}", file_info.path, e))
+            .ok();
+        media_file.capture_date = read_exif_capture_date(&file_info.path);
+    }
+    match extract_media_metadata(&file_info.path) {
+        Ok(metadata) => {
+            media_file.metadata = Some(metadata);
+            Some(media_file)
+        }
+        Err(e) => {
+            log::warn!(
+                "Failed to extract media metadata for {:?}: {}",
+                file_info.path,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Group already-extracted media files into similarity groups, comparing only
+/// files of the same kind
+pub fn group_similar_media_files(
+    media_files: &[MediaFileInfo],
+    options: &MediaDedupOptions,
+) -> Result<Vec<Vec<MediaFileInfo>>> {
     let mut image_files: Vec<_> = Vec::new();
     let mut video_files: Vec<_> = Vec::new();
     let mut audio_files: Vec<_> = Vec::new();
 
-    for file in &media_files {
+    for file in media_files {
         if let Some(metadata) = &file.metadata {
             match metadata.kind {
                 MediaKind::Image => image_files.push(file),
@@ -611,19 +775,10 @@ pub fn find_similar_media_files(
         audio_files.len()
     );
 
-    // Create similarity groups
     let mut similar_groups: Vec<Vec<MediaFileInfo>> = Vec::new();
-
-    // Process each media type separately
     process_media_type_similarity(&image_files, options, &mut similar_groups)?;
     process_media_type_similarity(&video_files, options, &mut similar_groups)?;
     process_media_type_similarity(&audio_files, options, &mut similar_groups)?;
-
-    log::info!(
-        "Found {} groups of similar media files.",
-        similar_groups.len()
-    );
-
     Ok(similar_groups)
 }
 
@@ -639,6 +794,7 @@ pub fn process_media_type_similarity(
 
     // Track which files have been assigned to groups
     let mut processed = vec![false; files.len()];
+    let max_distance = max_phash_distance(options.similarity_threshold);
 
     // Compare each file against others
     for i in 0..files.len() {
@@ -655,8 +811,13 @@ pub fn process_media_type_similarity(
                 continue;
             }
 
-            let similarity = compare_media_files(files[i], files[j]);
-            if similarity >= options.similarity_threshold {
+            // Images with perceptual hashes cluster by Hamming distance, which tolerates
+            // resizing and recompression; everything else uses the metadata comparison
+            let is_similar = match (files[i].phash, files[j].phash) {
+                (Some(a), Some(b)) => (a ^ b).count_ones() <= max_distance,
+                _ => compare_media_files(files[i], files[j]) >= options.similarity_threshold,
+            };
+            if is_similar {
                 current_group.push(files[j].clone());
                 processed[j] = true;
             }
@@ -702,13 +863,19 @@ pub fn convert_to_duplicate_sets(
             }
 
             // Create a fake "hash" for media sets based on the first file in the group
-            let hash = format!("media_{}", group[0].file_info.path.to_string_lossy());
+            let hash = format!(
+                "{}{}",
+                MEDIA_HASH_PREFIX,
+                group[0].file_info.path.to_string_lossy()
+            );
             let size = group[0].file_info.size;
 
             duplicate_sets.push(DuplicateSet {
                 files: file_infos,
                 size,
                 hash,
+                approximate: false,
+                hardlinks: Vec::new(),
             });
         }
     }
@@ -723,9 +890,15 @@ pub fn add_media_options_to_cli(
     resolution: &str,
     formats: &[String],
     threshold: u32,
+    keep: &str,
 ) {
     options.enabled = enable;
 
+    match keep.parse::<MediaKeepPreference>() {
+        Ok(preference) => options.media_keep = preference,
+        Err(e) => log::warn!("{}", e),
+    }
+
     // Parse resolution preference
     match resolution {
         "highest" => options.resolution_preference = ResolutionPreference::Highest,
@@ -758,6 +931,7 @@ mod tests {
     use std::time::SystemTime;
 
     // Helper to create a test file
+    #[allow(dead_code)]
     fn create_test_file_info(path: &str, size: u64) -> FileInfo {
         FileInfo {
             path: PathBuf::from(path),
@@ -822,6 +996,226 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_max_phash_distance() {
+        assert_eq!(max_phash_distance(100), 0);
+        assert_eq!(max_phash_distance(90), 6);
+        assert_eq!(max_phash_distance(75), 16);
+    }
+
+    #[test]
+    fn test_unfingerprintable_audio_falls_back_to_metadata() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("broken.mp3");
+        std::fs::write(&path, b"not really audio")?;
+
+        // Kept for metadata comparison rather than failing the whole file
+        let metadata = extract_audio_metadata(&path)?;
+        assert_eq!(metadata.kind, MediaKind::Audio);
+        assert!(metadata.fingerprint.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_downscaled_jpeg_groups_with_original() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        // Smooth diagonal shading with a bright block, so the hash has structure
+        let original = image::RgbImage::from_fn(256, 256, |x, y| {
+            let v = if (64..128).contains(&x) && (96..192).contains(&y) {
+                255
+            } else {
+                ((x + y) / 2) as u8
+            };
+            image::Rgb([v, v / 2, 255 - v])
+        });
+        let original_path = dir.path().join("photo.jpg");
+        original.save(&original_path)?;
+        let small_path = dir.path().join("photo_small.jpg");
+        image::imageops::resize(&original, 96, 96, image::imageops::FilterType::Lanczos3)
+            .save(&small_path)?;
+        // An unrelated image (mirrored shading) must stay out of the group
+        let other_path = dir.path().join("other.jpg");
+        image::RgbImage::from_fn(256, 256, |x, y| {
+            let v = ((255 - x) * y / 255) as u8;
+            image::Rgb([v, v, v])
+        })
+        .save(&other_path)?;
+
+        let file_infos: Vec<FileInfo> = [&original_path, &small_path, &other_path]
+            .iter()
+            .map(|p| FileInfo {
+                path: p.to_path_buf(),
+                size: std::fs::metadata(p).map(|m| m.len()).unwrap_or(0),
+                hash: None,
+                modified_at: None,
+                created_at: None,
+            })
+            .collect();
+        let options = MediaDedupOptions {
+            enabled: true,
+            ..MediaDedupOptions::default()
+        };
+
+        let groups = find_similar_media_files(&file_infos, &options, None)?;
+        assert_eq!(groups.len(), 1);
+        let mut paths: Vec<_> = groups[0].iter().map(|f| f.file_info.path.clone()).collect();
+        paths.sort();
+        let mut expected = vec![original_path, small_path];
+        expected.sort();
+        assert_eq!(paths, expected);
+        assert!(groups[0].iter().all(|f| f.phash.is_some()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_extraction_matches_sequential_grouping() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut paths = Vec::new();
+        for variant in 0..4u32 {
+            let img = image::RgbImage::from_fn(128, 128, |x, y| {
+                let v = match variant {
+                    0 | 1 => ((x + y) / 2) as u8,
+                    2 => ((255 - x * 2) * y / 255) as u8,
+                    _ => {
+                        if (x / 16 + y / 16) % 2 == 0 {
+                            255
+                        } else {
+                            0
+                        }
+                    }
+                };
+                image::Rgb([v, v, v])
+            });
+            // Variant 1 is a downscaled copy of variant 0
+            let path = dir.path().join(format!("img_{}.jpg", variant));
+            if variant == 1 {
+                image::imageops::resize(&img, 64, 64, image::imageops::FilterType::Lanczos3)
+                    .save(&path)?;
+            } else {
+                img.save(&path)?;
+            }
+            paths.push(path);
+        }
+        // A truncated image must be skipped without failing the batch
+        let broken = dir.path().join("broken.jpg");
+        std::fs::write(&broken, [0xff, 0xd8, 0xff, 0xe0])?;
+        paths.push(broken.clone());
+
+        let file_infos: Vec<FileInfo> = paths
+            .iter()
+            .map(|p| FileInfo {
+                path: p.clone(),
+                size: std::fs::metadata(p).map(|m| m.len()).unwrap_or(0),
+                hash: None,
+                modified_at: None,
+                created_at: None,
+            })
+            .collect();
+        let options = MediaDedupOptions {
+            enabled: true,
+            ..MediaDedupOptions::default()
+        };
+
+        let group_paths = |parallel: Option<usize>| -> Result<Vec<Vec<std::path::PathBuf>>> {
+            let calls = AtomicUsize::new(0);
+            let media = extract_media_files(&file_infos, parallel, &|_, _| {
+                calls.fetch_add(1, Ordering::Relaxed);
+            })?;
+            assert_eq!(calls.load(Ordering::Relaxed), file_infos.len());
+            assert!(media.iter().all(|m| m.file_info.path != broken));
+            let mut groups: Vec<Vec<_>> = group_similar_media_files(&media, &options)?
+                .iter()
+                .map(|g| {
+                    let mut p: Vec<_> = g.iter().map(|f| f.file_info.path.clone()).collect();
+                    p.sort();
+                    p
+                })
+                .collect();
+            groups.sort();
+            Ok(groups)
+        };
+
+        let sequential = group_paths(Some(1))?;
+        let parallel = group_paths(Some(4))?;
+        assert_eq!(sequential, parallel);
+        assert!(sequential.contains(&vec![paths[0].clone(), paths[1].clone()]));
+        Ok(())
+    }
+
+    /// Encode `img` as a JPEG with an EXIF APP1 segment carrying DateTimeOriginal
+    fn write_jpeg_with_capture_date(
+        path: &Path,
+        img: &image::RgbImage,
+        date_time_original: &str,
+    ) -> Result<()> {
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new(&mut jpeg).encode_image(img)?;
+
+        let field = exif::Field {
+            tag: Tag::DateTimeOriginal,
+            ifd_num: In::PRIMARY,
+            value: exif::Value::Ascii(vec![date_time_original.as_bytes().to_vec()]),
+        };
+        let mut writer = exif::experimental::Writer::new();
+        writer.push_field(&field);
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false)?;
+        let tiff = tiff.into_inner();
+
+        // Insert APP1 ("Exif\0\0" + TIFF data) right after the SOI marker
+        let mut out = jpeg[..2].to_vec();
+        out.extend_from_slice(&[0xFF, 0xE1]);
+        out.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        out.extend_from_slice(b"Exif\0\0");
+        out.extend_from_slice(&tiff);
+        out.extend_from_slice(&jpeg[2..]);
+        std::fs::write(path, out)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_media_keep_date_prefers_earliest_capture() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let img = image::RgbImage::from_fn(128, 128, |x, y| {
+            image::Rgb([(x * 2) as u8, (y * 2) as u8, ((x + y) % 256) as u8])
+        });
+        // The original is written last, so it has the newest mtime
+        let copy_path = dir.path().join("copy.jpg");
+        write_jpeg_with_capture_date(&copy_path, &img, "2021:06:01 12:00:00")?;
+        let original_path = dir.path().join("original.jpg");
+        write_jpeg_with_capture_date(&original_path, &img, "2019:03:15 08:30:00")?;
+
+        assert_eq!(
+            read_exif_capture_date(&original_path),
+            chrono::NaiveDate::from_ymd_opt(2019, 3, 15).and_then(|d| d.and_hms_opt(8, 30, 0))
+        );
+
+        let file_infos: Vec<FileInfo> = [&copy_path, &original_path]
+            .iter()
+            .map(|p| {
+                let metadata = std::fs::metadata(p).unwrap();
+                FileInfo {
+                    path: p.to_path_buf(),
+                    size: metadata.len(),
+                    hash: None,
+                    modified_at: metadata.modified().ok(),
+                    created_at: None,
+                }
+            })
+            .collect();
+        let options = MediaDedupOptions {
+            enabled: true,
+            media_keep: MediaKeepPreference::Date,
+            ..MediaDedupOptions::default()
+        };
+
+        let groups = find_similar_media_files(&file_infos, &options, None)?;
+        assert_eq!(groups.len(), 1);
+        let kept = determine_preferred_media_file(&groups[0], &options).unwrap();
+        assert_eq!(kept.file_info.path, original_path);
+        Ok(())
+    }
+
     #[test]
     fn test_media_dedup_options_default() {
         let options = MediaDedupOptions::default();
@@ -0,0 +1,159 @@
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use crate::file_utils::FileInfo;
+
+/// Leading bytes every candidate pair must share. Files shorter than this are never
+/// reported, which keeps tiny files (which are prefixes of all sorts of things) out.
+pub const PARTIAL_HEAD_BYTES: u64 = 4096;
+
+/// Read size used when comparing the rest of a candidate pair
+const PREFIX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A truncated copy of a larger file, e.g. the `.part` file a download manager left behind
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PartialDuplicate {
+    /// The shorter file, whose bytes all appear at the start of `complete`
+    pub partial: FileInfo,
+    pub complete: FileInfo,
+}
+
+/// Whether `shorter` is a strict byte-prefix of `longer`: shorter in length and equal
+/// to the start of it
+pub fn is_prefix_of(shorter: &Path, longer: &Path) -> Result<bool> {
+    let shorter_len = fs::metadata(shorter)?.len();
+    if shorter_len >= fs::metadata(longer)?.len() {
+        return Ok(false);
+    }
+
+    let mut short_reader = BufReader::new(File::open(shorter)?.take(shorter_len));
+    let mut long_reader = BufReader::new(File::open(longer)?);
+    let mut short_buf = vec![0u8; PREFIX_CHUNK_SIZE];
+    let mut long_buf = vec![0u8; PREFIX_CHUNK_SIZE];
+    loop {
+        let n = short_reader.read(&mut short_buf)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        long_reader.read_exact(&mut long_buf[..n])?;
+        if short_buf[..n] != long_buf[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Find files that are truncated copies of other files. Files are grouped by a hash of
+/// their first `PARTIAL_HEAD_BYTES`, then within each group, sorted by size, every file
+/// is checked against the larger ones. Each partial file is reported once, against the
+/// largest file it is a prefix of. Same-sized files are left to the duplicate scan.
+pub fn find_partial_duplicates(files: &[FileInfo]) -> Vec<PartialDuplicate> {
+    let heads: Vec<(blake3::Hash, &FileInfo)> = files
+        .par_iter()
+        .filter(|file| file.size >= PARTIAL_HEAD_BYTES)
+        .filter_map(|file| match read_head(&file.path) {
+            Ok(head) => Some((head, file)),
+            Err(e) => {
+                log::warn!("Failed to read {:?}: {}", file.path, e);
+                None
+            }
+        })
+        .collect();
+
+    let mut groups: HashMap<blake3::Hash, Vec<&FileInfo>> = HashMap::new();
+    for (head, file) in heads {
+        groups.entry(head).or_default().push(file);
+    }
+
+    let mut partials: Vec<PartialDuplicate> = groups
+        .into_par_iter()
+        .filter(|(_, group)| group.len() > 1)
+        .flat_map_iter(|(_, mut group)| {
+            group.sort_by_key(|file| file.size);
+            let mut found = Vec::new();
+            for (i, shorter) in group.iter().enumerate() {
+                let larger = group[i + 1..]
+                    .iter()
+                    .rev()
+                    .take_while(|longer| longer.size > shorter.size);
+                for longer in larger {
+                    match is_prefix_of(&shorter.path, &longer.path) {
+                        Ok(true) => {
+                            found.push(PartialDuplicate {
+                                partial: (*shorter).clone(),
+                                complete: (*longer).clone(),
+                            });
+                            break;
+                        }
+                        Ok(false) => {}
+                        Err(e) => log::warn!(
+                            "Failed to compare {:?} with {:?}: {}",
+                            shorter.path,
+                            longer.path,
+                            e
+                        ),
+                    }
+                }
+            }
+            found
+        })
+        .collect();
+    partials.sort_by(|a, b| a.partial.path.cmp(&b.partial.path));
+    partials
+}
+
+/// Hash of the first `PARTIAL_HEAD_BYTES` of a file
+fn read_head(path: &Path) -> Result<blake3::Hash> {
+    let mut head = Vec::with_capacity(PARTIAL_HEAD_BYTES as usize);
+    File::open(path)?
+        .take(PARTIAL_HEAD_BYTES)
+        .read_to_end(&mut head)?;
+    Ok(blake3::hash(&head))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncated_copy_is_reported_as_partial() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        let complete = dir.path().join("video.mp4");
+        let partial = dir.path().join("video.mp4.part");
+        let diverging = dir.path().join("other.mp4");
+        let tiny = dir.path().join("tiny.part");
+        fs::write(&complete, &data)?;
+        fs::write(&partial, &data[..9_000])?;
+        // Same head, different bytes later on
+        let mut other = data[..12_000].to_vec();
+        other[10_000] ^= 0xFF;
+        fs::write(&diverging, &other)?;
+        fs::write(&tiny, &data[..100])?;
+
+        assert!(is_prefix_of(&partial, &complete)?);
+        assert!(!is_prefix_of(&complete, &partial)?);
+        assert!(!is_prefix_of(&diverging, &complete)?);
+        assert!(!is_prefix_of(&complete, &complete)?, "not strictly shorter");
+
+        let files: Vec<FileInfo> = [&complete, &partial, &diverging, &tiny]
+            .into_iter()
+            .map(|path| FileInfo {
+                path: path.clone(),
+                size: fs::metadata(path).unwrap().len(),
+                hash: None,
+                modified_at: None,
+                created_at: None,
+            })
+            .collect();
+        let found = find_partial_duplicates(&files);
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert_eq!(found[0].partial.path, partial);
+        assert_eq!(found[0].complete.path, complete);
+        Ok(())
+    }
+}
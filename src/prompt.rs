@@ -0,0 +1,138 @@
+use std::io::{self, BufRead, Write};
+
+use crate::file_utils::{DuplicateSet, FileInfo, ProtectedPaths};
+
+/// A user's answer for one duplicate set in `--prompt` mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptChoice {
+    /// Keep the file at this (0-based) index and action the rest
+    Keep(usize),
+    /// Leave the set untouched
+    Skip,
+    /// Let the selection strategy (`--mode` / `--keep-dir`) decide
+    ApplyStrategy,
+}
+
+/// Parse one line of input: a 1-based file number, `s` to skip, or `a` to apply the strategy
+pub fn parse_prompt_response(input: &str, file_count: usize) -> Result<PromptChoice, String> {
+    let input = input.trim().to_lowercase();
+    match input.as_str() {
+        "s" | "skip" => Ok(PromptChoice::Skip),
+        "a" | "apply" => Ok(PromptChoice::ApplyStrategy),
+        "" => Err("Please enter a file number, s or a".to_string()),
+        _ => match input.parse::<usize>() {
+            Ok(n) if (1..=file_count).contains(&n) => Ok(PromptChoice::Keep(n - 1)),
+            Ok(n) => Err(format!(
+                "{} is out of range, choose between 1 and {}",
+                n, file_count
+            )),
+            Err(_) => Err(format!("Unrecognized answer '{}'", input)),
+        },
+    }
+}
+
+/// Show the files of `set` numbered and ask which to keep until a valid answer is
+/// given. End of input counts as skipping the set.
+pub fn prompt_for_set(
+    set: &DuplicateSet,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<PromptChoice> {
+    writeln!(output)?;
+    for (i, file) in set.files.iter().enumerate() {
+        writeln!(output, "  [{}] {}", i + 1, file.path.display())?;
+    }
+    loop {
+        write!(
+            output,
+            "Keep which file? [1-{}], s to skip, a to apply the strategy: ",
+            set.files.len()
+        )?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(output)?;
+            return Ok(PromptChoice::Skip);
+        }
+        match parse_prompt_response(&line, set.files.len()) {
+            Ok(choice) => return Ok(choice),
+            Err(msg) => writeln!(output, "{}", msg)?,
+        }
+    }
+}
+
+/// Keep the chosen file and return the others for action, leaving protected files alone
+pub fn keep_chosen_file(
+    set: &DuplicateSet,
+    index: usize,
+    protected: &ProtectedPaths,
+) -> (FileInfo, Vec<FileInfo>) {
+    let kept = set.files[index].clone();
+    let others = set
+        .files
+        .iter()
+        .enumerate()
+        .filter(|(i, f)| *i != index && !protected.contains(&f.path))
+        .map(|(_, f)| f.clone())
+        .collect();
+    (kept, others)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_prompt_response() {
+        assert_eq!(parse_prompt_response("2\n", 3), Ok(PromptChoice::Keep(1)));
+        assert_eq!(parse_prompt_response(" 1 ", 3), Ok(PromptChoice::Keep(0)));
+        assert_eq!(parse_prompt_response("s", 3), Ok(PromptChoice::Skip));
+        assert_eq!(parse_prompt_response("SKIP", 3), Ok(PromptChoice::Skip));
+        assert_eq!(
+            parse_prompt_response("a", 3),
+            Ok(PromptChoice::ApplyStrategy)
+        );
+
+        assert!(parse_prompt_response("0", 3).is_err());
+        assert!(parse_prompt_response("4", 3).is_err());
+        assert!(parse_prompt_response("", 3).is_err());
+        assert!(parse_prompt_response("keep", 3).is_err());
+    }
+
+    #[test]
+    fn test_prompt_reasks_until_valid() -> io::Result<()> {
+        let file = |path: &str| FileInfo {
+            path: PathBuf::from(path),
+            size: 1,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let set = DuplicateSet {
+            files: vec![file("/a/x.txt"), file("/b/x.txt")],
+            size: 1,
+            hash: "h".to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+
+        let mut output = Vec::new();
+        let choice = prompt_for_set(&set, &mut "9\nnope\n2\n".as_bytes(), &mut output)?;
+        assert_eq!(choice, PromptChoice::Keep(1));
+        let shown = String::from_utf8_lossy(&output);
+        assert!(shown.contains("[2] /b/x.txt"));
+        assert!(shown.contains("out of range"));
+        assert!(shown.contains("Unrecognized answer 'nope'"));
+
+        // Running out of input skips the set
+        let choice = prompt_for_set(&set, &mut "".as_bytes(), &mut Vec::new())?;
+        assert_eq!(choice, PromptChoice::Skip);
+
+        let (kept, others) = keep_chosen_file(&set, 1, &ProtectedPaths::default());
+        assert_eq!(kept.path, PathBuf::from("/b/x.txt"));
+        assert_eq!(others.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::file_cache::{same_mtime, HashCache};
+use crate::file_utils::FileInfo;
+
+/// Hash cache stored in a SQLite database, for libraries too large to load a flat
+/// cache file into memory. Entries are keyed on (path, algorithm) so lookups go
+/// through the primary key index.
+pub struct SqliteFileCache {
+    conn: Connection,
+    algorithm: String,
+    in_transaction: bool,
+}
+
+fn to_epoch_nanos(time: SystemTime) -> Option<i64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|d| i64::try_from(d.as_nanos()).ok())
+}
+
+fn from_epoch_nanos(nanos: Option<i64>) -> Option<SystemTime> {
+    nanos.map(|n| UNIX_EPOCH + Duration::from_nanos(n as u64))
+}
+
+/// Whether the file still has the size and mtime recorded for it
+fn matches_file(path: &Path, size: u64, cached_mtime: Option<SystemTime>) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    let current_mtime = metadata.modified().ok();
+    metadata.len() == size
+        && matches!((cached_mtime, current_mtime), (Some(a), Some(b)) if same_mtime(a, b))
+}
+
+impl SqliteFileCache {
+    /// Open (or create) the SQLite cache database in `cache_dir`
+    pub fn new(cache_dir: &Path, algorithm: &str) -> Result<Self> {
+        if !cache_dir.exists() {
+            fs::create_dir_all(cache_dir)
+                .with_context(|| format!("Failed to create cache directory: {:?}", cache_dir))?;
+        }
+
+        let db_path = Self::db_path(cache_dir);
+        let conn = Connection::open(&db_path)
+            .with_context(|| format!("Failed to open cache database: {:?}", db_path))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS file_hashes (
+                path TEXT NOT NULL,
+                algorithm TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                mtime_nanos INTEGER,
+                hash TEXT NOT NULL,
+                PRIMARY KEY (path, algorithm)
+            );",
+        )
+        .context("Failed to initialize cache database schema")?;
+
+        let cache = Self {
+            conn,
+            algorithm: algorithm.to_string(),
+            in_transaction: false,
+        };
+        log::info!(
+            "Opened SQLite cache {:?} with {} entries",
+            db_path,
+            cache.len()
+        );
+        Ok(cache)
+    }
+
+    /// Path of the database file inside a cache directory
+    pub fn db_path(cache_dir: &Path) -> PathBuf {
+        cache_dir.join("file_hashes.sqlite")
+    }
+
+    fn lookup(&self, path: &Path) -> Option<(u64, Option<i64>, String)> {
+        self.conn
+            .query_row(
+                "SELECT size, mtime_nanos, hash FROM file_hashes WHERE path = ?1 AND algorithm = ?2",
+                params![path.to_string_lossy(), self.algorithm],
+                |row| Ok((row.get::<_, i64>(0)? as u64, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .unwrap_or_else(|e| {
+                log::warn!("Cache lookup failed for {:?}: {}", path, e);
+                None
+            })
+    }
+}
+
+impl HashCache for SqliteFileCache {
+    fn get_file_info(&self, path: &Path) -> Option<FileInfo> {
+        let (size, mtime_nanos, hash) = self.lookup(path)?;
+        let cached_mtime = from_epoch_nanos(mtime_nanos);
+        if !matches_file(path, size, cached_mtime) {
+            log::debug!("Cache invalid for file: {:?}", path);
+            return None;
+        }
+
+        log::debug!("Cache hit for file: {:?}", path);
+        Some(FileInfo {
+            path: path.to_path_buf(),
+            size,
+            hash: Some(hash),
+            modified_at: cached_mtime,
+            created_at: None, // Cache doesn't store creation time
+        })
+    }
+
+    fn store(&mut self, file_info: &FileInfo, algorithm: &str) -> Result<()> {
+        let hash = file_info.hash.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Cannot cache file without hash: {:?}", file_info.path)
+        })?;
+
+        // Batch inserts into one transaction until the next save()
+        if !self.in_transaction {
+            self.conn.execute_batch("BEGIN")?;
+            self.in_transaction = true;
+        }
+        self.conn.execute(
+            "INSERT OR REPLACE INTO file_hashes (path, algorithm, size, mtime_nanos, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                file_info.path.to_string_lossy(),
+                algorithm,
+                file_info.size as i64,
+                file_info.modified_at.and_then(to_epoch_nanos),
+                hash
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn prune_stale(&mut self, roots: &[PathBuf]) -> Result<usize> {
+        let entries: Vec<(String, u64, Option<i64>)> = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT path, size, mtime_nanos FROM file_hashes WHERE algorithm = ?1")?;
+            let rows = stmt.query_map(params![self.algorithm], |row| {
+                Ok((row.get(0)?, row.get::<_, i64>(1)? as u64, row.get(2)?))
+            })?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        let mut removed = 0;
+        for (path, size, mtime_nanos) in entries {
+            let file = Path::new(&path);
+            if !roots.iter().any(|root| file.starts_with(root))
+                || matches_file(file, size, from_epoch_nanos(mtime_nanos))
+            {
+                continue;
+            }
+            if !self.in_transaction {
+                self.conn.execute_batch("BEGIN")?;
+                self.in_transaction = true;
+            }
+            removed += self.conn.execute(
+                "DELETE FROM file_hashes WHERE path = ?1 AND algorithm = ?2",
+                params![path, self.algorithm],
+            )?;
+        }
+        Ok(removed)
+    }
+
+    fn save(&mut self) -> Result<()> {
+        if self.in_transaction {
+            self.conn
+                .execute_batch("COMMIT")
+                .context("Failed to commit cache database")?;
+            self.in_transaction = false;
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM file_hashes WHERE algorithm = ?1",
+                params![self.algorithm],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|n| n as usize)
+            .unwrap_or(0)
+    }
+}
+
+impl Drop for SqliteFileCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            log::error!("Failed to save cache on drop: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn file_info_for(path: &Path) -> Result<FileInfo> {
+        let metadata = fs::metadata(path)?;
+        Ok(FileInfo {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            hash: Some("test_hash".to_string()),
+            modified_at: metadata.modified().ok(),
+            created_at: None,
+        })
+    }
+
+    #[test]
+    fn test_sqlite_cache_storage_and_retrieval() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let cache_dir = temp_dir.path().join("cache");
+        let file_path = temp_dir.path().join("a.txt");
+        fs::write(&file_path, b"hello world")?;
+
+        let mut cache = SqliteFileCache::new(&cache_dir, "xxhash")?;
+        cache.store(&file_info_for(&file_path)?, "xxhash")?;
+        assert_eq!(
+            cache.get_file_info(&file_path).and_then(|f| f.hash),
+            Some("test_hash".to_string())
+        );
+        cache.save()?;
+        drop(cache);
+
+        // Entries persist, and are scoped to their algorithm
+        let cache = SqliteFileCache::new(&cache_dir, "xxhash")?;
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get_file_info(&file_path).is_some());
+        assert!(SqliteFileCache::new(&cache_dir, "sha256")?
+            .get_file_info(&file_path)
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sqlite_cache_invalidates_on_mtime_change() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("a.txt");
+        fs::write(&file_path, b"hello world")?;
+
+        let mut cache = SqliteFileCache::new(temp_dir.path(), "xxhash")?;
+        cache.store(&file_info_for(&file_path)?, "xxhash")?;
+        assert!(cache.get_file_info(&file_path).is_some());
+
+        let later = SystemTime::now() + Duration::from_secs(120);
+        filetime::set_file_mtime(&file_path, filetime::FileTime::from_system_time(later))?;
+        assert!(cache.get_file_info(&file_path).is_none());
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use anyhow::Result;
 use crossterm::{
     event::{
         self, DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode, KeyEvent,
-        KeyEventKind, KeyModifiers,
+        KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
     },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
@@ -14,27 +14,54 @@ use ratatui::widgets::*;
 use std::collections::HashMap; // For grouping
 use std::io::{stdout, Stdout};
 use std::path::{Path, PathBuf}; // Ensure Path is imported here
-use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc as std_mpsc; // Alias to avoid conflict if crate::mpsc is used elsewhere
+use std::sync::Arc;
 use std::thread as std_thread; // Alias for clarity
 use std::time::{Duration, Instant};
 use tui_input::backend::crossterm::EventHandler; // For tui-input
 use tui_input::Input;
 
+use crate::dir_dedup::{self, DuplicateDirGroup};
 use crate::file_utils::{
-    self, delete_files, move_files, DuplicateSet, FileInfo, SelectionStrategy, SortCriterion,
-    SortOrder,
+    self, delete_files, hardlink_files, move_files, move_files_preserving_structure, reflink_files,
+    symlink_to_kept, DuplicateSet, FileInfo, SelectionStrategy, SortCriterion, SortOrder,
 };
+use crate::transfer::ConflictPolicy;
 use crate::Cli; // Added SortCriterion, SortOrder
 
+mod preview;
+mod session;
+pub mod theme;
+use preview::FilePreview;
+use session::Session;
+use theme::Theme;
+
 // Application state
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)] // Added PartialEq, Eq
 pub enum ActionType {
     Keep, // Implicit action for the one file not chosen for delete/move
     Delete,
-    Move(PathBuf), // Target directory for move
-    Copy(PathBuf), // Target directory for copy
-    Ignore,        // New action type
+    Move(PathBuf),     // Target directory for move
+    Copy(PathBuf),     // Target directory for copy
+    Ignore,            // New action type
+    Reflink(PathBuf),  // Replace with a reflink clone of the kept file at this path
+    Hardlink(PathBuf), // Replace with a hardlink to the kept file at this path
+    Symlink(PathBuf),  // Replace with a symlink to the kept file at this path
+}
+
+impl ActionType {
+    /// Whether the action takes the file away from its path (deleted, moved or replaced by a link)
+    pub fn removes_file(&self) -> bool {
+        matches!(
+            self,
+            ActionType::Delete
+                | ActionType::Move(_)
+                | ActionType::Reflink(_)
+                | ActionType::Hardlink(_)
+                | ActionType::Symlink(_)
+        )
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -51,16 +78,52 @@ pub enum ActivePanel {
     Jobs,
 }
 
+/// Where a list panel was last drawn, so mouse clicks can be mapped back to rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PanelLayout {
+    pub area: Rect,
+    pub scroll_offset: usize, // Index of the first visible row, as scrolled by ratatui
+}
+
+impl PanelLayout {
+    /// Map a terminal cell to the list index under it, if any. The area includes the
+    /// one-cell border drawn around every panel.
+    pub fn index_at(&self, column: u16, row: u16, item_count: usize) -> Option<usize> {
+        let inner = self.area.inner(&Margin {
+            horizontal: 1,
+            vertical: 1,
+        });
+        if column < inner.x
+            || column >= inner.x + inner.width
+            || row < inner.y
+            || row >= inner.y + inner.height
+        {
+            return None;
+        }
+        let index = self.scroll_offset + (row - inner.y) as usize;
+        (index < item_count).then_some(index)
+    }
+
+    fn contains(&self, column: u16, row: u16) -> bool {
+        column >= self.area.x
+            && column < self.area.x + self.area.width
+            && row >= self.area.y
+            && row < self.area.y + self.area.height
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InputMode {
     Normal,
     CopyDestination,
-    Settings, // New mode for settings
-    Help,     // New mode for help screen
+    Settings,       // New mode for settings
+    Help,           // New mode for help screen
+    SetFilter,      // Typing a substring to filter the sets list
+    ConfirmExecute, // y/N prompt before running destructive jobs
 }
 
 // ---- New structs for parent folder grouping ----
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ParentFolderGroup {
     pub path: PathBuf,
     pub sets: Vec<DuplicateSet>,
@@ -73,6 +136,7 @@ pub enum DisplayListItem {
         path: PathBuf,
         is_expanded: bool,
         set_count: usize,
+        original_group_index: usize,
     },
     SetEntry {
         set_hash_preview: String,
@@ -126,21 +190,36 @@ pub struct AppState {
     pub log_scroll: usize,          // For scrolling the log
     pub log_focus: bool,            // Whether log area is focused
     pub log_filter: Option<String>, // For filtering (stub for now)
+    pub set_filter: Option<String>, // Case-insensitive substring filter for the sets list
+
+    // File preview pane; the cached preview is reused until the selected path changes
+    pub show_preview: bool,
+    pub show_action_preview: bool, // Before/after view of the sets touched by pending jobs
+    pub preview_cache: Option<FilePreview>,
+
+    // Last rendered panel positions, for mouse support
+    pub sets_panel_layout: PanelLayout,
+    pub files_panel_layout: PanelLayout,
+    pub jobs_panel_layout: PanelLayout,
 
     pub is_processing_jobs: bool,
     pub job_processing_message: String,
     pub job_progress: (usize, usize), // (done, total)
 
     pub dry_run: bool, // Indicates if actions should be performed in dry run mode
+    pub update_mode: bool, // --update: copy jobs replace older destination files instead of renaming
+    pub duplicate_dirs: Vec<DuplicateDirGroup>, // Folders that are complete copies of each other
+    pub theme: Theme,
 }
 
 // Channel for messages from scan thread to TUI thread
 #[derive(Debug)]
 pub enum ScanMessage {
     StatusUpdate(u8, String), // Stage number (1-3) + message
+    HashProgress(String, crate::file_utils::HashProgress), // Stage 3 message + percent/ETA
     // ProgressUpdate(f32), // If we have percentage
     Completed(Result<Vec<DuplicateSet>>),
-    Error(String),
+    Error(u16, String), // `error_code` of the failure + message
 }
 
 pub struct App {
@@ -149,16 +228,18 @@ pub struct App {
     scan_thread_join_handle: Option<std_thread::JoinHandle<()>>,
     scan_rx: Option<std_mpsc::Receiver<ScanMessage>>,
     scan_tx: Option<std_mpsc::Sender<ScanMessage>>, // Added sender to be stored for rescans
+    scan_cancel: Arc<AtomicBool>,                   // Set on quit so a running scan stops early
     cli_config: Cli,                                // Store the initial CLI config
 }
 
 impl App {
-    pub fn new(cli_args: &Cli) -> Self {
-        let strategy = SelectionStrategy::from_str(&cli_args.mode)
+    fn initial_state(cli_args: &Cli) -> AppState {
+        let strategy = cli_args
+            .selection_strategy()
             .unwrap_or(SelectionStrategy::NewestModified);
         let initial_status = "Preparing to scan for duplicates...";
 
-        let app_state = AppState {
+        AppState {
             grouped_data: Vec::new(),
             display_list: Vec::new(),
             selected_display_list_index: 0,
@@ -188,23 +269,42 @@ impl App {
             log_scroll: 0,
             log_focus: false,
             log_filter: None,
+            set_filter: None,
+            show_preview: false,
+            show_action_preview: false,
+            preview_cache: None,
+            sets_panel_layout: PanelLayout::default(),
+            files_panel_layout: PanelLayout::default(),
+            jobs_panel_layout: PanelLayout::default(),
             is_processing_jobs: false,
             job_processing_message: String::new(),
             job_progress: (0, 0),
             dry_run: cli_args.dry_run, // Initialize from CLI args
-        };
+            update_mode: cli_args.update,
+            duplicate_dirs: Vec::new(),
+            theme: Theme::from_config(cli_args.theme.as_deref(), &cli_args.theme_config),
+        }
+    }
+
+    pub fn new(cli_args: &Cli) -> Self {
+        let app_state = App::initial_state(cli_args);
 
         // Always perform async scan for TUI
-        log::info!(
-            "Initializing TUI with directory: {:?}",
-            cli_args.directories[0]
-        );
+        let scan_target = match &cli_args.files_from {
+            Some(list) => format!("file list {}", list.display()),
+            None => cli_args
+                .directories
+                .first()
+                .map(|d| d.display().to_string())
+                .unwrap_or_default(),
+        };
+        log::info!("Initializing TUI for {}", scan_target);
         let (tx, rx) = std_mpsc::channel::<ScanMessage>();
 
         // Send an immediate status update to show we're properly initialized
         tx.send(ScanMessage::StatusUpdate(
             1,
-            format!("Starting scan of {}...", cli_args.directories[0].display()),
+            format!("Starting scan of {}...", scan_target),
         ))
         .unwrap_or_else(|e| log::error!("Failed to send initial status update: {}", e));
 
@@ -221,6 +321,8 @@ impl App {
         );
 
         let thread_tx = tx.clone();
+        let scan_cancel = Arc::new(AtomicBool::new(false));
+        let thread_cancel = scan_cancel.clone();
         let scan_thread = std_thread::spawn(move || {
             log::info!("[ScanThread] Starting initial duplicate scan...");
             thread_tx
@@ -232,11 +334,12 @@ impl App {
                     log::error!("[ScanThread] Failed to send initialization message: {}", e)
                 });
 
-            match file_utils::find_duplicate_files_with_progress(
+            match file_utils::find_duplicate_files_cancellable(
                 &current_cli_for_scan,
                 thread_tx.clone(),
+                &thread_cancel,
             ) {
-                Ok(raw_sets) => {
+                Ok((raw_sets, _sampled)) => {
                     log::info!(
                         "[ScanThread] Scan completed successfully with {} sets",
                         raw_sets.len()
@@ -250,7 +353,13 @@ impl App {
                 }
                 Err(e) => {
                     log::error!("[ScanThread] Scan failed with error: {}", e);
-                    if thread_tx.send(ScanMessage::Error(e.to_string())).is_err() {
+                    if thread_tx
+                        .send(ScanMessage::Error(
+                            crate::error::error_code(&e),
+                            e.to_string(),
+                        ))
+                        .is_err()
+                    {
                         log::error!("[ScanThread] Failed to send error message to TUI.");
                     }
                 }
@@ -271,10 +380,106 @@ impl App {
             scan_thread_join_handle: scan_join_handle,
             scan_rx: Some(rx),
             scan_tx: Some(tx),
+            scan_cancel,
             cli_config: cli_args.clone(),
         }
     }
 
+    /// Build the app from a saved session instead of scanning. Directories come from
+    /// the session unless given on the command line, so rescans still work.
+    pub fn from_session(cli_args: &Cli, session: Session) -> Self {
+        let mut cli_config = cli_args.clone();
+        if cli_config.directories.is_empty() {
+            cli_config.directories = session.directories.clone();
+        }
+
+        let mut app = Self {
+            state: App::initial_state(&cli_config),
+            should_quit: false,
+            scan_thread_join_handle: None,
+            scan_rx: None,
+            scan_tx: None,
+            scan_cancel: Arc::new(AtomicBool::new(false)),
+            cli_config,
+        };
+        app.state.is_loading = false;
+        app.restore_session(session);
+        app
+    }
+
+    fn session_path(&self) -> PathBuf {
+        self.cli_config
+            .load_session
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(session::DEFAULT_SESSION_FILE))
+    }
+
+    fn save_session(&mut self) {
+        let path = self.session_path();
+        let session = Session::new(
+            &self.cli_config.directories,
+            &self.state.grouped_data,
+            &self.state.jobs,
+        );
+        let message = match session.save(&path) {
+            Ok(()) => format!(
+                "Saved session ({} jobs) to {}",
+                self.state.jobs.len(),
+                path.display()
+            ),
+            Err(e) => format!("Failed to save session: {}", e),
+        };
+        self.state.log_messages.push(message.clone());
+        self.state.status_message = Some(message);
+    }
+
+    fn load_session(&mut self) {
+        if self.state.is_loading {
+            self.state.status_message =
+                Some("Cannot load a session while a scan is running.".to_string());
+            return;
+        }
+        let path = self.session_path();
+        match Session::load(&path) {
+            Ok(session) => self.restore_session(session),
+            Err(e) => {
+                let message = format!("Failed to load session: {}", e);
+                self.state.log_messages.push(message.clone());
+                self.state.status_message = Some(message);
+            }
+        }
+    }
+
+    /// Replace the current sets and jobs with a session's, logging any files that
+    /// have disappeared since it was saved
+    fn restore_session(&mut self, session: Session) {
+        let missing = session.missing_files();
+        for path in &missing {
+            self.state.log_messages.push(format!(
+                "Missing since session was saved: {}",
+                path.display()
+            ));
+        }
+        let saved_at = session.saved_at;
+        self.state.grouped_data = session.grouped_data;
+        self.state.jobs = session.jobs;
+        self.state.selected_display_list_index = 0;
+        self.state.selected_file_index_in_set = 0;
+        self.state.selected_job_index = 0;
+        self.apply_sort_settings();
+
+        let message = format!(
+            "Loaded session from {} ({} jobs, {} missing files)",
+            saved_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M"),
+            self.state.jobs.len(),
+            missing.len()
+        );
+        self.state.log_messages.push(message.clone());
+        self.state.status_message = Some(message);
+    }
+
     fn process_raw_sets_into_grouped_view(
         sets: Vec<DuplicateSet>,
         default_expanded: bool,
@@ -305,22 +510,44 @@ impl App {
             group.sets.sort_by(|a, b| a.hash.cmp(&b.hash)); // Ensure consistent order of sets within a folder
         }
 
-        let display_list = App::build_display_list_from_grouped_data(&grouped_data);
+        let display_list = App::build_display_list_from_grouped_data(&grouped_data, None);
         (grouped_data, display_list)
     }
 
+    /// Build the flattened folder/set list. With a filter, a folder is kept if its own
+    /// path matches (showing all its sets) or if any of its sets has a matching file path
+    /// (showing only those sets). Matching is a case-insensitive substring test.
     fn build_display_list_from_grouped_data(
         grouped_data: &[ParentFolderGroup],
+        filter: Option<&str>,
     ) -> Vec<DisplayListItem> {
+        let needle = filter.filter(|f| !f.is_empty()).map(|f| f.to_lowercase());
+        let path_matches =
+            |path: &Path, needle: &str| path.to_string_lossy().to_lowercase().contains(needle);
+
         let mut display_list = Vec::new();
         for (group_idx, group) in grouped_data.iter().enumerate() {
+            let visible_sets: Vec<(usize, &DuplicateSet)> = match &needle {
+                Some(needle) if !path_matches(&group.path, needle) => group
+                    .sets
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, set)| set.files.iter().any(|f| path_matches(&f.path, needle)))
+                    .collect(),
+                _ => group.sets.iter().enumerate().collect(),
+            };
+            if needle.is_some() && visible_sets.is_empty() {
+                continue;
+            }
+
             display_list.push(DisplayListItem::Folder {
                 path: group.path.clone(),
                 is_expanded: group.is_expanded,
-                set_count: group.sets.len(),
+                set_count: visible_sets.len(),
+                original_group_index: group_idx,
             });
             if group.is_expanded {
-                for (set_idx, set_item) in group.sets.iter().enumerate() {
+                for (set_idx, set_item) in visible_sets {
                     display_list.push(DisplayListItem::SetEntry {
                         set_hash_preview: set_item.hash.chars().take(8).collect(),
                         set_total_size: set_item.size,
@@ -390,6 +617,7 @@ impl App {
                 &self.state.media_resolution,
                 &self.state.media_formats,
                 self.state.media_similarity,
+                &self.cli_config.media_keep,
             );
         }
 
@@ -410,13 +638,16 @@ impl App {
 
         // Create the scan thread
         let thread_tx = tx.clone();
+        self.scan_cancel = Arc::new(AtomicBool::new(false));
+        let thread_cancel = self.scan_cancel.clone();
         let scan_thread = std_thread::spawn(move || {
             log::info!("[ScanThread] Starting rescan...");
-            match file_utils::find_duplicate_files_with_progress(
+            match file_utils::find_duplicate_files_cancellable(
                 &current_cli_for_scan,
                 thread_tx.clone(),
+                &thread_cancel,
             ) {
-                Ok(raw_sets) => {
+                Ok((raw_sets, _sampled)) => {
                     log::info!(
                         "[ScanThread] Rescan completed successfully with {} sets",
                         raw_sets.len()
@@ -430,7 +661,13 @@ impl App {
                 }
                 Err(e) => {
                     log::error!("[ScanThread] Rescan failed with error: {}", e);
-                    if thread_tx.send(ScanMessage::Error(e.to_string())).is_err() {
+                    if thread_tx
+                        .send(ScanMessage::Error(
+                            crate::error::error_code(&e),
+                            e.to_string(),
+                        ))
+                        .is_err()
+                    {
                         log::error!("[ScanThread] Failed to send rescan error to TUI.");
                     }
                 }
@@ -454,6 +691,10 @@ impl App {
             match rx.try_recv() {
                 Ok(message) => {
                     match message {
+                        ScanMessage::HashProgress(msg, _) => {
+                            self.state.loading_message = format!("🔄 [3/3] {}", msg);
+                            log::debug!("Updated loading message: {}", self.state.loading_message);
+                        }
                         ScanMessage::StatusUpdate(stage, msg) => {
                             // Format the stage indicator for display
                             let stage_prefix = match stage {
@@ -472,6 +713,17 @@ impl App {
                                 Ok(sets) => {
                                     log::info!("Scan completed with {} sets", sets.len());
                                     self.state.is_loading = false;
+                                    self.state.duplicate_dirs =
+                                        dir_dedup::find_duplicate_directories(
+                                            &sets,
+                                            &self.cli_config.directories,
+                                        );
+                                    if !self.state.duplicate_dirs.is_empty() {
+                                        self.state.log_messages.push(format!(
+                                            "Found {} groups of duplicate folders. Press D on a folder to delete that copy.",
+                                            self.state.duplicate_dirs.len()
+                                        ));
+                                    }
 
                                     // Process the raw sets into our grouped view
                                     let (grouped_data, display_list) =
@@ -498,7 +750,7 @@ impl App {
                                 }
                             }
                         }
-                        ScanMessage::Error(err) => {
+                        ScanMessage::Error(_, err) => {
                             log::error!("Scan error: {}", err);
                             self.state.is_loading = false;
                             self.state.status_message = Some(format!("Scan error: {}", err));
@@ -530,10 +782,85 @@ impl App {
             InputMode::CopyDestination => self.handle_copy_dest_input_key(key_event),
             InputMode::Settings => self.handle_settings_mode_key(key_event),
             InputMode::Help => self.handle_help_mode_key(key_event),
+            InputMode::SetFilter => self.handle_set_filter_key(key_event),
+            InputMode::ConfirmExecute => self.handle_confirm_execute_key(key_event),
         }
         self.validate_selection_indices(); // Ensure selections are valid after any action
     }
 
+    /// Preview of the highlighted file, loaded from disk only when the selection changes
+    fn current_preview(&mut self) -> Option<&FilePreview> {
+        let path = self.current_selected_file()?.path.clone();
+        if self
+            .state
+            .preview_cache
+            .as_ref()
+            .is_none_or(|cached| cached.path != path)
+        {
+            self.state.preview_cache = Some(FilePreview::load(&path));
+        }
+        self.state.preview_cache.as_ref()
+    }
+
+    pub fn on_mouse(&mut self, mouse_event: MouseEvent) {
+        // Modal screens and text input own the whole UI
+        if self.state.input_mode != InputMode::Normal {
+            return;
+        }
+        let (column, row) = (mouse_event.column, mouse_event.row);
+
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if self.state.sets_panel_layout.contains(column, row) {
+                    self.state.active_panel = ActivePanel::Sets;
+                    if let Some(index) = self.state.sets_panel_layout.index_at(
+                        column,
+                        row,
+                        self.state.display_list.len(),
+                    ) {
+                        if index != self.state.selected_display_list_index {
+                            self.state.selected_display_list_index = index;
+                            self.state.selected_file_index_in_set = 0;
+                        }
+                    }
+                } else if self.state.files_panel_layout.contains(column, row) {
+                    self.state.active_panel = ActivePanel::Files;
+                    let file_count = self
+                        .current_selected_set_from_display_list()
+                        .map_or(0, |set| set.files.len());
+                    if let Some(index) = self
+                        .state
+                        .files_panel_layout
+                        .index_at(column, row, file_count)
+                    {
+                        self.state.selected_file_index_in_set = index;
+                    }
+                } else if self.state.jobs_panel_layout.contains(column, row) {
+                    self.state.active_panel = ActivePanel::Jobs;
+                    if let Some(index) =
+                        self.state
+                            .jobs_panel_layout
+                            .index_at(column, row, self.state.jobs.len())
+                    {
+                        self.state.selected_job_index = index;
+                    }
+                }
+            }
+            MouseEventKind::ScrollDown => match self.state.active_panel {
+                ActivePanel::Sets => self.select_next_set(),
+                ActivePanel::Files => self.select_next_file_in_set(),
+                ActivePanel::Jobs => self.select_next_job(),
+            },
+            MouseEventKind::ScrollUp => match self.state.active_panel {
+                ActivePanel::Sets => self.select_previous_set(),
+                ActivePanel::Files => self.select_previous_file_in_set(),
+                ActivePanel::Jobs => self.select_previous_job(),
+            },
+            _ => {}
+        }
+        self.validate_selection_indices();
+    }
+
     fn handle_normal_mode_key(&mut self, key_event: KeyEvent) {
         match key_event.code {
             KeyCode::Char('q') | KeyCode::Char('c')
@@ -659,14 +986,11 @@ impl App {
                             self.state.status_message =
                                 Some("All files in set marked for delete".to_string());
                         }
-                        DisplayListItem::Folder { .. } => {
-                            // Find the group for this folder
-                            let group_index = self.state.display_list
-                                [..=self.state.selected_display_list_index]
-                                .iter()
-                                .filter(|item| matches!(item, DisplayListItem::Folder { .. }))
-                                .count()
-                                - 1;
+                        DisplayListItem::Folder {
+                            original_group_index,
+                            ..
+                        } => {
+                            let group_index = *original_group_index;
                             let files_to_process =
                                 if let Some(group) = self.state.grouped_data.get(group_index) {
                                     group
@@ -728,14 +1052,11 @@ impl App {
                             self.state.status_message =
                                 Some("All files in set marked to keep".to_string());
                         }
-                        DisplayListItem::Folder { .. } => {
-                            // Find the group for this folder
-                            let group_index = self.state.display_list
-                                [..=self.state.selected_display_list_index]
-                                .iter()
-                                .filter(|item| matches!(item, DisplayListItem::Folder { .. }))
-                                .count()
-                                - 1;
+                        DisplayListItem::Folder {
+                            original_group_index,
+                            ..
+                        } => {
+                            let group_index = *original_group_index;
                             let files_to_process =
                                 if let Some(group) = self.state.grouped_data.get(group_index) {
                                     group
@@ -767,18 +1088,11 @@ impl App {
                 self.cycle_active_panel();
             }
             KeyCode::Char('e') => {
-                let result = self.process_pending_jobs();
-                match result {
-                    Ok(_) => {
-                        self.state
-                            .log_messages
-                            .push("Executed all pending jobs.".to_string());
-                    }
-                    Err(e) => {
-                        self.state
-                            .log_messages
-                            .push(format!("Error processing jobs: {}", e));
-                    }
+                // Nothing is destroyed in dry run, so only confirm real deletes/moves
+                if !self.state.dry_run && jobs_need_confirmation(&self.state.jobs) {
+                    self.state.input_mode = InputMode::ConfirmExecute;
+                } else {
+                    self.execute_jobs();
                 }
             }
             KeyCode::Char('r') => {
@@ -794,6 +1108,18 @@ impl App {
             KeyCode::Char('c') => {
                 self.initiate_copy_action();
             }
+            KeyCode::Char('f') => {
+                self.mark_set_for_link(ActionType::Reflink, "REFLINK");
+            }
+            KeyCode::Char('L') => {
+                self.mark_set_for_link(ActionType::Hardlink, "HARDLINK");
+            }
+            KeyCode::Char('K') => {
+                self.mark_set_for_link(ActionType::Symlink, "SYMLINK");
+            }
+            KeyCode::Char('D') => {
+                self.mark_duplicate_folder_for_deletion();
+            }
             KeyCode::Up => match self.state.active_panel {
                 ActivePanel::Sets => self.select_previous_set(),
                 ActivePanel::Files => self.select_previous_file_in_set(),
@@ -865,6 +1191,44 @@ impl App {
                 self.state.status_message =
                     Some("Log filter: (type to filter, Esc to clear)".to_string());
             }
+            KeyCode::Char('w') => {
+                self.export_jobs();
+            }
+            KeyCode::Char('S') => {
+                self.save_session();
+            }
+            KeyCode::Char('O') => {
+                self.load_session();
+            }
+            KeyCode::Char('o') => {
+                self.reveal_selected_file();
+            }
+            KeyCode::Char('p') => {
+                self.state.show_preview = !self.state.show_preview;
+                self.state.status_message = Some(if self.state.show_preview {
+                    "Preview pane ON".to_string()
+                } else {
+                    "Preview pane OFF".to_string()
+                });
+            }
+            KeyCode::Char('v') => {
+                self.state.show_action_preview = !self.state.show_action_preview;
+                self.state.status_message = Some(if self.state.show_action_preview {
+                    "Pending changes view ON".to_string()
+                } else {
+                    "Pending changes view OFF".to_string()
+                });
+            }
+            KeyCode::Char('/') => {
+                self.state.input_mode = InputMode::SetFilter;
+                self.state.current_input =
+                    Input::new(self.state.set_filter.clone().unwrap_or_default());
+                self.state.status_message =
+                    Some("Filter sets by path (Enter to keep, Esc to clear):".to_string());
+            }
+            KeyCode::Esc if self.state.set_filter.is_some() => {
+                self.clear_set_filter();
+            }
             _ => {}
         }
     }
@@ -901,7 +1265,7 @@ impl App {
                 self.state.selected_setting_category_index =
                     (self.state.selected_setting_category_index + 1).min(8); // Max index is 8 now including media options
             }
-            // Strategy selection keys (n, o, s, l)
+            // Strategy selection keys (n, o, s, l, b, t)
             KeyCode::Char('n') if self.state.selected_setting_category_index == 0 => {
                 self.state.default_selection_strategy = SelectionStrategy::NewestModified;
                 self.state.status_message = Some("Strategy: Newest Modified".to_string());
@@ -918,6 +1282,14 @@ impl App {
                 self.state.default_selection_strategy = SelectionStrategy::LongestPath;
                 self.state.status_message = Some("Strategy: Longest Path".to_string());
             }
+            KeyCode::Char('b') if self.state.selected_setting_category_index == 0 => {
+                self.state.default_selection_strategy = SelectionStrategy::LargestSize;
+                self.state.status_message = Some("Strategy: Largest Size".to_string());
+            }
+            KeyCode::Char('t') if self.state.selected_setting_category_index == 0 => {
+                self.state.default_selection_strategy = SelectionStrategy::SmallestSize;
+                self.state.status_message = Some("Strategy: Smallest Size".to_string());
+            }
             // Algorithm selection keys (m, a, b, x, g, f, c)
             KeyCode::Char('m') if self.state.selected_setting_category_index == 1 => {
                 self.state.current_algorithm = "md5".to_string();
@@ -1194,6 +1566,119 @@ impl App {
         }
     }
 
+    fn handle_confirm_execute_key(&mut self, key_event: KeyEvent) {
+        self.state.input_mode = InputMode::Normal;
+        match key_event.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => self.execute_jobs(),
+            _ => {
+                // Esc, n, or anything else cancels; the job queue is left untouched
+                self.state.status_message = Some(format!(
+                    "Execution cancelled. {} jobs still pending.",
+                    self.state.jobs.len()
+                ));
+            }
+        }
+    }
+
+    fn export_jobs(&mut self) {
+        if self.state.jobs.is_empty() {
+            self.state.status_message = Some("No jobs to export.".to_string());
+            return;
+        }
+        let extension = if cfg!(windows) { "bat" } else { "sh" };
+        let path = PathBuf::from(format!(
+            "dedups_jobs_{}.{}",
+            chrono::Local::now().format("%Y%m%d_%H%M%S"),
+            extension
+        ));
+        let message = match export_jobs_to_script(&self.state.jobs, &path) {
+            Ok(()) => format!(
+                "Exported {} jobs to {}",
+                self.state.jobs.len(),
+                path.display()
+            ),
+            Err(e) => format!("Failed to export jobs: {}", e),
+        };
+        self.state.log_messages.push(message.clone());
+        self.state.status_message = Some(message);
+    }
+
+    fn reveal_selected_file(&mut self) {
+        let Some(path) = self.current_selected_file().map(|file| file.path.clone()) else {
+            self.state.status_message = Some("No file selected to reveal.".to_string());
+            return;
+        };
+        let (program, args) = reveal_command(&path, std::env::consts::OS);
+        // Spawned without waiting so the file manager doesn't block the TUI
+        let message = match std::process::Command::new(&program)
+            .args(&args)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+        {
+            Ok(_) => format!("Revealed {} in the file manager", path.display()),
+            Err(e) => format!(
+                "Failed to run {} to reveal {}: {}",
+                program,
+                path.display(),
+                e
+            ),
+        };
+        self.state.log_messages.push(message.clone());
+        self.state.status_message = Some(message);
+    }
+
+    fn execute_jobs(&mut self) {
+        match self.process_pending_jobs() {
+            Ok(_) => {
+                self.state
+                    .log_messages
+                    .push("Executed all pending jobs.".to_string());
+            }
+            Err(e) => {
+                self.state
+                    .log_messages
+                    .push(format!("Error processing jobs: {}", e));
+            }
+        }
+    }
+
+    fn handle_set_filter_key(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Enter => {
+                self.state.current_input.reset();
+                self.state.input_mode = InputMode::Normal;
+                self.state.status_message = self
+                    .state
+                    .set_filter
+                    .as_ref()
+                    .map(|f| format!("Filtering sets by \"{}\" (Esc to clear)", f));
+            }
+            KeyCode::Esc => {
+                self.state.current_input.reset();
+                self.state.input_mode = InputMode::Normal;
+                self.clear_set_filter();
+            }
+            _ => {
+                self.state
+                    .current_input
+                    .handle_event(&CEvent::Key(key_event));
+                // Re-filter as the user types
+                let query = self.state.current_input.value().to_string();
+                self.state.set_filter = (!query.is_empty()).then_some(query);
+                self.state.selected_display_list_index = 0;
+                self.rebuild_display_list();
+            }
+        }
+    }
+
+    fn clear_set_filter(&mut self) {
+        self.state.set_filter = None;
+        self.rebuild_display_list();
+        self.state.status_message = Some("Set filter cleared.".to_string());
+    }
+
     fn initiate_copy_action(&mut self) {
         if let Some(selected_file) = self.current_selected_file().cloned() {
             self.state.file_for_copy_move = Some(selected_file);
@@ -1393,9 +1878,11 @@ impl App {
                 return;
             }
 
-            match file_utils::determine_action_targets(
+            match file_utils::determine_protected_action_targets(
                 &selected_set_to_action,
-                self.state.default_selection_strategy,
+                self.state.default_selection_strategy.clone(),
+                &self.cli_config.tiebreak,
+                &file_utils::ProtectedPaths::from_cli(&self.cli_config),
             ) {
                 Ok((kept_file, files_to_delete)) => {
                     let kept_file_path = kept_file.path.clone();
@@ -1448,6 +1935,132 @@ impl App {
         }
     }
 
+    /// Keep one file in the selected set (per the default strategy) and queue the others to be
+    /// replaced by a link to it, using `link_action` to build the job for the kept file's path.
+    fn mark_set_for_link(&mut self, link_action: fn(PathBuf) -> ActionType, label: &str) {
+        let Some(selected_set) = self.current_selected_set_from_display_list().cloned() else {
+            self.state.status_message = Some("No set selected.".to_string());
+            return;
+        };
+
+        match file_utils::determine_protected_action_targets(
+            &selected_set,
+            self.state.default_selection_strategy.clone(),
+            &self.cli_config.tiebreak,
+            &file_utils::ProtectedPaths::from_cli(&self.cli_config),
+        ) {
+            Ok((kept_file, files_to_link)) => {
+                self.state.jobs.retain(|job| {
+                    !selected_set
+                        .files
+                        .iter()
+                        .any(|f_in_set| f_in_set.path == job.file_info.path)
+                });
+                let linked_count = files_to_link.len();
+                self.state.jobs.push(Job {
+                    action: ActionType::Keep,
+                    file_info: kept_file.clone(),
+                });
+                for file_info in files_to_link {
+                    self.state.jobs.push(Job {
+                        action: link_action(kept_file.path.clone()),
+                        file_info,
+                    });
+                }
+                self.state.status_message = Some(format!(
+                    "Marked {} files for {}, 1 to KEEP in current set.",
+                    linked_count, label
+                ));
+            }
+            Err(e) => {
+                self.state.status_message =
+                    Some(format!("Error determining actions for set: {}", e));
+                log::error!("Could not determine action targets for set: {}", e);
+            }
+        }
+    }
+
+    /// Queue every file under the selected folder for deletion when the folder is a complete
+    /// copy of another one. Delete jobs on the other copies are dropped so one always survives.
+    fn mark_duplicate_folder_for_deletion(&mut self) {
+        let selected_path = match self
+            .state
+            .display_list
+            .get(self.state.selected_display_list_index)
+        {
+            Some(DisplayListItem::Folder {
+                original_group_index,
+                ..
+            }) => self
+                .state
+                .grouped_data
+                .get(*original_group_index)
+                .map(|g| g.path.clone()),
+            Some(DisplayListItem::SetEntry { .. }) => self
+                .current_selected_set_from_display_list()
+                .and_then(|set| set.files.get(self.state.selected_file_index_in_set))
+                .and_then(|f| f.path.parent().map(Path::to_path_buf)),
+            None => None,
+        };
+        let Some(selected_path) = selected_path else {
+            self.state.status_message = Some("No folder selected.".to_string());
+            return;
+        };
+
+        // The outermost duplicate folder containing the selection
+        let Some((group, folder)) = self
+            .state
+            .duplicate_dirs
+            .iter()
+            .flat_map(|g| g.directories.iter().map(move |d| (g, d)))
+            .filter(|(_, d)| selected_path.starts_with(d))
+            .min_by_key(|(_, d)| d.components().count())
+            .map(|(g, d)| (g.clone(), d.clone()))
+        else {
+            self.state.status_message = Some(format!(
+                "{} is not a complete copy of another folder.",
+                selected_path.display()
+            ));
+            return;
+        };
+
+        let files_in_folder: Vec<FileInfo> = self
+            .state
+            .grouped_data
+            .iter()
+            .flat_map(|g| &g.sets)
+            .flat_map(|s| &s.files)
+            .filter(|f| f.path.starts_with(&folder))
+            .cloned()
+            .collect();
+        let other_copies: Vec<&PathBuf> =
+            group.directories.iter().filter(|d| **d != folder).collect();
+        self.state.jobs.retain(|job| {
+            let in_folder = job.file_info.path.starts_with(&folder);
+            let deletes_other_copy = matches!(job.action, ActionType::Delete)
+                && other_copies
+                    .iter()
+                    .any(|d| job.file_info.path.starts_with(d));
+            !in_folder && !deletes_other_copy
+        });
+        let count = files_in_folder.len();
+        for file_info in files_in_folder {
+            self.state.jobs.push(Job {
+                action: ActionType::Delete,
+                file_info,
+            });
+        }
+        self.state.status_message = Some(format!(
+            "Marked folder {} ({} files) for DELETE; kept copy: {}",
+            folder.display(),
+            count,
+            other_copies
+                .first()
+                .map(|d| d.display().to_string())
+                .unwrap_or_default()
+        ));
+    }
+
     fn validate_selection_indices(&mut self) {
         if self.state.display_list.is_empty() {
             self.state.selected_display_list_index = 0;
@@ -1532,6 +2145,54 @@ impl App {
             .and_then(|set| set.files.get(self.state.selected_file_index_in_set))
     }
 
+    /// The checks `actions::apply_actions` makes, for one job that removes a file. Sets
+    /// with only a partial hash (`--hash-limit`) need --allow-approximate-delete for
+    /// anything but a move. When the hashes alone aren't trusted (`--verify`,
+    /// `--algorithm auto` or a weak hash) the file must also match the link target, or
+    /// for a delete or move, a file in its set that no queued job removes.
+    fn check_job(
+        &self,
+        job: &Job,
+        removed_paths: &std::collections::HashSet<PathBuf>,
+    ) -> Result<()> {
+        let Some(set) = self
+            .state
+            .grouped_data
+            .iter()
+            .flat_map(|group| &group.sets)
+            .find(|set| set.files.iter().any(|f| f.path == job.file_info.path))
+        else {
+            if !self.cli_config.verify_before_action() {
+                return Ok(());
+            }
+            return Err(anyhow::anyhow!(
+                "{} is not in a duplicate set, so it can't be verified",
+                job.file_info.path.display()
+            ));
+        };
+        if !matches!(job.action, ActionType::Move(_)) {
+            file_utils::check_approximate_delete_allowed(
+                set,
+                self.cli_config.allow_approximate_delete,
+            )?;
+        }
+        if !self.cli_config.verify_set_before_action(set) {
+            return Ok(());
+        }
+        let kept: Vec<&Path> = match &job.action {
+            ActionType::Reflink(kept) | ActionType::Hardlink(kept) | ActionType::Symlink(kept) => {
+                vec![kept.as_path()]
+            }
+            _ => set
+                .files
+                .iter()
+                .map(|f| f.path.as_path())
+                .filter(|path| *path != job.file_info.path && !removed_paths.contains(*path))
+                .collect(),
+        };
+        crate::actions::verify_against_kept(set, &job.file_info.path, &kept)
+    }
+
     fn process_pending_jobs(&mut self) -> Result<()> {
         if self.state.jobs.is_empty() {
             self.state.status_message = Some("No jobs to process.".to_string());
@@ -1562,153 +2223,323 @@ impl App {
         let mut success_count = 0;
         let mut fail_count = 0;
         let jobs_to_process = self.state.jobs.drain(..).collect::<Vec<_>>(); // Take ownership
+        let protected = file_utils::ProtectedPaths::from_cli(&self.cli_config);
+        let removed_paths: std::collections::HashSet<PathBuf> = jobs_to_process
+            .iter()
+            .filter(|job| job.action.removes_file())
+            .map(|job| job.file_info.path.clone())
+            .collect();
         for (idx, job) in jobs_to_process.into_iter().enumerate() {
             self.state.job_progress = (idx + 1, total_jobs);
-            let result: Result<(), anyhow::Error> = match job.action {
-                ActionType::Delete => {
-                    match delete_files(&[job.file_info.clone()], dry_run_mode) {
-                        Ok((1, logs)) => {
-                            // Add logs from delete_files to our log messages
-                            for log in logs {
-                                self.state.log_messages.push(log);
+            // Manually queued jobs can still point at protected files; refuse them here
+            let removes_file = job.action.removes_file();
+            let checked = if removes_file {
+                self.check_job(&job, &removed_paths)
+            } else {
+                Ok(())
+            };
+            let result: Result<(), anyhow::Error> = if removes_file
+                && protected.contains(&job.file_info.path)
+            {
+                Err(anyhow::anyhow!(
+                    "Refusing to {:?} protected file {}",
+                    job.action,
+                    job.file_info.path.display()
+                ))
+            } else if checked.is_err() {
+                checked
+            } else {
+                match job.action {
+                    ActionType::Delete => {
+                        match delete_files(
+                            std::slice::from_ref(&job.file_info),
+                            dry_run_mode,
+                            self.cli_config.trash,
+                            self.cli_config.retries,
+                        ) {
+                            Ok((actioned, logs)) if actioned.len() == 1 => {
+                                // Add logs from delete_files to our log messages
+                                for log in logs {
+                                    self.state.log_messages.push(log);
+                                }
+                                Ok(())
                             }
-                            Ok(())
-                        }
-                        Ok((count, logs)) => {
-                            // Add logs anyway even when count is unexpected
-                            for log in logs {
-                                self.state.log_messages.push(log);
+                            Ok((actioned, logs)) => {
+                                // Add logs anyway even when count is unexpected
+                                for log in logs {
+                                    self.state.log_messages.push(log);
+                                }
+                                Err(anyhow::anyhow!(
+                                    "Delete action affected {} files, expected 1.",
+                                    actioned.len()
+                                ))
                             }
-                            Err(anyhow::anyhow!(
-                                "Delete action affected {} files, expected 1.",
-                                count
-                            ))
+                            Err(e) => Err(e),
                         }
-                        Err(e) => Err(e),
                     }
-                }
-                ActionType::Move(ref target_dir) => {
-                    match move_files(&[job.file_info.clone()], target_dir, dry_run_mode) {
-                        Ok((1, logs)) => {
-                            // Add logs from move_files to our log messages
-                            for log in logs {
-                                self.state.log_messages.push(log);
+                    ActionType::Move(ref target_dir) => {
+                        let moved = if self.cli_config.preserve_structure {
+                            move_files_preserving_structure(
+                                std::slice::from_ref(&job.file_info),
+                                target_dir,
+                                &self.cli_config.directories,
+                                dry_run_mode,
+                                self.cli_config.on_conflict,
+                                self.cli_config.retries,
+                            )
+                        } else {
+                            move_files(
+                                std::slice::from_ref(&job.file_info),
+                                target_dir,
+                                dry_run_mode,
+                                self.cli_config.on_conflict,
+                                self.cli_config.retries,
+                            )
+                        };
+                        match moved {
+                            Ok((actioned, logs)) if actioned.len() == 1 => {
+                                // Add logs from move_files to our log messages
+                                for log in logs {
+                                    self.state.log_messages.push(log);
+                                }
+                                Ok(())
                             }
-                            Ok(())
-                        }
-                        Ok((count, logs)) => {
-                            // Add logs anyway even when count is unexpected
-                            for log in logs {
-                                self.state.log_messages.push(log);
+                            Ok((actioned, logs)) => {
+                                // Add logs anyway even when count is unexpected
+                                for log in logs {
+                                    self.state.log_messages.push(log);
+                                }
+                                Err(anyhow::anyhow!(
+                                    "Move action affected {} files, expected 1.",
+                                    actioned.len()
+                                ))
                             }
-                            Err(anyhow::anyhow!(
-                                "Move action affected {} files, expected 1.",
-                                count
-                            ))
+                            Err(e) => Err(e),
                         }
-                        Err(e) => Err(e),
                     }
-                }
-                ActionType::Copy(ref target_dir) => {
-                    log::debug!(
-                        "Attempting to copy {:?} to {:?}",
-                        job.file_info.path,
-                        target_dir
-                    );
-
-                    if dry_run_mode {
-                        self.state.log_messages.push(format!(
-                            "[DRY RUN] Would copy {} to {}",
-                            job.file_info.path.display(),
-                            target_dir.display()
-                        ));
+                    ActionType::Copy(ref target_dir) => {
+                        log::debug!(
+                            "Attempting to copy {:?} to {:?}",
+                            job.file_info.path,
+                            target_dir
+                        );
 
-                        // Add more detailed logs similar to delete_files and move_files
-                        if !target_dir.exists() {
+                        if dry_run_mode {
                             self.state.log_messages.push(format!(
-                                "[DRY RUN] Would create target directory: {}",
+                                "[DRY RUN] Would copy {} to {}",
+                                job.file_info.path.display(),
                                 target_dir.display()
                             ));
-                        }
 
-                        // Check for potential destination conflicts (even in dry run mode)
-                        let file_name = job.file_info.path.file_name().unwrap_or_default();
-                        let dest_path = target_dir.join(file_name);
-                        if dest_path.exists() {
-                            self.state.log_messages.push(format!(
-                                "[DRY RUN] Note: Destination {} exists. Would be renamed with _copy suffix",
-                                dest_path.display()));
-                        }
-
-                        self.state
-                            .log_messages
-                            .push(format!("[DRY RUN] File size: {} bytes", job.file_info.size));
+                            // Add more detailed logs similar to delete_files and move_files
+                            if !target_dir.exists() {
+                                self.state.log_messages.push(format!(
+                                    "[DRY RUN] Would create target directory: {}",
+                                    target_dir.display()
+                                ));
+                            }
 
-                        Ok(())
-                    } else {
-                        if !target_dir.exists() {
-                            if let Err(e) = std::fs::create_dir_all(target_dir) {
-                                let error_msg = format!(
-                                    "Failed to create target directory {}: {}",
-                                    target_dir.display(),
-                                    e
-                                );
-                                self.state.log_messages.push(error_msg);
-                                log::error!(
-                                    "Failed to create target directory {:?} for copy: {}",
-                                    target_dir,
-                                    e
-                                );
-                                return Err(e.into());
+                            // Check for potential destination conflicts (even in dry run mode)
+                            let file_name = job.file_info.path.file_name().unwrap_or_default();
+                            let dest_path = target_dir.join(file_name);
+                            if dest_path.exists() && self.state.update_mode {
+                                self.state.log_messages.push(format!(
+                                "[DRY RUN] Note: Destination {} exists. Would be replaced only if the source is newer",
+                                dest_path.display()));
+                            } else if dest_path.exists() {
+                                let outcome = match self.cli_config.on_conflict {
+                                    ConflictPolicy::Skip => "The copy would be skipped",
+                                    ConflictPolicy::Overwrite => "Would be overwritten",
+                                    ConflictPolicy::Rename => "Would be renamed with _copy suffix",
+                                };
+                                self.state.log_messages.push(format!(
+                                    "[DRY RUN] Note: Destination {} exists. {}",
+                                    dest_path.display(),
+                                    outcome
+                                ));
                             }
+
                             self.state
                                 .log_messages
-                                .push(format!("Created directory: {}", target_dir.display()));
-                        }
-                        let file_name = job.file_info.path.file_name().unwrap_or_default();
-                        let mut dest_path = target_dir.join(file_name);
-                        let mut counter = 1;
-                        while dest_path.exists() {
-                            let stem = dest_path.file_stem().unwrap_or_default().to_string_lossy();
-                            let ext = dest_path.extension().unwrap_or_default().to_string_lossy();
-                            let new_name = format!(
-                                "{}_copy({}){}{}",
-                                stem.trim_end_matches(&format!("_copy({})", counter - 1))
-                                    .trim_end_matches("_copy"),
-                                counter,
-                                if ext.is_empty() { "" } else { "." },
-                                ext
-                            );
-                            dest_path = target_dir.join(new_name);
-                            counter += 1;
-                        }
-                        std::fs::copy(&job.file_info.path, &dest_path)
-                            .map(|size| {
+                                .push(format!("[DRY RUN] File size: {} bytes", job.file_info.size));
+
+                            Ok(())
+                        } else {
+                            if !target_dir.exists() {
+                                if let Err(e) = std::fs::create_dir_all(target_dir) {
+                                    let error_msg = format!(
+                                        "Failed to create target directory {}: {}",
+                                        target_dir.display(),
+                                        e
+                                    );
+                                    self.state.log_messages.push(error_msg);
+                                    log::error!(
+                                        "Failed to create target directory {:?} for copy: {}",
+                                        target_dir,
+                                        e
+                                    );
+                                    return Err(e.into());
+                                }
+                                self.state
+                                    .log_messages
+                                    .push(format!("Created directory: {}", target_dir.display()));
+                            }
+                            let file_name = job.file_info.path.file_name().unwrap_or_default();
+                            let planned_path = target_dir.join(file_name);
+                            let up_to_date = self.state.update_mode
+                                && crate::update_mode::decide(&job.file_info, &planned_path)
+                                    == crate::update_mode::UpdateDecision::Skip;
+                            // Update mode replaces outdated copies in place, so it never conflicts
+                            let dest_path = if self.state.update_mode {
+                                Some(planned_path.clone())
+                            } else {
+                                crate::file_utils::resolve_conflict(
+                                    planned_path.clone(),
+                                    self.cli_config.on_conflict,
+                                )
+                            };
+                            if up_to_date {
                                 self.state.log_messages.push(format!(
-                                    "Copied: {} -> {} ({} bytes)",
-                                    job.file_info.path.display(),
-                                    dest_path.display(),
-                                    size
+                                    "Skipped: {} (destination is up to date)",
+                                    planned_path.display()
                                 ));
-                            })
-                            .map_err(|e| {
-                                let error_msg = format!(
-                                    "Failed to copy {}: {}",
+                                Ok(())
+                            } else if dest_path.is_none() {
+                                self.state.log_messages.push(format!(
+                                    "Skipped: {} (destination {} exists)",
                                     job.file_info.path.display(),
-                                    e
-                                );
-                                self.state.log_messages.push(error_msg);
-                                log::error!(
-                                    "Failed to copy {:?} to {:?}: {}",
-                                    job.file_info.path,
-                                    dest_path,
-                                    e
-                                );
-                                anyhow::Error::from(e)
-                            })
+                                    planned_path.display()
+                                ));
+                                Ok(())
+                            } else {
+                                let dest_path = dest_path.unwrap_or(planned_path);
+                                // Overwrites can't be undone, so only new copies go in the journal
+                                let is_new_copy = std::fs::symlink_metadata(&dest_path).is_err();
+                                crate::transfer::copy_file(
+                                    &job.file_info.path,
+                                    &dest_path,
+                                    &crate::transfer::TransferOptions::from_cli(&self.cli_config),
+                                )
+                                .map(|size| {
+                                    if is_new_copy {
+                                        crate::journal::record_action(
+                                            crate::journal::JournalAction::Copy,
+                                            &job.file_info.path,
+                                            Some(&dest_path),
+                                        );
+                                    }
+                                    if !self.cli_config.no_preserve_timestamps {
+                                        if let Err(e) = crate::file_utils::preserve_file_timestamps(
+                                            &job.file_info,
+                                            &dest_path,
+                                        ) {
+                                            self.state.log_messages.push(format!(
+                                                "Warning: could not preserve timestamps on {}: {}",
+                                                dest_path.display(),
+                                                e
+                                            ));
+                                        }
+                                    }
+                                    self.state.log_messages.push(format!(
+                                        "Copied: {} -> {} ({} bytes)",
+                                        job.file_info.path.display(),
+                                        dest_path.display(),
+                                        size
+                                    ));
+                                })
+                                .map_err(|e| {
+                                    let error_msg = format!(
+                                        "Failed to copy {}: {}",
+                                        job.file_info.path.display(),
+                                        e
+                                    );
+                                    self.state.log_messages.push(error_msg);
+                                    log::error!(
+                                        "Failed to copy {:?} to {:?}: {}",
+                                        job.file_info.path,
+                                        dest_path,
+                                        e
+                                    );
+                                    anyhow::Error::from(e)
+                                })
+                            }
+                        }
+                    }
+                    ActionType::Reflink(ref kept_path) => {
+                        let kept = FileInfo {
+                            path: kept_path.clone(),
+                            ..job.file_info.clone()
+                        };
+                        match reflink_files(
+                            &kept,
+                            std::slice::from_ref(&job.file_info),
+                            dry_run_mode,
+                        ) {
+                            Ok((actioned, logs)) if actioned.len() == 1 => {
+                                self.state.log_messages.extend(logs);
+                                Ok(())
+                            }
+                            Ok((actioned, logs)) => {
+                                self.state.log_messages.extend(logs);
+                                Err(anyhow::anyhow!(
+                                    "Reflink action affected {} files, expected 1.",
+                                    actioned.len()
+                                ))
+                            }
+                            Err(e) => Err(e),
+                        }
                     }
+                    ActionType::Hardlink(ref kept_path) => {
+                        let kept = FileInfo {
+                            path: kept_path.clone(),
+                            ..job.file_info.clone()
+                        };
+                        match hardlink_files(
+                            &kept,
+                            std::slice::from_ref(&job.file_info),
+                            dry_run_mode,
+                        ) {
+                            Ok((actioned, logs)) if actioned.len() == 1 => {
+                                self.state.log_messages.extend(logs);
+                                Ok(())
+                            }
+                            Ok((actioned, logs)) => {
+                                self.state.log_messages.extend(logs);
+                                Err(anyhow::anyhow!(
+                                    "Hardlink action affected {} files, expected 1.",
+                                    actioned.len()
+                                ))
+                            }
+                            Err(e) => Err(e),
+                        }
+                    }
+                    ActionType::Symlink(ref kept_path) => {
+                        let kept = FileInfo {
+                            path: kept_path.clone(),
+                            ..job.file_info.clone()
+                        };
+                        match symlink_to_kept(
+                            &kept,
+                            std::slice::from_ref(&job.file_info),
+                            self.cli_config.relative_symlinks,
+                            dry_run_mode,
+                        ) {
+                            Ok((actioned, logs)) if actioned.len() == 1 => {
+                                self.state.log_messages.extend(logs);
+                                Ok(())
+                            }
+                            Ok((actioned, logs)) => {
+                                self.state.log_messages.extend(logs);
+                                Err(anyhow::anyhow!(
+                                    "Symlink action affected {} files, expected 1.",
+                                    actioned.len()
+                                ))
+                            }
+                            Err(e) => Err(e),
+                        }
+                    }
+                    ActionType::Keep | ActionType::Ignore => Ok(()),
                 }
-                ActionType::Keep | ActionType::Ignore => Ok(()),
             };
             if result.is_ok() {
                 success_count += 1;
@@ -1803,8 +2634,10 @@ impl App {
     }
 
     fn rebuild_display_list(&mut self) {
-        self.state.display_list =
-            App::build_display_list_from_grouped_data(&self.state.grouped_data);
+        self.state.display_list = App::build_display_list_from_grouped_data(
+            &self.state.grouped_data,
+            self.state.set_filter.as_deref(),
+        );
         self.validate_selection_indices(); // Ensure selection is still valid
     }
 
@@ -1836,6 +2669,12 @@ impl App {
 type TerminalBackend = CrosstermBackend<Stdout>;
 
 pub fn run_tui_app(cli: &Cli) -> Result<()> {
+    // Load the session before taking over the terminal so errors print normally
+    let session = match &cli.load_session {
+        Some(path) => Some(Session::load(path)?),
+        None => None,
+    };
+
     enable_raw_mode()?;
     let mut stdout = stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -1847,11 +2686,15 @@ pub fn run_tui_app(cli: &Cli) -> Result<()> {
     tui_cli.progress = true;
     tui_cli.progress_tui = true;
 
-    let mut app = App::new(&tui_cli);
+    let show_tui_progress = session.is_none();
+    let mut app = match session {
+        Some(session) => App::from_session(&tui_cli, session),
+        None => App::new(&tui_cli),
+    };
     app.validate_selection_indices(); // Initial validation for sync loaded data if any
 
     // Always enable progress for TUI mode regardless of cli.progress setting
-    let res = run_main_loop(&mut terminal, &mut app, true);
+    let res = run_main_loop(&mut terminal, &mut app, show_tui_progress);
 
     // Restore terminal
     disable_raw_mode()?;
@@ -1862,7 +2705,8 @@ pub fn run_tui_app(cli: &Cli) -> Result<()> {
     )?;
     terminal.show_cursor()?;
 
-    // Join scan thread if it exists (especially on quit)
+    // Join scan thread if it exists (especially on quit), stopping it first
+    app.scan_cancel.store(true, Ordering::Relaxed);
     if let Some(handle) = app.scan_thread_join_handle.take() {
         log::debug!("Attempting to join scan thread...");
         if let Err(e) = handle.join() {
@@ -1921,10 +2765,10 @@ fn run_main_loop(
             .unwrap_or_else(|| Duration::from_secs(0));
 
         if crossterm::event::poll(timeout)? {
-            if let CEvent::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    app.on_key(key);
-                }
+            match event::read()? {
+                CEvent::Key(key) if key.kind == KeyEventKind::Press => app.on_key(key),
+                CEvent::Mouse(mouse) => app.on_mouse(mouse),
+                _ => {}
             }
         }
 
@@ -1984,6 +2828,236 @@ fn parse_progress_from_message(message: &str) -> (String, String, Option<f64>) {
     (stage, progress_text, percentage)
 }
 
+/// Quote a path for a POSIX shell: wrap in single quotes, escaping embedded ones
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+/// Command that shows `path` in the file manager of `os` (as in `std::env::consts::OS`):
+/// selected in Finder or Explorer, or its containing folder opened via `xdg-open` elsewhere
+pub fn reveal_command(path: &Path, os: &str) -> (String, Vec<std::ffi::OsString>) {
+    match os {
+        "macos" => ("open".to_string(), vec!["-R".into(), path.into()]),
+        "windows" => {
+            let mut select = std::ffi::OsString::from("/select,");
+            select.push(path);
+            ("explorer".to_string(), vec![select])
+        }
+        _ => {
+            let parent = path
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            ("xdg-open".to_string(), vec![parent.into()])
+        }
+    }
+}
+
+/// Quote a path for cmd.exe: double quotes, with `%` doubled so it isn't expanded
+fn batch_quote(path: &Path) -> String {
+    format!("\"{}\"", path.to_string_lossy().replace('%', "%%"))
+}
+
+/// Write the job queue as a script that performs the same actions, so it can be
+/// reviewed and run later. A `.bat` path produces a Windows batch file; anything
+/// else produces a POSIX shell script, marked executable on Unix.
+pub fn export_jobs_to_script(jobs: &[Job], path: &Path) -> Result<()> {
+    let batch = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("bat"));
+    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+    let mut lines = Vec::new();
+
+    if batch {
+        lines.push("@echo off".to_string());
+        lines.push(format!("REM Generated by dedups on {}", timestamp));
+        lines.push(format!("REM {} jobs", jobs.len()));
+    } else {
+        lines.push("#!/bin/sh".to_string());
+        lines.push(format!("# Generated by dedups on {}", timestamp));
+        lines.push(format!("# {} jobs", jobs.len()));
+        lines.push("set -e".to_string());
+    }
+    lines.push(String::new());
+
+    for job in jobs {
+        let src = &job.file_info.path;
+        let file_name = Path::new(src.file_name().unwrap_or_default());
+        if batch {
+            let q = batch_quote;
+            match &job.action {
+                ActionType::Delete => lines.push(format!("del /f /q {}", q(src))),
+                ActionType::Move(dir) => {
+                    lines.push(format!("if not exist {0} mkdir {0}", q(dir)));
+                    lines.push(format!("move /y {} {}", q(src), q(&dir.join(file_name))));
+                }
+                ActionType::Copy(dir) => {
+                    lines.push(format!("if not exist {0} mkdir {0}", q(dir)));
+                    lines.push(format!("copy /y {} {}", q(src), q(&dir.join(file_name))));
+                }
+                ActionType::Hardlink(kept) => {
+                    lines.push(format!("del /f /q {}", q(src)));
+                    lines.push(format!("mklink /H {} {}", q(src), q(kept)));
+                }
+                ActionType::Symlink(kept) => {
+                    lines.push(format!("del /f /q {}", q(src)));
+                    lines.push(format!("mklink {} {}", q(src), q(kept)));
+                }
+                ActionType::Reflink(kept) => {
+                    // cmd has no reflink support; fall back to a full copy
+                    lines.push(format!("copy /y {} {}", q(kept), q(src)));
+                }
+                ActionType::Keep => lines.push(format!("REM keep {}", q(src))),
+                ActionType::Ignore => lines.push(format!("REM ignore {}", q(src))),
+            }
+        } else {
+            let q = shell_quote;
+            match &job.action {
+                ActionType::Delete => lines.push(format!("rm -f -- {}", q(src))),
+                ActionType::Move(dir) => {
+                    lines.push(format!("mkdir -p -- {}", q(dir)));
+                    lines.push(format!("mv -- {} {}", q(src), q(&dir.join(file_name))));
+                }
+                ActionType::Copy(dir) => {
+                    lines.push(format!("mkdir -p -- {}", q(dir)));
+                    lines.push(format!("cp -p -- {} {}", q(src), q(&dir.join(file_name))));
+                }
+                ActionType::Hardlink(kept) => {
+                    lines.push(format!("ln -f -- {} {}", q(kept), q(src)))
+                }
+                ActionType::Symlink(kept) => {
+                    lines.push(format!("ln -sf -- {} {}", q(kept), q(src)))
+                }
+                ActionType::Reflink(kept) => {
+                    lines.push(format!("cp --reflink=always -f -- {} {}", q(kept), q(src)))
+                }
+                ActionType::Keep => lines.push(format!("# keep {}", q(src))),
+                ActionType::Ignore => lines.push(format!("# ignore {}", q(src))),
+            }
+        }
+    }
+
+    let newline = if batch { "\r\n" } else { "\n" };
+    let mut script = lines.join(newline);
+    script.push_str(newline);
+    std::fs::write(path, script)
+        .map_err(|e| anyhow::anyhow!("Failed to write script {:?}: {}", path, e))?;
+
+    #[cfg(unix)]
+    if !batch {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// What the job queue will do to one duplicate set
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetActionSummary {
+    /// Every file in the set with its pending action; files without a job are kept
+    pub files: Vec<(PathBuf, ActionType)>,
+    /// Number of files in the set that have a job queued
+    pub pending_jobs: usize,
+    /// Space freed once the jobs run (deleted or linked copies)
+    pub reclaimable_bytes: u64,
+}
+
+/// Pair each file in `set` with the job queued for it, if any
+pub fn summarize_set_actions(set: &DuplicateSet, jobs: &[Job]) -> SetActionSummary {
+    let mut summary = SetActionSummary {
+        files: Vec::with_capacity(set.files.len()),
+        pending_jobs: 0,
+        reclaimable_bytes: 0,
+    };
+    for file in &set.files {
+        let action = match jobs.iter().find(|job| job.file_info.path == file.path) {
+            Some(job) => {
+                summary.pending_jobs += 1;
+                job.action.clone()
+            }
+            None => ActionType::Keep,
+        };
+        if matches!(
+            action,
+            ActionType::Delete
+                | ActionType::Reflink(_)
+                | ActionType::Hardlink(_)
+                | ActionType::Symlink(_)
+        ) {
+            summary.reclaimable_bytes += file.size;
+        }
+        summary.files.push((file.path.clone(), action));
+    }
+    summary
+}
+
+/// Row marker and style for a file with a pending job
+fn action_marker(action: &ActionType, theme: &Theme) -> (&'static str, Style) {
+    let style = Style::default();
+    match action {
+        ActionType::Keep => ("[K]", style.fg(theme.keep).add_modifier(Modifier::BOLD)),
+        ActionType::Delete => (
+            "[D]",
+            style.fg(theme.delete).add_modifier(Modifier::CROSSED_OUT),
+        ),
+        ActionType::Copy(_) => ("[C]", style.fg(theme.copy)),
+        ActionType::Move(_) => ("[M]", style.fg(theme.move_to)),
+        ActionType::Ignore => ("[I]", style.fg(theme.ignore)),
+        ActionType::Reflink(_) => ("[R]", style.fg(theme.reflink)),
+        ActionType::Hardlink(_) => ("[H]", style.fg(theme.hardlink)),
+        ActionType::Symlink(_) => ("[S]", style.fg(theme.symlink)),
+    }
+}
+
+/// Whether the queue holds jobs that can't be undone from inside the TUI
+fn jobs_need_confirmation(jobs: &[Job]) -> bool {
+    jobs.iter()
+        .any(|job| matches!(job.action, ActionType::Delete | ActionType::Move(_)))
+}
+
+/// Prompt shown before executing jobs, e.g. "About to delete 42 files, move 3 — y/N"
+fn execute_confirmation_summary(jobs: &[Job], dry_run: bool) -> String {
+    let deletes = jobs
+        .iter()
+        .filter(|job| job.action == ActionType::Delete)
+        .count();
+    let moves = jobs
+        .iter()
+        .filter(|job| matches!(job.action, ActionType::Move(_)))
+        .count();
+    let others = jobs
+        .iter()
+        .filter(|job| !matches!(job.action, ActionType::Keep | ActionType::Ignore))
+        .count()
+        - deletes
+        - moves;
+
+    let mut parts = Vec::new();
+    if deletes > 0 {
+        parts.push(format!(
+            "delete {} file{}",
+            deletes,
+            if deletes == 1 { "" } else { "s" }
+        ));
+    }
+    if moves > 0 {
+        parts.push(format!("move {}", moves));
+    }
+    if others > 0 || parts.is_empty() {
+        parts.push(format!(
+            "run {} other job{}",
+            others,
+            if others == 1 { "" } else { "s" }
+        ));
+    }
+    let prefix = if dry_run {
+        "[DRY RUN] About to simulate:"
+    } else {
+        "About to"
+    };
+    format!("{} {} — y/N", prefix, parts.join(", "))
+}
+
 fn format_file_size(size: u64, raw_sizes: bool) -> String {
     if raw_sizes {
         format!("{} bytes", size)
@@ -1993,6 +3067,7 @@ fn format_file_size(size: u64, raw_sizes: bool) -> String {
 }
 
 fn ui(frame: &mut Frame, app: &mut App) {
+    let theme = app.state.theme;
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -2055,7 +3130,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
                     .borders(Borders::ALL)
                     .title("Overall Progress"),
             )
-            .gauge_style(Style::default().fg(Color::Cyan).bg(Color::Black))
+            .gauge_style(Style::default().fg(theme.gauge_overall).bg(theme.gauge_bg))
             .label(total_progress_text)
             .ratio(total_progress);
 
@@ -2074,7 +3149,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
 
         let stage_gauge = Gauge::default()
             .block(Block::default().borders(Borders::ALL).title(stage_str))
-            .gauge_style(Style::default().fg(Color::White).bg(Color::Black))
+            .gauge_style(Style::default().fg(theme.gauge_stage).bg(theme.gauge_bg))
             .label(progress_text)
             .ratio(stage_progress_value);
 
@@ -2113,43 +3188,43 @@ fn ui(frame: &mut Frame, app: &mut App) {
         match app.state.selected_setting_category_index {
             0 => {
                 strategy_style = strategy_style
-                    .fg(Color::Yellow)
+                    .fg(theme.highlight)
                     .add_modifier(Modifier::BOLD)
             }
-            1 => algo_style = algo_style.fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            1 => algo_style = algo_style.fg(theme.highlight).add_modifier(Modifier::BOLD),
             2 => {
                 parallel_style = parallel_style
-                    .fg(Color::Yellow)
+                    .fg(theme.highlight)
                     .add_modifier(Modifier::BOLD)
             }
             3 => {
                 sort_criterion_style = sort_criterion_style
-                    .fg(Color::Yellow)
+                    .fg(theme.highlight)
                     .add_modifier(Modifier::BOLD)
             }
             4 => {
                 sort_order_style = sort_order_style
-                    .fg(Color::Yellow)
+                    .fg(theme.highlight)
                     .add_modifier(Modifier::BOLD)
             }
             5 => {
                 media_mode_style = media_mode_style
-                    .fg(Color::Yellow)
+                    .fg(theme.highlight)
                     .add_modifier(Modifier::BOLD)
             }
             6 => {
                 media_resolution_style = media_resolution_style
-                    .fg(Color::Yellow)
+                    .fg(theme.highlight)
                     .add_modifier(Modifier::BOLD)
             }
             7 => {
                 media_format_style = media_format_style
-                    .fg(Color::Yellow)
+                    .fg(theme.highlight)
                     .add_modifier(Modifier::BOLD)
             }
             8 => {
                 media_similarity_style = media_similarity_style
-                    .fg(Color::Yellow)
+                    .fg(theme.highlight)
                     .add_modifier(Modifier::BOLD)
             }
             _ => {}
@@ -2157,7 +3232,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
 
         let settings_text = vec![
             Line::from(Span::styled(format!("1. File Selection Strategy: {:?}", app.state.default_selection_strategy), strategy_style)),
-            Line::from(Span::styled("   (n:newest, o:oldest, s:shortest, l:longest)".to_string(), strategy_style)),
+            Line::from(Span::styled("   (n:newest, o:oldest, s:shortest, l:longest, b:biggest, t:tiniest)".to_string(), strategy_style)),
             Line::from(Span::raw("")),
             Line::from(Span::styled(format!("2. Hashing Algorithm: {}", app.state.current_algorithm), algo_style)),
             Line::from(Span::styled("   (m:md5, a:sha256, b:blake3, x:xxhash, g:gxhash, f:fnv1a, c:crc32)".to_string(), algo_style)),
@@ -2244,6 +3319,13 @@ fn ui(frame: &mut Frame, app: &mut App) {
             Line::from("  h          : Show this Help screen (Esc to close)"),
             Line::from("  Ctrl+R     : Trigger a rescan with current settings"),
             Line::from("  Ctrl+S     : Open Settings menu (Esc to close)"),
+            Line::from("  /          : Filter sets by path substring (Esc clears the filter)"),
+            Line::from("  Mouse      : Click a row to select it, scroll wheel moves the selection"),
+            Line::from("  S / O      : Save / open the session (sets and jobs) to resume later"),
+            Line::from("  w          : Export pending jobs to a shell script (.bat on Windows)"),
+            Line::from("  p          : Toggle a preview of the selected file (text or hexdump)"),
+            Line::from("  o          : Reveal the selected file in the system file manager"),
+            Line::from("  v          : Toggle the pending changes view (per-set outcome of the job queue)"),
             Line::from("  Ctrl+E     : Execute all pending jobs"),
             Line::from("  Ctrl+D     : Toggle Dry Run mode (simulates actions without making changes)"),
             Line::from(""),
@@ -2252,6 +3334,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
             Line::from("  Down/j     : Select next folder/set"),
             Line::from("  Enter/l    : Focus Files panel for selected set / Expand/Collapse folder (TODO)"),
             Line::from("  d          : Mark all but one file (per strategy) in selected set for deletion"),
+            Line::from("  D          : Delete the selected folder if it is a complete copy of another folder"),
             // Line::from("  Ctrl+A : Select all files in all sets for action (TODO)"),
             // Line::from("  /        : Filter sets by regex (TODO)"),
             Line::from(""),
@@ -2263,6 +3346,9 @@ fn ui(frame: &mut Frame, app: &mut App) {
             Line::from("  d          : Mark selected file for DELETE"),
             Line::from("  c          : Mark selected file for COPY (prompts for destination)"),
             Line::from("  i          : Mark selected file to be IGNORED (won't be deleted/moved/copied)"),
+            Line::from("  f          : Keep one file (per strategy), REFLINK the rest to it (btrfs/XFS)"),
+            Line::from("  L          : Keep one file (per strategy), HARDLINK the rest to it"),
+            Line::from("  K          : Keep one file (per strategy), replace the rest with SYMLINKs to it"),
             Line::from(""),
             Line::from(Span::styled("Jobs Panel (Right):", Style::default().add_modifier(Modifier::BOLD))),
             Line::from("  Up/k       : Select previous job"),
@@ -2303,9 +3389,9 @@ fn ui(frame: &mut Frame, app: &mut App) {
         // Helper to create a block with a title and border, highlighting if active
         let create_block = |title_string: String, is_active: bool| {
             let base_style = if is_active {
-                Style::default().fg(Color::Yellow)
+                Style::default().fg(theme.active_border)
             } else {
-                Style::default().fg(Color::White)
+                Style::default().fg(theme.inactive_border)
             };
             Block::default()
                 .borders(Borders::ALL)
@@ -2315,12 +3401,17 @@ fn ui(frame: &mut Frame, app: &mut App) {
 
         // Left Panel: Duplicate Sets (actually folders and sets)
         let sets_panel_title_string = format!(
-            "Parent Folders / Duplicate Sets ({}/{}) (Tab to navigate)",
+            "Parent Folders / Duplicate Sets ({}/{}){} (Tab to navigate)",
             app.state
                 .selected_display_list_index
                 .saturating_add(1)
                 .min(app.state.display_list.len()),
-            app.state.display_list.len()
+            app.state.display_list.len(),
+            app.state
+                .set_filter
+                .as_ref()
+                .map(|f| format!(" [filter: {}]", f))
+                .unwrap_or_default()
         );
         let sets_block = create_block(
             sets_panel_title_string,
@@ -2342,26 +3433,41 @@ fn ui(frame: &mut Frame, app: &mut App) {
                     let prefix = if *is_expanded { "[-]" } else { "[+]" };
                     ListItem::new(Line::from(Span::styled(
                         format!("{} {} ({} sets)", prefix, path.display(), set_count),
-                        Style::default().add_modifier(Modifier::BOLD),
+                        Style::default()
+                            .fg(theme.folder)
+                            .add_modifier(Modifier::BOLD),
                     )))
                 }
                 DisplayListItem::SetEntry {
                     set_hash_preview,
                     set_total_size,
                     file_count_in_set,
+                    original_group_index,
+                    original_set_index_in_group,
                     indent,
-                    ..
                 } => {
                     let indent_str = if *indent { "  " } else { "" };
+                    let approximate = app
+                        .state
+                        .grouped_data
+                        .get(*original_group_index)
+                        .and_then(|group| group.sets.get(*original_set_index_in_group))
+                        .is_some_and(|set| set.approximate);
+                    let (approx_marker, style) = if approximate {
+                        (" ~approx", Style::default().fg(theme.approximate_set))
+                    } else {
+                        ("", Style::default().fg(theme.set))
+                    };
                     ListItem::new(Line::from(Span::styled(
                         format!(
-                            "{}Hash: {}... ({} files, {})",
+                            "{}Hash: {}... ({} files, {}){}",
                             indent_str,
                             set_hash_preview,
                             file_count_in_set,
-                            format_file_size(*set_total_size, app.cli_config.raw_sizes)
+                            format_file_size(*set_total_size, app.cli_config.raw_sizes),
+                            approx_marker
                         ),
-                        Style::default(),
+                        style,
                     )))
                 }
             })
@@ -2372,7 +3478,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
             .highlight_style(
                 Style::default()
                     .add_modifier(Modifier::BOLD)
-                    .bg(Color::Blue),
+                    .bg(theme.selected_set_bg),
             )
             .highlight_symbol(">> ");
         let mut sets_list_state = ListState::default();
@@ -2380,75 +3486,68 @@ fn ui(frame: &mut Frame, app: &mut App) {
             sets_list_state.select(Some(app.state.selected_display_list_index));
         }
         frame.render_stateful_widget(sets_list, main_chunks[0], &mut sets_list_state);
+        app.state.sets_panel_layout = PanelLayout {
+            area: main_chunks[0],
+            scroll_offset: sets_list_state.offset(),
+        };
 
         // Middle Panel: Files in Selected Set
-        let (files_panel_title_string, file_items) = if let Some(selected_set) =
-            app.current_selected_set_from_display_list()
-        {
-            let title = format!(
-                "Files ({}/{}) (s:keep d:del c:copy i:ign h:back)",
-                app.state
-                    .selected_file_index_in_set
-                    .saturating_add(1)
-                    .min(selected_set.files.len()),
-                selected_set.files.len()
-            );
-            let items: Vec<ListItem> = selected_set
-                .files
-                .iter()
-                .map(|file_info| {
-                    let mut style = Style::default();
-                    let mut prefix = "   ";
-                    if let Some(job) = app
-                        .state
-                        .jobs
-                        .iter()
-                        .find(|j| j.file_info.path == file_info.path)
-                    {
-                        match job.action {
-                            ActionType::Keep => {
-                                style = style.fg(Color::Green).add_modifier(Modifier::BOLD);
-                                prefix = "[K]";
-                            }
-                            ActionType::Delete => {
-                                style = style.fg(Color::Red).add_modifier(Modifier::CROSSED_OUT);
-                                prefix = "[D]";
-                            }
-                            ActionType::Copy(_) => {
-                                style = style.fg(Color::Cyan);
-                                prefix = "[C]";
-                            }
-                            ActionType::Move(_) => {
-                                style = style.fg(Color::Magenta);
-                                prefix = "[M]";
-                            }
-                            ActionType::Ignore => {
-                                style = style.fg(Color::DarkGray);
-                                prefix = "[I]";
+        let (files_panel_title_string, file_items) =
+            if let Some(selected_set) = app.current_selected_set_from_display_list() {
+                let title = format!(
+                    "Files ({}/{}) (s:keep d:del c:copy i:ign h:back)",
+                    app.state
+                        .selected_file_index_in_set
+                        .saturating_add(1)
+                        .min(selected_set.files.len()),
+                    selected_set.files.len()
+                );
+                let items: Vec<ListItem> = selected_set
+                    .files
+                    .iter()
+                    .map(|file_info| {
+                        let mut style = Style::default();
+                        let mut prefix = "   ";
+                        if let Some(job) = app
+                            .state
+                            .jobs
+                            .iter()
+                            .find(|j| j.file_info.path == file_info.path)
+                        {
+                            (prefix, style) = action_marker(&job.action, &theme);
+                        } else if let Ok((default_kept, _)) =
+                            file_utils::determine_action_targets_with_tiebreak(
+                                selected_set,
+                                app.state.default_selection_strategy.clone(),
+                                &app.cli_config.tiebreak,
+                            )
+                        {
+                            if default_kept.path == file_info.path {
+                                style = style.fg(theme.keep);
+                                prefix = "[k]";
                             }
                         }
-                    } else if let Ok((default_kept, _)) = file_utils::determine_action_targets(
-                        selected_set,
-                        app.state.default_selection_strategy,
-                    ) {
-                        if default_kept.path == file_info.path {
-                            style = style.fg(Color::Green);
-                            prefix = "[k]";
-                        }
-                    }
-                    ListItem::new(Line::from(vec![
-                        Span::styled(format!("{} ", prefix), style),
-                        Span::styled(file_info.path.display().to_string(), style),
-                    ]))
-                })
-                .collect();
-            (title, items)
-        } else {
-            (
-                "Files (0/0)".to_string(),
-                vec![ListItem::new("No set selected or set is empty")],
-            )
-        };
+                        ListItem::new(Line::from(vec![
+                            Span::styled(format!("{} ", prefix), style),
+                            Span::styled(file_info.path.display().to_string(), style),
+                        ]))
+                    })
+                    // Hardlinks are informational only; they come after the selectable files
+                    .chain(selected_set.hardlinks.iter().map(|file_info| {
+                        let style = Style::default().fg(theme.ignore);
+                        ListItem::new(Line::from(vec![
+                            Span::styled("[=] ", style),
+                            Span::styled(format!("{} (hardlink)", file_info.path.display()), style),
+                        ]))
+                    }))
+                    .collect();
+                (title, items)
+            } else {
+                (
+                    "Files (0/0)".to_string(),
+                    vec![ListItem::new("No set selected or set is empty")],
+                )
+            };
         let files_block = create_block(
             files_panel_title_string,
             app.state.active_panel == ActivePanel::Files
@@ -2459,7 +3558,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
             .highlight_style(
                 Style::default()
                     .add_modifier(Modifier::BOLD)
-                    .bg(Color::DarkGray),
+                    .bg(theme.selected_file_bg),
             )
             .highlight_symbol("> ");
 
@@ -2470,7 +3569,58 @@ fn ui(frame: &mut Frame, app: &mut App) {
         {
             files_list_state.select(Some(app.state.selected_file_index_in_set));
         }
-        frame.render_stateful_widget(files_list, main_chunks[1], &mut files_list_state);
+        // The preview pane, when shown, takes the lower part of the Files column
+        let (files_area, preview_area) = if app.state.show_preview {
+            let split = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(main_chunks[1]);
+            (split[0], Some(split[1]))
+        } else {
+            (main_chunks[1], None)
+        };
+        frame.render_stateful_widget(files_list, files_area, &mut files_list_state);
+        app.state.files_panel_layout = PanelLayout {
+            area: files_area,
+            scroll_offset: files_list_state.offset(),
+        };
+
+        if let Some(preview_area) = preview_area {
+            let raw_sizes = app.cli_config.raw_sizes;
+            let preview_lines: Vec<Line> = match app.current_preview() {
+                Some(preview) => {
+                    let modified = preview
+                        .modified_at
+                        .map(|t| {
+                            chrono::DateTime::<chrono::Local>::from(t)
+                                .format("%Y-%m-%d %H:%M:%S")
+                                .to_string()
+                        })
+                        .unwrap_or_else(|| "unknown".to_string());
+                    let size = preview
+                        .size
+                        .map(|s| format_file_size(s, raw_sizes))
+                        .unwrap_or_else(|| "unknown".to_string());
+                    let mut lines = vec![
+                        Line::from(Span::styled(
+                            format!("Size: {} | Modified: {}", size, modified),
+                            Style::default().fg(theme.info),
+                        )),
+                        Line::from(""),
+                    ];
+                    lines.extend(preview.body_lines().into_iter().map(Line::from));
+                    lines
+                }
+                None => vec![Line::from("No file selected")],
+            };
+            let preview_widget = Paragraph::new(preview_lines).block(
+                Block::default().borders(Borders::ALL).title(format!(
+                    "Preview (first {} KiB, p:hide)",
+                    preview::PREVIEW_BYTES / 1024
+                )),
+            );
+            frame.render_widget(preview_widget, preview_area);
+        }
 
         // Right Panel: Jobs
         let jobs_panel_title_string =
@@ -2491,12 +3641,18 @@ fn ui(frame: &mut Frame, app: &mut App) {
                     ActionType::Move(dest) => format!("MOVE to {}", dest.display()),
                     ActionType::Copy(dest) => format!("COPY to {}", dest.display()),
                     ActionType::Ignore => "IGNORE".to_string(),
+                    ActionType::Reflink(kept) => format!("REFLINK to {}", kept.display()),
+                    ActionType::Hardlink(kept) => format!("HARDLINK to {}", kept.display()),
+                    ActionType::Symlink(kept) => format!("SYMLINK to {}", kept.display()),
                 };
-                let content = Line::from(Span::raw(format!(
-                    "{} - {:?}",
-                    action_str,
-                    job.file_info.path.file_name().unwrap_or_default()
-                )));
+                let content = Line::from(Span::styled(
+                    format!(
+                        "{} - {:?}",
+                        action_str,
+                        job.file_info.path.file_name().unwrap_or_default()
+                    ),
+                    Style::default().fg(theme.job),
+                ));
                 ListItem::new(content)
             })
             .collect();
@@ -2505,14 +3661,66 @@ fn ui(frame: &mut Frame, app: &mut App) {
             .highlight_style(
                 Style::default()
                     .add_modifier(Modifier::BOLD)
-                    .bg(Color::Magenta),
+                    .bg(theme.selected_job_bg),
             )
             .highlight_symbol(">> ");
         let mut jobs_list_state = ListState::default();
         if !app.state.jobs.is_empty() {
             jobs_list_state.select(Some(app.state.selected_job_index));
         }
-        frame.render_stateful_widget(jobs_list_widget, main_chunks[2], &mut jobs_list_state);
+        // The pending changes view, when shown, takes the lower part of the Jobs column
+        let (jobs_area, changes_area) = if app.state.show_action_preview {
+            let split = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .split(main_chunks[2]);
+            (split[0], Some(split[1]))
+        } else {
+            (main_chunks[2], None)
+        };
+        frame.render_stateful_widget(jobs_list_widget, jobs_area, &mut jobs_list_state);
+        app.state.jobs_panel_layout = PanelLayout {
+            area: jobs_area,
+            scroll_offset: jobs_list_state.offset(),
+        };
+
+        if let Some(changes_area) = changes_area {
+            let raw_sizes = app.cli_config.raw_sizes;
+            let mut change_lines: Vec<Line> = Vec::new();
+            let mut total_reclaimable = 0;
+            for set in app.state.grouped_data.iter().flat_map(|g| &g.sets) {
+                let summary = summarize_set_actions(set, &app.state.jobs);
+                if summary.pending_jobs == 0 {
+                    continue;
+                }
+                total_reclaimable += summary.reclaimable_bytes;
+                change_lines.push(Line::from(Span::styled(
+                    format!(
+                        "Hash: {}... frees {}",
+                        set.hash.chars().take(8).collect::<String>(),
+                        format_file_size(summary.reclaimable_bytes, raw_sizes)
+                    ),
+                    Style::default().fg(theme.set).add_modifier(Modifier::BOLD),
+                )));
+                for (path, action) in &summary.files {
+                    let (marker, style) = action_marker(action, &theme);
+                    change_lines.push(Line::from(Span::styled(
+                        format!("  {} {}", marker, path.display()),
+                        style,
+                    )));
+                }
+            }
+            if change_lines.is_empty() {
+                change_lines.push(Line::from("No pending jobs"));
+            }
+            let changes_widget = Paragraph::new(change_lines).block(
+                Block::default().borders(Borders::ALL).title(format!(
+                    "Pending Changes (frees {}, v:hide)",
+                    format_file_size(total_reclaimable, raw_sizes)
+                )),
+            );
+            frame.render_widget(changes_widget, changes_area);
+        }
 
         // Status Bar / Input Area
         match app.state.input_mode {
@@ -2531,9 +3739,9 @@ fn ui(frame: &mut Frame, app: &mut App) {
 
                 let status_style = if app.state.dry_run {
                     // Use yellow for dry run mode to make it more obvious
-                    Style::default().fg(Color::Yellow)
+                    Style::default().fg(theme.dry_run_status)
                 } else {
-                    Style::default().fg(Color::LightCyan)
+                    Style::default().fg(theme.status)
                 };
 
                 let status_bar = Paragraph::new(status_text)
@@ -2551,16 +3759,67 @@ fn ui(frame: &mut Frame, app: &mut App) {
                     .status_message
                     .as_deref()
                     .unwrap_or("Enter destination path for copy (Enter:confirm, Esc:cancel):");
-                let prompt_p = Paragraph::new(prompt_text).fg(Color::Yellow);
+                let prompt_p = Paragraph::new(prompt_text).fg(theme.prompt);
                 frame.render_widget(prompt_p, input_chunks[0]);
                 let input_field = Paragraph::new(app.state.current_input.value())
                     .block(
                         Block::default()
                             .borders(Borders::TOP)
                             .title("Path")
-                            .border_style(Style::default().fg(Color::Yellow)),
+                            .border_style(Style::default().fg(theme.prompt)),
                     )
-                    .fg(Color::White);
+                    .fg(theme.input);
+                frame.render_widget(input_field, input_chunks[1]);
+                frame.set_cursor(
+                    input_chunks[1].x + app.state.current_input.visual_cursor() as u16 + 1,
+                    input_chunks[1].y + 1,
+                );
+            }
+            InputMode::ConfirmExecute => {
+                let prompt = Paragraph::new("y: execute jobs | Esc/n: cancel (jobs stay queued)")
+                    .fg(theme.prompt);
+                frame.render_widget(prompt, chunks[3]);
+
+                // Centered modal over the panels
+                let area = chunks[2];
+                let width = area.width.min(70);
+                let popup = Rect::new(
+                    area.x + (area.width - width) / 2,
+                    area.y + area.height.saturating_sub(5) / 2,
+                    width,
+                    area.height.min(5),
+                );
+                let dialog = Paragraph::new(execute_confirmation_summary(
+                    &app.state.jobs,
+                    app.state.dry_run,
+                ))
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true })
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Confirm Execution")
+                        .border_style(Style::default().fg(theme.delete)),
+                );
+                frame.render_widget(Clear, popup);
+                frame.render_widget(dialog, popup);
+            }
+            InputMode::SetFilter => {
+                let input_chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(1), Constraint::Length(1)])
+                    .split(chunks[3]);
+                let prompt_p =
+                    Paragraph::new("Filter sets by path (Enter:keep, Esc:clear):").fg(theme.prompt);
+                frame.render_widget(prompt_p, input_chunks[0]);
+                let input_field = Paragraph::new(app.state.current_input.value())
+                    .block(
+                        Block::default()
+                            .borders(Borders::TOP)
+                            .title("Filter")
+                            .border_style(Style::default().fg(theme.prompt)),
+                    )
+                    .fg(theme.input);
                 frame.render_widget(input_field, input_chunks[1]);
                 frame.set_cursor(
                     input_chunks[1].x + app.state.current_input.visual_cursor() as u16 + 1,
@@ -2597,7 +3856,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
             // Top gauge shows overall progress
             let top_gauge = Gauge::default()
                 .block(Block::default().borders(Borders::NONE).title(""))
-                .gauge_style(Style::default().fg(Color::Cyan).bg(Color::Black))
+                .gauge_style(Style::default().fg(theme.gauge_overall).bg(theme.gauge_bg))
                 .label(format!(
                     "Overall: {}/{} jobs ({:.1}%)",
                     done,
@@ -2609,7 +3868,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
             // Bottom gauge shows per-job details
             let bottom_gauge = Gauge::default()
                 .block(Block::default().borders(Borders::NONE).title(""))
-                .gauge_style(Style::default().fg(Color::Green).bg(Color::Black))
+                .gauge_style(Style::default().fg(theme.gauge_job).bg(theme.gauge_bg))
                 .label(format!(
                     "Current job: {}/{} - {}",
                     done, total, app.state.job_processing_message
@@ -2652,7 +3911,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
             // Top gauge shows overall progress across all stages
             let top_gauge = Gauge::default()
                 .block(Block::default().borders(Borders::NONE).title(""))
-                .gauge_style(Style::default().fg(Color::Cyan).bg(Color::Black))
+                .gauge_style(Style::default().fg(theme.gauge_overall).bg(theme.gauge_bg))
                 .label(
                     if let (Some(current), Some(total)) = (current_stage, total_stages) {
                         format!(
@@ -2681,7 +3940,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
 
             let bottom_gauge = Gauge::default()
                 .block(Block::default().borders(Borders::NONE).title(""))
-                .gauge_style(Style::default().fg(Color::White).bg(Color::Black))
+                .gauge_style(Style::default().fg(theme.gauge_stage).bg(theme.gauge_bg))
                 .label(progress_text)
                 .ratio(stage_progress_value);
 
@@ -2697,7 +3956,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
             };
             let gauge = Gauge::default()
                 .block(Block::default().borders(Borders::ALL).title("Job Progress"))
-                .gauge_style(Style::default().fg(Color::White).bg(Color::Black))
+                .gauge_style(Style::default().fg(theme.gauge_stage).bg(theme.gauge_bg))
                 .label(format!(
                     "Pending jobs: {} | Ctrl+E: Execute, x: Remove job",
                     total
@@ -2714,7 +3973,7 @@ fn ui(frame: &mut Frame, app: &mut App) {
         let help =
             "h: Help | ↑/↓: Navigate | Space: Toggle | a: Toggle Keep/Delete | q/Ctrl+C: Quit";
         let help_bar = ratatui::widgets::Paragraph::new(help)
-            .style(Style::default().fg(Color::DarkGray))
+            .style(Style::default().fg(theme.hint))
             .alignment(Alignment::Center);
         frame.render_widget(help_bar, chunks[5]);
 
@@ -2821,3 +4080,417 @@ fn parse_stage_numbers(stage_str: &str) -> (Option<usize>, Option<usize>) {
     // Default if we couldn't parse the stage numbers
     (None, None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_with_paths(hash: &str, paths: &[&str]) -> DuplicateSet {
+        DuplicateSet {
+            files: paths
+                .iter()
+                .map(|p| FileInfo {
+                    path: PathBuf::from(p),
+                    size: 10,
+                    hash: Some(hash.to_string()),
+                    modified_at: None,
+                    created_at: None,
+                })
+                .collect(),
+            size: 10,
+            hash: hash.to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        }
+    }
+
+    /// An app for `cli` with no scan thread, for driving job processing directly
+    fn app_without_scan(cli: Cli) -> App {
+        App {
+            state: App::initial_state(&cli),
+            should_quit: false,
+            scan_thread_join_handle: None,
+            scan_rx: None,
+            scan_tx: None,
+            scan_cancel: Arc::new(AtomicBool::new(false)),
+            cli_config: cli,
+        }
+    }
+
+    #[test]
+    fn test_update_overwrite_is_not_undone_as_a_copy() -> Result<()> {
+        use clap::Parser;
+        let dir = tempfile::tempdir()?;
+        let source = dir.path().join("source");
+        let target = dir.path().join("target");
+        std::fs::create_dir_all(&source)?;
+        std::fs::create_dir_all(&target)?;
+        std::fs::write(target.join("report.txt"), "original")?;
+        let old = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(target.join("report.txt"), old)?;
+        std::fs::write(source.join("report.txt"), "newer")?;
+        std::fs::write(source.join("extra.txt"), "extra")?;
+
+        let mut app = app_without_scan(Cli::parse_from([
+            "dedups",
+            "--update",
+            source.to_str().unwrap(),
+        ]));
+        let file_info = |name: &str| -> Result<FileInfo> {
+            let path = source.join(name);
+            Ok(FileInfo {
+                size: std::fs::metadata(&path)?.len(),
+                modified_at: Some(std::fs::metadata(&path)?.modified()?),
+                path,
+                hash: None,
+                created_at: None,
+            })
+        };
+        app.state.jobs = vec![
+            Job {
+                action: ActionType::Copy(target.clone()),
+                file_info: file_info("report.txt")?,
+            },
+            Job {
+                action: ActionType::Copy(target.clone()),
+                file_info: file_info("extra.txt")?,
+            },
+        ];
+        let journal_path = dir.path().join("journal.ndjson");
+        crate::journal::record_for_test(&journal_path, dir.path(), || app.process_pending_jobs())?;
+        assert_eq!(std::fs::read_to_string(target.join("report.txt"))?, "newer");
+
+        let (undone, _) = crate::journal::undo_last_operation(&journal_path, false)?;
+        assert_eq!(undone, 1, "only the new copy is undone");
+        assert!(!target.join("extra.txt").exists());
+        assert!(
+            target.join("report.txt").exists(),
+            "overwritten target kept"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_mode_jobs_are_verified_before_delete() -> Result<()> {
+        use clap::Parser;
+        let dir = tempfile::tempdir()?;
+        let file = |name: &str, content: &str| -> Result<FileInfo> {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content)?;
+            Ok(FileInfo {
+                path,
+                size: content.len() as u64,
+                hash: Some("collide".to_string()),
+                modified_at: None,
+                created_at: None,
+            })
+        };
+        // c.txt only shares the fast hash, as after a collision
+        let kept = file("a.txt", "same bytes")?;
+        let copy = file("b.txt", "same bytes")?;
+        let collided = file("c.txt", "diff bytes")?;
+
+        let mut app = app_without_scan(Cli::parse_from([
+            "dedups",
+            "--algorithm",
+            "auto",
+            dir.path().to_str().unwrap(),
+        ]));
+        app.state.grouped_data = vec![ParentFolderGroup {
+            path: dir.path().to_path_buf(),
+            sets: vec![DuplicateSet {
+                files: vec![kept.clone(), copy.clone(), collided.clone()],
+                size: 10,
+                hash: "collide".to_string(),
+                approximate: false,
+                hardlinks: Vec::new(),
+            }],
+            is_expanded: true,
+        }];
+        app.state.jobs = vec![
+            Job {
+                action: ActionType::Delete,
+                file_info: copy.clone(),
+            },
+            Job {
+                action: ActionType::Delete,
+                file_info: collided.clone(),
+            },
+        ];
+        app.process_pending_jobs()?;
+
+        assert!(kept.path.exists());
+        assert!(!copy.path.exists());
+        assert!(
+            collided.path.exists(),
+            "unverified file must not be deleted"
+        );
+        assert!(app
+            .state
+            .log_messages
+            .iter()
+            .any(|m| m.contains("not byte-identical")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_flag_checks_tui_link_jobs() -> Result<()> {
+        use clap::Parser;
+        let dir = tempfile::tempdir()?;
+        let kept = dir.path().join("kept.bin");
+        let other = dir.path().join("other.bin");
+        std::fs::write(&kept, "original")?;
+        std::fs::write(&other, "modified")?;
+        let file_info = |path: &Path| FileInfo {
+            path: path.to_path_buf(),
+            size: 8,
+            hash: Some("stale".to_string()),
+            modified_at: None,
+            created_at: None,
+        };
+
+        let mut app = app_without_scan(Cli::parse_from([
+            "dedups",
+            "--verify",
+            dir.path().to_str().unwrap(),
+        ]));
+        app.state.grouped_data = vec![ParentFolderGroup {
+            path: dir.path().to_path_buf(),
+            sets: vec![DuplicateSet {
+                files: vec![file_info(&kept), file_info(&other)],
+                size: 8,
+                hash: "stale".to_string(),
+                approximate: false,
+                hardlinks: Vec::new(),
+            }],
+            is_expanded: true,
+        }];
+        app.state.jobs = vec![Job {
+            action: ActionType::Hardlink(kept.clone()),
+            file_info: file_info(&other),
+        }];
+        app.process_pending_jobs()?;
+
+        assert_eq!(std::fs::read_to_string(&other)?, "modified");
+        Ok(())
+    }
+
+    #[test]
+    fn test_approximate_sets_need_opt_in_for_tui_deletes() -> Result<()> {
+        use clap::Parser;
+        let dir = tempfile::tempdir()?;
+        let mut files = Vec::new();
+        for name in ["a.iso", "b.iso"] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, "same prefix")?;
+            files.push(FileInfo {
+                path,
+                size: 11,
+                hash: Some("prefix".to_string()),
+                modified_at: None,
+                created_at: None,
+            });
+        }
+        let set = DuplicateSet {
+            files: files.clone(),
+            size: 11,
+            hash: "prefix".to_string(),
+            approximate: true,
+            hardlinks: Vec::new(),
+        };
+
+        for allow in [false, true] {
+            let mut argv = vec!["dedups", dir.path().to_str().unwrap()];
+            if allow {
+                argv.push("--allow-approximate-delete");
+            }
+            let mut app = app_without_scan(Cli::parse_from(argv));
+            app.state.grouped_data = vec![ParentFolderGroup {
+                path: dir.path().to_path_buf(),
+                sets: vec![set.clone()],
+                is_expanded: true,
+            }];
+            app.state.jobs = vec![Job {
+                action: ActionType::Delete,
+                file_info: files[1].clone(),
+            }];
+            app.process_pending_jobs()?;
+            assert_eq!(files[1].path.exists(), !allow);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_reveal_command_per_platform() {
+        let path = Path::new("/data/photos/a b.jpg");
+        let (program, args) = reveal_command(path, "macos");
+        assert_eq!(program, "open");
+        assert_eq!(args, ["-R", "/data/photos/a b.jpg"]);
+
+        let (program, args) = reveal_command(Path::new(r"C:\data\a.jpg"), "windows");
+        assert_eq!(program, "explorer");
+        assert_eq!(args, [r"/select,C:\data\a.jpg"]);
+
+        let (program, args) = reveal_command(path, "linux");
+        assert_eq!(program, "xdg-open");
+        assert_eq!(args, ["/data/photos"]);
+        let (_, args) = reveal_command(Path::new("a.jpg"), "freebsd");
+        assert_eq!(args, ["."]);
+    }
+
+    #[test]
+    fn test_export_jobs_to_script() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = |p: &str| FileInfo {
+            path: PathBuf::from(p),
+            size: 1,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let jobs = vec![
+            Job {
+                action: ActionType::Delete,
+                file_info: file("/data/it's a dup.txt"),
+            },
+            Job {
+                action: ActionType::Move(PathBuf::from("/archive/old files")),
+                file_info: file("/data/b.txt"),
+            },
+        ];
+        let script_path = dir.path().join("jobs.sh");
+        export_jobs_to_script(&jobs, &script_path)?;
+
+        let script = std::fs::read_to_string(&script_path)?;
+        assert!(script.starts_with("#!/bin/sh\n# Generated by dedups on "));
+        let lines: Vec<&str> = script.lines().collect();
+        assert!(lines.contains(&"rm -f -- '/data/it'\\''s a dup.txt'"));
+        let mkdir = lines
+            .iter()
+            .position(|l| *l == "mkdir -p -- '/archive/old files'")
+            .expect("mkdir line");
+        assert_eq!(
+            lines[mkdir + 1],
+            "mv -- '/data/b.txt' '/archive/old files/b.txt'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_summarize_set_actions() {
+        let set = set_with_paths("h", &["/a/keep.txt", "/b/dup.txt", "/c/dup.txt"]);
+        let jobs: Vec<Job> = set.files[1..]
+            .iter()
+            .map(|file| Job {
+                action: ActionType::Delete,
+                file_info: file.clone(),
+            })
+            .collect();
+
+        let summary = summarize_set_actions(&set, &jobs);
+        assert_eq!(
+            summary.files,
+            vec![
+                (PathBuf::from("/a/keep.txt"), ActionType::Keep),
+                (PathBuf::from("/b/dup.txt"), ActionType::Delete),
+                (PathBuf::from("/c/dup.txt"), ActionType::Delete),
+            ]
+        );
+        assert_eq!(summary.pending_jobs, 2);
+        assert_eq!(summary.reclaimable_bytes, 20);
+
+        // A set with no queued jobs is untouched
+        assert_eq!(summarize_set_actions(&set, &[]).pending_jobs, 0);
+    }
+
+    #[test]
+    fn test_execute_confirmation_summary() {
+        let job = |action: ActionType| Job {
+            action,
+            file_info: FileInfo {
+                path: PathBuf::from("/tmp/f"),
+                size: 1,
+                hash: None,
+                modified_at: None,
+                created_at: None,
+            },
+        };
+        let mut jobs: Vec<Job> = (0..42).map(|_| job(ActionType::Delete)).collect();
+        jobs.extend((0..3).map(|_| job(ActionType::Move(PathBuf::from("/dest")))));
+        jobs.push(job(ActionType::Keep));
+
+        assert!(jobs_need_confirmation(&jobs));
+        assert_eq!(
+            execute_confirmation_summary(&jobs, false),
+            "About to delete 42 files, move 3 — y/N"
+        );
+        assert!(execute_confirmation_summary(&jobs, true).starts_with("[DRY RUN]"));
+
+        let copies = vec![job(ActionType::Copy(PathBuf::from("/dest")))];
+        assert!(!jobs_need_confirmation(&copies));
+        assert_eq!(
+            execute_confirmation_summary(&copies, false),
+            "About to run 1 other job — y/N"
+        );
+    }
+
+    #[test]
+    fn test_panel_layout_maps_clicks_to_rows() {
+        let layout = PanelLayout {
+            area: Rect::new(10, 5, 20, 8), // Inner rows are y=6..=11
+            scroll_offset: 0,
+        };
+        assert_eq!(layout.index_at(15, 6, 10), Some(0));
+        assert_eq!(layout.index_at(15, 9, 10), Some(3));
+        assert_eq!(layout.index_at(15, 9, 3), None, "past the last item");
+        assert_eq!(layout.index_at(15, 5, 10), None, "top border");
+        assert_eq!(layout.index_at(10, 7, 10), None, "left border");
+        assert_eq!(layout.index_at(15, 12, 10), None, "bottom border");
+        assert_eq!(layout.index_at(40, 7, 10), None, "outside the panel");
+
+        let scrolled = PanelLayout {
+            scroll_offset: 7,
+            ..layout
+        };
+        assert_eq!(scrolled.index_at(15, 6, 20), Some(7));
+        assert_eq!(scrolled.index_at(15, 8, 20), Some(9));
+    }
+
+    #[test]
+    fn test_display_list_filter_keeps_only_matching_entries() {
+        let sets = vec![
+            set_with_paths("aaaa", &["/photos/Holiday.jpg", "/backup/holiday.jpg"]),
+            set_with_paths("bbbb", &["/photos/cat.png", "/backup/cat.png"]),
+            set_with_paths("cccc", &["/music/song.mp3", "/music/song copy.mp3"]),
+        ];
+        let (grouped, full) = App::process_raw_sets_into_grouped_view(sets, true);
+        assert_eq!(full.len(), 5); // 2 folders + 3 sets
+
+        // Case-insensitive match on a file path shows the folder and just that set
+        let filtered = App::build_display_list_from_grouped_data(&grouped, Some("HOLIDAY"));
+        assert_eq!(filtered.len(), 2);
+        match &filtered[0] {
+            DisplayListItem::Folder {
+                path, set_count, ..
+            } => {
+                assert_eq!(path, &PathBuf::from("/photos"));
+                assert_eq!(*set_count, 1);
+            }
+            other => panic!("expected folder, got {:?}", other),
+        }
+        assert!(matches!(
+            &filtered[1],
+            DisplayListItem::SetEntry { set_hash_preview, .. } if set_hash_preview == "aaaa"
+        ));
+
+        // A folder-path match keeps every set in the folder
+        let filtered = App::build_display_list_from_grouped_data(&grouped, Some("music"));
+        assert_eq!(filtered.len(), 2);
+
+        assert!(App::build_display_list_from_grouped_data(&grouped, Some("nomatch")).is_empty());
+        assert_eq!(
+            App::build_display_list_from_grouped_data(&grouped, Some("")).len(),
+            full.len()
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How much of a file the preview pane reads, regardless of the file's size
+pub const PREVIEW_BYTES: usize = 4096;
+
+/// Bytes shown per hexdump line
+const HEX_BYTES_PER_LINE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewContent {
+    Text(String),
+    Binary(Vec<u8>),
+    Unreadable(String),
+}
+
+/// The first few KiB of a file plus its metadata, as shown in the TUI preview pane
+#[derive(Debug, Clone)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    pub size: Option<u64>,
+    pub modified_at: Option<SystemTime>,
+    pub content: PreviewContent,
+}
+
+impl FilePreview {
+    /// Read at most `PREVIEW_BYTES` from `path`
+    pub fn load(path: &Path) -> Self {
+        let metadata = fs::metadata(path).ok();
+        let content = match read_head(path) {
+            Ok(buf) if looks_like_text(&buf) => {
+                PreviewContent::Text(String::from_utf8_lossy(&buf).into_owned())
+            }
+            Ok(buf) => PreviewContent::Binary(buf),
+            Err(e) => PreviewContent::Unreadable(e.to_string()),
+        };
+        Self {
+            path: path.to_path_buf(),
+            size: metadata.as_ref().map(|m| m.len()),
+            modified_at: metadata.and_then(|m| m.modified().ok()),
+            content,
+        }
+    }
+
+    /// Text lines for the pane body (text as-is, binary as a hexdump)
+    pub fn body_lines(&self) -> Vec<String> {
+        match &self.content {
+            PreviewContent::Text(text) => text.lines().map(str::to_string).collect(),
+            PreviewContent::Binary(buf) => hexdump(buf),
+            PreviewContent::Unreadable(err) => vec![format!("Cannot read file: {}", err)],
+        }
+    }
+}
+
+fn read_head(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(PREVIEW_BYTES);
+    File::open(path)?
+        .take(PREVIEW_BYTES as u64)
+        .read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Treat a buffer as text if it has no NUL bytes and is valid UTF-8. A multi-byte
+/// character cut off by the read cap at the very end still counts as text.
+pub fn looks_like_text(buf: &[u8]) -> bool {
+    if buf.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(buf) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// Classic `offset  hex bytes  |ascii|` dump, 16 bytes per line
+pub fn hexdump(buf: &[u8]) -> Vec<String> {
+    buf.chunks(HEX_BYTES_PER_LINE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!(
+                "{:08x}  {:<47}  |{}|",
+                i * HEX_BYTES_PER_LINE,
+                hex.join(" "),
+                ascii
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_vs_binary_detection() {
+        assert!(looks_like_text(b"plain ascii\nwith lines\n"));
+        assert!(looks_like_text("caf\u{e9} \u{1f600}".as_bytes()));
+        assert!(looks_like_text(b""));
+        // A UTF-8 sequence truncated by the read cap is still text
+        let emoji = "\u{1f600}".as_bytes();
+        assert!(looks_like_text(&[b"abc", &emoji[..2]].concat()));
+
+        assert!(!looks_like_text(b"PK\x03\x04\x00\x00"), "NUL bytes");
+        assert!(!looks_like_text(&[0xff, 0xfe, 0x41, 0x42]), "invalid UTF-8");
+    }
+
+    #[test]
+    fn test_hexdump_format() {
+        let lines = hexdump(b"0123456789abcdefXY");
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("00000000  30 31 32"));
+        assert!(lines[0].ends_with("|0123456789abcdef|"));
+        assert!(lines[1].starts_with("00000010  58 59"));
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{Job, ParentFolderGroup};
+
+/// Session file used by the save/load keybindings when `--load-session` wasn't given
+pub const DEFAULT_SESSION_FILE: &str = "dedups_session.json";
+
+/// Scan results and queued jobs from a TUI session, saved so a large dedup project
+/// can be resumed later without rescanning.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub saved_at: DateTime<Utc>,
+    /// Directories the scan covered, reused for rescans after loading
+    pub directories: Vec<PathBuf>,
+    pub grouped_data: Vec<ParentFolderGroup>,
+    pub jobs: Vec<Job>,
+}
+
+impl Session {
+    pub fn new(directories: &[PathBuf], grouped_data: &[ParentFolderGroup], jobs: &[Job]) -> Self {
+        Self {
+            saved_at: Utc::now(),
+            directories: directories.to_vec(),
+            grouped_data: grouped_data.to_vec(),
+            jobs: jobs.to_vec(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).with_context(|| format!("Failed to write session {:?}", path))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read session {:?}", path))?;
+        serde_json::from_str(&json).with_context(|| format!("Invalid session file {:?}", path))
+    }
+
+    /// Files referenced by the session (in sets or jobs) that no longer exist on disk
+    pub fn missing_files(&self) -> Vec<PathBuf> {
+        let mut missing: Vec<PathBuf> = self
+            .grouped_data
+            .iter()
+            .flat_map(|group| &group.sets)
+            .flat_map(|set| &set.files)
+            .chain(self.jobs.iter().map(|job| &job.file_info))
+            .filter(|file| !file.path.exists())
+            .map(|file| file.path.clone())
+            .collect();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_utils::{DuplicateSet, FileInfo};
+    use crate::tui_app::ActionType;
+
+    #[test]
+    fn test_session_round_trip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let kept = dir.path().join("a.txt");
+        fs::write(&kept, "same")?;
+        let file = |path: PathBuf| FileInfo {
+            path,
+            size: 4,
+            hash: Some("abcd".to_string()),
+            modified_at: None,
+            created_at: None,
+        };
+        let gone = dir.path().join("deleted_since.txt");
+        let groups = vec![ParentFolderGroup {
+            path: dir.path().to_path_buf(),
+            sets: vec![DuplicateSet {
+                files: vec![file(kept.clone()), file(gone.clone())],
+                size: 4,
+                hash: "abcd".to_string(),
+                approximate: false,
+                hardlinks: Vec::new(),
+            }],
+            is_expanded: true,
+        }];
+        let jobs = vec![
+            Job {
+                action: ActionType::Delete,
+                file_info: file(gone.clone()),
+            },
+            Job {
+                action: ActionType::Keep,
+                file_info: file(kept),
+            },
+        ];
+
+        let session_path = dir.path().join("session.json");
+        Session::new(&[dir.path().to_path_buf()], &groups, &jobs).save(&session_path)?;
+        let loaded = Session::load(&session_path)?;
+
+        assert_eq!(loaded.jobs.len(), jobs.len());
+        assert_eq!(loaded.jobs[0].action, ActionType::Delete);
+        assert_eq!(loaded.grouped_data.len(), 1);
+        assert_eq!(loaded.grouped_data[0].sets[0].files.len(), 2);
+        assert_eq!(loaded.directories, vec![dir.path().to_path_buf()]);
+        assert_eq!(loaded.missing_files(), vec![gone]);
+        Ok(())
+    }
+}
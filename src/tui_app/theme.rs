@@ -0,0 +1,292 @@
+use ratatui::style::Color;
+use std::str::FromStr;
+
+use crate::config::ThemeConfig;
+
+/// Preset used when no theme is configured or the configured one is unknown
+pub const DEFAULT_THEME: &str = "dark";
+
+/// Names accepted by `--theme` and the `[theme]` config section
+pub const THEME_NAMES: [&str; 3] = ["dark", "light", "high-contrast"];
+
+/// Colors used when rendering the TUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub active_border: Color,
+    pub inactive_border: Color,
+    pub folder: Color,
+    pub set: Color,
+    pub approximate_set: Color,
+    pub job: Color,
+    pub selected_set_bg: Color,
+    pub selected_file_bg: Color,
+    pub selected_job_bg: Color,
+    pub keep: Color,
+    pub delete: Color,
+    pub copy: Color,
+    pub move_to: Color,
+    pub ignore: Color,
+    pub reflink: Color,
+    pub hardlink: Color,
+    pub symlink: Color,
+    pub info: Color,
+    pub status: Color,
+    pub dry_run_status: Color,
+    /// Selected entry in the settings menu
+    pub highlight: Color,
+    /// Prompt text and input borders in the status bar
+    pub prompt: Color,
+    /// Text typed into an input field
+    pub input: Color,
+    /// Key help at the bottom of the screen
+    pub hint: Color,
+    /// Overall progress gauges
+    pub gauge_overall: Color,
+    /// Per-stage and pending-job gauges
+    pub gauge_stage: Color,
+    /// Current-job gauge while jobs run
+    pub gauge_job: Color,
+    /// Unfilled part of every gauge
+    pub gauge_bg: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    /// The original colors, for terminals with a dark background
+    pub fn dark() -> Self {
+        Self {
+            active_border: Color::Yellow,
+            inactive_border: Color::White,
+            folder: Color::White,
+            set: Color::White,
+            approximate_set: Color::Yellow,
+            job: Color::White,
+            selected_set_bg: Color::Blue,
+            selected_file_bg: Color::DarkGray,
+            selected_job_bg: Color::Magenta,
+            keep: Color::Green,
+            delete: Color::Red,
+            copy: Color::Cyan,
+            move_to: Color::Magenta,
+            ignore: Color::DarkGray,
+            reflink: Color::Blue,
+            hardlink: Color::LightBlue,
+            symlink: Color::LightCyan,
+            info: Color::Cyan,
+            status: Color::LightCyan,
+            dry_run_status: Color::Yellow,
+            highlight: Color::Yellow,
+            prompt: Color::Yellow,
+            input: Color::White,
+            hint: Color::DarkGray,
+            gauge_overall: Color::Cyan,
+            gauge_stage: Color::White,
+            gauge_job: Color::Green,
+            gauge_bg: Color::Black,
+        }
+    }
+
+    /// Darker foregrounds and pale highlights for terminals with a light background
+    pub fn light() -> Self {
+        Self {
+            active_border: Color::Blue,
+            inactive_border: Color::DarkGray,
+            folder: Color::Black,
+            set: Color::Black,
+            approximate_set: Color::Rgb(160, 90, 0),
+            job: Color::Black,
+            selected_set_bg: Color::LightBlue,
+            selected_file_bg: Color::Gray,
+            selected_job_bg: Color::LightMagenta,
+            keep: Color::Rgb(0, 120, 0),
+            delete: Color::Red,
+            copy: Color::Rgb(0, 110, 140),
+            move_to: Color::Magenta,
+            ignore: Color::Gray,
+            reflink: Color::Blue,
+            hardlink: Color::Rgb(40, 70, 160),
+            symlink: Color::Rgb(0, 90, 110),
+            info: Color::Blue,
+            status: Color::Blue,
+            dry_run_status: Color::Rgb(160, 90, 0),
+            highlight: Color::Blue,
+            prompt: Color::Rgb(160, 90, 0),
+            input: Color::Black,
+            hint: Color::DarkGray,
+            gauge_overall: Color::Blue,
+            gauge_stage: Color::DarkGray,
+            gauge_job: Color::Rgb(0, 120, 0),
+            gauge_bg: Color::Gray,
+        }
+    }
+
+    /// Bright, widely separated colors
+    pub fn high_contrast() -> Self {
+        Self {
+            active_border: Color::LightYellow,
+            inactive_border: Color::Gray,
+            folder: Color::LightYellow,
+            set: Color::White,
+            approximate_set: Color::LightRed,
+            job: Color::White,
+            selected_set_bg: Color::Red,
+            selected_file_bg: Color::Red,
+            selected_job_bg: Color::Red,
+            keep: Color::LightGreen,
+            delete: Color::LightRed,
+            copy: Color::LightCyan,
+            move_to: Color::LightMagenta,
+            ignore: Color::Gray,
+            reflink: Color::LightBlue,
+            hardlink: Color::LightBlue,
+            symlink: Color::LightCyan,
+            info: Color::LightCyan,
+            status: Color::White,
+            dry_run_status: Color::LightYellow,
+            highlight: Color::LightYellow,
+            prompt: Color::LightYellow,
+            input: Color::White,
+            hint: Color::Gray,
+            gauge_overall: Color::LightCyan,
+            gauge_stage: Color::White,
+            gauge_job: Color::LightGreen,
+            gauge_bg: Color::Black,
+        }
+    }
+
+    /// Look up a preset by name, falling back to the default for unknown names
+    pub fn from_name(name: &str) -> Theme {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "dark" => Self::dark(),
+            "light" => Self::light(),
+            "high-contrast" => Self::high_contrast(),
+            other => {
+                log::warn!(
+                    "Unknown theme '{}', using {} (available: {})",
+                    other,
+                    DEFAULT_THEME,
+                    THEME_NAMES.join(", ")
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Build the theme for a session: `name` (from `--theme`) wins over the configured
+    /// preset, then any per-slot colors from the config are laid on top
+    pub fn from_config(name: Option<&str>, config: &ThemeConfig) -> Theme {
+        let mut theme = Self::from_name(name.or(config.name.as_deref()).unwrap_or(DEFAULT_THEME));
+        if let Some(color) = parse_color("selected_row", &config.selected_row) {
+            theme.selected_set_bg = color;
+            theme.selected_file_bg = color;
+            theme.selected_job_bg = color;
+        }
+        if let Some(color) = parse_color("folder", &config.folder) {
+            theme.folder = color;
+        }
+        if let Some(color) = parse_color("set", &config.set) {
+            theme.set = color;
+        }
+        if let Some(color) = parse_color("job", &config.job) {
+            theme.job = color;
+        }
+        theme
+    }
+
+    /// Every color slot with its name
+    pub fn slots(&self) -> [(&'static str, Color); 28] {
+        [
+            ("active_border", self.active_border),
+            ("inactive_border", self.inactive_border),
+            ("folder", self.folder),
+            ("set", self.set),
+            ("approximate_set", self.approximate_set),
+            ("job", self.job),
+            ("selected_set_bg", self.selected_set_bg),
+            ("selected_file_bg", self.selected_file_bg),
+            ("selected_job_bg", self.selected_job_bg),
+            ("keep", self.keep),
+            ("delete", self.delete),
+            ("copy", self.copy),
+            ("move_to", self.move_to),
+            ("ignore", self.ignore),
+            ("reflink", self.reflink),
+            ("hardlink", self.hardlink),
+            ("symlink", self.symlink),
+            ("info", self.info),
+            ("status", self.status),
+            ("dry_run_status", self.dry_run_status),
+            ("highlight", self.highlight),
+            ("prompt", self.prompt),
+            ("input", self.input),
+            ("hint", self.hint),
+            ("gauge_overall", self.gauge_overall),
+            ("gauge_stage", self.gauge_stage),
+            ("gauge_job", self.gauge_job),
+            ("gauge_bg", self.gauge_bg),
+        ]
+    }
+}
+
+/// Parse a configured color, warning about (and ignoring) ones ratatui doesn't know
+fn parse_color(slot: &str, value: &Option<String>) -> Option<Color> {
+    let value = value.as_deref()?;
+    match Color::from_str(value.trim()) {
+        Ok(color) => Some(color),
+        Err(_) => {
+            log::warn!("Ignoring unknown color '{}' for theme.{}", value, slot);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_theme_falls_back_to_default() {
+        assert_eq!(Theme::from_name("nonexistent"), Theme::default());
+        assert_eq!(Theme::from_name("Light"), Theme::light());
+        assert_eq!(Theme::from_name("high_contrast"), Theme::high_contrast());
+    }
+
+    #[test]
+    fn test_every_preset_defines_all_slots() {
+        for name in THEME_NAMES {
+            let theme = Theme::from_name(name);
+            for (slot, color) in theme.slots() {
+                assert_ne!(color, Color::Reset, "{} leaves {} unset", name, slot);
+            }
+        }
+        assert_ne!(Theme::light(), Theme::dark());
+        assert_ne!(Theme::high_contrast(), Theme::dark());
+    }
+
+    #[test]
+    fn test_config_overrides_apply_on_top_of_preset() {
+        let config = ThemeConfig {
+            name: Some("light".to_string()),
+            selected_row: Some("green".to_string()),
+            folder: Some("#ff8800".to_string()),
+            set: Some("not-a-color".to_string()),
+            job: None,
+        };
+        let theme = Theme::from_config(None, &config);
+        assert_eq!(theme.selected_set_bg, Color::Green);
+        assert_eq!(theme.selected_job_bg, Color::Green);
+        assert_eq!(theme.folder, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(theme.set, Theme::light().set);
+        assert_eq!(theme.job, Theme::light().job);
+
+        // --theme picks the preset, the config colors still apply
+        let theme = Theme::from_config(Some("dark"), &config);
+        assert_eq!(theme.keep, Theme::dark().keep);
+        assert_eq!(theme.selected_file_bg, Color::Green);
+    }
+}
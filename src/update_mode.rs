@@ -0,0 +1,134 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::file_utils::{preserve_file_timestamps, FileInfo};
+use crate::journal::{self, JournalAction};
+use crate::transfer::{self, TransferOptions};
+
+/// What `--update` does with one source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateDecision {
+    /// The target doesn't have the file yet
+    Copy,
+    /// The target has an older version
+    Update,
+    /// The target's copy is as new as the source, or newer
+    Skip,
+}
+
+/// Compare a source file against the file at `target_path`. Without a usable
+/// modification time on either side the existing target is left alone.
+pub fn decide(source: &FileInfo, target_path: &Path) -> UpdateDecision {
+    let target_modified = match fs::metadata(target_path) {
+        Ok(metadata) => metadata.modified().ok(),
+        Err(_) => return UpdateDecision::Copy,
+    };
+    match (source.modified_at, target_modified) {
+        (Some(source_time), Some(target_time)) if source_time > target_time => {
+            UpdateDecision::Update
+        }
+        _ => UpdateDecision::Skip,
+    }
+}
+
+/// Counts reported after an `--update` run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UpdateSummary {
+    pub copied: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+/// Mirror `source_files` (all under `source_dir`) into `target_dir`, keeping their
+/// relative paths. New files are copied; existing target files are overwritten only
+/// when the source's `modified_at` is newer.
+pub fn update_files(
+    source_dir: &Path,
+    source_files: &[FileInfo],
+    target_dir: &Path,
+    dry_run: bool,
+    preserve_timestamps: bool,
+    transfer_options: &TransferOptions,
+) -> Result<(UpdateSummary, Vec<String>)> {
+    let mut summary = UpdateSummary::default();
+    let mut logs = Vec::new();
+
+    for file in source_files {
+        let relative_path = file
+            .path
+            .strip_prefix(source_dir)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| PathBuf::from(file.path.file_name().unwrap_or_default()));
+        let target_path = target_dir.join(relative_path);
+
+        let decision = decide(file, &target_path);
+        if decision == UpdateDecision::Skip {
+            log::debug!("Target {:?} is up to date, skipping", target_path);
+            summary.skipped += 1;
+            continue;
+        }
+
+        let verb = if decision == UpdateDecision::Copy {
+            "copy"
+        } else {
+            "update"
+        };
+        if dry_run {
+            logs.push(format!(
+                "[DRY RUN] Would {} {} -> {}",
+                verb,
+                file.path.display(),
+                target_path.display()
+            ));
+        } else {
+            if let Some(parent) = target_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if let Err(e) = transfer::copy_file(&file.path, &target_path, transfer_options) {
+                let msg = format!(
+                    "Failed to {} {} -> {}: {}",
+                    verb,
+                    file.path.display(),
+                    target_path.display(),
+                    e
+                );
+                log::error!("{}", msg);
+                logs.push(msg);
+                continue;
+            }
+            // Overwrites can't be undone, so only new copies go in the journal
+            if decision == UpdateDecision::Copy {
+                journal::record_action(JournalAction::Copy, &file.path, Some(&target_path));
+            }
+            if preserve_timestamps {
+                if let Err(e) = preserve_file_timestamps(file, &target_path) {
+                    logs.push(format!(
+                        "Warning: could not preserve timestamps on {}: {}",
+                        target_path.display(),
+                        e
+                    ));
+                }
+            }
+            logs.push(format!(
+                "{}: {} -> {}",
+                if decision == UpdateDecision::Copy {
+                    "Copied"
+                } else {
+                    "Updated"
+                },
+                file.path.display(),
+                target_path.display()
+            ));
+        }
+
+        match decision {
+            UpdateDecision::Copy => summary.copied += 1,
+            UpdateDecision::Update => summary.updated += 1,
+            UpdateDecision::Skip => {}
+        }
+    }
+
+    Ok((summary, logs))
+}
@@ -0,0 +1,505 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::actions;
+use crate::file_cache::{open_cache, HashCache};
+use crate::file_utils::{
+    self, calculate_hash_buffered, read_buffer_size, resolved_algorithm, DuplicateSet, FileInfo,
+    FilterRules,
+};
+use crate::Cli;
+
+/// A changed path is handled once no further events have arrived for it for this long,
+/// so a file that is still being written is hashed once rather than on every write
+const WATCH_SETTLE: Duration = Duration::from_secs(1);
+
+/// Files under the watched roots, indexed by size. Hashes are filled in lazily: a file is
+/// only hashed once a new file of the same size shows up.
+pub struct DuplicateWatcher {
+    rules: FilterRules,
+    algorithm: String,
+    read_buffer: usize,
+    include_empty: bool,
+    by_size: HashMap<u64, Vec<FileInfo>>,
+    cache: Option<Box<dyn HashCache>>,
+}
+
+impl DuplicateWatcher {
+    /// Index the files the initial scan found (see `find_duplicate_files_indexed`). Hashes
+    /// come from the `--cache-location` cache when one is configured, and newly computed
+    /// hashes are stored back in it.
+    pub fn new(cli: &Cli, scanned: Vec<FileInfo>) -> Result<Self> {
+        let files = on_watch_roots(cli, scanned);
+        let cache = match cli
+            .cache_location
+            .as_ref()
+            .filter(|_| cli.hash_limit.is_none())
+        {
+            Some(cache_dir) => {
+                match open_cache(
+                    &cli.cache_backend,
+                    cache_dir,
+                    resolved_algorithm(cli),
+                    Duration::from_secs(cli.cache_lock_timeout),
+                ) {
+                    Ok(cache) => Some(cache),
+                    Err(e) => {
+                        log::warn!("[Watch] Failed to initialize file cache: {}", e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        Self::from_files(cli, files, cache)
+    }
+
+    /// A watcher over already scanned files
+    pub fn from_files(
+        cli: &Cli,
+        files: Vec<FileInfo>,
+        cache: Option<Box<dyn HashCache>>,
+    ) -> Result<Self> {
+        let mut by_size: HashMap<u64, Vec<FileInfo>> = HashMap::new();
+        for file in files {
+            by_size.entry(file.size).or_default().push(file);
+        }
+        Ok(Self {
+            rules: FilterRules::new(cli)?,
+            algorithm: resolved_algorithm(cli).to_string(),
+            read_buffer: read_buffer_size(cli.read_buffer),
+            include_empty: cli.include_empty,
+            by_size,
+            cache,
+        })
+    }
+
+    /// Number of files currently indexed
+    pub fn len(&self) -> usize {
+        self.by_size.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Handle a file that was created or modified. Returns the duplicate set it now
+    /// belongs to, with the new file last, or `None` if its content is unique or the
+    /// file is filtered out.
+    pub fn handle_path(&mut self, path: &Path) -> Result<Option<DuplicateSet>> {
+        self.forget(path);
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            // Already gone again, or a directory
+            _ => return Ok(None),
+        };
+        let size = metadata.len();
+        let modified_at = metadata.modified().ok();
+        if (size == 0 && !self.include_empty)
+            || !self.rules.accepts_listed_path(path)
+            || !self.rules.matches_size_and_mtime(size, modified_at)
+        {
+            return Ok(None);
+        }
+
+        let mut file = FileInfo {
+            path: path.to_path_buf(),
+            size,
+            hash: None,
+            modified_at,
+            created_at: metadata.created().ok(),
+        };
+        file.hash = Some(self.hash_of(&file)?);
+
+        let mut same_size = self.by_size.remove(&size).unwrap_or_default();
+        same_size.retain_mut(|other| {
+            if other.hash.is_none() {
+                // The scan only recorded path and size; the selection strategy needs the rest
+                let hashed = fs::metadata(&other.path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|metadata| {
+                        other.modified_at = metadata.modified().ok();
+                        other.created_at = metadata.created().ok();
+                        self.hash_of(other)
+                    });
+                match hashed {
+                    Ok(hash) => other.hash = Some(hash),
+                    Err(e) => {
+                        // Most likely deleted since it was indexed
+                        log::debug!("[Watch] Dropping {:?} from the index: {}", other.path, e);
+                        return false;
+                    }
+                }
+            }
+            true
+        });
+        let matches: Vec<FileInfo> = same_size
+            .iter()
+            .filter(|other| other.hash == file.hash)
+            .cloned()
+            .collect();
+        same_size.push(file.clone());
+        self.by_size.insert(size, same_size);
+
+        if matches.is_empty() {
+            return Ok(None);
+        }
+        let hash = file.hash.clone().unwrap_or_default();
+        let mut files = matches;
+        files.push(file);
+        Ok(Some(DuplicateSet {
+            files,
+            size,
+            hash,
+            approximate: false,
+            hardlinks: Vec::new(),
+        }))
+    }
+
+    /// Drop a removed (or about to be re-read) file from the index
+    pub fn forget(&mut self, path: &Path) {
+        for files in self.by_size.values_mut() {
+            files.retain(|file| file.path != path);
+        }
+        self.by_size.retain(|_, files| !files.is_empty());
+    }
+
+    /// Persist hashes computed while watching
+    pub fn save_cache(&mut self) {
+        if let Some(cache) = &mut self.cache {
+            if let Err(e) = cache.save() {
+                log::warn!("[Watch] Failed to save file cache: {}", e);
+            }
+        }
+    }
+
+    fn hash_of(&mut self, file: &FileInfo) -> Result<String> {
+        if let Some(cached) = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get_file_info(&file.path))
+            .and_then(|cached| cached.hash)
+        {
+            return Ok(cached);
+        }
+        let hash = calculate_hash_buffered(&file.path, &self.algorithm, None, self.read_buffer)?;
+        if let Some(cache) = &mut self.cache {
+            let mut hashed = file.clone();
+            hashed.hash = Some(hash.clone());
+            if let Err(e) = cache.store(&hashed, &self.algorithm) {
+                log::warn!("[Watch] Failed to cache hash of {:?}: {}", file.path, e);
+            }
+        }
+        Ok(hash)
+    }
+}
+
+/// Report a duplicate set found while watching and apply the configured action to it
+/// through `actions::apply_actions`, with the same verification and safety checks as a
+/// scan. Returns the files that are no longer regular files at their path (deleted, moved
+/// or replaced by a link) and the report lines to show.
+pub fn act_on_duplicate(cli: &Cli, set: &DuplicateSet) -> Result<(Vec<FileInfo>, Vec<String>)> {
+    let new_file = set.files.last().map(|file| file.path.display().to_string());
+    let mut logs = vec![format!(
+        "New duplicate: {} ({} copies)",
+        new_file.unwrap_or_default(),
+        set.files.len()
+    )];
+    if !actions::has_action(cli) {
+        logs.extend(
+            set.files[..set.files.len() - 1]
+                .iter()
+                .map(|file| format!("    same content as {}", file.path.display())),
+        );
+        return Ok((Vec::new(), logs));
+    }
+
+    // Watching is unattended, so there is no --prompt
+    actions::apply_actions(cli, std::slice::from_ref(set), false)?;
+    let actioned = set
+        .files
+        .iter()
+        .filter(|file| {
+            !fs::symlink_metadata(&file.path)
+                .map(|metadata| metadata.is_file())
+                .unwrap_or(false)
+        })
+        .cloned()
+        .collect();
+    Ok((actioned, logs))
+}
+
+/// Scan roots as absolute paths, so they compare equal to the paths in watch events
+fn watch_roots(cli: &Cli) -> Vec<PathBuf> {
+    cli.directories
+        .iter()
+        .map(|dir| fs::canonicalize(dir).unwrap_or_else(|_| dir.clone()))
+        .collect()
+}
+
+/// Move scanned paths from the scan roots as given onto `watch_roots`
+fn on_watch_roots(cli: &Cli, mut files: Vec<FileInfo>) -> Vec<FileInfo> {
+    let roots: Vec<(&PathBuf, PathBuf)> = cli.directories.iter().zip(watch_roots(cli)).collect();
+    for file in &mut files {
+        let root = roots
+            .iter()
+            .filter(|(dir, _)| file.path.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count());
+        if let Some((dir, watched)) = root {
+            if let Ok(rel) = file.path.strip_prefix(dir) {
+                file.path = watched.join(rel);
+            }
+        }
+    }
+    files
+}
+
+/// `--watch`: after the initial scan, keep watching the scan roots and handle each
+/// duplicate as it appears, until Ctrl-C. `scanned` is the initial scan's file index.
+pub fn run_watch(cli: &Cli, scanned: Vec<FileInfo>) -> Result<()> {
+    let mut watcher_state = DuplicateWatcher::new(cli, scanned)?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Failed to start watching")?;
+    let roots = watch_roots(cli);
+    for root in &roots {
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {:?}", root))?;
+    }
+    println!(
+        "Watching {} for new duplicates ({} files indexed). Press Ctrl-C to stop.",
+        roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        watcher_state.len()
+    );
+
+    let interrupted = file_utils::interrupt_flag();
+    // Changed paths and when they were last touched
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    while !interrupted.load(Ordering::Relaxed) {
+        // Wake up when the next pending path settles
+        let wait = pending
+            .values()
+            .map(|touched| WATCH_SETTLE.saturating_sub(touched.elapsed()))
+            .min()
+            .unwrap_or(WATCH_SETTLE);
+        match rx.recv_timeout(wait) {
+            Ok(Ok(event)) => match event.kind {
+                EventKind::Create(_) | EventKind::Modify(_) => {
+                    let now = Instant::now();
+                    pending.extend(event.paths.into_iter().map(|path| (path, now)));
+                }
+                EventKind::Remove(_) => {
+                    for path in &event.paths {
+                        pending.remove(path);
+                        watcher_state.forget(path);
+                    }
+                }
+                _ => {}
+            },
+            Ok(Err(e)) => log::warn!("[Watch] Watch error: {}", e),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        // A busy folder may never go quiet, so each path is handled once it has settled
+        let settled = take_settled(&mut pending, Instant::now());
+        for path in &settled {
+            handle_changed_path(cli, &mut watcher_state, path);
+        }
+        if !settled.is_empty() {
+            watcher_state.save_cache();
+        }
+    }
+    watcher_state.save_cache();
+    println!("Stopped watching.");
+    Ok(())
+}
+
+/// Remove and return the pending paths that haven't been touched for `WATCH_SETTLE`
+fn take_settled(pending: &mut HashMap<PathBuf, Instant>, now: Instant) -> Vec<PathBuf> {
+    let settled: Vec<PathBuf> = pending
+        .iter()
+        .filter(|(_, touched)| now.duration_since(**touched) >= WATCH_SETTLE)
+        .map(|(path, _)| path.clone())
+        .collect();
+    for path in &settled {
+        pending.remove(path);
+    }
+    settled
+}
+
+fn handle_changed_path(cli: &Cli, watcher_state: &mut DuplicateWatcher, path: &Path) {
+    let set = match watcher_state.handle_path(path) {
+        Ok(Some(set)) => set,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("[Watch] Failed to check {:?}: {}", path, e);
+            return;
+        }
+    };
+    match act_on_duplicate(cli, &set) {
+        Ok((actioned, logs)) => {
+            for log_msg in logs {
+                log::info!("{}", log_msg);
+                println!("{}", log_msg);
+            }
+            for file in &actioned {
+                watcher_state.forget(&file.path);
+            }
+        }
+        Err(e) => {
+            log::error!("[Watch] Failed to handle duplicate {:?}: {}", path, e);
+            eprintln!("Error: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_created_duplicate_is_detected_and_handled() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        let cache_dir = dir.path().join("cache");
+        fs::create_dir_all(&root)?;
+        fs::write(root.join("original.zip"), "downloaded archive")?;
+        fs::write(root.join("other.zip"), "another download!!")?;
+
+        let cli = Cli::parse_from([
+            "dedups",
+            "--cache-location",
+            cache_dir.to_str().unwrap(),
+            root.to_str().unwrap(),
+        ]);
+        // The index comes from the initial scan
+        let mut scanned = Vec::new();
+        let (tx, _rx) = mpsc::channel();
+        let cancel = std::sync::atomic::AtomicBool::new(false);
+        file_utils::find_duplicate_files_indexed(&cli, tx, &cancel, &mut scanned)?;
+        let mut watcher = DuplicateWatcher::new(&cli, scanned)?;
+        assert_eq!(watcher.len(), 2);
+
+        // Simulate the event for a fresh download of the same file
+        let root = fs::canonicalize(&root)?;
+        let copy = root.join("original (1).zip");
+        fs::write(&copy, "downloaded archive")?;
+        let set = watcher.handle_path(&copy)?.expect("duplicate detected");
+        assert_eq!(set.files.len(), 2);
+        assert_eq!(set.files[0].path, root.join("original.zip"));
+        assert!(set.files[0].modified_at.is_some());
+        assert_eq!(set.files[1].path, copy);
+
+        // A new unique file and a repeated event for the same file aren't duplicates of themselves
+        fs::write(root.join("fresh.zip"), "something new here")?;
+        assert!(watcher.handle_path(&root.join("fresh.zip"))?.is_none());
+        assert!(watcher.handle_path(&root.join("fresh.zip"))?.is_none());
+        assert_eq!(watcher.len(), 4);
+
+        // Hashes computed while watching go to the cache
+        watcher.save_cache();
+        drop(watcher);
+        let cache = open_cache(
+            "file",
+            &cache_dir,
+            "xxhash",
+            crate::file_cache::DEFAULT_LOCK_TIMEOUT,
+        )?;
+        assert!(cache.get_file_info(&copy).is_some());
+
+        // With --delete, the copy with the longer path goes
+        let delete_cli = Cli::parse_from([
+            "dedups",
+            "--delete",
+            "--mode",
+            "shortest_path",
+            root.to_str().unwrap(),
+        ]);
+        let (actioned, _) = act_on_duplicate(&delete_cli, &set)?;
+        assert_eq!(actioned.len(), 1);
+        assert_eq!(actioned[0].path, copy);
+        assert!(!copy.exists());
+        assert!(root.join("original.zip").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_busy_paths_settle_independently() {
+        let start = Instant::now();
+        let mut pending = HashMap::new();
+        pending.insert(PathBuf::from("/w/done.zip"), start);
+        pending.insert(
+            PathBuf::from("/w/still_writing.zip"),
+            start + WATCH_SETTLE / 2,
+        );
+
+        // Events for the second file keep coming, but the first is handled on time
+        let settled = take_settled(&mut pending, start + WATCH_SETTLE);
+        assert_eq!(settled, [PathBuf::from("/w/done.zip")]);
+        assert_eq!(pending.len(), 1);
+        assert!(take_settled(&mut pending, start + WATCH_SETTLE).is_empty());
+        assert_eq!(
+            take_settled(&mut pending, start + WATCH_SETTLE * 2).len(),
+            1
+        );
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_watch_actions_verify_and_respect_approximate_sets() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = fs::canonicalize(dir.path())?;
+        fs::write(root.join("a.txt"), "aaaa")?;
+        fs::write(root.join("b.txt"), "bbbb")?;
+        let file = |name: &str| FileInfo {
+            path: root.join(name),
+            size: 4,
+            hash: Some("same".to_string()),
+            modified_at: None,
+            created_at: None,
+        };
+        // A hash collision: same (weak) hash, different bytes
+        let set = DuplicateSet {
+            files: vec![file("a.txt"), file("b.txt")],
+            size: 4,
+            hash: "same".to_string(),
+            approximate: false,
+            hardlinks: Vec::new(),
+        };
+
+        let weak_cli = Cli::parse_from([
+            "dedups",
+            "--delete",
+            "--algorithm",
+            "fnv1a",
+            root.to_str().unwrap(),
+        ]);
+        let (actioned, _) = act_on_duplicate(&weak_cli, &set)?;
+        assert!(actioned.is_empty());
+        assert!(root.join("a.txt").exists() && root.join("b.txt").exists());
+
+        // Only a prefix was hashed, so deleting needs --allow-approximate-delete
+        fs::write(root.join("b.txt"), "aaaa")?;
+        let approximate = DuplicateSet {
+            approximate: true,
+            ..set
+        };
+        let cli = Cli::parse_from(["dedups", "--delete", root.to_str().unwrap()]);
+        let (actioned, _) = act_on_duplicate(&cli, &approximate)?;
+        assert!(actioned.is_empty());
+        assert!(root.join("a.txt").exists() && root.join("b.txt").exists());
+        Ok(())
+    }
+}
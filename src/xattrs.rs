@@ -0,0 +1,97 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::Path;
+
+/// Extended attribute names mapped to their values
+pub type Xattrs = BTreeMap<OsString, Vec<u8>>;
+
+/// Whether this build can read extended attributes. Elsewhere every file reads as having
+/// none, so `--compare-xattrs` never splits a set.
+#[cfg(unix)]
+pub const XATTRS_SUPPORTED: bool = xattr::SUPPORTED_PLATFORM;
+#[cfg(not(unix))]
+pub const XATTRS_SUPPORTED: bool = false;
+
+/// Whether two files carry the same extended attributes (names and values)
+pub fn xattrs_equal(a: &Path, b: &Path) -> Result<bool> {
+    Ok(read_xattrs(a)? == read_xattrs(b)?)
+}
+
+/// All extended attributes of a file, following symlinks. A filesystem without xattr
+/// support reads as none.
+#[cfg(unix)]
+pub fn read_xattrs(path: &Path) -> Result<Xattrs> {
+    let names = match xattr::list_deref(path) {
+        Ok(names) => names,
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => return Ok(Xattrs::new()),
+        Err(e) => {
+            return Err(anyhow::anyhow!(
+                "Failed to list xattrs of {:?}: {}",
+                path,
+                e
+            ))
+        }
+    };
+
+    let mut xattrs = Xattrs::new();
+    for name in names {
+        let value = xattr::get_deref(path, &name)
+            .map_err(|e| anyhow::anyhow!("Failed to read xattr {:?} of {:?}: {}", name, path, e))?;
+        // An attribute removed since it was listed is simply gone
+        if let Some(value) = value {
+            xattrs.insert(name, value);
+        }
+    }
+    Ok(xattrs)
+}
+
+#[cfg(not(unix))]
+pub fn read_xattrs(_path: &Path) -> Result<Xattrs> {
+    Ok(Xattrs::new())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::file_utils::find_duplicate_files_with_progress;
+    use crate::Cli;
+    use std::fs;
+
+    #[test]
+    fn test_differing_xattr_splits_set_under_flag() -> Result<()> {
+        use clap::Parser;
+
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        fs::create_dir_all(&root)?;
+        let tagged = root.join("tagged.txt");
+        let plain = root.join("plain.txt");
+        fs::write(&tagged, "same contents")?;
+        fs::write(&plain, "same contents")?;
+        if let Err(e) = xattr::set(&tagged, "user.dedups.test", b"label") {
+            eprintln!("Skipping: filesystem has no user xattrs ({})", e);
+            return Ok(());
+        }
+        assert!(!xattrs_equal(&tagged, &plain)?);
+        assert_eq!(
+            read_xattrs(&tagged)?.get(&OsString::from("user.dedups.test")),
+            Some(&b"label".to_vec())
+        );
+
+        let scan = |args: &[&str]| -> Result<usize> {
+            let mut argv = vec!["dedups"];
+            argv.extend_from_slice(args);
+            argv.push(root.to_str().unwrap());
+            let (tx, _rx) = std::sync::mpsc::channel();
+            Ok(find_duplicate_files_with_progress(&Cli::parse_from(argv), tx)?.len())
+        };
+        assert_eq!(scan(&[])?, 1);
+        assert_eq!(scan(&["--compare-xattrs"])?, 0);
+
+        xattr::set(&plain, "user.dedups.test", b"label")?;
+        assert!(xattrs_equal(&tagged, &plain)?);
+        assert_eq!(scan(&["--compare-xattrs"])?, 1);
+        Ok(())
+    }
+}
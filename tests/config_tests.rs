@@ -21,6 +21,7 @@ fn test_config_defaults() {
 }
 
 #[test]
+#[allow(clippy::field_reassign_with_default)]
 fn test_save_and_load_config() -> anyhow::Result<()> {
     // Create a temporary directory for testing
     let temp_dir = tempdir()?;
@@ -151,6 +152,7 @@ fn test_create_default_if_not_exists() -> anyhow::Result<()> {
 }
 
 #[test]
+#[allow(clippy::field_reassign_with_default)]
 fn test_custom_config_file() -> anyhow::Result<()> {
     use clap::Parser;
 
@@ -196,3 +198,98 @@ fn test_custom_config_file() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_config_fills_only_arguments_left_at_their_defaults() -> anyhow::Result<()> {
+    let temp_dir = tempdir()?;
+    let config_path = temp_dir.path().join("deduprc");
+    DedupConfig {
+        algorithm: "sha1".to_string(),
+        mode: "oldest_modified".to_string(),
+        format: "csv".to_string(),
+        ..Default::default()
+    }
+    .save_to_path(&config_path)?;
+    let config_arg = config_path.to_str().unwrap();
+
+    let cli = Cli::with_config_from(["dedups", "--config-file", config_arg, "."])?;
+    assert_eq!(cli.algorithm, "sha1");
+    assert_eq!(cli.mode, "oldest_modified");
+    assert_eq!(cli.format, "csv");
+
+    // Passing the clap default explicitly still beats the config
+    let cli = Cli::with_config_from([
+        "dedups",
+        "--config-file",
+        config_arg,
+        "--algorithm",
+        "xxhash",
+        "--mode",
+        "newest_modified",
+        ".",
+    ])?;
+    assert_eq!(cli.algorithm, "xxhash");
+    assert_eq!(cli.mode, "newest_modified");
+    assert_eq!(cli.format, "csv");
+    Ok(())
+}
+
+#[test]
+fn test_config_includes_merge_in_order() -> anyhow::Result<()> {
+    let temp_dir = tempdir()?;
+    let shared_dir = temp_dir.path().join("shared");
+    fs::create_dir(&shared_dir)?;
+
+    // The base sets two fields; the user file overrides one and keeps the other
+    fs::write(
+        shared_dir.join("base.toml"),
+        "algorithm = \"sha1\"\nparallel = 3\n[theme]\nname = \"light\"\nfolder = \"blue\"\n",
+    )?;
+    let user_config = temp_dir.path().join("user.toml");
+    fs::write(
+        &user_config,
+        "config_includes = [\"shared/base.toml\"]\nalgorithm = \"blake3\"\n[theme]\nfolder = \"red\"\n",
+    )?;
+
+    let config = DedupConfig::load_with_includes(&user_config)?;
+    assert_eq!(config.algorithm, "blake3");
+    assert_eq!(config.parallel, Some(3));
+    assert_eq!(config.theme.name.as_deref(), Some("light"));
+    assert_eq!(config.theme.folder.as_deref(), Some("red"));
+    assert!(config.config_includes.is_empty());
+
+    // Without --follow-config-includes only the file itself is read
+    let config = DedupConfig::load_from_path(&user_config)?;
+    assert_eq!(config.parallel, None);
+    assert_eq!(config.config_includes.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_config_include_cycle_is_rejected() -> anyhow::Result<()> {
+    let temp_dir = tempdir()?;
+    fs::write(
+        temp_dir.path().join("a.toml"),
+        "config_includes = [\"b.toml\"]\n",
+    )?;
+    fs::write(
+        temp_dir.path().join("b.toml"),
+        "config_includes = [\"a.toml\"]\n",
+    )?;
+
+    let err = DedupConfig::load_with_includes(&temp_dir.path().join("a.toml")).unwrap_err();
+    assert!(
+        err.to_string().contains("cycle"),
+        "unexpected error: {:#}",
+        err
+    );
+
+    // A missing include is an error rather than silently ignored
+    fs::write(
+        temp_dir.path().join("c.toml"),
+        "config_includes = [\"missing.toml\"]\n",
+    )?;
+    assert!(DedupConfig::load_with_includes(&temp_dir.path().join("c.toml")).is_err());
+    Ok(())
+}
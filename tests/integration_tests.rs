@@ -12,8 +12,11 @@ use std::time::{Duration, SystemTime};
 // Assuming your crate's main library functions are accessible via `dedups::`
 use dedups::file_utils::{self, FileInfo, SelectionStrategy, SortCriterion, SortOrder};
 use dedups::media_dedup::MediaDedupOptions;
+use dedups::transfer::{ConflictPolicy, TransferOptions};
+use dedups::update_mode::{self, UpdateSummary};
 use dedups::Cli; // Assuming Cli is public or pub(crate) and accessible // Import MediaDedupOptions directly
                  // use dedups::tui_app::AppState; // Remove unused import
+use indicatif::ProgressBar;
 
 // --- Test Constants ---
 // const TEST_BASE_DIR_NAME: &str = "dedup_integration_tests"; // Remove unused constant
@@ -80,6 +83,7 @@ impl TestEnv {
         }
     }
 
+    #[allow(dead_code)]
     pub fn create_file_with_size_and_time(
         &mut self,
         path: &Path,
@@ -192,37 +196,116 @@ impl TestEnv {
 
     fn default_cli_args(&self) -> Cli {
         Cli {
+            command: None,
             directories: vec![self.root_path.clone()],
             target: None,
             deduplicate: false,
             delete: false,
+            reflink: false,
+            hardlink: false,
+            symlink_kept: false,
+            relative_symlinks: false,
+            trash: false,
+            journal: false,
             move_to: None,
+            preserve_structure: false,
+            on_conflict: ConflictPolicy::Rename,
+            fix_symlinks: false,
+            quarantine: None,
             log: false, // Avoid log file creation during tests unless specific test needs it
             log_file: None, // Add the missing log_file field
+            log_format: "text".to_string(),
             output: None,
             format: "json".to_string(),
+            write_manifest: None,
+            manifest: None,
             algorithm: "blake3".to_string(), // Fast algorithm for tests
             parallel: Some(1),               // Controlled parallelism for predictable testing
+            threads_io: 4,
+            progress_interval: None,
+            progress_every: None,
             mode: "newest_modified".to_string(),
+            keep_dir: None,
+            tiebreak: Vec::new(),
+            load_session: None,
             interactive: false,
+            theme: None,
             verbose: 0,
             include: Vec::new(),
             exclude: Vec::new(),
+            include_regex: Vec::new(),
+            exclude_regex: Vec::new(),
+            exclude_dir: Vec::new(),
+            files_from: None,
+            null_input: false,
+            print0: false,
+            summary_only: false,
+            verify: false,
+            same_name_only: false,
+            compare_xattrs: false,
+            ignore_metadata: false,
+            min_set_size: 2,
+            exclude_same_dir: false,
+            deterministic: false,
+            retries: 2,
+            case_insensitive_names: None,
+            protect: Vec::new(),
+            prompt: false,
+            include_hidden: false,
+            no_default_excludes: false,
+            follow_symlinks: false,
+            max_depth: None,
+            include_empty: false,
+            union: false,
+            html_report: None,
+            top: None,
+            order_by: None,
+            json_events: None,
+            json_pretty: false,
             filter_from: None,
+            min_size: None,
+            max_size: None,
+            since: None,
+            limit: None,
             progress: false, // TUI progress not relevant for these tests
             progress_tui: false,
             sort_by: SortCriterion::ModifiedAt, // Default, can be changed per test
             sort_order: SortOrder::Descending,  // Default
             raw_sizes: false,
             cache_location: None,
+            cache_backend: "file".to_string(),
+            cache_lock_timeout: 10,
             config_file: None,
+            follow_config_includes: false,
             dry_run: false,
             fast_mode: false,
+            incremental: false,
+            refresh_cache: false,
+            no_prefilter: false,
+            hash_limit: None,
+            max_memory: None,
+            read_buffer: 64 * 1024,
+            allow_approximate_delete: false,
+            show_hardlinks: false,
+            detect_partial: false,
+            block_dedup: false,
+            block_min_size: None,
+            block_overlap: dedups::block_dedup::DEFAULT_BLOCK_OVERLAP,
+            watch: false,
+            no_preserve_timestamps: false,
+            verify_after: false,
+            bwlimit: None,
+            resume: false,
+            update: false,
             media_mode: false,
             media_resolution: "highest".to_string(),
             media_formats: Vec::new(),
             media_similarity: 90,
+            media_keep: "format".to_string(),
             media_dedup_options: MediaDedupOptions::default(),
+            theme_config: Default::default(),
+            default_excludes: None,
+            hash_overrides: Default::default(),
         }
     }
 }
@@ -287,6 +370,7 @@ mod integration {
         Ok(())
     }
 
+    #[allow(dead_code)]
     fn setup_basic_duplicates(env: &mut TestEnv) {
         let now = SystemTime::now();
         let subdir1 = env.create_subdir("sub1");
@@ -423,10 +507,11 @@ mod integration {
             return Ok(());
         }
 
-        let (delete_count, _delete_logs) = file_utils::delete_files(&files_to_delete_info, false)?; // false for dry_run -> actual delete
+        let (deleted, _delete_logs) =
+            file_utils::delete_files(&files_to_delete_info, false, false, 0)?; // false for dry_run -> actual delete
 
         assert_eq!(
-            delete_count,
+            deleted.len(),
             files_to_be_deleted_paths.len(),
             "Mismatch in number of deleted files."
         );
@@ -504,10 +589,15 @@ mod integration {
             return Ok(());
         }
 
-        let (move_count, _logs) =
-            file_utils::move_files(&files_to_move_info, &target_move_dir, false)?;
+        let (moved, _logs) = file_utils::move_files(
+            &files_to_move_info,
+            &target_move_dir,
+            false,
+            ConflictPolicy::Rename,
+            0,
+        )?;
         assert_eq!(
-            move_count,
+            moved.len(),
             files_to_be_moved_original_paths.len(),
             "Mismatch in number of moved files."
         );
@@ -725,7 +815,13 @@ mod integration {
         assert_eq!(missing_files.len(), 4, "There should be 4 files missing in target (unique1, unique2, and both duplicate files)");
 
         // Copy the missing files
-        file_utils::copy_missing_files(&missing_files, &target_dir, false)?;
+        file_utils::copy_missing_files(
+            &missing_files,
+            &target_dir,
+            false,
+            true,
+            &TransferOptions::default(),
+        )?;
 
         // Verify the results
         let final_target_files = fs::read_dir(&target_dir)?.count();
@@ -760,6 +856,7 @@ mod integration {
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_deduplicate_between_directories_integration() -> Result<()> {
         // Create a test environment with two separate directories
         let mut env = TestEnv::new();
@@ -897,7 +994,13 @@ mod integration {
         }
 
         // Copy the missing files
-        file_utils::copy_missing_files(&missing_files, &target_dir, false)?;
+        file_utils::copy_missing_files(
+            &missing_files,
+            &target_dir,
+            false,
+            true,
+            &TransferOptions::default(),
+        )?;
 
         // Verify unique_source.txt was copied (might be in a subdirectory)
         let unique_file_exists = fs::read_dir(&target_dir)?.filter_map(|e| e.ok()).any(|e| {
@@ -1026,8 +1129,8 @@ mod integration {
         }
 
         let delete_count = if !files_to_delete.is_empty() {
-            let (count, _) = file_utils::delete_files(&files_to_delete, false)?;
-            count
+            let (deleted, _) = file_utils::delete_files(&files_to_delete, false, false, 0)?;
+            deleted.len()
         } else {
             0
         };
@@ -1061,7 +1164,13 @@ mod integration {
         }
 
         // Copy missing files
-        file_utils::copy_missing_files(&missing_files, &target_dir, false)?;
+        file_utils::copy_missing_files(
+            &missing_files,
+            &target_dir,
+            false,
+            true,
+            &TransferOptions::default(),
+        )?;
 
         // Verify final target state
         let final_target_files = fs::read_dir(&target_dir)?.count();
@@ -1080,4 +1189,1099 @@ mod integration {
 
         Ok(())
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlinks_are_not_offered_for_deletion() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("hardlinks");
+        let original = dir.join("original.txt");
+        let linked = dir.join("linked.txt");
+        let copy = dir.join("copy.txt");
+        env.create_file_with_content_and_time(&original, "hardlinked content", None);
+        fs::hard_link(&original, &linked)?;
+        env.create_file_with_content_and_time(&copy, "hardlinked content", None);
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = vec![dir.clone()];
+
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets.len(), 1, "Expected one duplicate set: {:?}", sets);
+        let set = &sets[0];
+        assert_eq!(
+            set.files.len(),
+            2,
+            "Hardlinks should collapse into one entry"
+        );
+        assert!(set.files.iter().any(|f| f.path == copy));
+        assert!(set.hardlinks.is_empty());
+
+        // Whatever is kept, the hardlinked pair is never both up for deletion
+        let (_, to_delete) =
+            file_utils::determine_action_targets(set, SelectionStrategy::ShortestPath)?;
+        assert_eq!(to_delete.len(), 1);
+
+        // With --show-hardlinks the extra link is reported, but still not actionable
+        cli_args.show_hardlinks = true;
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets[0].files.len(), 2);
+        assert_eq!(sets[0].hardlinks.len(), 1);
+
+        // A set consisting only of hardlinks disappears entirely
+        fs::remove_file(&copy)?;
+        cli_args.show_hardlinks = false;
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert!(
+            sets.is_empty(),
+            "Hardlink-only set should be dropped: {:?}",
+            sets
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reflink_files_integration() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("reflink");
+        let kept_path = dir.join("kept.bin");
+        let dup_path = dir.join("dup.bin");
+        env.create_file_with_content_and_time(&kept_path, "reflink content", None);
+        env.create_file_with_content_and_time(&dup_path, "reflink content", None);
+
+        let file_info = |path: &Path| FileInfo {
+            path: path.to_path_buf(),
+            size: 15,
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let kept = file_info(&kept_path);
+        let dup = file_info(&dup_path);
+
+        // Dry run never touches the filesystem, whatever it supports
+        let (actioned, logs) = file_utils::reflink_files(&kept, std::slice::from_ref(&dup), true)?;
+        assert_eq!(actioned.len(), 1);
+        assert!(logs
+            .iter()
+            .any(|l| l.starts_with("[DRY RUN] Would reflink")));
+
+        let (actioned, logs) = file_utils::reflink_files(&kept, std::slice::from_ref(&dup), false)?;
+        if actioned.is_empty() {
+            println!(
+                "Skipping reflink assertions: temp dir does not support reflinks ({:?})",
+                logs
+            );
+            assert_eq!(fs::read_to_string(&dup_path)?, "reflink content");
+            return Ok(());
+        }
+
+        assert!(logs.iter().any(|l| l.starts_with("Reflinked:")));
+        assert!(kept_path.exists() && dup_path.exists());
+        assert_eq!(fs::read_to_string(&dup_path)?, "reflink content");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_files_integration() -> Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("hardlink");
+        let content = "hardlink me";
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            env.create_file_with_content_and_time(&dir.join(name), content, None);
+        }
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = vec![dir.clone()];
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets.len(), 1);
+
+        let (kept, redundants) =
+            file_utils::determine_action_targets(&sets[0], SelectionStrategy::ShortestPath)?;
+        let (actioned, logs) = file_utils::hardlink_files(&kept, &redundants, false)?;
+        assert_eq!(actioned.len(), 2, "Logs: {:?}", logs);
+
+        let kept_meta = fs::metadata(&kept.path)?;
+        assert_eq!(kept_meta.nlink(), 3);
+        for file_info in &redundants {
+            let meta = fs::metadata(&file_info.path)?;
+            assert_eq!(meta.ino(), kept_meta.ino());
+            assert_eq!(meta.nlink(), kept_meta.nlink());
+            assert_eq!(fs::read_to_string(&file_info.path)?, content);
+        }
+
+        // A rescan now sees a single file's worth of storage and reports nothing
+        let (tx, _rx) = std::sync::mpsc::channel();
+        assert!(file_utils::find_duplicate_files_with_progress(&cli_args, tx)?.is_empty());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_to_kept_replaces_duplicates_with_links() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("symlink");
+        let nested = env.create_subdir("symlink/nested");
+        let content = "symlink me";
+        env.create_file_with_content_and_time(&dir.join("a.txt"), content, None);
+        env.create_file_with_content_and_time(&dir.join("b.txt"), content, None);
+        env.create_file_with_content_and_time(&nested.join("c.txt"), content, None);
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = vec![dir.clone()];
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets.len(), 1);
+        let (kept, redundants) =
+            file_utils::determine_action_targets(&sets[0], SelectionStrategy::ShortestPath)?;
+        assert_eq!(redundants.len(), 2);
+
+        let (actioned, _) = file_utils::symlink_to_kept(&kept, &redundants, true, true)?;
+        assert_eq!(actioned.len(), 2);
+        assert!(redundants
+            .iter()
+            .all(|f| !f.path.symlink_metadata().unwrap().is_symlink()));
+
+        let (actioned, logs) = file_utils::symlink_to_kept(&kept, &redundants, true, false)?;
+        assert_eq!(actioned.len(), 2, "Logs: {:?}", logs);
+        let kept_canonical = fs::canonicalize(&kept.path)?;
+        for file_info in &redundants {
+            assert!(fs::symlink_metadata(&file_info.path)?.is_symlink());
+            let target = fs::read_link(&file_info.path)?;
+            assert!(target.is_relative(), "{:?} is not relative", target);
+            assert_eq!(fs::canonicalize(&file_info.path)?, kept_canonical);
+            assert_eq!(fs::read_to_string(&file_info.path)?, content);
+        }
+        assert!(!fs::symlink_metadata(&kept.path)?.is_symlink());
+
+        // Symlinks aren't scanned, so nothing is left to deduplicate
+        let (tx, _rx) = std::sync::mpsc::channel();
+        assert!(file_utils::find_duplicate_files_with_progress(&cli_args, tx)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_missing_files_preserves_mtime() -> Result<()> {
+        let mut env = TestEnv::new();
+        let source_dir = env.create_subdir("ts_source/album");
+        let target_dir = env.create_subdir("ts_target");
+        let source = source_dir.join("photo.jpg");
+        let old_time = SystemTime::now() - Duration::from_secs(400 * 24 * 60 * 60);
+        env.create_file_with_content_and_time(&source, "photo_content", Some(old_time));
+
+        let metadata = fs::metadata(&source)?;
+        let file_info = FileInfo {
+            path: source.clone(),
+            size: metadata.len(),
+            hash: None,
+            modified_at: metadata.modified().ok(),
+            created_at: metadata.created().ok(),
+        };
+
+        file_utils::copy_missing_files(
+            std::slice::from_ref(&file_info),
+            &target_dir,
+            false,
+            true,
+            &TransferOptions::default(),
+        )?;
+        let copied = target_dir.join("album").join("photo.jpg");
+        let copied_mtime = fs::metadata(&copied)?.modified()?;
+        let diff = match copied_mtime.duration_since(old_time) {
+            Ok(d) => d,
+            Err(e) => e.duration(),
+        };
+        assert!(
+            diff <= Duration::from_secs(1),
+            "Copied mtime should match the source (diff {:?})",
+            diff
+        );
+
+        // Opting out leaves the copy with a fresh timestamp
+        let fresh_dir = env.create_subdir("ts_target_fresh");
+        file_utils::copy_missing_files(
+            &[file_info],
+            &fresh_dir,
+            false,
+            false,
+            &TransferOptions::default(),
+        )?;
+        let fresh_mtime = fs::metadata(fresh_dir.join("album").join("photo.jpg"))?.modified()?;
+        assert!(fresh_mtime.duration_since(old_time)? > Duration::from_secs(60));
+        Ok(())
+    }
+
+    #[test]
+    fn test_on_conflict_policies_for_move_and_copy() -> Result<()> {
+        let mut env = TestEnv::new();
+        let source_dir = env.create_subdir("conflict_source/album");
+        let target_dir = env.create_subdir("conflict_target");
+        let file_info = |path: &Path| FileInfo {
+            path: path.to_path_buf(),
+            size: fs::metadata(path).unwrap().len(),
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+
+        for (policy, expected_content, expected_extra) in [
+            (ConflictPolicy::Skip, "existing", false),
+            (ConflictPolicy::Overwrite, "incoming", false),
+            (ConflictPolicy::Rename, "existing", true),
+        ] {
+            // Move: the target already has a photo.jpg
+            let move_dir = target_dir.join(format!("moved_{}", policy));
+            fs::create_dir_all(&move_dir)?;
+            env.create_file_with_content_and_time(&move_dir.join("photo.jpg"), "existing", None);
+            let source = source_dir.join("photo.jpg");
+            env.create_file_with_content_and_time(&source, "incoming", None);
+
+            let (moved, _) =
+                file_utils::move_files(&[file_info(&source)], &move_dir, false, policy, 0)?;
+            assert_eq!(moved.len(), usize::from(policy != ConflictPolicy::Skip));
+            assert_eq!(source.exists(), policy == ConflictPolicy::Skip);
+            assert_eq!(
+                fs::read_to_string(move_dir.join("photo.jpg"))?,
+                expected_content,
+                "move with {}",
+                policy
+            );
+            assert_eq!(move_dir.join("photo_copy(1).jpg").exists(), expected_extra);
+
+            // Copy: the target already has album/photo.jpg
+            let copy_dir = target_dir.join(format!("copied_{}", policy));
+            fs::create_dir_all(copy_dir.join("album"))?;
+            env.create_file_with_content_and_time(
+                &copy_dir.join("album/photo.jpg"),
+                "existing",
+                None,
+            );
+            env.create_file_with_content_and_time(&source, "incoming", None);
+            let transfer = TransferOptions {
+                on_conflict: policy,
+                ..TransferOptions::default()
+            };
+            let (copied, logs) = file_utils::copy_missing_files(
+                &[file_info(&source)],
+                &copy_dir,
+                false,
+                true,
+                &transfer,
+            )?;
+            assert_eq!(copied, usize::from(policy != ConflictPolicy::Skip));
+            assert_eq!(
+                fs::read_to_string(copy_dir.join("album/photo.jpg"))?,
+                expected_content,
+                "copy with {}",
+                policy
+            );
+            if expected_extra {
+                assert_eq!(
+                    fs::read_to_string(copy_dir.join("album/photo_copy(1).jpg"))?,
+                    "incoming"
+                );
+            }
+            if policy == ConflictPolicy::Skip {
+                assert!(logs.iter().any(|l| l.starts_with("Skipped:")), "{:?}", logs);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_only_bypasses_conflict_policy_for_partial_copies() -> Result<()> {
+        let mut env = TestEnv::new();
+        let source_dir = env.create_subdir("resume_source");
+        let target_dir = env.create_subdir("resume_target");
+        // Copies keep their parent folder name below the target
+        let dest_dir = env.create_subdir("resume_target/resume_source");
+        let unrelated = source_dir.join("notes.txt");
+        let partial = source_dir.join("video.bin");
+        env.create_file_with_content_and_time(&unrelated, "incoming notes", None);
+        env.create_file_with_content_and_time(&partial, "0123456789abcdef", None);
+        // An unrelated file that happens to share the name, and a truncated copy
+        env.create_file_with_content_and_time(&dest_dir.join("notes.txt"), "keep me", None);
+        env.create_file_with_content_and_time(&dest_dir.join("video.bin"), "01234567", None);
+
+        let file_info = |path: &Path| FileInfo {
+            path: path.to_path_buf(),
+            size: fs::metadata(path).unwrap().len(),
+            hash: None,
+            modified_at: None,
+            created_at: None,
+        };
+        let transfer = TransferOptions {
+            resume_transfers: true,
+            on_conflict: ConflictPolicy::Skip,
+            ..TransferOptions::default()
+        };
+        let (copied, _) = file_utils::copy_missing_files_with_progress(
+            &[file_info(&unrelated), file_info(&partial)],
+            &target_dir,
+            false,
+            true,
+            &transfer,
+            &ProgressBar::hidden(),
+            &ProgressBar::hidden(),
+        )?;
+
+        assert_eq!(fs::read_to_string(dest_dir.join("notes.txt"))?, "keep me");
+        assert_eq!(
+            fs::read_to_string(dest_dir.join("video.bin"))?,
+            "0123456789abcdef"
+        );
+        assert_eq!(copied.len(), 1, "only the partial copy was resumed");
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_files_only_replaces_older_targets() -> Result<()> {
+        let mut env = TestEnv::new();
+        let source_dir = env.create_subdir("update_source");
+        let target_dir = env.create_subdir("update_target");
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+
+        // Source older than target: the target's copy must survive
+        env.create_file_with_content_and_time(
+            &source_dir.join("stale.txt"),
+            "old source",
+            Some(now - 10 * day),
+        );
+        env.create_file_with_content_and_time(
+            &target_dir.join("stale.txt"),
+            "newer target",
+            Some(now - day),
+        );
+        // Source newer than target: the target gets replaced
+        env.create_file_with_content_and_time(
+            &source_dir.join("fresh.txt"),
+            "new source",
+            Some(now - day),
+        );
+        env.create_file_with_content_and_time(
+            &target_dir.join("fresh.txt"),
+            "old target",
+            Some(now - 10 * day),
+        );
+        // Not in the target at all
+        fs::create_dir_all(source_dir.join("sub"))?;
+        env.create_file_with_content_and_time(&source_dir.join("sub/new.txt"), "brand new", None);
+
+        let cli = env.default_cli_args();
+        let source_files = file_utils::scan_directory(&cli, &source_dir, false)?;
+        let (summary, _) = update_mode::update_files(
+            &source_dir,
+            &source_files,
+            &target_dir,
+            false,
+            true,
+            &TransferOptions::default(),
+        )?;
+
+        assert_eq!(
+            summary,
+            UpdateSummary {
+                copied: 1,
+                updated: 1,
+                skipped: 1,
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(target_dir.join("stale.txt"))?,
+            "newer target"
+        );
+        assert_eq!(
+            fs::read_to_string(target_dir.join("fresh.txt"))?,
+            "new source"
+        );
+        assert_eq!(
+            fs::read_to_string(target_dir.join("sub/new.txt"))?,
+            "brand new"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_missing_files_with_progress_advances_overall_bar() -> Result<()> {
+        let mut env = TestEnv::new();
+        let source_dir = env.create_subdir("progress_source/docs");
+        let target_dir = env.create_subdir("progress_target");
+        let mut missing = Vec::new();
+        for i in 0..3 {
+            let path = source_dir.join(format!("doc{}.txt", i));
+            env.create_file_with_content_and_time(&path, &format!("content {}", i), None);
+            missing.push(FileInfo {
+                path: path.clone(),
+                size: fs::metadata(&path)?.len(),
+                hash: None,
+                modified_at: None,
+                created_at: None,
+            });
+        }
+
+        let overall = ProgressBar::hidden();
+        let current = ProgressBar::hidden();
+        let (copied, _) = file_utils::copy_missing_files_with_progress(
+            &missing,
+            &target_dir,
+            false,
+            true,
+            &TransferOptions::default(),
+            &overall,
+            &current,
+        )?;
+
+        assert_eq!(copied.len(), 3);
+        assert_eq!(overall.length(), Some(3));
+        assert_eq!(overall.position(), 3);
+        assert_eq!(current.position(), missing[2].size);
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_max_size_boundaries() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("size_bounds");
+        // Pairs of duplicates at 99, 100, 200 and 201 bytes
+        for (size, tag) in [(99, 'a'), (100, 'b'), (200, 'c'), (201, 'd')] {
+            let content: String = std::iter::repeat_n(tag, size).collect();
+            for copy in 0..2 {
+                env.create_file_with_content_and_time(
+                    &dir.join(format!("{}_{}.bin", size, copy)),
+                    &content,
+                    None,
+                );
+            }
+        }
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = vec![dir.clone()];
+        cli_args.min_size = Some(100);
+        cli_args.max_size = Some(200);
+
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        let mut sizes: Vec<u64> = sets.iter().map(|s| s.size).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![100, 200], "Both bounds should be inclusive");
+
+        // The pre-scan count must agree with the walk so progress stays accurate
+        let rules = file_utils::FilterRules::new(&cli_args)?;
+        assert_eq!(file_utils::count_files_in_directory(&dir, &rules)?, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_filter_rejects_invalid_values() {
+        use clap::Parser;
+
+        assert!(Cli::try_parse_from(["dedups", "--min-size=abc", "."]).is_err());
+        let cli = Cli::try_parse_from(["dedups", "--min-size", "4K", "--max-size", "2G", "."])
+            .expect("valid sizes should parse");
+        assert_eq!(cli.min_size, Some(4096));
+        assert_eq!(cli.max_size, Some(2 * 1024 * 1024 * 1024));
+
+        let env = TestEnv::new();
+        let mut cli_args = env.default_cli_args();
+        cli_args.min_size = Some(2048);
+        cli_args.max_size = Some(1024);
+        assert!(file_utils::FilterRules::new(&cli_args).is_err());
+    }
+
+    #[test]
+    fn test_include_hidden_files() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("hidden_scan");
+        let dot_dir = env.create_subdir("hidden_scan/.config");
+        env.create_file_with_content_and_time(&dir.join("settings.toml"), "same_settings", None);
+        env.create_file_with_content_and_time(&dir.join(".settings.bak"), "same_settings", None);
+        env.create_file_with_content_and_time(
+            &dot_dir.join("settings.toml"),
+            "same_settings",
+            None,
+        );
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = vec![dir.clone()];
+
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert!(sets.is_empty(), "Hidden files are skipped by default");
+        let rules = file_utils::FilterRules::new(&cli_args)?;
+        assert_eq!(file_utils::count_files_in_directory(&dir, &rules)?, 1);
+
+        cli_args.include_hidden = true;
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].files.len(), 3);
+        // The pre-scan count must use the same setting as the walk
+        let rules = file_utils::FilterRules::new(&cli_args)?;
+        assert_eq!(file_utils::count_files_in_directory(&dir, &rules)?, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_depth_limits_scan() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("depth_scan");
+        env.create_subdir("depth_scan/one/two");
+        env.create_file_with_content_and_time(&dir.join("top_a.txt"), "top level", None);
+        env.create_file_with_content_and_time(&dir.join("top_b.txt"), "top level", None);
+        env.create_file_with_content_and_time(&dir.join("one/mid.txt"), "top level", None);
+        env.create_file_with_content_and_time(&dir.join("one/two/deep.txt"), "top level", None);
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = vec![dir.clone()];
+        cli_args.max_depth = Some(1);
+
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets.len(), 1);
+        let mut names: Vec<_> = sets[0]
+            .files
+            .iter()
+            .map(|f| f.path.file_name().unwrap().to_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["top_a.txt", "top_b.txt"]);
+        // The pre-scan count must use the same depth as the walk
+        let rules = file_utils::FilterRules::new(&cli_args)?;
+        assert_eq!(file_utils::count_files_in_directory(&dir, &rules)?, 2);
+
+        cli_args.max_depth = Some(3);
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets[0].files.len(), 4);
+
+        cli_args.max_depth = Some(0);
+        assert!(file_utils::FilterRules::new(&cli_args).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_with_loop() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("symlink_scan");
+        let real_dir = env.create_subdir("symlink_target");
+        env.create_file_with_content_and_time(&dir.join("a.txt"), "linked_content", None);
+        env.create_file_with_content_and_time(&real_dir.join("b.txt"), "linked_content", None);
+        std::os::unix::fs::symlink(&real_dir, dir.join("mounted"))?;
+        // A link back to its own parent would recurse forever without the loop guard
+        std::os::unix::fs::symlink(&dir, dir.join("loop"))?;
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = vec![dir.clone()];
+
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert!(sets.is_empty(), "Symlinks are skipped by default");
+
+        cli_args.follow_symlinks = true;
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets.len(), 1);
+        assert_eq!(
+            sets[0].files.len(),
+            2,
+            "Each real file should be found exactly once: {:?}",
+            sets[0].files
+        );
+
+        let rules = file_utils::FilterRules::new(&cli_args)?;
+        assert_eq!(file_utils::count_files_in_directory(&dir, &rules)?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_empty_files_form_one_set() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("empties");
+        let nested = env.create_subdir("empties/nested");
+        for path in [dir.join("a.log"), dir.join("b.log"), nested.join("c.log")] {
+            env.create_file_with_content_and_time(&path, "", None);
+        }
+        env.create_file_with_content_and_time(&dir.join("data.txt"), "not empty", None);
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = vec![dir.clone()];
+
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert!(sets.is_empty(), "Empty files are skipped by default");
+
+        cli_args.include_empty = true;
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].size, 0);
+        assert_eq!(sets[0].hash, file_utils::EMPTY_FILE_HASH);
+        assert_eq!(sets[0].files.len(), 3);
+
+        let (kept, to_delete) =
+            file_utils::determine_action_targets(&sets[0], SelectionStrategy::ShortestPath)?;
+        assert_eq!(to_delete.len(), 2);
+        assert!(!to_delete.contains(&kept));
+        Ok(())
+    }
+
+    #[test]
+    fn test_union_finds_duplicates_across_directories() -> Result<()> {
+        let mut env = TestEnv::new();
+        let photos = env.create_subdir("union_photos");
+        let backup = env.create_subdir("union_backup");
+        let nested = env.create_subdir("union_backup/nested");
+        env.create_file_with_content_and_time(&photos.join("img.jpg"), "shared_image", None);
+        env.create_file_with_content_and_time(&backup.join("img_copy.jpg"), "shared_image", None);
+        env.create_file_with_content_and_time(&photos.join("only_here.jpg"), "unique", None);
+
+        let mut cli_args = env.default_cli_args();
+        // The nested directory is already covered by `backup` and must not be walked twice
+        cli_args.directories = vec![photos.clone(), backup.clone(), nested.clone()];
+
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert!(
+            sets.is_empty(),
+            "Only the first directory is scanned by default"
+        );
+
+        cli_args.union = true;
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets.len(), 1);
+        let mut paths: Vec<_> = sets[0].files.iter().map(|f| f.path.clone()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![backup.join("img_copy.jpg"), photos.join("img.jpg")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_name_only_splits_sets_by_file_name() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("same_name");
+        for sub in ["import_a", "import_b"] {
+            let sub_dir = env.create_subdir(&format!("same_name/{}", sub));
+            env.create_file_with_content_and_time(&sub_dir.join("IMG_1234.jpg"), "pixels", None);
+            env.create_file_with_content_and_time(&sub_dir.join("IMG_5678.jpg"), "pixels", None);
+        }
+        env.create_file_with_content_and_time(&dir.join("renamed.jpg"), "pixels", None);
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = vec![dir.clone()];
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let all = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].files.len(), 5);
+
+        cli_args.same_name_only = true;
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert_eq!(sets.len(), 2);
+        for set in &sets {
+            assert_eq!(set.files.len(), 2);
+            assert_eq!(set.files[0].path.file_name(), set.files[1].path.file_name());
+        }
+        assert!(!sets
+            .iter()
+            .flat_map(|s| &s.files)
+            .any(|f| f.path.ends_with("renamed.jpg")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_case_insensitive_names_match_target_file() -> Result<()> {
+        let mut env = TestEnv::new();
+        let source_dir = env.create_subdir("case_source");
+        let target_dir = env.create_subdir("case_target");
+        env.create_file_with_content_and_time(&source_dir.join("README.md"), "new readme", None);
+        env.create_file_with_content_and_time(&target_dir.join("readme.md"), "old readme", None);
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = vec![source_dir.clone(), target_dir.clone()];
+        cli_args.target = Some(target_dir.clone());
+
+        cli_args.case_insensitive_names = Some(false);
+        let result = file_utils::compare_directories(&cli_args)?;
+        assert_eq!(result.missing_in_target.len(), 1);
+
+        cli_args.case_insensitive_names = Some(true);
+        let result = file_utils::compare_directories(&cli_args)?;
+        assert!(
+            result.missing_in_target.is_empty(),
+            "README.md should match readme.md ignoring case"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_files_from_list_skips_directory_walk() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("listed");
+        env.create_file_with_content_and_time(&dir.join("a.txt"), "same", None);
+        env.create_file_with_content_and_time(&dir.join("b.txt"), "same", None);
+        env.create_file_with_content_and_time(&dir.join("c.txt"), "other", None);
+        // Not in the list, so never considered even though it matches a.txt
+        env.create_file_with_content_and_time(&dir.join("unlisted.txt"), "same", None);
+
+        let list_path = env.root_path.join("files.txt");
+        let list = ["a.txt", "b.txt", "c.txt"]
+            .iter()
+            .map(|name| dir.join(name).display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::write(&list_path, list)?;
+
+        let mut cli_args = env.default_cli_args();
+        cli_args.directories = Vec::new();
+        cli_args.files_from = Some(list_path);
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+
+        assert_eq!(sets.len(), 1);
+        let mut names: Vec<_> = sets[0]
+            .files
+            .iter()
+            .map(|f| f.path.file_name().unwrap().to_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["a.txt", "b.txt"]);
+
+        // Media mode has no directory to walk either, so it works from the same list
+        let media_list = env.root_path.join("media_files.txt");
+        fs::write(
+            &media_list,
+            format!(
+                "{}\n{}",
+                dir.join("a.txt").display(),
+                dir.join("c.txt").display()
+            ),
+        )?;
+        cli_args.files_from = Some(media_list);
+        cli_args.media_mode = true;
+        cli_args.media_dedup_options.enabled = true;
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let sets = file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+        assert!(sets.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress_interval_controls_update_count() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("progress_cadence");
+        for i in 0..30 {
+            env.create_file_with_content_and_time(
+                &dir.join(format!("file_{}.txt", i)),
+                &"x".repeat(i + 1),
+                None,
+            );
+        }
+
+        let count_messages = |interval_ms: u64| -> Result<usize> {
+            let mut cli_args = env.default_cli_args();
+            cli_args.directories = vec![dir.clone()];
+            cli_args.progress_interval = Some(interval_ms);
+            // Take the count-based cadence out of the picture
+            cli_args.progress_every = Some(1_000_000);
+            let (tx, rx) = std::sync::mpsc::channel();
+            file_utils::find_duplicate_files_with_progress(&cli_args, tx)?;
+            Ok(rx.try_iter().count())
+        };
+
+        let frequent = count_messages(0)?;
+        let sparse = count_messages(3_600_000)?;
+        assert!(
+            frequent > sparse + 20,
+            "expected many more updates with a 0ms interval ({} vs {})",
+            frequent,
+            sparse
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fix_symlinks_repoints_links_to_deleted_duplicate() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("symlink_farm");
+        let older = SystemTime::now() - Duration::from_secs(3600);
+        env.create_file_with_content_and_time(&dir.join("old.txt"), "same", Some(older));
+        env.create_file_with_content_and_time(&dir.join("new.txt"), "same", None);
+        let link = dir.join("link_to_old");
+        std::os::unix::fs::symlink("old.txt", &link)?;
+
+        // newest_modified keeps new.txt, so old.txt is deleted
+        let output = assert_cmd::Command::cargo_bin("dedups")?
+            .arg(&dir)
+            .arg("--delete")
+            .arg("--fix-symlinks")
+            .output()?;
+        assert_eq!(
+            output.status.code(),
+            Some(dedups::exit_code::DUPLICATES_FOUND)
+        );
+
+        assert!(!dir.join("old.txt").exists());
+        assert_eq!(
+            fs::canonicalize(&link)?,
+            fs::canonicalize(dir.join("new.txt"))?
+        );
+        assert_eq!(fs::read_to_string(&link)?, "same");
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_events_file_contains_final_result() -> Result<()> {
+        let mut env = TestEnv::new();
+        let dir = env.create_subdir("json_events_scan");
+        env.create_file_with_content_and_time(&dir.join("a.txt"), "same", None);
+        env.create_file_with_content_and_time(&dir.join("b.txt"), "same", None);
+        let events_path = env.root_path.join("events.ndjson");
+
+        let output = assert_cmd::Command::cargo_bin("dedups")?
+            .arg(&dir)
+            .arg("--json-events")
+            .arg(&events_path)
+            .output()?;
+        assert_eq!(
+            output.status.code(),
+            Some(dedups::exit_code::DUPLICATES_FOUND)
+        );
+        // Human-readable summary still goes to stdout
+        assert!(String::from_utf8_lossy(&output.stdout).contains("sets of duplicate files"));
+
+        let events: Vec<serde_json::Value> = fs::read_to_string(&events_path)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(events[0]["type"], "meta");
+        assert_eq!(events[0]["dedups_version"], env!("CARGO_PKG_VERSION"));
+        assert!(events
+            .iter()
+            .all(|e| e["schema_version"] == dedups::json_events::SCHEMA_VERSION));
+        assert!(events.iter().any(|e| e["type"] == "progress"));
+        let final_result = events
+            .iter()
+            .find(|e| e["type"] == "final_result")
+            .expect("final_result event");
+        assert_eq!(final_result["total_sets"], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_events_error_carries_code() -> Result<()> {
+        let env = TestEnv::new();
+        let events_path = env.root_path.join("error_events.ndjson");
+
+        let output = assert_cmd::Command::cargo_bin("dedups")?
+            .arg(env.root_path.join("no_such_dir"))
+            .arg("--json-events")
+            .arg(&events_path)
+            .output()?;
+        assert!(!output.status.success());
+
+        let events: Vec<serde_json::Value> = fs::read_to_string(&events_path)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        let error = events
+            .iter()
+            .find(|e| e["type"] == "error")
+            .expect("error event");
+        assert_eq!(
+            error["code"],
+            dedups::error::DedupError::DirectoryNotFound(Default::default()).code()
+        );
+        assert!(error["message"]
+            .as_str()
+            .unwrap()
+            .contains("does not exist"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit_codes_distinguish_duplicates_from_errors() -> Result<()> {
+        let env = TestEnv::new();
+        let dir = env.root_path.join("exit_codes");
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("a.txt"), "one")?;
+        fs::write(dir.join("b.txt"), "two")?;
+        // A private HOME, so no user config is read or created
+        let run = |args: &[&std::ffi::OsStr]| -> Result<Option<i32>> {
+            Ok(assert_cmd::Command::cargo_bin("dedups")?
+                .env("HOME", &env.root_path)
+                .args(args)
+                .output()?
+                .status
+                .code())
+        };
+
+        assert_eq!(
+            run(&[dir.as_os_str()])?,
+            Some(dedups::exit_code::NO_DUPLICATES)
+        );
+
+        fs::write(dir.join("c.txt"), "one")?;
+        assert_eq!(
+            run(&[dir.as_os_str()])?,
+            Some(dedups::exit_code::DUPLICATES_FOUND)
+        );
+
+        let missing = env.root_path.join("no_such_dir");
+        assert_eq!(
+            run(&[missing.as_os_str()])?,
+            Some(dedups::exit_code::FATAL_ERROR)
+        );
+        assert_eq!(
+            run(&["--no-such-flag".as_ref(), dir.as_os_str()])?,
+            Some(dedups::exit_code::FATAL_ERROR)
+        );
+        assert_eq!(run(&["--help".as_ref()])?, Some(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_print0_keeps_messages_off_stdout() -> Result<()> {
+        let env = TestEnv::new();
+        let dir = env.root_path.join("print0_actions");
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("a.txt"), "duplicate content")?;
+        fs::write(dir.join("b.txt"), "duplicate content")?;
+
+        let output = assert_cmd::Command::cargo_bin("dedups")?
+            .env("HOME", &env.root_path)
+            .arg(&dir)
+            .args(["--print0", "--delete", "--dry-run", "--detect-partial"])
+            .arg("--html-report")
+            .arg(env.root_path.join("print0_report.html"))
+            .output()?;
+        assert_eq!(
+            output.status.code(),
+            Some(dedups::exit_code::DUPLICATES_FOUND)
+        );
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let entries: Vec<&str> = stdout.split('\0').filter(|e| !e.is_empty()).collect();
+        assert_eq!(entries.len(), 2, "{:?}", stdout);
+        assert!(
+            entries.iter().all(|e| Path::new(e).is_file()),
+            "{:?}",
+            stdout
+        );
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        for message in [
+            "HTML report saved",
+            "DRY RUN",
+            "Keeping:",
+            "deleted 1 files",
+        ] {
+            assert!(
+                stderr.contains(message),
+                "{:?} missing from {:?}",
+                message,
+                stderr
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_rejects_multi_directory_mode() -> Result<()> {
+        let env = TestEnv::new();
+        let source = env.root_path.join("watch_source");
+        let target = env.root_path.join("watch_target");
+        fs::create_dir_all(&source)?;
+        fs::create_dir_all(&target)?;
+
+        let output = assert_cmd::Command::cargo_bin("dedups")?
+            .env("HOME", &env.root_path)
+            .arg(&source)
+            .arg(&target)
+            .arg("--watch")
+            .output()?;
+        assert_eq!(output.status.code(), Some(dedups::exit_code::FATAL_ERROR));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--union"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_limit_is_rejected_when_comparing_directories() -> Result<()> {
+        let env = TestEnv::new();
+        let source = env.root_path.join("limit_source");
+        let target = env.root_path.join("limit_target");
+        fs::create_dir_all(&source)?;
+        fs::create_dir_all(&target)?;
+        fs::write(source.join("a.txt"), "only in source")?;
+
+        let output = assert_cmd::Command::cargo_bin("dedups")?
+            .env("HOME", &env.root_path)
+            .arg(&source)
+            .arg(&target)
+            .args(["--limit", "1"])
+            .output()?;
+        assert_eq!(output.status.code(), Some(dedups::exit_code::FATAL_ERROR));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--limit"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_limit_samples_first_n_files_and_flags_output() -> Result<()> {
+        let env = TestEnv::new();
+        let dir = env.root_path.join("limit_sample");
+        fs::create_dir_all(&dir)?;
+        // Every file is identical, so the one duplicate set holds every file considered
+        for i in 0..300 {
+            fs::write(dir.join(format!("copy_{:03}.txt", i)), "same content")?;
+        }
+        let run = |limit: &str| -> Result<(String, serde_json::Value)> {
+            let events_path = env.root_path.join(format!("limit_{}.ndjson", limit));
+            let output = assert_cmd::Command::cargo_bin("dedups")?
+                .env("HOME", &env.root_path)
+                .arg(&dir)
+                .args(["--limit", limit, "--json-events"])
+                .arg(&events_path)
+                .output()?;
+            assert_eq!(
+                output.status.code(),
+                Some(dedups::exit_code::DUPLICATES_FOUND)
+            );
+            let final_result = fs::read_to_string(&events_path)?
+                .lines()
+                .map(serde_json::from_str::<serde_json::Value>)
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .find(|e| e["type"] == "final_result")
+                .expect("final_result event");
+            Ok((
+                String::from_utf8_lossy(&output.stdout).into_owned(),
+                final_result,
+            ))
+        };
+
+        let (stdout, final_result) = run("50")?;
+        assert_eq!(final_result["duplicate_files"], 50);
+        assert_eq!(final_result["sample_limit"], 50);
+        assert!(stdout.contains("partial sample"), "stdout: {}", stdout);
+
+        // A limit the tree never reaches isn't a sample
+        let (stdout, final_result) = run("1000")?;
+        assert_eq!(final_result["duplicate_files"], 300);
+        assert!(final_result.get("sample_limit").is_none());
+        assert!(!stdout.contains("partial sample"));
+        Ok(())
+    }
 }